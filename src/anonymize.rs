@@ -0,0 +1,83 @@
+use crate::structs::{AnonymizeConfig, Record};
+
+/// Applies the anonymization settings to one record.
+///
+/// Every temperature statistic (including the standard deviation, range, and confidence
+/// interval) is replaced by the lower
+/// bound of its `bucket_width` range, e.g. 23.7 becomes 20.0 with a width of 5.
+///
+/// # Returns
+///
+/// Returns `None` if the record is suppressed because its `count` is below `min_group_size`.
+pub fn anonymize(mut record: Record, config: &AnonymizeConfig) -> Option<Record> {
+    if config.min_group_size.is_some_and(|k| record.count < k) {
+        return None;
+    }
+    if let Some(width) = config.bucket_width {
+        for value in [
+            &mut record.avg_temp,
+            &mut record.min_temp,
+            &mut record.max_temp,
+            &mut record.std_dev,
+            &mut record.median_temp,
+            &mut record.percentile_25,
+            &mut record.percentile_75,
+            &mut record.percentile_90,
+            &mut record.percentile_95,
+        ] {
+            *value = bucket(*value, width);
+        }
+        for value in [
+            &mut record.ci_low,
+            &mut record.ci_high,
+            &mut record.range_temp,
+            &mut record.mode_temp,
+            &mut record.trimmed_mean,
+            &mut record.geometric_mean,
+            &mut record.harmonic_mean,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *value = bucket(*value, width);
+        }
+        for (_, value) in &mut record.percentiles {
+            *value = bucket(*value, width);
+        }
+        if let Some(celsius) = &mut record.celsius {
+            for value in [
+                &mut celsius.avg_temp,
+                &mut celsius.min_temp,
+                &mut celsius.max_temp,
+                &mut celsius.std_dev,
+                &mut celsius.median_temp,
+                &mut celsius.percentile_25,
+                &mut celsius.percentile_75,
+                &mut celsius.percentile_90,
+                &mut celsius.percentile_95,
+            ] {
+                *value = bucket(*value, width);
+            }
+            for (_, value) in &mut celsius.percentiles {
+                *value = bucket(*value, width);
+            }
+        }
+    }
+    Some(record)
+}
+
+/// Anonymizes a result set in place, returning the number of suppressed records.
+pub fn anonymize_all(records: &mut Vec<Record>, config: &AnonymizeConfig) -> usize {
+    let before = records.len();
+    *records = std::mem::take(records)
+        .into_iter()
+        .filter_map(|record| anonymize(record, config))
+        .collect();
+    before - records.len()
+}
+
+/// Lower bound of the `width`-sized bucket containing `value`.
+fn bucket(value: f64, width: f64) -> f64 {
+    // Adding 0.0 normalizes -0.0 so it is not written as "-0.00"
+    (value / width).floor() * width + 0.0
+}
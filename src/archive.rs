@@ -0,0 +1,253 @@
+use crate::error::{PipelineError, Result};
+use crate::ledger::Ledger;
+use crate::structs::{DATA_EXTENSIONS, InputSource};
+use glob::Pattern;
+use log::debug;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes the extraction directories of several archives in one process
+static NEXT_ARCHIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Extraction directories of the archives alive in this process
+static EXTRACTION_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Members of an archive extracted to a temporary directory.
+///
+/// The directory is removed when the value is dropped, so it must outlive processing. It is
+/// registered in the ledger while it exists, so `transformer clean` removes it after a crash.
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    archive: PathBuf,
+    dir: PathBuf,
+    members: Vec<PathBuf>,
+    ledger: Ledger,
+}
+
+impl ExtractedArchive {
+    /// The archive the members were extracted from.
+    pub fn archive(&self) -> &Path {
+        &self.archive
+    }
+
+    /// Paths of the extracted members, in archive order.
+    pub fn members(&self) -> &[PathBuf] {
+        &self.members
+    }
+}
+
+impl Drop for ExtractedArchive {
+    fn drop(&mut self) {
+        extraction_dirs().retain(|dir| *dir != self.dir);
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            debug!(
+                "Failed to remove archive directory {}: {}",
+                self.dir.display(),
+                e
+            );
+        } else if let Err(e) = self.ledger.release(&self.dir) {
+            debug!(
+                "Failed to release archive directory {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+fn extraction_dirs() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    EXTRACTION_DIRS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Removes the extraction directories of all archives still alive, for a process about to exit
+/// without dropping them.
+pub fn remove_extraction_dirs() {
+    for dir in extraction_dirs().drain(..) {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+/// Whether `path` names a supported archive (`.zip`, `.tar`, `.tar.gz`, `.tgz`).
+pub fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+#[derive(Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Whether an archive member is processed: it matches `pattern`, or without a pattern, has
+/// one of the [`DATA_EXTENSIONS`].
+fn is_selected(member: &str, pattern: Option<&Pattern>) -> bool {
+    match pattern {
+        Some(pattern) => pattern.matches(member),
+        None => Path::new(member).extension().is_some_and(|ext| {
+            DATA_EXTENSIONS
+                .iter()
+                .any(|data| ext.eq_ignore_ascii_case(data))
+        }),
+    }
+}
+
+/// Extracts the selected members of an archive to a temporary directory.
+///
+/// # Arguments
+///
+/// * `path` - A `.zip`, `.tar`, `.tar.gz`, or `.tgz` archive
+/// * `pattern` - Glob matched against member paths inside the archive (e.g. `2024/*.parquet`);
+///   defaults to every `.parquet`, `.csv`, `.ndjson`, and `.jsonl` member
+/// * `ledger` - Ledger the extraction directory is registered in until it is removed
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if the path is not a supported archive or no member matches,
+/// and `PipelineError::Io` / `PipelineError::Zip` if the archive cannot be read.
+pub fn extract_archive(
+    path: &Path,
+    pattern: Option<&Pattern>,
+    ledger: &Ledger,
+) -> Result<ExtractedArchive> {
+    let kind = archive_kind(path).ok_or_else(|| {
+        PipelineError::Data(format!("{} is not a supported archive", path.display()))
+    })?;
+    let dir = std::env::temp_dir().join(format!(
+        "transformer-archive-{}-{}",
+        std::process::id(),
+        NEXT_ARCHIVE.fetch_add(1, Ordering::Relaxed)
+    ));
+    ledger.register(&dir)?;
+    fs::create_dir_all(&dir)?;
+    extraction_dirs().push(dir.clone());
+    let mut extracted = ExtractedArchive {
+        archive: path.to_path_buf(),
+        dir,
+        members: Vec::new(),
+        ledger: ledger.clone(),
+    };
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+            for index in 0..zip.len() {
+                let mut member = zip.by_index(index)?;
+                let name = member.name()?.into_owned();
+                if member.is_file() && is_selected(&name, pattern) {
+                    extracted.add_member(&name, &mut member)?;
+                }
+            }
+        }
+        ArchiveKind::Tar => extract_tar(File::open(path)?, pattern, &mut extracted)?,
+        ArchiveKind::TarGz => extract_tar(
+            flate2::read::GzDecoder::new(File::open(path)?),
+            pattern,
+            &mut extracted,
+        )?,
+    }
+
+    if extracted.members.is_empty() {
+        return Err(PipelineError::Data(format!(
+            "archive {} contains no members matching {}",
+            path.display(),
+            pattern.map_or("*.parquet, *.csv, *.ndjson, or *.jsonl", Pattern::as_str)
+        )));
+    }
+    debug!(
+        "Extracted {} members of {} to {}",
+        extracted.members.len(),
+        path.display(),
+        extracted.dir.display()
+    );
+    Ok(extracted)
+}
+
+fn extract_tar(
+    reader: impl Read,
+    pattern: Option<&Pattern>,
+    extracted: &mut ExtractedArchive,
+) -> Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if is_selected(&name, pattern) {
+            extracted.add_member(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+impl ExtractedArchive {
+    /// Copies one member into the extraction directory.
+    ///
+    /// Members are flattened to `<index>-<file name>` so member paths can never escape the
+    /// directory and same-named files in different folders do not collide.
+    fn add_member(&mut self, name: &str, reader: &mut impl Read) -> Result<()> {
+        let file_name = Path::new(name)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "member".to_string());
+        let target = self
+            .dir
+            .join(format!("{:04}-{}", self.members.len(), file_name));
+        io::copy(reader, &mut File::create(&target)?)?;
+        self.members.push(target);
+        Ok(())
+    }
+}
+
+/// Replaces archive inputs by their extracted members, which keep the archive's column mapping.
+///
+/// # Returns
+///
+/// Returns the expanded inputs and the extracted archives, which must be kept alive while the
+/// inputs are processed.
+///
+/// # Errors
+///
+/// Returns the errors of [`extract_archive`].
+pub fn expand_archives(
+    inputs: Vec<InputSource>,
+    pattern: Option<&Pattern>,
+    ledger: &Ledger,
+) -> Result<(Vec<InputSource>, Vec<ExtractedArchive>)> {
+    let mut expanded = Vec::with_capacity(inputs.len());
+    let mut archives = Vec::new();
+    for input in inputs {
+        if !is_archive(&input.path) {
+            expanded.push(input);
+            continue;
+        }
+        let archive = extract_archive(&input.path, pattern, ledger)?;
+        expanded.extend(archive.members().iter().map(|member| InputSource {
+            path: member.clone(),
+            columns: input.columns.clone(),
+            format: input.format,
+        }));
+        archives.push(archive);
+    }
+    Ok((expanded, archives))
+}
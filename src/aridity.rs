@@ -0,0 +1,43 @@
+/// Days per year used to annualize a group's mean daily precipitation
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// De Martonne aridity index: `I = P / (T + 10)`.
+///
+/// `P` is the annual precipitation in millimetres and `T` the mean temperature in degrees
+/// Celsius. Values below 10 indicate an arid climate, 10-20 semi-arid, 20-24 Mediterranean,
+/// 24-28 semi-humid, 28-35 humid, and above 35 very humid.
+///
+/// # Returns
+///
+/// Returns `None` when `T <= -10 °C`, where the index is undefined.
+pub fn de_martonne_index(annual_precip_mm: f64, mean_temp_c: f64) -> Option<f64> {
+    let denominator = mean_temp_c + 10.0;
+    (denominator > 0.0).then(|| annual_precip_mm / denominator)
+}
+
+/// Precipitation and temperature totals over the days of a group that have both readings
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ClimateTotals {
+    precip_mm: f64,
+    temp_c: f64,
+    days: usize,
+}
+
+impl ClimateTotals {
+    /// Adds one day's reading.
+    pub fn add(&mut self, temp_c: f64, precip_mm: f64) {
+        self.precip_mm += precip_mm;
+        self.temp_c += temp_c;
+        self.days += 1;
+    }
+
+    /// Aridity index of the group, with the mean daily precipitation scaled to a year so that
+    /// monthly and multi-year groups are comparable.
+    pub fn aridity_index(&self) -> Option<f64> {
+        if self.days == 0 {
+            return None;
+        }
+        let days = self.days as f64;
+        de_martonne_index(self.precip_mm / days * DAYS_PER_YEAR, self.temp_c / days)
+    }
+}
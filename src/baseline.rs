@@ -0,0 +1,130 @@
+use crate::structs::{BaselineExceedance, BaselinePeriod, Record, StdDevMode, TemperatureUnit};
+use crate::units::{LinearScale, scale_for};
+use std::collections::{BTreeMap, HashMap};
+
+/// Computes the baseline mean of monthly averages per country and calendar month.
+///
+/// # Arguments
+///
+/// * `records` - Monthly records covering the baseline period
+/// * `period` - Baseline years; records outside the period are ignored
+///
+/// # Returns
+///
+/// Returns a map from `(country, month)` to the mean `avg_temp` across all baseline years.
+/// Records with a collapsed month dimension (reported as 0) are ignored.
+pub fn baseline_means(records: &[Record], period: &BaselinePeriod) -> HashMap<(String, u32), f64> {
+    let mut sums: HashMap<(String, u32), (f64, u32)> = HashMap::new();
+    for record in records
+        .iter()
+        .filter(|r| r.month != 0 && (period.start_year..=period.end_year).contains(&r.year))
+    {
+        let entry = sums
+            .entry((record.country.clone(), record.month))
+            .or_default();
+        entry.0 += record.avg_temp;
+        entry.1 += 1;
+    }
+    sums.into_iter()
+        .map(|(key, (sum, n))| (key, sum / n as f64))
+        .collect()
+}
+
+/// Summarizes, per country, how many months exceed the baseline mean by more than `margin`.
+///
+/// # Arguments
+///
+/// * `results` - Monthly records for the requested range
+/// * `means` - Baseline means from [`baseline_means`]
+/// * `margin` - Degrees (in the output unit) a month must exceed its baseline mean by
+///
+/// # Returns
+///
+/// Returns one `BaselineExceedance` per country, sorted by country. Months without a baseline
+/// mean for their country and calendar month are not counted.
+pub fn above_baseline(
+    results: &[Record],
+    means: &HashMap<(String, u32), f64>,
+    margin: f64,
+) -> Vec<BaselineExceedance> {
+    let mut counts: BTreeMap<&str, (u32, u32)> = BTreeMap::new();
+    for record in results {
+        if let Some(mean) = means.get(&(record.country.clone(), record.month)) {
+            let entry = counts.entry(&record.country).or_default();
+            entry.0 += 1;
+            if record.avg_temp - mean > margin {
+                entry.1 += 1;
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(country, (months, months_above))| BaselineExceedance {
+            country: country.to_string(),
+            months,
+            months_above,
+            fraction_above: months_above as f64 / months as f64,
+        })
+        .collect()
+}
+
+/// Sets each record's `anomaly_c`: its `avg_temp` minus the baseline mean of its country and
+/// calendar month, in degrees Celsius.
+///
+/// # Arguments
+///
+/// * `records` - Records of any unit; `avg_temp` is converted to Celsius with its own unit
+/// * `means` - Baseline means from [`baseline_means`], in `means_unit`
+/// * `means_unit` - Unit of the records the baseline means were computed from
+/// * `custom` - Scale of `TemperatureUnit::Custom`
+///
+/// Records with a collapsed month dimension, or without a baseline mean for their country and
+/// calendar month, get no anomaly.
+pub fn attach_anomalies(
+    records: &mut [Record],
+    means: &HashMap<(String, u32), f64>,
+    means_unit: TemperatureUnit,
+    custom: &LinearScale,
+) {
+    let means_scale = scale_for(means_unit, custom);
+    for record in records {
+        let scale = scale_for(record.unit, custom);
+        record.anomaly_c = means
+            .get(&(record.country.clone(), record.month))
+            .map(|mean| scale.to_celsius(record.avg_temp) - means_scale.to_celsius(*mean));
+    }
+}
+
+/// Sets each record's `zscore_vs_period`: its `avg_temp` in standard deviations from the mean
+/// `avg_temp` of every record of the same country and unit in the results, with the standard
+/// deviation computed in `std_dev` mode like the records' own `std_dev`.
+///
+/// The distribution covers the whole requested period, so z-scores are comparable across
+/// countries with different climates. Countries with fewer than 2 records, or whose records
+/// all share the same mean, get no z-score.
+pub fn attach_period_zscores(records: &mut [Record], std_dev: StdDevMode) {
+    let mut groups: HashMap<(String, TemperatureUnit), Vec<f64>> = HashMap::new();
+    for record in records.iter() {
+        groups
+            .entry((record.country.clone(), record.unit))
+            .or_default()
+            .push(record.avg_temp);
+    }
+    let moments: HashMap<(String, TemperatureUnit), (f64, f64)> = groups
+        .into_iter()
+        .filter(|(_, means)| means.len() >= 2)
+        .map(|(key, means)| {
+            let n = means.len() as f64;
+            let mean = means.iter().sum::<f64>() / n;
+            let variance = means.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+                / std_dev.divisor(means.len());
+            (key, (mean, variance.sqrt()))
+        })
+        .collect();
+    for record in records {
+        record.zscore_vs_period = moments
+            .get(&(record.country.clone(), record.unit))
+            .filter(|(_, std_dev)| *std_dev > 0.0)
+            .map(|(mean, std_dev)| (record.avg_temp - mean) / std_dev);
+    }
+}
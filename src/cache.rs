@@ -0,0 +1,187 @@
+use crate::error::Result;
+use crate::ledger::{Ledger, write_atomic};
+use crate::structs::{
+    ColumnMapping, InputSource, MonthSet, ProcessingStats, Record, TransformConfig,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the cached layout or the statistics computed from the input change
+const CACHE_FORMAT: u32 = 2;
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Cached results of one extraction and analysis
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    records: Vec<Record>,
+    stats: ProcessingStats,
+}
+
+/// On-disk cache of aggregation results keyed by input content and transform settings.
+///
+/// Re-running with identical input and filters but different output options (formats,
+/// schema version, baseline reports) loads the records instead of re-reading the Parquet file.
+#[derive(Debug, Clone)]
+pub struct StatsCache {
+    dir: PathBuf,
+}
+
+impl StatsCache {
+    /// Opens (creating if needed) the cache directory.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` if the directory cannot be created.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Computes the cache key of a run from the input files' content and every setting that
+    /// affects the computed records.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` if an input file cannot be read.
+    pub fn key(
+        inputs: &[InputSource],
+        target_countries: &[String],
+        excluded_countries: &[String],
+        start_year: i32,
+        end_year: i32,
+        months: Option<MonthSet>,
+        config: &TransformConfig,
+    ) -> Result<String> {
+        let mut content = FNV_OFFSET;
+        let mut buffer = vec![0u8; 64 * 1024];
+        for input in inputs {
+            let mut reader = BufReader::new(File::open(&input.path)?);
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                content = fnv1a(content, &buffer[..read]);
+            }
+        }
+
+        let columns: Vec<&ColumnMapping> = inputs.iter().map(|input| &input.columns).collect();
+        // Source files are recorded by name, which the content checksum does not cover
+        let sources: Option<Vec<&Path>> = config
+            .provenance
+            .then(|| inputs.iter().map(|input| input.path.as_path()).collect());
+        #[cfg(feature = "plugins")]
+        let plugin = config.plugin.as_ref().map(|plugin| plugin.checksum());
+        #[cfg(not(feature = "plugins"))]
+        let plugin: Option<u64> = None;
+        #[cfg(feature = "scripting")]
+        let scripts = [&config.row_script, &config.record_script]
+            .map(|script| script.as_ref().map(|script| script.checksum()));
+        #[cfg(not(feature = "scripting"))]
+        let scripts: [Option<u64>; 2] = [None, None];
+        let settings = format!(
+            "{}|{}|{:?}|{:?}|{:?}|{}|{}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{}|{}|{:?}|{:?}|{:?}|{:?}|{}|{}|{}|{}|{}|{:?}|{}|{:?}|{:?}|{:?}|{}|{:?}|{}|{:?}|{:?}",
+            CACHE_FORMAT,
+            env!("CARGO_PKG_VERSION"),
+            columns,
+            target_countries,
+            excluded_countries,
+            start_year,
+            end_year,
+            months,
+            config.unit,
+            config.extra_units,
+            config.custom_scale,
+            config.threshold,
+            config.outlier_method,
+            config.outlier_window,
+            config.outlier_action,
+            config.quantile_method,
+            config.percentiles,
+            config.std_dev,
+            config.aggregate,
+            config.strict,
+            config.dimensions(),
+            config.enso,
+            config.hemisphere,
+            config.daily_collapse,
+            config.aridity,
+            config.distinct_days,
+            config.confidence_interval,
+            config.mode_range,
+            config.mode_bin_width,
+            config.trimmed_mean,
+            config.positive_means,
+            sources,
+            plugin,
+            scripts,
+            config.keep_celsius,
+            config.significant_digits,
+            config.streaming,
+            config.valid_min,
+            config.valid_max,
+        );
+        let settings = fnv1a(FNV_OFFSET, settings.as_bytes());
+        Ok(format!("{:016x}{:016x}", content, settings))
+    }
+
+    /// Returns the cached records and statistics for `key`, if present and readable.
+    ///
+    /// Unreadable entries (e.g. written by an older version) are treated as misses.
+    pub fn get(&self, key: &str) -> Option<(Vec<Record>, ProcessingStats)> {
+        let path = self.entry_path(key);
+        let file = File::open(&path).ok()?;
+        match serde_json::from_reader::<_, CacheEntry>(BufReader::new(file)) {
+            Ok(entry) => Some((entry.records, entry.stats)),
+            Err(e) => {
+                debug!("Ignoring unreadable cache entry {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Stores records and statistics under `key`, replacing any existing entry.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` or `PipelineError::Json` if the entry cannot be written.
+    pub fn put(
+        &self,
+        ledger: &Ledger,
+        key: &str,
+        records: &[Record],
+        stats: &ProcessingStats,
+    ) -> Result<()> {
+        let entry = CacheEntry {
+            records: records.to_vec(),
+            stats: stats.clone(),
+        };
+        write_atomic(ledger, &self.entry_path(key), |path| {
+            serde_json::to_writer(BufWriter::new(File::create(path)?), &entry)?;
+            Ok(())
+        })
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+/// 64-bit FNV-1a hash of a string.
+#[cfg(feature = "http")]
+pub(crate) fn fnv1a_str(value: &str) -> u64 {
+    fnv1a(FNV_OFFSET, value.as_bytes())
+}
+
+/// Folds `bytes` into a 64-bit FNV-1a hash.
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
@@ -0,0 +1,138 @@
+use crate::error::{PipelineError, Result};
+use crate::structs::{QuantileMethod, Record, StdDevMode};
+use std::collections::HashSet;
+use std::hash::BuildHasher;
+
+/// Absolute/relative tolerance used when comparing against the reference implementation
+const TOLERANCE: f64 = 1e-9;
+
+/// Picks a random subset of group indices to verify.
+///
+/// # Arguments
+///
+/// * `groups` - Total number of groups
+/// * `sample_size` - Number of groups to select (all groups if larger than `groups`)
+///
+/// # Returns
+///
+/// Returns the selected indices. The selection is seeded per process so that repeated
+/// runs exercise different groups.
+pub(crate) fn sample_groups(groups: usize, sample_size: usize) -> HashSet<usize> {
+    let state = std::collections::hash_map::RandomState::new();
+    let mut indices: Vec<usize> = (0..groups).collect();
+    indices.sort_by_key(|&i| state.hash_one(i));
+    indices.into_iter().take(sample_size).collect()
+}
+
+/// Recomputes a record's statistics with a naive reference implementation and compares them.
+///
+/// The reference deliberately avoids any shared helpers from the transform stage: it sorts a
+/// copy of the data once and reads every statistic straight from the sorted values.
+///
+/// # Arguments
+///
+/// * `record` - Record produced by the pipeline
+/// * `temps` - Readings the record was computed from (after cleaning and outlier removal)
+/// * `method` - Quantile method of the record's percentiles
+/// * `percentiles` - Requested percentiles of the record's `percentiles`
+/// * `std_dev_mode` - Denominator of the record's standard deviation
+///
+/// # Errors
+///
+/// Returns `PipelineError::SelfCheck` for the first statistic that disagrees beyond tolerance.
+pub(crate) fn verify_record(
+    record: &Record,
+    temps: &[f64],
+    method: QuantileMethod,
+    percentiles: &[f64],
+    std_dev_mode: StdDevMode,
+) -> Result<()> {
+    let mut sorted = temps.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len();
+
+    let mean = sorted.iter().sum::<f64>() / n as f64;
+    let denominator = match std_dev_mode {
+        StdDevMode::Sample => n - 1,
+        StdDevMode::Population => n,
+    };
+    let std_dev = if n > 1 {
+        (sorted.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / denominator as f64).sqrt()
+    } else {
+        0.0
+    };
+    let quantile = |p: f64, method: QuantileMethod| {
+        let h = (n - 1) as f64 * p;
+        let lo = sorted[h.floor() as usize];
+        let hi = sorted[h.ceil() as usize];
+        match method {
+            QuantileMethod::Linear => lo + (h - h.floor()) * (hi - lo),
+            QuantileMethod::Lower => lo,
+            QuantileMethod::Higher => hi,
+            QuantileMethod::Midpoint => lo + (hi - lo) / 2.0,
+            QuantileMethod::Nearest => {
+                // Halfway between two readings NumPy picks the even index
+                let fraction = h - h.floor();
+                let even_lo = (h.floor() as usize).is_multiple_of(2);
+                if fraction < 0.5 || (fraction == 0.5 && even_lo) {
+                    lo
+                } else {
+                    hi
+                }
+            }
+        }
+    };
+
+    let expected = [
+        ("count", n as f64, record.count as f64),
+        ("avg_temp", mean, record.avg_temp),
+        ("min_temp", sorted[0], record.min_temp),
+        ("max_temp", sorted[n - 1], record.max_temp),
+        ("std_dev", std_dev, record.std_dev),
+        (
+            "median_temp",
+            quantile(0.5, QuantileMethod::Linear),
+            record.median_temp,
+        ),
+        (
+            "percentile_25",
+            quantile(0.25, method),
+            record.percentile_25,
+        ),
+        (
+            "percentile_75",
+            quantile(0.75, method),
+            record.percentile_75,
+        ),
+        (
+            "percentile_90",
+            quantile(0.90, method),
+            record.percentile_90,
+        ),
+        (
+            "percentile_95",
+            quantile(0.95, method),
+            record.percentile_95,
+        ),
+    ];
+
+    let requested = record
+        .percentiles
+        .iter()
+        .zip(percentiles)
+        .map(|((name, actual), percent)| {
+            (name.as_str(), quantile(percent / 100.0, method), *actual)
+        });
+
+    for (field, expected, actual) in expected.into_iter().chain(requested) {
+        if (expected - actual).abs() > TOLERANCE * expected.abs().max(1.0) {
+            return Err(PipelineError::SelfCheck {
+                group: format!("{}/{}/{}", record.country, record.year, record.month),
+                field: field.to_string(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
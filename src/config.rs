@@ -0,0 +1,103 @@
+use crate::error::Result;
+use crate::structs::{ColumnMapping, InputFormat, InputSource};
+use serde::Deserialize;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Pipeline configuration file (`--config pipeline.yaml`)
+///
+/// ```yaml
+/// inputs:
+///   - path: archive/weather_2019.parquet
+///   - path: archive/weather_2024.parquet
+///     columns:
+///       temp: temp_mean_c
+///   - path: archive/stations.txt
+///     format: csv
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// Inputs processed in addition to `--input-file`, with optional column overrides
+    #[serde(default)]
+    pub inputs: Vec<InputConfig>,
+}
+
+/// One input of the configuration file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputConfig {
+    pub path: PathBuf,
+    /// Column names of this file; unspecified columns keep their default names
+    #[serde(default)]
+    pub columns: ColumnMapping,
+    /// Format of this file, detected by extension by default
+    #[serde(default)]
+    pub format: InputFormat,
+}
+
+impl PipelineConfig {
+    /// Loads a configuration file; relative input paths (other than URLs) are resolved against
+    /// its directory.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` if the file cannot be opened or `PipelineError::Yaml` if it
+    /// is malformed.
+    pub fn from_yaml(path: &Path) -> Result<Self> {
+        let mut config: PipelineConfig = serde_yaml::from_reader(File::open(path)?)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for input in &mut config.inputs {
+            let remote = InputSource::new(&input.path).is_remote();
+            if input.path.is_relative() && !remote {
+                input.path = base.join(&input.path);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Combines command-line input files with the configured inputs.
+    ///
+    /// A command-line file that is also listed in the configuration (same path) uses the
+    /// configured columns, other command-line files use `columns`; configured inputs not given
+    /// on the command line are appended.
+    pub fn resolve_inputs(&self, files: &[PathBuf], columns: &ColumnMapping) -> Vec<InputSource> {
+        let mut inputs: Vec<InputSource> = files
+            .iter()
+            .map(|file| match self.find(file) {
+                Some(configured) => InputSource {
+                    path: file.clone(),
+                    columns: configured.columns.clone(),
+                    format: configured.format,
+                },
+                None => InputSource {
+                    columns: columns.clone(),
+                    ..InputSource::new(file)
+                },
+            })
+            .collect();
+        for configured in &self.inputs {
+            if !files.iter().any(|file| same_file(file, &configured.path)) {
+                inputs.push(InputSource {
+                    path: configured.path.clone(),
+                    columns: configured.columns.clone(),
+                    format: configured.format,
+                });
+            }
+        }
+        inputs
+    }
+
+    fn find(&self, file: &Path) -> Option<&InputConfig> {
+        self.inputs
+            .iter()
+            .find(|configured| same_file(file, &configured.path))
+    }
+}
+
+/// Compares paths, resolving them when both exist so `./a.parquet` matches `a.parquet`.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -0,0 +1,37 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Bundled ISO 3166-1 alpha-2 table with display names and capital coordinates
+const BUNDLED_COUNTRIES: &str = include_str!("../data/countries.csv");
+
+/// Reference information about a country from the bundled table
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountryInfo {
+    pub alpha2: String,
+    pub name: String,
+    pub capital: String,
+    /// Latitude of the capital in decimal degrees
+    pub lat: f64,
+    /// Longitude of the capital in decimal degrees
+    pub lon: f64,
+}
+
+/// Looks up a country by its alpha-2 code (case-insensitive).
+pub fn country_info(alpha2: &str) -> Option<&'static CountryInfo> {
+    countries().get(alpha2.to_ascii_uppercase().as_str())
+}
+
+/// Returns the bundled country table keyed by alpha-2 code.
+pub fn countries() -> &'static HashMap<String, CountryInfo> {
+    static TABLE: OnceLock<HashMap<String, CountryInfo>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        csv::Reader::from_reader(BUNDLED_COUNTRIES.as_bytes())
+            .deserialize::<CountryInfo>()
+            .map(|row| {
+                let info = row.expect("bundled country table is valid");
+                (info.alpha2.clone(), info)
+            })
+            .collect()
+    })
+}
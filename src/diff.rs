@@ -0,0 +1,303 @@
+use crate::enso::EnsoPhase;
+use crate::error::{PipelineError, Result};
+use crate::schema::{Column, ColumnType, all_columns, json_rows};
+use crate::season::Season;
+use crate::structs::{ChangeType, FieldDrift, JsonCase, OutputComparison, Record, RecordChange};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value as Json};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Identity of a record across runs
+type RecordKey = (
+    String,
+    i32,
+    u32,
+    Option<u32>,
+    Option<EnsoPhase>,
+    Option<Season>,
+);
+
+fn record_key(record: &Record) -> RecordKey {
+    (
+        record.country.clone(),
+        record.year,
+        record.month,
+        record.day,
+        record.enso_phase,
+        record.season,
+    )
+}
+
+/// Reads the records of a previous run.
+///
+/// # Arguments
+///
+/// * `path` - A previous output directory (its `<name>.json` or, for streamed runs,
+///   `<name>.ndjson` is read) or a JSON / NDJSON output file
+/// * `null_value` - Text the run wrote for nulls (`--null-value`), read back as null; strings in
+///   numeric columns are read as null regardless
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if the directory contains no JSON output, or an I/O or JSON
+/// error if the file cannot be read.
+pub fn read_previous_records(path: &Path, null_value: Option<&str>) -> Result<Vec<Record>> {
+    read_output_rows(path, null_value)
+}
+
+/// Reads the rows of an output directory or JSON / NDJSON file (see [`read_previous_records`]).
+fn read_output_rows<T: DeserializeOwned>(path: &Path, null_value: Option<&str>) -> Result<Vec<T>> {
+    let file_path = if path.is_dir() {
+        previous_output_file(path)?
+    } else {
+        path.to_path_buf()
+    };
+    let reader = BufReader::new(File::open(&file_path)?);
+    let rows: Vec<Json> = if file_path.extension().is_some_and(|ext| ext == "ndjson") {
+        reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<_>>()?
+    } else {
+        json_rows(serde_json::from_reader(reader)?, JsonCase::Snake)
+    };
+    let columns = all_columns();
+    rows.into_iter()
+        .map(|row| {
+            let row = null_strings(snake_case_keys(row), &columns, null_value);
+            Ok(serde_json::from_value(row)?)
+        })
+        .collect()
+}
+
+/// Renames the keys of an output row written with `--json-case camel` or `pascal` back to the
+/// column names (`avgTemp` -> `avg_temp`, `Percentile25` -> `percentile_25`); snake_case keys
+/// are unchanged.
+fn snake_case_keys(row: Json) -> Json {
+    let Json::Object(map) = row else {
+        return row;
+    };
+    let map = map
+        .into_iter()
+        .map(|(key, value)| {
+            let mut name = String::with_capacity(key.len() + 4);
+            let mut previous: Option<char> = None;
+            for c in key.chars() {
+                let boundary = match previous {
+                    Some(p) if p != '_' => {
+                        c.is_ascii_uppercase() || (c.is_ascii_digit() && !p.is_ascii_digit())
+                    }
+                    _ => false,
+                };
+                if boundary {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_lowercase());
+                previous = Some(c);
+            }
+            (name, value)
+        })
+        .collect();
+    Json::Object(map)
+}
+
+/// Replaces the null text of `--null-value` in an output row with JSON `null`: strings equal to
+/// `null_value`, and any string in a numeric column.
+fn null_strings(row: Json, columns: &[Column], null_value: Option<&str>) -> Json {
+    let Json::Object(mut map) = row else {
+        return row;
+    };
+    for column in columns.iter().filter(|column| column.nullable) {
+        let Some(value) = map.get_mut(&column.name) else {
+            continue;
+        };
+        let is_null_text = value
+            .as_str()
+            .is_some_and(|text| column.data_type != ColumnType::Utf8 || Some(text) == null_value);
+        if is_null_text {
+            *value = Json::Null;
+        }
+    }
+    Json::Object(map)
+}
+
+/// Finds the JSON output inside a previous output directory.
+fn previous_output_file(dir: &Path) -> Result<PathBuf> {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    ["json", "ndjson"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            PipelineError::Data(format!(
+                "no {0}.json or {0}.ndjson output found in {1}",
+                name,
+                dir.display()
+            ))
+        })
+}
+
+/// Compares the current results with a previous run.
+///
+/// Records are matched by country, year, month, and ENSO phase; a matched record is changed if
+/// any statistic differs. The run columns of `--provenance` (`run_id`, `processed_at`) differ
+/// between every two runs and are not compared.
+///
+/// # Returns
+///
+/// Returns the added, changed, and removed records sorted by their key. Changed and added
+/// entries carry the current values, removed entries the previous ones.
+pub fn diff_records(previous: &[Record], current: &[Record]) -> Vec<RecordChange> {
+    let mut previous: BTreeMap<RecordKey, &Record> =
+        previous.iter().map(|r| (record_key(r), r)).collect();
+    let mut changes: BTreeMap<RecordKey, RecordChange> = BTreeMap::new();
+    for record in current {
+        let key = record_key(record);
+        let change_type = match previous.remove(&key) {
+            None => ChangeType::Added,
+            Some(old) if !same_values(old, record) => ChangeType::Changed,
+            Some(_) => continue,
+        };
+        changes.insert(
+            key,
+            RecordChange {
+                change_type,
+                record: record.clone(),
+            },
+        );
+    }
+    for (key, record) in previous {
+        changes.insert(
+            key,
+            RecordChange {
+                change_type: ChangeType::Removed,
+                record: record.clone(),
+            },
+        );
+    }
+    changes.into_values().collect()
+}
+
+/// Fields identifying the run rather than the record, which every run sets differently
+const RUN_FIELDS: [&str; 2] = ["run_id", "processed_at"];
+
+/// Whether two records are equal apart from their [`RUN_FIELDS`].
+fn same_values(old: &Record, new: &Record) -> bool {
+    if old.run_id.is_none() && new.run_id.is_none() {
+        return old == new;
+    }
+    let strip = |record: &Record| Record {
+        run_id: None,
+        processed_at: None,
+        ..record.clone()
+    };
+    strip(old) == strip(new)
+}
+
+/// Compares two output runs record by record, e.g. a run against a golden run after an
+/// upgrade.
+///
+/// Records are matched like [`diff_records`] and compared field by field on their JSON
+/// output, so only the emitted columns are checked. Numbers drift when they differ by more
+/// than `tolerance`; other values and fields missing from one run drift whenever they differ.
+/// The run columns of `--provenance` (`run_id`, `processed_at`) are not compared.
+///
+/// # Arguments
+///
+/// * `left`, `right` - Output directories or JSON / NDJSON output files
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if either run has no JSON output or a row without its key
+/// columns, or an I/O or JSON error if a file cannot be read.
+pub fn compare_outputs(left: &Path, right: &Path, tolerance: f64) -> Result<OutputComparison> {
+    let left = keyed_rows(left)?;
+    let mut right = keyed_rows(right)?;
+    let mut comparison = OutputComparison::default();
+    for (key, row) in left {
+        let Some(other) = right.remove(&key) else {
+            comparison.only_left.push(display_key(&key));
+            continue;
+        };
+        comparison.matched += 1;
+        let fields = row
+            .keys()
+            .chain(other.keys().filter(|field| !row.contains_key(*field)))
+            .filter(|field| !RUN_FIELDS.contains(&field.as_str()));
+        for field in fields {
+            let a = row.get(field).unwrap_or(&Json::Null);
+            let b = other.get(field).unwrap_or(&Json::Null);
+            let drifted = match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => (a - b).abs() > tolerance,
+                _ => a != b,
+            };
+            if drifted {
+                comparison.drifts.push(FieldDrift {
+                    record: display_key(&key),
+                    field: field.clone(),
+                    left: a.clone(),
+                    right: b.clone(),
+                });
+            }
+        }
+    }
+    comparison.only_right = right.keys().map(display_key).collect();
+    Ok(comparison)
+}
+
+/// Identity of an output row: country, year, month, day, ENSO phase, and season (if emitted)
+type RowKey = (
+    String,
+    i64,
+    i64,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+);
+
+fn keyed_rows(path: &Path) -> Result<BTreeMap<RowKey, Map<String, Json>>> {
+    let rows: Vec<Map<String, Json>> = read_output_rows(path, None)?;
+    rows.into_iter()
+        .map(|row| {
+            // Years and months collapsed by the grouping are written as null
+            let dimension = |name: &str| match row.get(name) {
+                Some(Json::Null) => Some(0),
+                value => value.and_then(Json::as_i64),
+            };
+            let key = (
+                row.get("country").and_then(Json::as_str),
+                dimension("year"),
+                dimension("month"),
+            );
+            let (Some(country), Some(year), Some(month)) = key else {
+                return Err(PipelineError::Data(format!(
+                    "row without country, year, and month in {}",
+                    path.display()
+                )));
+            };
+            let text = |name: &str| row.get(name).and_then(Json::as_str).map(str::to_string);
+            let day = row.get("day").and_then(Json::as_i64);
+            let (phase, season) = (text("enso_phase"), text("season"));
+            Ok(((country.to_string(), year, month, day, phase, season), row))
+        })
+        .collect()
+}
+
+fn display_key((country, year, month, day, phase, season): &RowKey) -> String {
+    let mut key = format!("{}/{}/{}", country, year, month);
+    if let Some(day) = day {
+        key.push_str(&format!("/{}", day));
+    }
+    for part in [phase, season].into_iter().flatten() {
+        key.push('/');
+        key.push_str(part);
+    }
+    key
+}
@@ -0,0 +1,98 @@
+use crate::error::{PipelineError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bundled ENSO episode table (centre months of NOAA CPC Oceanic Niño Index episodes, 1980-2024)
+const BUNDLED_EPISODES: &str = include_str!("../data/enso_episodes.csv");
+
+/// El Niño-Southern Oscillation phase of a month
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnsoPhase {
+    ElNino,
+    LaNina,
+    Neutral,
+}
+
+impl std::fmt::Display for EnsoPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EnsoPhase::ElNino => "el_nino",
+            EnsoPhase::LaNina => "la_nina",
+            EnsoPhase::Neutral => "neutral",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeRow {
+    start: String,
+    end: String,
+    phase: EnsoPhase,
+}
+
+/// Lookup table of El Niño / La Niña episodes; months outside every episode are neutral.
+#[derive(Debug, Clone)]
+pub struct EnsoTable {
+    /// Inclusive (start, end) month indices (`year * 12 + month - 1`) with their phase
+    episodes: Vec<(i32, i32, EnsoPhase)>,
+}
+
+impl EnsoTable {
+    /// Loads the episode table bundled with the crate.
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_EPISODES.as_bytes(), Path::new("<bundled>"))
+            .expect("bundled ENSO table is valid")
+    }
+
+    /// Loads an episode table from a CSV file with `start,end,phase` columns.
+    ///
+    /// `start` and `end` are inclusive `YYYY-MM` months and `phase` is `el_nino`, `la_nina`,
+    /// or `neutral`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be read or contains malformed rows.
+    pub fn from_csv(path: &Path) -> Result<Self> {
+        Self::parse(std::fs::File::open(path)?, path)
+    }
+
+    fn parse<R: std::io::Read>(reader: R, path: &Path) -> Result<Self> {
+        let mut episodes = Vec::new();
+        for row in csv::Reader::from_reader(reader).deserialize() {
+            let row: EpisodeRow = row?;
+            let start = parse_month(&row.start, path)?;
+            let end = parse_month(&row.end, path)?;
+            episodes.push((start, end, row.phase));
+        }
+        Ok(Self { episodes })
+    }
+
+    /// Returns the ENSO phase of the given month.
+    pub fn phase(&self, year: i32, month: u32) -> EnsoPhase {
+        let index = year * 12 + month as i32 - 1;
+        self.episodes
+            .iter()
+            .find(|(start, end, _)| (*start..=*end).contains(&index))
+            .map_or(EnsoPhase::Neutral, |(_, _, phase)| *phase)
+    }
+}
+
+/// Parses a `YYYY-MM` month into a month index.
+fn parse_month(value: &str, path: &Path) -> Result<i32> {
+    let invalid = || {
+        PipelineError::Data(format!(
+            "Invalid month '{}' in ENSO table {} (expected YYYY-MM)",
+            value,
+            path.display()
+        ))
+    };
+    let (year, month) = value.split_once('-').ok_or_else(invalid)?;
+    let year: i32 = year.parse().map_err(|_| invalid())?;
+    let month: i32 = month.parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) {
+        return Err(invalid());
+    }
+    Ok(year * 12 + month - 1)
+}
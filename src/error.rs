@@ -1,4 +1,5 @@
 use arrow_schema::ArrowError;
+use std::path::PathBuf;
 
 #[derive(Debug, thiserror::Error)]
 pub enum PipelineError {
@@ -10,10 +11,72 @@ pub enum PipelineError {
     Arrow(#[from] ArrowError),
     #[error("Data Error: {0}")]
     Data(String),
+    #[error("Data Error: column '{column}' not found in {}", file.display())]
+    MissingColumn { file: PathBuf, column: String },
+    #[error(
+        "Data Error: column '{column}' in {} is {found}, expected {expected}",
+        file.display()
+    )]
+    ColumnType {
+        file: PathBuf,
+        column: String,
+        expected: String,
+        found: String,
+    },
+    #[error(
+        "Data Error: bad row in {} (row group {row_group}, batch {batch}, row {row}): {reason}",
+        file.display()
+    )]
+    BadRow {
+        file: PathBuf,
+        row_group: usize,
+        batch: usize,
+        row: usize,
+        reason: String,
+    },
+    #[error("Self-check Error: {group} {field} expected {expected}, got {actual}")]
+    SelfCheck {
+        group: String,
+        field: String,
+        expected: f64,
+        actual: f64,
+    },
+    #[error("Expectation Error: {failed} of {total} expectations failed")]
+    Expectations { failed: usize, total: usize },
+    #[error("Comparison Error: outputs differ in {differences} records or fields")]
+    Comparison { differences: usize },
+    #[error("YAML Error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("HTTP Error: {url}: {reason}")]
+    Http { url: String, reason: String },
+    #[error("Verification Error: {}: {reason}", file.display())]
+    Verification { file: PathBuf, reason: String },
+    #[error("Thread Pool Error: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
     #[error("CSV Error: {0}")]
     Csv(#[from] csv::Error),
     #[error("JSON Error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Signal Error: {0}")]
+    Signal(#[from] ctrlc::Error),
+    #[cfg(feature = "archive")]
+    #[error("Zip Error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "iceberg")]
+    #[error("Avro Error: {0}")]
+    Avro(#[from] apache_avro::Error),
+    #[cfg(feature = "template")]
+    #[error("Template Error: {0:#}")]
+    Template(#[from] minijinja::Error),
+    #[cfg(feature = "plugins")]
+    #[error("Plugin Error: {}: {reason}", file.display())]
+    Plugin { file: PathBuf, reason: String },
+    #[cfg(feature = "scripting")]
+    #[error("Script Error: {}: {reason}", file.display())]
+    Script { file: PathBuf, reason: String },
+    #[cfg(feature = "excel")]
+    #[error("Excel Error: {0}")]
+    Excel(#[from] rust_xlsxwriter::XlsxError),
 }
 
 pub type Result<T> = std::result::Result<T, PipelineError>;
@@ -0,0 +1,197 @@
+use crate::error::Result;
+use crate::schema::{Value, output_columns};
+use crate::structs::{BaselineExceedance, OutputConfig, ProcessingStats, Record};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use std::path::Path;
+
+/// Excel workbook assembled from one or more report sheets.
+///
+/// Used both for single-sheet files (one workbook per report) and for the combined
+/// multi-sheet bundle (`--excel-combined`).
+pub struct ExcelWorkbook {
+    workbook: Workbook,
+    header: Format,
+    number: Format,
+}
+
+impl Default for ExcelWorkbook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExcelWorkbook {
+    pub fn new() -> Self {
+        Self {
+            workbook: Workbook::new(),
+            header: Format::new().set_bold(),
+            number: Format::new().set_num_format("0.00"),
+        }
+    }
+
+    /// Adds a sheet containing weather statistics with the configured output columns.
+    ///
+    /// # Errors
+    /// Returns error if the sheet name is invalid or the data exceeds Excel's limits.
+    pub fn add_records_sheet(
+        &mut self,
+        name: &str,
+        results: &[Record],
+        config: &OutputConfig,
+    ) -> Result<&mut Self> {
+        let columns = output_columns(config);
+        let sheet = self.workbook.add_worksheet();
+        sheet.set_name(name)?;
+
+        for (col, column) in columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, column.csv_header(), &self.header)?;
+        }
+        for (row, record) in results.iter().enumerate() {
+            for (col, column) in columns.iter().enumerate() {
+                write_value(
+                    sheet,
+                    row as u32 + 1,
+                    col as u16,
+                    column.value(record),
+                    config.null_value.as_deref(),
+                    &self.number,
+                )?;
+            }
+        }
+        sheet.set_freeze_panes(1, 0)?;
+        Ok(self)
+    }
+
+    /// Adds a sheet with the months-above-baseline summary.
+    ///
+    /// # Errors
+    /// Returns error if the sheet name is invalid.
+    pub fn add_baseline_sheet(
+        &mut self,
+        summary: &[BaselineExceedance],
+        margin: f64,
+    ) -> Result<&mut Self> {
+        let sheet = self.workbook.add_worksheet();
+        sheet.set_name("Above Baseline")?;
+
+        let headers = [
+            "Country",
+            "Margin",
+            "Months",
+            "Months_Above",
+            "Fraction_Above",
+        ];
+        for (col, header) in headers.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, *header, &self.header)?;
+        }
+        for (i, row) in summary.iter().enumerate() {
+            let r = i as u32 + 1;
+            sheet.write_string(r, 0, &row.country)?;
+            sheet.write_number_with_format(r, 1, margin, &self.number)?;
+            sheet.write_number(r, 2, row.months)?;
+            sheet.write_number(r, 3, row.months_above)?;
+            sheet.write_number(r, 4, row.fraction_above)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+        Ok(self)
+    }
+
+    /// Adds a data quality sheet with the run's row counters and warnings.
+    ///
+    /// # Errors
+    /// Returns error if the sheet name is invalid.
+    pub fn add_quality_sheet(&mut self, stats: &ProcessingStats) -> Result<&mut Self> {
+        let sheet = self.workbook.add_worksheet();
+        sheet.set_name("Quality")?;
+
+        sheet.write_string_with_format(0, 0, "Metric", &self.header)?;
+        sheet.write_string_with_format(0, 1, "Value", &self.header)?;
+        let counters = [
+            ("Total rows", stats.total_rows),
+            ("Rows in skipped row groups", stats.pruned_rows),
+            ("Rows dropped while decoding", stats.row_filtered_rows),
+            ("Matched rows", stats.filtered_rows),
+            ("Skipped rows", stats.skipped_rows),
+            ("Invalid temperatures", stats.invalid_temps),
+            ("Dropped by plugin or row script", stats.hook_dropped),
+            ("Rejected after plugin or row script", stats.hook_rejected),
+            ("Readings of partial seasons", stats.partial_seasons),
+            ("Outliers removed", stats.outliers_removed),
+            ("Outliers winsorized", stats.outliers_winsorized),
+            ("Groups", stats.groups),
+            ("Self-checked groups", stats.self_checked),
+        ];
+        for (i, (label, value)) in counters.iter().enumerate() {
+            sheet.write_string(i as u32 + 1, 0, *label)?;
+            sheet.write_number(i as u32 + 1, 1, *value as f64)?;
+        }
+
+        let warnings_row = counters.len() as u32 + 2;
+        sheet.write_string_with_format(warnings_row, 0, "Warnings", &self.header)?;
+        for (i, warning) in stats.warnings.iter().enumerate() {
+            sheet.write_string(warnings_row + 1 + i as u32, 0, warning.to_string())?;
+        }
+        sheet.set_column_width(0, 24)?;
+        Ok(self)
+    }
+
+    /// Saves the workbook to disk.
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be written.
+    pub fn save(&mut self, output_path: &Path) -> Result<()> {
+        self.workbook.save(output_path)?;
+        Ok(())
+    }
+}
+
+/// Writes one cell, formatting floats with two decimals and writing nulls as `null_value`
+/// (empty when unset).
+fn write_value(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Value,
+    null_value: Option<&str>,
+    number: &Format,
+) -> Result<()> {
+    match value {
+        Value::Utf8(v) => {
+            sheet.write_string(row, col, v)?;
+        }
+        Value::Int32(v) => {
+            sheet.write_number(row, col, v)?;
+        }
+        Value::UInt32(v) => {
+            sheet.write_number(row, col, v)?;
+        }
+        Value::Float64(v) if v.is_finite() => {
+            sheet.write_number_with_format(row, col, v, number)?;
+        }
+        Value::Float64(_) => {}
+        Value::Null => {
+            if let Some(text) = null_value {
+                sheet.write_string(row, col, text)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes weather statistics to a single-sheet Excel workbook.
+///
+/// # Arguments
+/// * `results` - Slice of Record structs containing weather statistics
+/// * `output_path` - Path where the `.xlsx` file will be created
+/// * `config` - Output configuration selecting the schema version
+///
+/// # Returns
+/// Returns `Ok(())` on success.
+///
+/// # Errors
+/// Returns error if the workbook cannot be built or written.
+pub fn write_excel(results: &[Record], output_path: &Path, config: &OutputConfig) -> Result<()> {
+    ExcelWorkbook::new()
+        .add_records_sheet("Stats", results, config)?
+        .save(output_path)
+}
@@ -0,0 +1,184 @@
+use crate::error::{PipelineError, Result};
+use crate::schema::{ColumnType, Value, all_columns};
+use crate::structs::Record;
+use serde::Deserialize;
+use std::fs::File;
+use std::path::Path;
+
+/// Number of offending records listed per failed expectation
+const MAX_EXAMPLES: usize = 5;
+
+/// A declarative data-quality check evaluated against the processed records.
+///
+/// Records are selected by the optional `country` / `year` / `month` filters, then each
+/// selected record's `field` must lie within `min..=max` (or `between: [min, max]`). An
+/// expectation whose filters match no records fails unless `allow_empty` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// Label used in the report (defaults to a description of the check)
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    /// Numeric output column to check (e.g. `avg_temp`, `count`)
+    pub field: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub between: Option<(f64, f64)>,
+    #[serde(default)]
+    pub allow_empty: bool,
+}
+
+/// Expectations file: a list of checks under `expectations:`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectationSet {
+    pub expectations: Vec<Expectation>,
+}
+
+/// Outcome of one expectation
+#[derive(Debug, Clone)]
+pub struct ExpectationResult {
+    pub name: String,
+    /// Records the filters selected
+    pub checked: usize,
+    /// Descriptions of the failures (empty if the expectation passed)
+    pub failures: Vec<String>,
+    /// Total number of offending records (failures only lists the first few)
+    pub violations: usize,
+}
+
+impl ExpectationResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl ExpectationSet {
+    /// Loads and validates an expectations YAML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Yaml` if the file is malformed, or `PipelineError::Data` if an
+    /// expectation names an unknown or non-numeric field or has no bounds.
+    pub fn from_yaml(path: &Path) -> Result<Self> {
+        let set: ExpectationSet = serde_yaml::from_reader(File::open(path)?)?;
+        let numeric: Vec<String> = all_columns()
+            .into_iter()
+            .filter(|column| column.data_type != ColumnType::Utf8)
+            .map(|column| column.name)
+            .collect();
+        for expectation in &set.expectations {
+            if !numeric.contains(&expectation.field) {
+                return Err(PipelineError::Data(format!(
+                    "expectation '{}' checks unknown field '{}' (expected one of {})",
+                    expectation.label(),
+                    expectation.field,
+                    numeric.join(", ")
+                )));
+            }
+            if expectation.bounds() == (None, None) {
+                return Err(PipelineError::Data(format!(
+                    "expectation '{}' needs min, max, or between",
+                    expectation.label()
+                )));
+            }
+        }
+        Ok(set)
+    }
+
+    /// Evaluates every expectation against `records`.
+    pub fn evaluate(&self, records: &[Record]) -> Vec<ExpectationResult> {
+        let columns = all_columns();
+        self.expectations
+            .iter()
+            .map(|expectation| {
+                let column = columns
+                    .iter()
+                    .find(|column| column.name == expectation.field)
+                    .expect("fields are validated on load");
+                expectation.evaluate(records, |record| match column.value(record) {
+                    Value::Int32(v) => Some(v as f64),
+                    Value::UInt32(v) => Some(v as f64),
+                    Value::Float64(v) => Some(v),
+                    Value::Utf8(_) | Value::Null => None,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Expectation {
+    fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        let mut scope: Vec<String> = Vec::new();
+        scope.extend(self.country.clone());
+        scope.extend(self.year.map(|y| y.to_string()));
+        scope.extend(self.month.map(|m| format!("month {}", m)));
+        let (min, max) = self.bounds();
+        let range = match (min, max) {
+            (Some(min), Some(max)) => format!("between {} and {}", min, max),
+            (Some(min), None) => format!(">= {}", min),
+            (None, Some(max)) => format!("<= {}", max),
+            (None, None) => "unbounded".to_string(),
+        };
+        let scope = if scope.is_empty() {
+            "all records".to_string()
+        } else {
+            scope.join(" ")
+        };
+        format!("{}: {} {}", scope, self.field, range)
+    }
+
+    fn bounds(&self) -> (Option<f64>, Option<f64>) {
+        match self.between {
+            Some((min, max)) => (Some(min), Some(max)),
+            None => (self.min, self.max),
+        }
+    }
+
+    fn matches(&self, record: &Record) -> bool {
+        self.country.as_ref().is_none_or(|c| *c == record.country)
+            && self.year.is_none_or(|y| y == record.year)
+            && self.month.is_none_or(|m| m == record.month)
+    }
+
+    fn evaluate(
+        &self,
+        records: &[Record],
+        value: impl Fn(&Record) -> Option<f64>,
+    ) -> ExpectationResult {
+        let (min, max) = self.bounds();
+        let mut checked = 0;
+        let mut violations = 0;
+        let mut failures = Vec::new();
+        for record in records.iter().filter(|r| self.matches(r)) {
+            checked += 1;
+            let Some(v) = value(record) else { continue };
+            if min.is_some_and(|min| v < min) || max.is_some_and(|max| v > max) {
+                violations += 1;
+                if failures.len() < MAX_EXAMPLES {
+                    failures.push(format!(
+                        "{} {}/{}: {} = {}",
+                        record.country, record.year, record.month, self.field, v
+                    ));
+                }
+            }
+        }
+        if violations > failures.len() {
+            failures.push(format!("... and {} more", violations - failures.len()));
+        }
+        if checked == 0 && !self.allow_empty {
+            failures.push("no records matched".to_string());
+        }
+        ExpectationResult {
+            name: self.label(),
+            checked,
+            failures,
+            violations,
+        }
+    }
+}
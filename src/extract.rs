@@ -0,0 +1,808 @@
+use crate::error::{PipelineError, Result};
+use crate::structs::{ColumnMapping, InputFormat, InputSource, TransformConfig};
+use crate::transform::{CountryFilter, DATE_FORMAT, DateColumn, DateFilter, UNIX_EPOCH_DATE};
+use arrow_array::builder::{Float64Builder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, ArrayRef, BooleanArray, RecordBatch, StringArray};
+use arrow_buffer::{BooleanBuffer, NullBuffer};
+use arrow_csv::ReaderBuilder;
+use arrow_csv::reader::Format;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta};
+use log::debug;
+use parquet::arrow::ProjectionMask;
+use parquet::arrow::arrow_reader::{
+    ArrowPredicateFn, ArrowReaderMetadata, ParquetRecordBatchReaderBuilder, RowFilter,
+};
+use parquet::basic::{LogicalType, TimeUnit};
+use parquet::file::metadata::{ColumnChunkMetaData, RowGroupMetaData};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Record batches of an input file
+pub(crate) type BatchReader =
+    Box<dyn Iterator<Item = std::result::Result<RecordBatch, ArrowError>>>;
+
+/// Rows sampled from a CSV input to infer its column names
+const CSV_INFER_ROWS: usize = 100;
+
+/// Rows per record batch of an NDJSON input
+const NDJSON_BATCH_ROWS: usize = 8192;
+
+/// Parquet row groups decoded by a reader opened from a [`BatchPlan`]
+#[derive(Debug, Default)]
+pub(crate) struct RowGroups {
+    /// Index, first row in the file, and row count of each decoded row group, in file order
+    read: Vec<(usize, usize, usize)>,
+    /// Rows dropped by the row filter so far, counted as the batches are decoded
+    row_filtered: Arc<AtomicUsize>,
+    /// Compressed size of the decoded column chunks; the file size of CSV and NDJSON inputs
+    pub bytes: u64,
+}
+
+impl RowGroups {
+    /// Rows the row filter dropped from the batches decoded so far.
+    pub fn row_filtered(&self) -> usize {
+        self.row_filtered.load(Ordering::Relaxed)
+    }
+
+    /// Row group and row in the file of the `row`-th decoded row; CSV and NDJSON rows are all
+    /// in row group 0.
+    pub fn locate(&self, row: usize) -> (usize, usize) {
+        let mut before = 0;
+        for &(index, start, rows) in &self.read {
+            if row < before + rows {
+                return (index, start + row - before);
+            }
+            before += rows;
+        }
+        (0, row)
+    }
+}
+
+/// How an input is decoded: for Parquet inputs, the row groups left after skipping by their
+/// statistics, which can be opened together or in chunks decoded on separate threads.
+pub(crate) struct BatchPlan<'a> {
+    input: &'a InputSource,
+    config: &'a TransformConfig,
+    /// Metadata of a Parquet input, `None` for CSV and NDJSON inputs
+    metadata: Option<ArrowReaderMetadata>,
+    /// Index, first row in the file, and row count of each row group to decode
+    read: Vec<(usize, usize, usize)>,
+    /// Row groups skipped by their date or country statistics, and their rows
+    pub pruned: usize,
+    pub pruned_rows: usize,
+    /// Row filter of the decoded row groups, if one is needed
+    filter: Option<RowPredicate>,
+}
+
+/// Rows kept by the Parquet row filter, see [`keep_rows`]
+#[derive(Clone)]
+struct RowPredicate {
+    countries: Vec<String>,
+    excluded: Vec<String>,
+    dates: Option<DateFilter>,
+}
+
+/// Plans how an input is decoded, see [`BatchPlan`].
+///
+/// Only the mapped date, country, and temperature columns (and the precipitation column when
+/// `config.aridity` is set) are decoded from Parquet inputs. Parquet row groups whose date or
+/// country statistics show that no row falls within the years of `dates` in a country
+/// `countries` keeps are skipped before decoding, and a row filter drops the rows of the
+/// remaining row groups that lie outside them (or outside the months of `dates`), so their
+/// temperatures are never decoded.
+/// `config.strict` disables both, as it validates every row.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Io` / `PipelineError::Parquet` if the Parquet metadata cannot be
+/// read.
+pub(crate) fn plan_batches<'a>(
+    input: &'a InputSource,
+    config: &'a TransformConfig,
+    countries: CountryFilter<'_>,
+    dates: DateFilter,
+) -> Result<BatchPlan<'a>> {
+    let DateFilter {
+        start_year,
+        end_year,
+        months,
+    } = dates;
+    let mut plan = BatchPlan {
+        input,
+        config,
+        metadata: None,
+        read: Vec::new(),
+        pruned: 0,
+        pruned_rows: 0,
+        filter: None,
+    };
+    if input.resolved_format() != InputFormat::Parquet {
+        return Ok(plan);
+    }
+    let columns = &input.columns;
+    let metadata = ArrowReaderMetadata::load(&File::open(&input.path)?, Default::default())?;
+    let mut start = 0;
+    // Whether a decoded row group may hold dates outside the year range or months
+    let mut filter_dates = months.is_some();
+    for (index, row_group) in metadata.metadata().row_groups().iter().enumerate() {
+        let rows = row_group.num_rows() as usize;
+        let ruled_out = outside_years(row_group, &columns.date, start_year, end_year)
+            || excludes_countries(row_group, &columns.country, countries);
+        if !config.strict && ruled_out {
+            plan.pruned += 1;
+            plan.pruned_rows += rows;
+        } else {
+            filter_dates |= !year_range(row_group, &columns.date)
+                .is_some_and(|(min, max)| min >= start_year && max <= end_year);
+            plan.read.push((index, start, rows));
+        }
+        start += rows;
+    }
+    if !config.strict && (filter_dates || !countries.is_all()) {
+        plan.filter = Some(RowPredicate {
+            countries: countries.include.to_vec(),
+            excluded: countries.exclude.to_vec(),
+            dates: filter_dates.then_some(dates),
+        });
+    }
+    plan.metadata = Some(metadata);
+    Ok(plan)
+}
+
+impl BatchPlan<'_> {
+    /// Number of chunks the input can be decoded in: its row groups to decode, or 1 for CSV
+    /// and NDJSON inputs.
+    pub fn chunks(&self) -> usize {
+        match self.metadata {
+            Some(_) => self.read.len(),
+            None => 1,
+        }
+    }
+
+    /// Format the input is read in.
+    pub fn format(&self) -> InputFormat {
+        self.input.resolved_format()
+    }
+
+    /// Opens the whole input as a stream of record batches.
+    ///
+    /// # Returns
+    ///
+    /// Returns the batches and the row groups they are decoded from.
+    pub fn open(&self) -> Result<(BatchReader, RowGroups)> {
+        self.open_chunks(0..self.chunks())
+    }
+
+    /// Opens the chunks in `range` (see [`BatchPlan::chunks`]) as a stream of record batches.
+    pub fn open_chunks(&self, range: Range<usize>) -> Result<(BatchReader, RowGroups)> {
+        let (input, config) = (self.input, self.config);
+        let Some(metadata) = &self.metadata else {
+            let row_groups = RowGroups {
+                bytes: fs::metadata(&input.path)?.len(),
+                ..RowGroups::default()
+            };
+            let reader: BatchReader = match input.resolved_format() {
+                InputFormat::Csv => Box::new(read_csv(input, config)?),
+                _ => Box::new(read_ndjson(input, config)?),
+            };
+            return Ok((reader, row_groups));
+        };
+        let columns = &input.columns;
+        let mut needed = vec![
+            columns.date.as_str(),
+            columns.country.as_str(),
+            columns.temp.as_str(),
+        ];
+        if config.aridity {
+            needed.push(&columns.precip);
+        }
+        let mut row_groups = RowGroups {
+            read: self.read[range].to_vec(),
+            ..RowGroups::default()
+        };
+        for &(index, ..) in &row_groups.read {
+            row_groups.bytes += metadata
+                .metadata()
+                .row_group(index)
+                .columns()
+                .iter()
+                .filter(|column| needed.contains(&column.column_path().string().as_str()))
+                .map(|column| column.compressed_size().max(0) as u64)
+                .sum::<u64>();
+        }
+        let builder = ParquetRecordBatchReaderBuilder::new_with_metadata(
+            File::open(&input.path)?,
+            metadata.clone(),
+        );
+        let schema = builder.parquet_schema();
+        let leaves = |names: &[&str]| {
+            let leaves: Vec<usize> = schema
+                .columns()
+                .iter()
+                .enumerate()
+                .filter(|(_, column)| names.contains(&column.path().string().as_str()))
+                .map(|(leaf, _)| leaf)
+                .collect();
+            ProjectionMask::leaves(schema, leaves)
+        };
+        let projection = leaves(&needed);
+        let filter = self.filter.clone().map(|predicate| {
+            let mask = leaves(&[&columns.date, &columns.country]);
+            let columns = columns.clone();
+            let dropped = row_groups.row_filtered.clone();
+            RowFilter::new(vec![Box::new(ArrowPredicateFn::new(
+                mask,
+                move |batch: RecordBatch| {
+                    let countries = CountryFilter {
+                        include: &predicate.countries,
+                        exclude: &predicate.excluded,
+                    };
+                    let keep = keep_rows(&batch, &columns, countries, predicate.dates)?;
+                    dropped.fetch_add(batch.num_rows() - keep.true_count(), Ordering::Relaxed);
+                    Ok(keep)
+                },
+            ))])
+        });
+        let mut builder = builder
+            .with_projection(projection)
+            .with_row_groups(row_groups.read.iter().map(|&(index, ..)| index).collect());
+        if let Some(filter) = filter {
+            builder = builder.with_row_filter(filter);
+        }
+        Ok((Box::new(builder.build()?), row_groups))
+    }
+}
+
+/// Rows of a batch kept by the Parquet row filter: those of a country `countries` keeps and,
+/// given `dates`, dated within their years and months. Rows whose country or date is null, or
+/// whose date is not a valid `YYYY-MM-DD`, are kept for the row loop, which reports
+/// and counts them like any other; so are all rows of a column that is neither a string nor a
+/// date column.
+fn keep_rows(
+    batch: &RecordBatch,
+    columns: &ColumnMapping,
+    countries: CountryFilter<'_>,
+    dates: Option<DateFilter>,
+) -> std::result::Result<BooleanArray, ArrowError> {
+    let len = batch.num_rows();
+    let strings = |name: &str| {
+        batch
+            .column_by_name(name)
+            .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+    };
+    let valid = |nulls: Option<&NullBuffer>| {
+        nulls.map_or_else(
+            || BooleanBuffer::new_set(len),
+            |nulls| nulls.inner().clone(),
+        )
+    };
+    let mut keep = BooleanBuffer::new_set(len);
+    let date_col = batch
+        .column_by_name(&columns.date)
+        .and_then(DateColumn::new);
+    if let (Some(dates), Some(date_col)) = (dates, date_col) {
+        let (parsed, strict) = date_col.days()?;
+        let known = &valid(parsed.nulls()) & &strict;
+        keep = &keep & &(&!&known | &dates.mask(&parsed)?);
+    }
+    if let (false, Some(country_col)) = (countries.is_all(), strings(&columns.country)) {
+        keep = &keep & &(&!&valid(country_col.nulls()) | &countries.mask(country_col)?);
+    }
+    Ok(BooleanArray::new(keep, None))
+}
+
+/// Whether the min/max statistics of a row group's date column rule out every year from
+/// `start_year` to `end_year`.
+fn outside_years(
+    row_group: &RowGroupMetaData,
+    date_column: &str,
+    start_year: i32,
+    end_year: i32,
+) -> bool {
+    year_range(row_group, date_column).is_some_and(|(min, max)| max < start_year || min > end_year)
+}
+
+/// Years of the minimum and maximum of a row group's date column, if its statistics hold them.
+///
+/// `%Y-%m-%d` strings sort by year, so every row's year lies between the years of the minimum
+/// and maximum; strings whose bounds do not start with a four-digit year have no range. `DATE`
+/// and `TIMESTAMP` columns (at their UTC date) give the years of their bounds. Row groups
+/// without statistics have no range.
+fn year_range(row_group: &RowGroupMetaData, date_column: &str) -> Option<(i32, i32)> {
+    let column = column_chunk(row_group, date_column)?;
+    let stats = column
+        .statistics()
+        .filter(|stats| stats.has_min_max_set())?;
+    match (stats, column.column_descr().logical_type()) {
+        (Statistics::ByteArray(_), _) => Some((
+            year_prefix(stats.min_bytes())?,
+            year_prefix(stats.max_bytes())?,
+        )),
+        (Statistics::Int32(days), Some(LogicalType::Date)) => {
+            let year = |days: i32| {
+                UNIX_EPOCH_DATE
+                    .checked_add_signed(TimeDelta::try_days(days.into())?)
+                    .map(|date| date.year())
+            };
+            Some((year(*days.min())?, year(*days.max())?))
+        }
+        (Statistics::Int64(values), Some(LogicalType::Timestamp { unit, .. })) => {
+            let year = |value: i64| {
+                let timestamp = match unit {
+                    TimeUnit::MILLIS(_) => DateTime::from_timestamp_millis(value),
+                    TimeUnit::MICROS(_) => DateTime::from_timestamp_micros(value),
+                    TimeUnit::NANOS(_) => Some(DateTime::from_timestamp_nanos(value)),
+                };
+                timestamp.map(|timestamp| timestamp.year())
+            };
+            Some((year(*values.min())?, year(*values.max())?))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the min/max statistics of a row group's country column rule out every country
+/// `countries` keeps: every listed country lies outside them, or they hold a single excluded
+/// country. Never when every country is kept.
+fn excludes_countries(
+    row_group: &RowGroupMetaData,
+    country_column: &str,
+    countries: CountryFilter<'_>,
+) -> bool {
+    !countries.is_all()
+        && value_range(row_group, country_column).is_some_and(|(min, max)| {
+            let outside = |country: &String| {
+                country.as_bytes() < min.as_slice() || country.as_bytes() > max.as_slice()
+            };
+            let excluded = min == max
+                && countries
+                    .exclude
+                    .iter()
+                    .any(|c| c.as_bytes() == min.as_slice());
+            excluded || (!countries.include.is_empty() && countries.include.iter().all(outside))
+        })
+}
+
+/// Year of a date string starting with `YYYY-`.
+fn year_prefix(date: &[u8]) -> Option<i32> {
+    let (year, rest) = date.split_at_checked(4)?;
+    if !(rest.starts_with(b"-") && year.iter().all(u8::is_ascii_digit)) {
+        return None;
+    }
+    std::str::from_utf8(year).ok()?.parse().ok()
+}
+
+/// Reads a CSV input with a header row and the columns of the Parquet input; the mapped
+/// temperature column (and precipitation column when `config.aridity` is set) is read as
+/// Float64 and every other column as text.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Io` if the file cannot be opened and `PipelineError::Arrow` if its
+/// header cannot be read.
+pub fn read_csv(input: &InputSource, config: &TransformConfig) -> Result<arrow_csv::Reader<File>> {
+    let format = Format::default().with_header(true);
+    let (inferred, _) = format.infer_schema(File::open(&input.path)?, Some(CSV_INFER_ROWS))?;
+    let fields: Vec<Field> = inferred
+        .fields()
+        .iter()
+        .map(|field| {
+            let numeric = *field.name() == input.columns.temp
+                || (config.aridity && *field.name() == input.columns.precip);
+            let data_type = if numeric {
+                DataType::Float64
+            } else {
+                DataType::Utf8
+            };
+            Field::new(field.name(), data_type, true)
+        })
+        .collect();
+    Ok(ReaderBuilder::new(Arc::new(Schema::new(fields)))
+        .with_format(format)
+        .build(File::open(&input.path)?)?)
+}
+
+/// Record batches of a newline-delimited JSON input, see [`read_ndjson`]
+pub struct NdjsonBatches {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    /// Number of the last line read (1-based)
+    line: usize,
+    columns: ColumnMapping,
+    schema: SchemaRef,
+}
+
+impl NdjsonBatches {
+    /// Reads up to `NDJSON_BATCH_ROWS` objects into a batch, `None` at the end of the file.
+    fn next_batch(&mut self) -> std::result::Result<Option<RecordBatch>, ArrowError> {
+        let mut dates = StringBuilder::new();
+        let mut countries = StringBuilder::new();
+        let mut temps = Float64Builder::new();
+        let mut precips = Float64Builder::new();
+        let mut rows = 0;
+        while rows < NDJSON_BATCH_ROWS {
+            let Some(line) = self.lines.next() else {
+                break;
+            };
+            self.line += 1;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row: Value = serde_json::from_str(&line).map_err(|e| {
+                ArrowError::JsonError(format!("{} line {}: {}", self.path.display(), self.line, e))
+            })?;
+            dates.append_option(row.get(&self.columns.date).and_then(Value::as_str));
+            countries.append_option(row.get(&self.columns.country).and_then(Value::as_str));
+            temps.append_option(row.get(&self.columns.temp).and_then(Value::as_f64));
+            precips.append_option(row.get(&self.columns.precip).and_then(Value::as_f64));
+            rows += 1;
+        }
+        if rows == 0 {
+            return Ok(None);
+        }
+        let mut arrays: Vec<ArrayRef> = vec![
+            Arc::new(dates.finish()),
+            Arc::new(countries.finish()),
+            Arc::new(temps.finish()),
+        ];
+        if self.schema.fields().len() > arrays.len() {
+            arrays.push(Arc::new(precips.finish()));
+        }
+        RecordBatch::try_new(self.schema.clone(), arrays).map(Some)
+    }
+}
+
+impl Iterator for NdjsonBatches {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
+
+/// Reads a newline-delimited JSON input, one object per line, into batches with the columns
+/// of the Parquet input.
+///
+/// The mapped date and country fields are read as strings and the temperature field (and
+/// precipitation field when `config.aridity` is set) as a number; missing fields, or values of
+/// another type, are nulls, skipped like nulls in the Parquet input. Blank lines are ignored.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Io` if the file cannot be opened; a line that is not valid JSON
+/// fails its batch with `ArrowError::JsonError`, giving the line number.
+pub fn read_ndjson(input: &InputSource, config: &TransformConfig) -> Result<NdjsonBatches> {
+    let columns = &input.columns;
+    let mut fields = vec![
+        Field::new(&columns.date, DataType::Utf8, true),
+        Field::new(&columns.country, DataType::Utf8, true),
+        Field::new(&columns.temp, DataType::Float64, true),
+    ];
+    if config.aridity {
+        fields.push(Field::new(&columns.precip, DataType::Float64, true));
+    }
+    Ok(NdjsonBatches {
+        path: input.path.clone(),
+        lines: BufReader::new(File::open(&input.path)?).lines(),
+        line: 0,
+        columns: columns.clone(),
+        schema: Arc::new(Schema::new(fields)),
+    })
+}
+
+/// Fails fast when no requested country can occur in the inputs, before any row is decoded.
+///
+/// The min/max statistics of the country column are checked in every row group of every
+/// input; only when all of them rule out every country `countries` keeps is the country
+/// column read on its own to list the countries the inputs do hold. Inputs without statistics,
+/// and CSV and NDJSON inputs, are assumed to hold any country.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` listing the available countries if none of the listed and
+/// not excluded countries occurs, and `PipelineError::Io` / `PipelineError::Parquet` if the metadata cannot be read.
+pub(crate) fn check_countries(inputs: &[InputSource], countries: CountryFilter<'_>) -> Result<()> {
+    let parquet = |input: &InputSource| input.resolved_format() == InputFormat::Parquet;
+    if countries.include.is_empty() || !inputs.iter().all(parquet) {
+        return Ok(());
+    }
+    for input in inputs {
+        let reader = SerializedFileReader::new(File::open(&input.path)?)?;
+        for row_group in reader.metadata().row_groups() {
+            if !excludes_countries(row_group, &input.columns.country, countries) {
+                return Ok(());
+            }
+        }
+    }
+    let Some(available) = distinct_countries(inputs)? else {
+        return Ok(());
+    };
+    Err(PipelineError::Data(format!(
+        "none of the requested countries ({}) occur in the input; available countries: {}",
+        countries
+            .include
+            .iter()
+            .filter(|country| countries.contains(country))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", "),
+        available.into_iter().collect::<Vec<_>>().join(", ")
+    )))
+}
+
+/// Minimum and maximum of a string column in a row group, if its statistics hold them.
+fn value_range(row_group: &RowGroupMetaData, column: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    match column_chunk(row_group, column)?.statistics()? {
+        stats @ Statistics::ByteArray(_) if stats.has_min_max_set() => {
+            Some((stats.min_bytes().to_vec(), stats.max_bytes().to_vec()))
+        }
+        _ => None,
+    }
+}
+
+/// Metadata of the chunk of column `column` in a row group.
+fn column_chunk<'a>(
+    row_group: &'a RowGroupMetaData,
+    column: &str,
+) -> Option<&'a ColumnChunkMetaData> {
+    row_group
+        .columns()
+        .iter()
+        .find(|c| c.column_path().string() == column)
+}
+
+/// Distinct values of the country column of every input, reading that column only; `None`
+/// if an input lacks the column or it is not a string column, which the scan reports.
+fn distinct_countries(inputs: &[InputSource]) -> Result<Option<BTreeSet<String>>> {
+    let mut countries = BTreeSet::new();
+    for input in inputs {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&input.path)?)?;
+        let schema = builder.parquet_schema();
+        let Some(leaf) = schema
+            .columns()
+            .iter()
+            .position(|c| c.path().string() == input.columns.country)
+        else {
+            return Ok(None);
+        };
+        let mask = ProjectionMask::leaves(schema, [leaf]);
+        for batch in builder.with_projection(mask).build()? {
+            let batch = batch?;
+            let Some(column) = batch.column(0).as_any().downcast_ref::<StringArray>() else {
+                return Ok(None);
+            };
+            countries.extend(column.iter().flatten().map(str::to_string));
+        }
+    }
+    Ok(Some(countries))
+}
+
+/// Distinct countries of the inputs, without running the pipeline.
+///
+/// A Parquet row group whose country statistics have equal minimum and maximum contributes
+/// that country without being decoded; the other row groups decode the country column only.
+/// CSV and NDJSON inputs are read in full.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if an input lacks the mapped country column or it is not a
+/// string column, and `PipelineError::Io` / `PipelineError::Parquet` if an input cannot be read.
+pub fn list_countries(inputs: &[InputSource]) -> Result<BTreeSet<String>> {
+    let mut countries = BTreeSet::new();
+    for input in inputs {
+        distinct_values(
+            input,
+            &input.columns.country,
+            &mut countries,
+            |row_group| {
+                let (min, max) = value_range(row_group, &input.columns.country)?;
+                (min == max).then(|| String::from_utf8(min).ok()).flatten()
+            },
+            |country| Some(country.to_string()),
+        )?;
+    }
+    Ok(countries)
+}
+
+/// Distinct years of the `%Y-%m-%d` (or native date) dates of the inputs, without running the
+/// pipeline.
+///
+/// A Parquet row group whose date statistics start with the same year contributes that year
+/// without being decoded; the other row groups decode the date column only. Unparsable dates
+/// are ignored. CSV and NDJSON inputs are read in full.
+///
+/// # Errors
+///
+/// Returns the same errors as [`list_countries`] for the mapped date column.
+pub fn list_years(inputs: &[InputSource]) -> Result<BTreeSet<i32>> {
+    let mut years = BTreeSet::new();
+    for input in inputs {
+        distinct_values(
+            input,
+            &input.columns.date,
+            &mut years,
+            |row_group| {
+                let (min, max) = year_range(row_group, &input.columns.date)?;
+                (min == max).then_some(min)
+            },
+            |date| {
+                NaiveDate::parse_from_str(date, DATE_FORMAT)
+                    .ok()
+                    .map(|date| date.year())
+            },
+        )?;
+    }
+    Ok(years)
+}
+
+/// Adds the distinct values of a string column of `input` to `values`; native date columns
+/// are read as `DATE_FORMAT` strings.
+///
+/// `from_stats` maps the statistics of a Parquet row group to the one value the row group can
+/// hold, if they determine it; row groups it resolves are not decoded. `parse` maps each
+/// decoded value, skipping those it rejects.
+fn distinct_values<T: Ord>(
+    input: &InputSource,
+    column: &str,
+    values: &mut BTreeSet<T>,
+    from_stats: impl Fn(&RowGroupMetaData) -> Option<T>,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<()> {
+    let missing = || {
+        PipelineError::Data(format!(
+            "{} has no string or date column '{}' (see --column-map)",
+            input.path.display(),
+            column
+        ))
+    };
+    if input.resolved_format() != InputFormat::Parquet {
+        let config = TransformConfig::default();
+        let (reader, _) =
+            plan_batches(input, &config, CountryFilter::ALL, DateFilter::ALL)?.open()?;
+        for batch in reader {
+            let batch = batch?;
+            let strings = batch
+                .column_by_name(column)
+                .and_then(as_strings)
+                .ok_or_else(missing)?;
+            values.extend(strings.iter().flatten().filter_map(&parse));
+        }
+        return Ok(());
+    }
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&input.path)?)?;
+    let mut decode = Vec::new();
+    for (index, row_group) in builder.metadata().row_groups().iter().enumerate() {
+        match from_stats(row_group) {
+            Some(value) => {
+                values.insert(value);
+            }
+            None => decode.push(index),
+        }
+    }
+    debug!(
+        "{}: {} row groups resolved by statistics, {} decoded",
+        input.path.display(),
+        builder.metadata().num_row_groups() - decode.len(),
+        decode.len()
+    );
+    if decode.is_empty() {
+        return Ok(());
+    }
+    let schema = builder.parquet_schema();
+    let leaf = schema
+        .columns()
+        .iter()
+        .position(|c| c.path().string() == column)
+        .ok_or_else(missing)?;
+    let mask = ProjectionMask::leaves(schema, [leaf]);
+    for batch in builder
+        .with_row_groups(decode)
+        .with_projection(mask)
+        .build()?
+    {
+        let batch = batch?;
+        let strings = as_strings(batch.column(0)).ok_or_else(missing)?;
+        values.extend(strings.iter().flatten().filter_map(&parse));
+    }
+    Ok(())
+}
+
+/// A string column, or a native date column formatted as `DATE_FORMAT` strings; `None` for
+/// other columns.
+fn as_strings(column: &ArrayRef) -> Option<StringArray> {
+    match DateColumn::new(column)? {
+        DateColumn::Text(strings) => Some(strings.clone()),
+        DateColumn::Days(days) => {
+            let strings = arrow_cast::cast(&days, &DataType::Utf8).ok()?;
+            Some(strings.as_string::<i32>().clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::process_inputs_with_stats;
+    use std::path::Path;
+
+    fn write_input(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "transformer-extract-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const READINGS: &str = "date,country_alpha2,temp_mean_c_approx,station\n\
+                            2020-01-01,DE,1,10\n\
+                            2020-01-02,DE,2.5,10\n\
+                            2020-01-03,FR,7,20\n";
+
+    #[test]
+    fn detects_csv_by_extension_or_forced_format() {
+        assert!(InputSource::new(Path::new("weather.CSV")).is_csv());
+        assert!(!InputSource::new(Path::new("weather.parquet")).is_csv());
+        let mut forced = InputSource::new(Path::new("weather.txt"));
+        assert!(!forced.is_csv());
+        forced.format = InputFormat::Csv;
+        assert!(forced.is_csv());
+    }
+
+    #[test]
+    fn reads_mapped_temperature_as_float_and_other_columns_as_text() {
+        let path = write_input("typed.csv", READINGS);
+        let input = InputSource::new(&path);
+
+        let batch = read_csv(&input, &TransformConfig::default())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let schema = batch.schema();
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(2).data_type(), &DataType::Float64);
+        assert_eq!(schema.field(3).data_type(), &DataType::Utf8);
+        let temps = batch
+            .column(2)
+            .as_primitive::<arrow_array::types::Float64Type>();
+        assert_eq!(temps.values().to_vec(), vec![1.0, 2.5, 7.0]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn aggregates_csv_input_like_parquet() {
+        let path = write_input("aggregate.csv", READINGS);
+
+        let (records, stats) = process_inputs_with_stats(
+            &[InputSource::new(&path)],
+            &[],
+            &[],
+            2020,
+            2020,
+            None,
+            &TransformConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(stats.total_rows, 3);
+        let de = records.iter().find(|r| r.country == "DE").unwrap();
+        assert_eq!((de.year, de.month, de.count), (2020, 1, 2));
+        assert!((de.avg_temp - 1.75).abs() < 1e-9);
+        assert_eq!(records.len(), 2);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use crate::countries::{countries, country_info};
+use crate::error::{PipelineError, Result};
+use crate::structs::Record;
+use serde_json::{Map, Value as Json, json};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Property keys checked (in order) to find a feature's alpha-2 code in a base GeoJSON
+const ISO_KEYS: [&str; 5] = [
+    "ISO_A2",
+    "iso_a2",
+    "ISO_A2_EH",
+    "ISO3166-1-Alpha-2",
+    "alpha2",
+];
+
+/// Per-country values joined onto GeoJSON features
+#[derive(Debug, Default)]
+struct CountryStats {
+    /// Sum and count of monthly means per year
+    annual: BTreeMap<i32, (f64, u32)>,
+    /// Sum and count of monthly anomalies per year
+    anomaly: BTreeMap<i32, (f64, u32)>,
+}
+
+/// Writes a choropleth-ready GeoJSON with per-country annual means (and anomalies).
+///
+/// Each feature gets `mean` (mean of all monthly means), `mean_<year>` per year, and, when
+/// baseline means are supplied, `anomaly` and `anomaly_<year>` relative to the baseline
+/// calendar-month means.
+///
+/// # Arguments
+/// * `results` - Monthly records (records with collapsed year/month are ignored)
+/// * `baseline` - Optional baseline means per `(country, month)` for anomalies
+/// * `base_geojson` - Optional FeatureCollection whose features carry an alpha-2 property
+///   (e.g. Natural Earth `ISO_A2`); defaults to bundled capital-city Point features
+/// * `output_path` - Path where the GeoJSON file will be created
+///
+/// # Returns
+/// Returns the number of features that received statistics.
+///
+/// # Errors
+/// Returns error if the base file cannot be read or is not a FeatureCollection, or if the
+/// output cannot be written.
+pub fn write_choropleth_geojson(
+    results: &[Record],
+    baseline: Option<&HashMap<(String, u32), f64>>,
+    base_geojson: Option<&Path>,
+    output_path: &Path,
+) -> Result<usize> {
+    let mut by_country: HashMap<&str, CountryStats> = HashMap::new();
+    for record in results.iter().filter(|r| r.year != 0 && r.month != 0) {
+        let entry = by_country.entry(&record.country).or_default();
+        let annual = entry.annual.entry(record.year).or_default();
+        annual.0 += record.avg_temp;
+        annual.1 += 1;
+        if let Some(mean) = baseline.and_then(|b| b.get(&(record.country.clone(), record.month))) {
+            let anomaly = entry.anomaly.entry(record.year).or_default();
+            anomaly.0 += record.avg_temp - mean;
+            anomaly.1 += 1;
+        }
+    }
+
+    let mut collection = match base_geojson {
+        Some(path) => serde_json::from_reader(std::io::BufReader::new(File::open(path)?))?,
+        None => bundled_points(),
+    };
+    let features = collection
+        .get_mut("features")
+        .and_then(Json::as_array_mut)
+        .ok_or_else(|| {
+            PipelineError::Data("Base GeoJSON is not a FeatureCollection".to_string())
+        })?;
+
+    let mut joined = 0;
+    for feature in features.iter_mut() {
+        let Some(properties) = feature.get_mut("properties").and_then(Json::as_object_mut) else {
+            continue;
+        };
+        let Some(code) = ISO_KEYS
+            .iter()
+            .find_map(|key| properties.get(*key).and_then(Json::as_str))
+            .map(str::to_ascii_uppercase)
+        else {
+            continue;
+        };
+        if let Some(stats) = by_country.get(code.as_str()) {
+            add_properties(properties, stats);
+            joined += 1;
+        }
+    }
+
+    serde_json::to_writer(BufWriter::new(File::create(output_path)?), &collection)?;
+    Ok(joined)
+}
+
+/// Adds mean/anomaly properties for one country to a feature.
+fn add_properties(properties: &mut Map<String, Json>, stats: &CountryStats) {
+    let mean_of = |values: &BTreeMap<i32, (f64, u32)>| {
+        let (sum, n) = values
+            .values()
+            .fold((0.0, 0), |(s, c), (sum, n)| (s + sum, c + n));
+        (n > 0).then(|| sum / n as f64)
+    };
+
+    properties.insert("mean".to_string(), json!(mean_of(&stats.annual)));
+    for (year, (sum, n)) in &stats.annual {
+        properties.insert(format!("mean_{}", year), json!(sum / *n as f64));
+    }
+    if !stats.anomaly.is_empty() {
+        properties.insert("anomaly".to_string(), json!(mean_of(&stats.anomaly)));
+        for (year, (sum, n)) in &stats.anomaly {
+            properties.insert(format!("anomaly_{}", year), json!(sum / *n as f64));
+        }
+    }
+}
+
+/// Builds a FeatureCollection of capital-city points from the bundled country table.
+fn bundled_points() -> Json {
+    let mut codes: Vec<&String> = countries().keys().collect();
+    codes.sort();
+    let features: Vec<Json> = codes
+        .into_iter()
+        .filter_map(|code| country_info(code))
+        .map(|info| {
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [info.lon, info.lat] },
+                "properties": { "iso_a2": info.alpha2, "name": info.name },
+            })
+        })
+        .collect();
+    json!({ "type": "FeatureCollection", "features": features })
+}
@@ -0,0 +1,288 @@
+use log::debug;
+use std::sync::{Mutex, OnceLock, mpsc};
+use wgpu::util::DeviceExt;
+
+/// Threads per workgroup of the sort shader
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Largest workgroup count of one dispatch dimension guaranteed by WebGPU
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65_535;
+
+/// One compare-and-swap step of a bitonic sort over 64-bit keys stored as `(high, low)` words.
+///
+/// Keys are the order-preserving encoding of `f64` values (see [`order_key`]), since WGSL has
+/// no portable 64-bit float or integer type.
+const SORT_SHADER: &str = r#"
+struct Step {
+    j: u32,
+    k: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> keys: array<vec2<u32>>;
+@group(0) @binding(1) var<uniform> step: Step;
+
+fn greater(a: vec2<u32>, b: vec2<u32>) -> bool {
+    return a.x > b.x || (a.x == b.x && a.y > b.y);
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x + id.y * 65535u * 256u;
+    let partner = i ^ step.j;
+    if (i >= arrayLength(&keys) || partner <= i) {
+        return;
+    }
+    let a = keys[i];
+    let b = keys[partner];
+    let ascending = (i & step.k) == 0u;
+    if (greater(a, b) == ascending) {
+        keys[i] = b;
+        keys[partner] = a;
+    }
+}
+"#;
+
+/// GPU device with the compiled sort pipeline
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    /// Serializes sorts, which would otherwise compete for device memory
+    lock: Mutex<()>,
+}
+
+/// Sorts `data` ascending on the GPU.
+///
+/// # Returns
+///
+/// Returns `None` when no GPU adapter is available, the data does not fit in a storage buffer,
+/// or the device reports an error, in which case the caller sorts on the CPU.
+pub(crate) fn sort(data: &[f64]) -> Option<Vec<f64>> {
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    let gpu = GPU.get_or_init(Gpu::open).as_ref()?;
+    let _guard = gpu.lock.lock().ok()?;
+    let sorted = gpu.sort(data);
+    if sorted.is_none() {
+        debug!(
+            "GPU sort of {} readings failed, sorting on the CPU",
+            data.len()
+        );
+    }
+    sorted
+}
+
+impl Gpu {
+    fn open() -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = match pollster::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        ) {
+            Ok(adapter) => adapter,
+            Err(e) => {
+                debug!("No GPU adapter ({}), sorting on the CPU", e);
+                return None;
+            }
+        };
+        debug!("Sorting large groups on {}", adapter.get_info().name);
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("transformer"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .map_err(|e| debug!("GPU device request failed ({}), sorting on the CPU", e))
+        .ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("bitonic sort"),
+            source: wgpu::ShaderSource::Wgsl(SORT_SHADER.into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bitonic sort"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(8),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("bitonic sort"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("bitonic sort"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn sort(&self, data: &[f64]) -> Option<Vec<f64>> {
+        // Pad to a power of two with keys above every finite value
+        let len = data.len().checked_next_power_of_two()?;
+        let size = (len * 8) as u64;
+        let limits = self.device.limits();
+        if size > limits.max_storage_buffer_binding_size as u64 || size > limits.max_buffer_size {
+            return None;
+        }
+        let mut contents = Vec::with_capacity(len * 8);
+        for key in data
+            .iter()
+            .map(|&value| order_key(value))
+            .chain(std::iter::repeat(u64::MAX))
+        {
+            if contents.len() == len * 8 {
+                break;
+            }
+            contents.extend_from_slice(&((key >> 32) as u32).to_le_bytes());
+            contents.extend_from_slice(&(key as u32).to_le_bytes());
+        }
+
+        // One uniform slot per (j, k) step, addressed by dynamic offset
+        let stride = limits.min_uniform_buffer_offset_alignment.max(8) as usize;
+        let mut steps = Vec::new();
+        let mut k = 2;
+        while k <= len {
+            let mut j = k / 2;
+            while j > 0 {
+                let offset = steps.len();
+                steps.resize(offset + stride, 0);
+                steps[offset..offset + 4].copy_from_slice(&(j as u32).to_le_bytes());
+                steps[offset + 4..offset + 8].copy_from_slice(&(k as u32).to_le_bytes());
+                j /= 2;
+            }
+            k *= 2;
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let keys = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("sort keys"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("sort steps"),
+                contents: &steps,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sort readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bitonic sort"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: keys.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &params,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(8),
+                    }),
+                },
+            ],
+        });
+
+        let workgroups = (len as u32).div_ceil(WORKGROUP_SIZE);
+        let (x, y) = (
+            workgroups.min(MAX_WORKGROUPS_PER_DIMENSION),
+            workgroups.div_ceil(MAX_WORKGROUPS_PER_DIMENSION),
+        );
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            for offset in (0..steps.len()).step_by(stride) {
+                pass.set_bind_group(0, &bind_group, &[offset as u32]);
+                pass.dispatch_workgroups(x, y, 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(&keys, 0, &readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = mpsc::channel();
+        readback.map_async(wgpu::MapMode::Read, .., move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::Wait).ok()?;
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+        if let Some(error) = validation.or(out_of_memory) {
+            debug!("GPU sort error: {}", error);
+            return None;
+        }
+        receiver.recv().ok()?.ok()?;
+
+        let mapped = readback.get_mapped_range(..);
+        let sorted = mapped
+            .chunks_exact(8)
+            .take(data.len())
+            .map(|word| {
+                let high = u32::from_le_bytes(word[0..4].try_into().unwrap()) as u64;
+                let low = u32::from_le_bytes(word[4..8].try_into().unwrap()) as u64;
+                from_order_key(high << 32 | low)
+            })
+            .collect();
+        drop(mapped);
+        readback.unmap();
+        Some(sorted)
+    }
+}
+
+/// Maps an `f64` to a `u64` with the same order: the sign bit is flipped for non-negative
+/// values and all bits are flipped for negative ones.
+fn order_key(value: f64) -> u64 {
+    // Adding 0.0 turns -0.0 into 0.0, which `partial_cmp` treats as equal
+    let bits = (value + 0.0).to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | 1 << 63
+    }
+}
+
+fn from_order_key(key: u64) -> f64 {
+    if key >> 63 == 1 {
+        f64::from_bits(key & !(1 << 63))
+    } else {
+        f64::from_bits(!key)
+    }
+}
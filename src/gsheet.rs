@@ -0,0 +1,120 @@
+use crate::error::{PipelineError, Result};
+use crate::schema::{Value, output_columns};
+use crate::structs::{OutputConfig, Record};
+use log::debug;
+use serde_json::{Value as Json, json};
+
+/// Base URL of the Sheets API spreadsheet resources
+const SHEETS_API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+/// Maximum number of cells in a spreadsheet
+const MAX_CELLS: usize = 10_000_000;
+
+/// Replaces the contents of one sheet (tab) of a Google Sheet with the records.
+///
+/// The sheet is cleared and rewritten from `A1` with a header row of the CSV column names,
+/// so a dashboard reading it always sees the latest run. `token` is an OAuth 2.0 access token
+/// with the `spreadsheets` scope (e.g. from `gcloud auth print-access-token`).
+///
+/// # Returns
+///
+/// Returns the number of cells written.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if the results exceed the spreadsheet cell limit, or
+/// `PipelineError::Http` if the Sheets API rejects a request.
+pub fn write_gsheet(
+    results: &[Record],
+    spreadsheet_id: &str,
+    sheet: &str,
+    token: &str,
+    config: &OutputConfig,
+) -> Result<usize> {
+    let columns = output_columns(config);
+    let cells = (results.len() + 1) * columns.len();
+    if cells > MAX_CELLS {
+        return Err(PipelineError::Data(format!(
+            "{} records ({} cells) exceed the Google Sheets limit of {} cells",
+            results.len(),
+            cells,
+            MAX_CELLS
+        )));
+    }
+
+    let mut rows = Vec::with_capacity(results.len() + 1);
+    rows.push(Json::Array(
+        columns.iter().map(|c| Json::from(c.csv_header())).collect(),
+    ));
+    for record in results {
+        rows.push(Json::Array(
+            columns
+                .iter()
+                .map(|c| match c.value(record) {
+                    Value::Utf8(v) => Json::from(v),
+                    Value::Int32(v) => Json::from(v),
+                    Value::UInt32(v) => Json::from(v),
+                    Value::Float64(v) => Json::from(v),
+                    Value::Null => Json::from(config.null_value.as_deref().unwrap_or("")),
+                })
+                .collect(),
+        ));
+    }
+
+    let range = encode_range(&format!("'{}'", sheet.replace('\'', "''")));
+    let base = format!("{}/{}/values/{}", SHEETS_API, spreadsheet_id, range);
+    send(ureq::post(format!("{}:clear", base)), token, &json!({}))?;
+    debug!("Cleared sheet {} of spreadsheet {}", sheet, spreadsheet_id);
+    send(
+        ureq::put(format!("{}?valueInputOption=RAW", base)),
+        token,
+        &json!({"majorDimension": "ROWS", "values": rows}),
+    )?;
+    Ok(cells)
+}
+
+/// Sends an authorized JSON request, returning the API's error message on failure.
+fn send(
+    request: ureq::RequestBuilder<ureq::typestate::WithBody>,
+    token: &str,
+    body: &Json,
+) -> Result<()> {
+    let url = request
+        .uri_ref()
+        .map_or(String::new(), |uri| uri.to_string());
+    let http_error = |reason: String| PipelineError::Http {
+        url: url.clone(),
+        reason,
+    };
+    let mut response = request
+        .config()
+        .http_status_as_error(false)
+        .build()
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Content-Type", "application/json")
+        .send(serde_json::to_vec(body)?)
+        .map_err(|e| http_error(e.to_string()))?;
+    if response.status().is_success() {
+        return Ok(());
+    }
+    let status = response.status();
+    let body = response.body_mut().read_to_string().unwrap_or_default();
+    let message = serde_json::from_str::<Json>(&body)
+        .ok()
+        .and_then(|error| error["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or(body);
+    Err(http_error(format!("{}: {}", status, message)))
+}
+
+/// Percent-encodes an A1 range for use as a URL path segment.
+fn encode_range(range: &str) -> String {
+    range
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'!' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
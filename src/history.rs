@@ -0,0 +1,158 @@
+use crate::error::{PipelineError, Result};
+use crate::structs::{Record, TemperatureUnit};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Header of the history file; rows of older runs must have been written with the same one
+pub const HISTORY_HEADER: [&str; 14] = [
+    "Run_Timestamp",
+    "Output",
+    "Countries_Filter",
+    "Start_Year",
+    "End_Year",
+    "Unit",
+    "Country",
+    "Records",
+    "Readings",
+    "First_Period",
+    "Last_Period",
+    "Mean_Temp",
+    "Min_Temp",
+    "Max_Temp",
+];
+
+/// Run-level columns of the history rows
+#[derive(Debug, Clone)]
+pub struct HistoryRun {
+    /// Start time of the run
+    pub timestamp: DateTime<Utc>,
+    /// Output name of the run
+    pub output: String,
+    /// Country filter, `ALL` when unfiltered, followed by `except <codes>` with exclusions
+    pub countries: String,
+    pub start_year: i32,
+    pub end_year: i32,
+    pub unit: TemperatureUnit,
+}
+
+/// Per-country totals of a run's records
+#[derive(Debug, Clone, Copy)]
+struct CountrySummary {
+    records: usize,
+    readings: u64,
+    weighted_sum: f64,
+    first: (i32, u32),
+    last: (i32, u32),
+    min: f64,
+    max: f64,
+}
+
+/// Appends one summary row per country of `results` to the CSV file at `path`.
+///
+/// Each row carries the run's timestamp and filters with the country's record and reading
+/// counts, first and last `year-month`, reading-weighted mean of `avg_temp`, and extreme
+/// temperatures, so recurring runs build up a longitudinal log. The header is written when the
+/// file is new or empty. The rows of a run are appended with a single write to a file opened
+/// in append mode, so concurrent runs do not interleave their rows and an existing history is
+/// never rewritten.
+///
+/// # Returns
+///
+/// Returns the number of rows appended.
+///
+/// # Errors
+/// Returns `PipelineError::Data` if the file starts with a different header (e.g. it is not a
+/// history file), or `PipelineError::Io` if it cannot be read or appended to. Missing parent
+/// directories are created.
+pub fn append_history(path: &Path, run: &HistoryRun, results: &[Record]) -> Result<usize> {
+    let mut countries: BTreeMap<&str, CountrySummary> = BTreeMap::new();
+    for record in results {
+        let period = (record.year, record.month);
+        let summary = countries
+            .entry(record.country.as_str())
+            .or_insert(CountrySummary {
+                records: 0,
+                readings: 0,
+                weighted_sum: 0.0,
+                first: period,
+                last: period,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+            });
+        summary.records += 1;
+        summary.readings += u64::from(record.count);
+        summary.weighted_sum += record.avg_temp * f64::from(record.count);
+        summary.first = summary.first.min(period);
+        summary.last = summary.last.max(period);
+        summary.min = summary.min.min(record.min_temp);
+        summary.max = summary.max.max(record.max_temp);
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if !has_header(path)? {
+        writer.write_record(HISTORY_HEADER)?;
+    }
+    let timestamp = run.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true);
+    for (country, summary) in &countries {
+        writer.write_record([
+            timestamp.clone(),
+            run.output.clone(),
+            run.countries.clone(),
+            run.start_year.to_string(),
+            run.end_year.to_string(),
+            run.unit.to_string(),
+            country.to_string(),
+            summary.records.to_string(),
+            summary.readings.to_string(),
+            format!("{}-{:02}", summary.first.0, summary.first.1),
+            format!("{}-{:02}", summary.last.0, summary.last.1),
+            format!(
+                "{:.2}",
+                summary.weighted_sum / summary.readings.max(1) as f64
+            ),
+            format!("{:.2}", summary.min),
+            format!("{:.2}", summary.max),
+        ])?;
+    }
+    let rows = writer
+        .into_inner()
+        .map_err(|e| PipelineError::Io(e.into_error()))?;
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&rows)?;
+    file.sync_data()?;
+    Ok(countries.len())
+}
+
+/// Whether the history file exists with content, checking that it starts with
+/// [`HISTORY_HEADER`].
+fn has_header(path: &Path) -> Result<bool> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut first = String::new();
+    BufReader::new(file).read_line(&mut first)?;
+    let first = first.trim_end_matches(['\r', '\n']);
+    if first.is_empty() {
+        return Ok(false);
+    }
+    if first != HISTORY_HEADER.join(",") {
+        return Err(PipelineError::Data(format!(
+            "{} is not a run history file (header: {})",
+            path.display(),
+            first
+        )));
+    }
+    Ok(true)
+}
@@ -0,0 +1,1039 @@
+use crate::error::{PipelineError, Result};
+use crate::schema::{Column, ColumnType, Value, output_columns};
+use crate::structs::{DedupePolicy, IcebergMode, OutputConfig, Record, StdDevMode};
+use crate::transform::mean_confidence_interval;
+use apache_avro::types::Value as AvroValue;
+use apache_avro::{Reader, Schema as AvroSchema, Writer};
+use arrow_array::builder::{Float64Builder, Int32Builder, Int64Builder, StringBuilder};
+use arrow_array::{
+    Array, ArrayRef, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{Value as Json, json};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source column of the identity partition
+const PARTITION_COLUMN: &str = "year";
+
+/// Field ID of the partition field (Iceberg reserves IDs from 1000 for partition fields)
+const PARTITION_FIELD_ID: i32 = 1000;
+
+/// Block size recorded for data files (required by format v1, ignored by readers)
+const BLOCK_SIZE_BYTES: i64 = 64 * 1024 * 1024;
+
+/// Entry status values in a manifest
+const STATUS_EXISTING: i32 = 0;
+const STATUS_ADDED: i32 = 1;
+const STATUS_DELETED: i32 = 2;
+
+/// Avro schema of a format v1 manifest entry
+const MANIFEST_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "manifest_entry",
+  "fields": [
+    {"name": "status", "type": "int", "field-id": 0},
+    {"name": "snapshot_id", "type": "long", "field-id": 1},
+    {"name": "data_file", "field-id": 2, "type": {
+      "type": "record",
+      "name": "r2",
+      "fields": [
+        {"name": "file_path", "type": "string", "field-id": 100},
+        {"name": "file_format", "type": "string", "field-id": 101},
+        {"name": "partition", "field-id": 102, "type": {
+          "type": "record",
+          "name": "r102",
+          "fields": [
+            {"name": "year", "type": ["null", "int"], "default": null, "field-id": 1000}
+          ]
+        }},
+        {"name": "record_count", "type": "long", "field-id": 103},
+        {"name": "file_size_in_bytes", "type": "long", "field-id": 104},
+        {"name": "block_size_in_bytes", "type": "long", "field-id": 105}
+      ]
+    }}
+  ]
+}"#;
+
+/// Avro schema of a format v1 manifest list entry
+const MANIFEST_LIST_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "manifest_file",
+  "fields": [
+    {"name": "manifest_path", "type": "string", "field-id": 500},
+    {"name": "manifest_length", "type": "long", "field-id": 501},
+    {"name": "partition_spec_id", "type": "int", "field-id": 502},
+    {"name": "added_snapshot_id", "type": ["null", "long"], "default": null, "field-id": 503},
+    {"name": "added_data_files_count", "type": ["null", "int"], "default": null, "field-id": 504},
+    {"name": "existing_data_files_count", "type": ["null", "int"], "default": null, "field-id": 505},
+    {"name": "deleted_data_files_count", "type": ["null", "int"], "default": null, "field-id": 506},
+    {"name": "partitions", "default": null, "field-id": 507, "type": ["null", {
+      "type": "array",
+      "element-id": 508,
+      "items": {
+        "type": "record",
+        "name": "r508",
+        "fields": [
+          {"name": "contains_null", "type": "boolean", "field-id": 509},
+          {"name": "lower_bound", "type": ["null", "bytes"], "default": null, "field-id": 510},
+          {"name": "upper_bound", "type": ["null", "bytes"], "default": null, "field-id": 511}
+        ]
+      }
+    }]},
+    {"name": "added_rows_count", "type": ["null", "long"], "default": null, "field-id": 512},
+    {"name": "existing_rows_count", "type": ["null", "long"], "default": null, "field-id": 513},
+    {"name": "deleted_rows_count", "type": ["null", "long"], "default": null, "field-id": 514}
+  ]
+}"#;
+
+/// Outcome of one commit to an Iceberg table
+#[derive(Debug, Clone)]
+pub struct IcebergCommit {
+    pub snapshot_id: i64,
+    /// Table metadata file written by the commit
+    pub metadata_path: PathBuf,
+    pub added_files: usize,
+    /// Data files removed by an overwrite, or rewritten to drop duplicate records
+    pub deleted_files: usize,
+    /// Records whose (country, year, month) was already in the table
+    pub overlapping_records: usize,
+}
+
+/// Cells of one table row, in output column order
+type Row = Vec<Value>;
+
+/// (country, year, month) of a row
+type RowKey = (String, i32, u32);
+
+fn row_key(row: &Row, columns: &[Column]) -> Option<RowKey> {
+    let cell = |name: &str| {
+        columns
+            .iter()
+            .position(|column| column.name == name)
+            .map(|index| &row[index])
+    };
+    // Years and months collapsed by the grouping are null and keyed as 0
+    let year = match cell("year")? {
+        Value::Int32(year) => *year,
+        Value::Null => 0,
+        _ => return None,
+    };
+    let month = match cell("month")? {
+        Value::UInt32(month) => *month,
+        Value::Null => 0,
+        _ => return None,
+    };
+    match cell("country")? {
+        Value::Utf8(country) => Some((country.clone(), year, month)),
+        _ => None,
+    }
+}
+
+/// Data file tracked by a manifest entry
+struct DataFile {
+    status: i32,
+    snapshot_id: i64,
+    path: String,
+    year: Option<i32>,
+    record_count: i64,
+    file_size: i64,
+}
+
+impl DataFile {
+    fn to_avro(&self) -> AvroValue {
+        let year = match self.year {
+            Some(year) => AvroValue::Union(1, Box::new(AvroValue::Int(year))),
+            None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+        };
+        AvroValue::Record(vec![
+            ("status".to_string(), AvroValue::Int(self.status)),
+            ("snapshot_id".to_string(), AvroValue::Long(self.snapshot_id)),
+            (
+                "data_file".to_string(),
+                AvroValue::Record(vec![
+                    (
+                        "file_path".to_string(),
+                        AvroValue::String(self.path.clone()),
+                    ),
+                    (
+                        "file_format".to_string(),
+                        AvroValue::String("PARQUET".to_string()),
+                    ),
+                    (
+                        "partition".to_string(),
+                        AvroValue::Record(vec![(PARTITION_COLUMN.to_string(), year)]),
+                    ),
+                    (
+                        "record_count".to_string(),
+                        AvroValue::Long(self.record_count),
+                    ),
+                    (
+                        "file_size_in_bytes".to_string(),
+                        AvroValue::Long(self.file_size),
+                    ),
+                    (
+                        "block_size_in_bytes".to_string(),
+                        AvroValue::Long(BLOCK_SIZE_BYTES),
+                    ),
+                ]),
+            ),
+        ])
+    }
+
+    fn from_avro(entry: &AvroValue, manifest: &str) -> Result<Self> {
+        let corrupt = || PipelineError::Data(format!("unreadable Iceberg manifest {}", manifest));
+        let data_file = avro_field(entry, "data_file").ok_or_else(corrupt)?;
+        let year = avro_field(data_file, "partition")
+            .and_then(|partition| avro_field(partition, PARTITION_COLUMN))
+            .and_then(|year| match year {
+                AvroValue::Int(year) => Some(*year),
+                _ => None,
+            });
+        Ok(Self {
+            status: avro_int(entry, "status").ok_or_else(corrupt)? as i32,
+            snapshot_id: avro_int(entry, "snapshot_id").ok_or_else(corrupt)?,
+            path: match avro_field(data_file, "file_path") {
+                Some(AvroValue::String(path)) => path.clone(),
+                _ => return Err(corrupt()),
+            },
+            year,
+            record_count: avro_int(data_file, "record_count").ok_or_else(corrupt)?,
+            file_size: avro_int(data_file, "file_size_in_bytes").ok_or_else(corrupt)?,
+        })
+    }
+}
+
+/// Writes the results as a commit to a filesystem (Hadoop catalog layout) Iceberg table.
+///
+/// The table is created on first use with the configured output columns and an identity
+/// partition on `year`. Each run writes one Parquet data file per year and commits a new
+/// snapshot: `Append` adds the files to the table, `Overwrite` additionally removes the
+/// existing files of every year being written. Other years are left untouched.
+///
+/// An append checks the files of the years being written for records with the same
+/// (country, year, month) as the new ones and reports them in
+/// [`IcebergCommit::overlapping_records`]. With a `dedupe` policy, `Error` rejects the commit,
+/// while `KeepLatest` and `Merge` rewrite the affected files without the old records, merging
+/// them into the new ones with `Merge` (see [`merge_rows`]).
+///
+/// The commit becomes visible when `metadata/version-hint.text` is updated; files of a run that
+/// fails earlier are never referenced by the table.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if the existing table has a different schema or unreadable
+/// metadata, if another writer committed the same version concurrently, or if the append
+/// overlaps existing records under `DedupePolicy::Error`.
+pub fn write_iceberg(
+    results: &[Record],
+    table_dir: &Path,
+    config: &OutputConfig,
+    mode: IcebergMode,
+    dedupe: Option<DedupePolicy>,
+) -> Result<IcebergCommit> {
+    let metadata_dir = table_dir.join("metadata");
+    fs::create_dir_all(&metadata_dir)?;
+    let location = fs::canonicalize(table_dir)?;
+    let metadata_dir = location.join("metadata");
+    let columns = output_columns(config);
+    let fields = schema_fields(&columns);
+    let now = now_ms();
+    let snapshot_id = random_snapshot_id();
+
+    let (version, mut metadata) = match read_current_metadata(&metadata_dir)? {
+        Some((version, metadata)) => {
+            if current_schema_fields(&metadata) != Some(&fields) {
+                return Err(PipelineError::Data(format!(
+                    "Iceberg table {} has a different schema than the output columns",
+                    location.display()
+                )));
+            }
+            (version, metadata)
+        }
+        None => (0, new_metadata(&location, &fields, now)),
+    };
+
+    // Live files of the current snapshot
+    let parent_id = metadata["current-snapshot-id"]
+        .as_i64()
+        .filter(|id| *id >= 0);
+    let previous_list = parent_id
+        .and_then(|id| {
+            metadata["snapshots"]
+                .as_array()?
+                .iter()
+                .find(|snapshot| snapshot["snapshot-id"].as_i64() == Some(id))
+        })
+        .and_then(|snapshot| snapshot["manifest-list"].as_str().map(str::to_string));
+    let previous_manifests = match &previous_list {
+        Some(path) => read_avro(Path::new(path))?,
+        None => Vec::new(),
+    };
+
+    let mut rows: Vec<Row> = results
+        .iter()
+        .map(|record| columns.iter().map(|column| column.value(record)).collect())
+        .collect();
+    let added_years: HashSet<Option<i32>> = results.iter().map(|r| Some(r.year)).collect();
+
+    let mut manifest_entries: Vec<ManifestListEntry> = Vec::new();
+    let mut new_entries: Vec<DataFile> = Vec::new();
+    let mut deleted_files = 0;
+    let mut overlapping_records = 0;
+    match mode {
+        IcebergMode::Append => {
+            let live = live_data_files(&previous_manifests, previous_list.as_deref())?;
+            let new_keys: HashMap<RowKey, usize> = rows
+                .iter()
+                .enumerate()
+                .filter_map(|(index, row)| Some((row_key(row, &columns)?, index)))
+                .collect();
+            // Remaining rows of the files that held records of the new keys
+            let mut rewrites: HashMap<String, Vec<Row>> = HashMap::new();
+            for file in live.iter().filter(|file| added_years.contains(&file.year)) {
+                let existing = read_rows(Path::new(&file.path), &columns)?;
+                let (duplicates, kept): (Vec<Row>, Vec<Row>) =
+                    existing.into_iter().partition(|row| {
+                        row_key(row, &columns).is_some_and(|key| new_keys.contains_key(&key))
+                    });
+                if duplicates.is_empty() {
+                    continue;
+                }
+                overlapping_records += duplicates.len();
+                if dedupe == Some(DedupePolicy::Merge) {
+                    for duplicate in &duplicates {
+                        let index = row_key(duplicate, &columns).and_then(|key| new_keys.get(&key));
+                        if let Some(&index) = index {
+                            merge_rows(&mut rows[index], duplicate, &columns, config.std_dev);
+                        }
+                    }
+                }
+                rewrites.insert(file.path.clone(), kept);
+            }
+            match dedupe {
+                Some(DedupePolicy::Error) if overlapping_records > 0 => {
+                    return Err(PipelineError::Data(format!(
+                        "{} records are already in Iceberg table {} (--dedupe-output error)",
+                        overlapping_records,
+                        location.display()
+                    )));
+                }
+                Some(DedupePolicy::KeepLatest | DedupePolicy::Merge) if !rewrites.is_empty() => {
+                    // Rewrite the live files into the new manifest, replacing those with
+                    // duplicates by a copy of their remaining rows
+                    for mut file in live {
+                        match rewrites.remove(&file.path) {
+                            Some(kept) => {
+                                new_entries.extend(write_data_files(
+                                    &kept,
+                                    &location,
+                                    &columns,
+                                    snapshot_id,
+                                )?);
+                                file.status = STATUS_DELETED;
+                                file.snapshot_id = snapshot_id;
+                                deleted_files += 1;
+                            }
+                            None => file.status = STATUS_EXISTING,
+                        }
+                        new_entries.push(file);
+                    }
+                }
+                _ => {
+                    for manifest in &previous_manifests {
+                        manifest_entries.push(ManifestListEntry::Carried(manifest.clone()));
+                    }
+                }
+            }
+        }
+        IcebergMode::Overwrite => {
+            // Rewrite the live files into the new manifest, deleting those of overwritten years
+            for mut file in live_data_files(&previous_manifests, previous_list.as_deref())? {
+                if added_years.contains(&file.year) {
+                    file.status = STATUS_DELETED;
+                    file.snapshot_id = snapshot_id;
+                    deleted_files += 1;
+                } else {
+                    file.status = STATUS_EXISTING;
+                }
+                new_entries.push(file);
+            }
+        }
+    }
+    new_entries.extend(write_data_files(&rows, &location, &columns, snapshot_id)?);
+
+    let added_files = new_entries
+        .iter()
+        .filter(|file| file.status == STATUS_ADDED)
+        .count();
+    let added_records: i64 = count_records(&new_entries, STATUS_ADDED);
+    let deleted_records: i64 = count_records(&new_entries, STATUS_DELETED);
+    let schema_json = json!({"type": "struct", "schema-id": 0, "fields": fields}).to_string();
+    let spec_json = partition_spec_fields(&fields).to_string();
+    let manifest_path = metadata_dir.join(format!("{}-m0.avro", uuid_v4()));
+    let manifest_length = write_avro(
+        &manifest_path,
+        MANIFEST_SCHEMA,
+        new_entries.iter().map(DataFile::to_avro),
+        &[
+            ("schema", schema_json),
+            ("schema-id", "0".to_string()),
+            ("partition-spec", spec_json),
+            ("partition-spec-id", "0".to_string()),
+            ("format-version", "1".to_string()),
+        ],
+    )?;
+    manifest_entries.push(ManifestListEntry::New {
+        path: manifest_path.display().to_string(),
+        length: manifest_length,
+        snapshot_id,
+        files: new_entries,
+    });
+
+    let total_files: i64 = manifest_entries
+        .iter()
+        .map(ManifestListEntry::live_files)
+        .sum();
+    let total_records: i64 = manifest_entries
+        .iter()
+        .map(ManifestListEntry::live_records)
+        .sum();
+    let list_path = metadata_dir.join(format!("snap-{}-1-{}.avro", snapshot_id, uuid_v4()));
+    let parent = parent_id.map_or("null".to_string(), |id| id.to_string());
+    write_avro(
+        &list_path,
+        MANIFEST_LIST_SCHEMA,
+        manifest_entries.iter().map(ManifestListEntry::to_avro),
+        &[
+            ("snapshot-id", snapshot_id.to_string()),
+            ("parent-snapshot-id", parent),
+            ("format-version", "1".to_string()),
+        ],
+    )?;
+
+    // An append that rewrote files to drop duplicates also removes data
+    let operation = match mode {
+        IcebergMode::Append if deleted_files == 0 => "append",
+        IcebergMode::Append | IcebergMode::Overwrite => "overwrite",
+    };
+    let mut snapshot = json!({
+        "snapshot-id": snapshot_id,
+        "timestamp-ms": now,
+        "summary": {
+            "operation": operation,
+            "added-data-files": added_files.to_string(),
+            "added-records": added_records.to_string(),
+            "deleted-data-files": deleted_files.to_string(),
+            "deleted-records": deleted_records.to_string(),
+            "total-data-files": total_files.to_string(),
+            "total-records": total_records.to_string(),
+        },
+        "manifest-list": list_path.display().to_string(),
+        "schema-id": 0,
+    });
+    if let Some(parent_id) = parent_id {
+        snapshot["parent-snapshot-id"] = json!(parent_id);
+    }
+    push(&mut metadata, "snapshots", snapshot);
+    push(
+        &mut metadata,
+        "snapshot-log",
+        json!({"timestamp-ms": now, "snapshot-id": snapshot_id}),
+    );
+    if version > 0 {
+        let previous_file = metadata_dir.join(format!("v{}.metadata.json", version));
+        let entry = json!({
+            "timestamp-ms": metadata["last-updated-ms"],
+            "metadata-file": previous_file.display().to_string(),
+        });
+        push(&mut metadata, "metadata-log", entry);
+    }
+    metadata["current-snapshot-id"] = json!(snapshot_id);
+    metadata["last-updated-ms"] = json!(now);
+
+    let metadata_path = metadata_dir.join(format!("v{}.metadata.json", version + 1));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&metadata_path)
+        .map_err(|e| {
+            PipelineError::Data(format!(
+                "cannot commit Iceberg version {} ({}): {}",
+                version + 1,
+                metadata_path.display(),
+                e
+            ))
+        })?;
+    file.write_all(serde_json::to_string_pretty(&metadata)?.as_bytes())?;
+    file.sync_all()?;
+
+    let hint = metadata_dir.join("version-hint.text");
+    let hint_tmp = metadata_dir.join(format!(".version-hint.text.{}", std::process::id()));
+    fs::write(&hint_tmp, (version + 1).to_string())?;
+    fs::rename(&hint_tmp, &hint)?;
+
+    Ok(IcebergCommit {
+        snapshot_id,
+        metadata_path,
+        added_files,
+        deleted_files,
+        overlapping_records,
+    })
+}
+
+/// Data files of the current snapshot that are not deleted.
+fn live_data_files(manifests: &[AvroValue], list: Option<&str>) -> Result<Vec<DataFile>> {
+    let mut files = Vec::new();
+    for manifest in manifests {
+        let path = match avro_field(manifest, "manifest_path") {
+            Some(AvroValue::String(path)) => path.clone(),
+            _ => {
+                return Err(PipelineError::Data(format!(
+                    "unreadable Iceberg manifest list {}",
+                    list.unwrap_or_default()
+                )));
+            }
+        };
+        for entry in read_avro(Path::new(&path))? {
+            let file = DataFile::from_avro(&entry, &path)?;
+            if file.status != STATUS_DELETED {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Combines an existing row into the new row of the same key.
+///
+/// `count` is the sum of both rows and `avg_temp`, `min_temp`, `max_temp`, `std_dev`,
+/// `range_temp`, and the confidence interval are those of the combined readings. Other
+/// statistics are count-weighted means of both rows, which approximates the median,
+/// percentiles, and other order statistics. Text columns, and columns that are empty in either
+/// row, keep the new value. Both rows' `std_dev` are taken to use the `mode` denominator.
+fn merge_rows(row: &mut Row, existing: &Row, columns: &[Column], mode: StdDevMode) {
+    let column = |name: &str| columns.iter().position(|c| c.name == name);
+    let float = |row: &Row, name: &str| match column(name).map(|i| &row[i]) {
+        Some(Value::Float64(v)) => Some(*v),
+        _ => None,
+    };
+    let count = |row: &Row| match column("count").map(|i| &row[i]) {
+        Some(Value::UInt32(v)) => *v,
+        _ => 0,
+    };
+    let (n1, n2) = (count(row), count(existing));
+    let n = n1 + n2;
+    if n1 == 0 || n2 == 0 {
+        return;
+    }
+    let (w1, w2) = (n1 as f64 / n as f64, n2 as f64 / n as f64);
+    let (m1, m2) = (float(row, "avg_temp"), float(existing, "avg_temp"));
+    let (s1, s2) = (float(row, "std_dev"), float(existing, "std_dev"));
+
+    for (index, column) in columns.iter().enumerate() {
+        let merged = match (column.name.as_str(), &row[index], &existing[index]) {
+            ("count", _, _) => Value::UInt32(n),
+            ("min_temp", Value::Float64(a), Value::Float64(b)) => Value::Float64(a.min(*b)),
+            ("max_temp", Value::Float64(a), Value::Float64(b)) => Value::Float64(a.max(*b)),
+            ("distinct_days" | "outliers_removed", Value::UInt32(a), Value::UInt32(b)) => {
+                Value::UInt32(a + b)
+            }
+            (_, Value::Float64(a), Value::Float64(b)) => Value::Float64(a * w1 + b * w2),
+            _ => continue,
+        };
+        row[index] = merged;
+    }
+
+    // Combined variance from both groups' sums of squared deviations
+    let (Some(m1), Some(m2), Some(s1), Some(s2)) = (m1, m2, s1, s2) else {
+        return;
+    };
+    let mean = m1 * w1 + m2 * w2;
+    let [d1, d2, d] = [n1, n2, n].map(|count| mode.divisor(count as usize));
+    let (n1, n2) = (n1 as f64, n2 as f64);
+    let squares = d1 * s1 * s1 + d2 * s2 * s2 + n1 * (m1 - mean).powi(2) + n2 * (m2 - mean).powi(2);
+    let std_dev = (squares / d).sqrt();
+    if let Some(i) = column("std_dev") {
+        row[i] = Value::Float64(std_dev);
+    }
+    if let (Some(i), Some(min), Some(max)) = (
+        column("range_temp"),
+        float(row, "min_temp"),
+        float(row, "max_temp"),
+    ) {
+        row[i] = Value::Float64(max - min);
+    }
+    if let (Some(low), Some(high), Some((ci_low, ci_high))) = (
+        column("ci_low"),
+        column("ci_high"),
+        mean_confidence_interval(mean, std_dev, n, mode),
+    ) {
+        row[low] = Value::Float64(ci_low);
+        row[high] = Value::Float64(ci_high);
+    }
+}
+
+/// Reads the rows of a data file written by [`write_data_files`].
+fn read_rows(path: &Path, columns: &[Column]) -> Result<Vec<Row>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        for row in 0..batch.num_rows() {
+            rows.push(
+                columns
+                    .iter()
+                    .zip(batch.columns())
+                    .map(|(column, array)| read_value(array.as_ref(), column.data_type, row))
+                    .collect(),
+            );
+        }
+    }
+    Ok(rows)
+}
+
+/// Reads one cell of a data file column, narrowing `long` back to the output's `UInt32`.
+fn read_value(array: &dyn Array, data_type: ColumnType, row: usize) -> Value {
+    if array.is_null(row) {
+        return Value::Null;
+    }
+    let any = array.as_any();
+    match data_type {
+        ColumnType::Utf8 => any
+            .downcast_ref::<StringArray>()
+            .map_or(Value::Null, |a| Value::Utf8(a.value(row).to_string())),
+        ColumnType::Int32 => any
+            .downcast_ref::<Int32Array>()
+            .map_or(Value::Null, |a| Value::Int32(a.value(row))),
+        ColumnType::UInt32 => any
+            .downcast_ref::<Int64Array>()
+            .and_then(|a| u32::try_from(a.value(row)).ok())
+            .map_or(Value::Null, Value::UInt32),
+        ColumnType::Float64 => any
+            .downcast_ref::<Float64Array>()
+            .map_or(Value::Null, |a| Value::Float64(a.value(row))),
+    }
+}
+
+/// Manifest referenced by the new manifest list
+enum ManifestListEntry {
+    /// Manifest of an earlier snapshot, kept unchanged by an append
+    Carried(AvroValue),
+    /// Manifest written by this commit
+    New {
+        path: String,
+        length: i64,
+        snapshot_id: i64,
+        files: Vec<DataFile>,
+    },
+}
+
+impl ManifestListEntry {
+    fn live_files(&self) -> i64 {
+        match self {
+            Self::Carried(manifest) => {
+                avro_int(manifest, "added_data_files_count").unwrap_or(0)
+                    + avro_int(manifest, "existing_data_files_count").unwrap_or(0)
+            }
+            Self::New { files, .. } => files
+                .iter()
+                .filter(|file| file.status != STATUS_DELETED)
+                .count() as i64,
+        }
+    }
+
+    fn live_records(&self) -> i64 {
+        match self {
+            Self::Carried(manifest) => {
+                avro_int(manifest, "added_rows_count").unwrap_or(0)
+                    + avro_int(manifest, "existing_rows_count").unwrap_or(0)
+            }
+            Self::New { files, .. } => {
+                count_records(files, STATUS_ADDED) + count_records(files, STATUS_EXISTING)
+            }
+        }
+    }
+
+    fn to_avro(&self) -> AvroValue {
+        let (path, length, snapshot_id, files) = match self {
+            Self::Carried(manifest) => return manifest.clone(),
+            Self::New {
+                path,
+                length,
+                snapshot_id,
+                files,
+            } => (path, length, snapshot_id, files),
+        };
+        let count = |status| files.iter().filter(|file| file.status == status).count() as i32;
+        let years: Vec<i32> = files.iter().filter_map(|file| file.year).collect();
+        let bound = |year: Option<&i32>| match year {
+            Some(year) => {
+                AvroValue::Union(1, Box::new(AvroValue::Bytes(year.to_le_bytes().to_vec())))
+            }
+            None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+        };
+        let summary = AvroValue::Record(vec![
+            (
+                "contains_null".to_string(),
+                AvroValue::Boolean(files.iter().any(|file| file.year.is_none())),
+            ),
+            ("lower_bound".to_string(), bound(years.iter().min())),
+            ("upper_bound".to_string(), bound(years.iter().max())),
+        ]);
+        let optional_int = |v| AvroValue::Union(1, Box::new(AvroValue::Int(v)));
+        let optional_long = |v| AvroValue::Union(1, Box::new(AvroValue::Long(v)));
+        AvroValue::Record(vec![
+            ("manifest_path".to_string(), AvroValue::String(path.clone())),
+            ("manifest_length".to_string(), AvroValue::Long(*length)),
+            ("partition_spec_id".to_string(), AvroValue::Int(0)),
+            ("added_snapshot_id".to_string(), optional_long(*snapshot_id)),
+            (
+                "added_data_files_count".to_string(),
+                optional_int(count(STATUS_ADDED)),
+            ),
+            (
+                "existing_data_files_count".to_string(),
+                optional_int(count(STATUS_EXISTING)),
+            ),
+            (
+                "deleted_data_files_count".to_string(),
+                optional_int(count(STATUS_DELETED)),
+            ),
+            (
+                "partitions".to_string(),
+                AvroValue::Union(1, Box::new(AvroValue::Array(vec![summary]))),
+            ),
+            (
+                "added_rows_count".to_string(),
+                optional_long(count_records(files, STATUS_ADDED)),
+            ),
+            (
+                "existing_rows_count".to_string(),
+                optional_long(count_records(files, STATUS_EXISTING)),
+            ),
+            (
+                "deleted_rows_count".to_string(),
+                optional_long(count_records(files, STATUS_DELETED)),
+            ),
+        ])
+    }
+}
+
+/// Writes one Parquet data file per year under `data/year=<year>/`.
+fn write_data_files(
+    rows: &[Row],
+    location: &Path,
+    columns: &[Column],
+    snapshot_id: i64,
+) -> Result<Vec<DataFile>> {
+    let mut by_year: BTreeMap<i32, Vec<&Row>> = BTreeMap::new();
+    for row in rows {
+        if let Some((_, year, _)) = row_key(row, columns) {
+            by_year.entry(year).or_default().push(row);
+        }
+    }
+
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                Field::new(&column.name, arrow_type(column.data_type), column.nullable)
+                    .with_metadata(HashMap::from([(
+                        "PARQUET:field_id".to_string(),
+                        (index + 1).to_string(),
+                    )]))
+            })
+            .collect::<Vec<Field>>(),
+    ));
+
+    let mut files = Vec::with_capacity(by_year.len());
+    for (year, rows) in by_year {
+        let dir = location
+            .join("data")
+            .join(format!("{}={}", PARTITION_COLUMN, year));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.parquet", uuid_v4()));
+        let arrays: Vec<ArrayRef> = columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| build_array(column, index, &rows))
+            .collect();
+        let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+        let mut writer = ArrowWriter::try_new(File::create(&path)?, schema.clone(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        files.push(DataFile {
+            status: STATUS_ADDED,
+            snapshot_id,
+            path: path.display().to_string(),
+            year: Some(year),
+            record_count: rows.len() as i64,
+            file_size: fs::metadata(&path)?.len() as i64,
+        });
+    }
+    Ok(files)
+}
+
+/// Arrow type of a column in the data files, restricted to types Iceberg supports.
+fn arrow_type(data_type: ColumnType) -> DataType {
+    match data_type {
+        ColumnType::Utf8 => DataType::Utf8,
+        ColumnType::Int32 => DataType::Int32,
+        // Iceberg has no unsigned types, so widen to long
+        ColumnType::UInt32 => DataType::Int64,
+        ColumnType::Float64 => DataType::Float64,
+    }
+}
+
+fn iceberg_type(data_type: ColumnType) -> &'static str {
+    match data_type {
+        ColumnType::Utf8 => "string",
+        ColumnType::Int32 => "int",
+        ColumnType::UInt32 => "long",
+        ColumnType::Float64 => "double",
+    }
+}
+
+fn build_array(column: &Column, index: usize, rows: &[&Row]) -> ArrayRef {
+    let values = rows.iter().map(|row| row[index].clone());
+    match column.data_type {
+        ColumnType::Utf8 => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    Value::Utf8(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Int32 => {
+            let mut builder = Int32Builder::new();
+            for value in values {
+                match value {
+                    Value::Int32(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::UInt32 => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                match value {
+                    Value::UInt32(v) => builder.append_value(v.into()),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Float64 => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                match value {
+                    Value::Float64(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+/// Iceberg schema fields for the output columns, with IDs assigned in column order.
+fn schema_fields(columns: &[Column]) -> Json {
+    Json::Array(
+        columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                json!({
+                    "id": index + 1,
+                    "name": column.name,
+                    "required": !column.nullable,
+                    "type": iceberg_type(column.data_type),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn partition_spec_fields(fields: &Json) -> Json {
+    let source_id = fields
+        .as_array()
+        .and_then(|fields| fields.iter().find(|f| f["name"] == PARTITION_COLUMN))
+        .map_or(Json::Null, |field| field["id"].clone());
+    json!([{
+        "name": PARTITION_COLUMN,
+        "transform": "identity",
+        "source-id": source_id,
+        "field-id": PARTITION_FIELD_ID,
+    }])
+}
+
+/// Metadata of a new table without snapshots.
+fn new_metadata(location: &Path, fields: &Json, now: i64) -> Json {
+    let schema = json!({"type": "struct", "schema-id": 0, "fields": fields});
+    let spec = partition_spec_fields(fields);
+    json!({
+        "format-version": 1,
+        "table-uuid": uuid_v4(),
+        "location": location.display().to_string(),
+        "last-updated-ms": now,
+        "last-column-id": fields.as_array().map_or(0, Vec::len),
+        "schema": schema,
+        "current-schema-id": 0,
+        "schemas": [schema],
+        "partition-spec": spec,
+        "default-spec-id": 0,
+        "partition-specs": [{"spec-id": 0, "fields": spec}],
+        "last-partition-id": PARTITION_FIELD_ID,
+        "default-sort-order-id": 0,
+        "sort-orders": [{"order-id": 0, "fields": []}],
+        "properties": {"write.format.default": "parquet"},
+        "current-snapshot-id": -1,
+        "snapshots": [],
+        "snapshot-log": [],
+        "metadata-log": [],
+    })
+}
+
+/// Reads the metadata version named by `version-hint.text`, if the table exists.
+fn read_current_metadata(metadata_dir: &Path) -> Result<Option<(u64, Json)>> {
+    let hint = metadata_dir.join("version-hint.text");
+    if !hint.exists() {
+        return Ok(None);
+    }
+    let version: u64 = fs::read_to_string(&hint)?.trim().parse().map_err(|_| {
+        PipelineError::Data(format!("invalid Iceberg version hint {}", hint.display()))
+    })?;
+    let path = metadata_dir.join(format!("v{}.metadata.json", version));
+    let metadata = serde_json::from_reader(File::open(&path)?)?;
+    Ok(Some((version, metadata)))
+}
+
+fn current_schema_fields(metadata: &Json) -> Option<&Json> {
+    let current = metadata["current-schema-id"].as_i64();
+    match metadata["schemas"].as_array() {
+        Some(schemas) => schemas
+            .iter()
+            .find(|schema| schema["schema-id"].as_i64() == current)
+            .map(|schema| &schema["fields"]),
+        None => metadata.get("schema").map(|schema| &schema["fields"]),
+    }
+}
+
+fn push(metadata: &mut Json, key: &str, value: Json) {
+    match metadata[key].as_array_mut() {
+        Some(values) => values.push(value),
+        None => metadata[key] = json!([value]),
+    }
+}
+
+fn count_records(files: &[DataFile], status: i32) -> i64 {
+    files
+        .iter()
+        .filter(|file| file.status == status)
+        .map(|file| file.record_count)
+        .sum()
+}
+
+/// Writes an Avro container file and returns its length in bytes.
+fn write_avro(
+    path: &Path,
+    schema: &str,
+    values: impl Iterator<Item = AvroValue>,
+    metadata: &[(&str, String)],
+) -> Result<i64> {
+    let schema = AvroSchema::parse_str(schema)?;
+    let mut writer = Writer::new(&schema, Vec::new())?;
+    for (key, value) in metadata {
+        writer.add_user_metadata(key.to_string(), value)?;
+    }
+    for value in values {
+        writer.append_value(value)?;
+    }
+    let bytes = writer.into_inner()?;
+    fs::write(path, &bytes)?;
+    Ok(bytes.len() as i64)
+}
+
+fn read_avro(path: &Path) -> Result<Vec<AvroValue>> {
+    Reader::new(File::open(path)?)?
+        .map(|value| value.map_err(PipelineError::from))
+        .collect()
+}
+
+/// Looks up a record field, unwrapping optional (union) values.
+fn avro_field<'a>(record: &'a AvroValue, name: &str) -> Option<&'a AvroValue> {
+    let AvroValue::Record(fields) = record else {
+        return None;
+    };
+    match fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, v)| v)?
+    {
+        AvroValue::Union(_, value) => match value.as_ref() {
+            AvroValue::Null => None,
+            value => Some(value),
+        },
+        value => Some(value),
+    }
+}
+
+fn avro_int(record: &AvroValue, name: &str) -> Option<i64> {
+    match avro_field(record, name)? {
+        AvroValue::Int(v) => Some((*v).into()),
+        AvroValue::Long(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos()),
+    );
+    hasher.finish()
+}
+
+/// Positive random snapshot ID.
+fn random_snapshot_id() -> i64 {
+    (random_u64() >> 1) as i64
+}
+
+/// Random (version 4) UUID used for table and file names.
+fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random_u64().to_le_bytes());
+    bytes[8..].copy_from_slice(&random_u64().to_le_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,184 @@
+use crate::error::Result;
+use crate::structs::Record;
+use chrono::NaiveDate;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Measurement name of the emitted points
+pub const MEASUREMENT: &str = "weather_stats";
+
+/// Lines sent per HTTP write request
+#[cfg(feature = "http")]
+const LINES_PER_REQUEST: usize = 5000;
+
+/// Formats one record as an InfluxDB line protocol point.
+///
+/// Tags are `country`, `unit`, and (when tagged) `enso_phase`, `season`, and `run_id`; every statistic is a field, with
+/// `count` (and `distinct_days`, when counted) as integers. The timestamp is the record's day, or the first day of its month (January
+/// when not grouped by month), at midnight UTC in nanoseconds, and is omitted when the record is not
+/// grouped by year.
+pub fn line_protocol(record: &Record) -> String {
+    let mut line = format!(
+        "{},country={},unit={}",
+        MEASUREMENT,
+        escape_tag(&record.country),
+        record.unit
+    );
+    if let Some(phase) = record.enso_phase {
+        line.push_str(&format!(",enso_phase={}", phase));
+    }
+    if let Some(season) = record.season {
+        line.push_str(&format!(",season={}", season));
+    }
+    if let Some(run_id) = &record.run_id {
+        line.push_str(&format!(",run_id={}", escape_tag(run_id)));
+    }
+
+    let fields = [
+        ("avg_temp", record.avg_temp),
+        ("min_temp", record.min_temp),
+        ("max_temp", record.max_temp),
+        ("std_dev", record.std_dev),
+        ("median_temp", record.median_temp),
+        ("percentile_25", record.percentile_25),
+        ("percentile_75", record.percentile_75),
+        ("percentile_90", record.percentile_90),
+        ("percentile_95", record.percentile_95),
+    ];
+    line.push_str(&format!(" count={}i", record.count));
+    if let Some(days) = record.distinct_days {
+        line.push_str(&format!(",distinct_days={}i", days));
+    }
+    let optional = [
+        ("ci_low", record.ci_low),
+        ("ci_high", record.ci_high),
+        ("range_temp", record.range_temp),
+        ("mode_temp", record.mode_temp),
+        ("trimmed_mean", record.trimmed_mean),
+        ("geometric_mean", record.geometric_mean),
+        ("harmonic_mean", record.harmonic_mean),
+        ("zscore_vs_period", record.zscore_vs_period),
+        ("plugin_metric", record.plugin_metric),
+    ]
+    .into_iter()
+    .chain(record.celsius.iter().flat_map(|c| {
+        [
+            ("avg_temp_celsius", c.avg_temp),
+            ("min_temp_celsius", c.min_temp),
+            ("max_temp_celsius", c.max_temp),
+            ("std_dev_celsius", c.std_dev),
+            ("median_temp_celsius", c.median_temp),
+            ("percentile_25_celsius", c.percentile_25),
+            ("percentile_75_celsius", c.percentile_75),
+            ("percentile_90_celsius", c.percentile_90),
+            ("percentile_95_celsius", c.percentile_95),
+        ]
+        .map(|(name, value)| (name, Some(value)))
+    }))
+    .filter_map(|(name, value)| value.map(|value| (name, value)));
+    // InfluxDB rejects NaN and infinite floats
+    for (name, value) in fields
+        .into_iter()
+        .chain(optional)
+        .filter(|(_, value)| value.is_finite())
+    {
+        line.push_str(&format!(",{}={}", name, value));
+    }
+
+    if let Some(timestamp) = timestamp_nanos(record) {
+        line.push_str(&format!(" {}", timestamp));
+    }
+    line
+}
+
+/// Writes the records as InfluxDB line protocol, one point per line.
+///
+/// # Errors
+/// Returns error if the file cannot be written.
+pub fn write_line_protocol(results: &[Record], output_path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    for record in results {
+        writeln!(writer, "{}", line_protocol(record))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Posts the records as line protocol to an InfluxDB write endpoint.
+///
+/// `url` is the full write URL, e.g. `http://localhost:8086/api/v2/write?org=<org>&bucket=<bucket>`
+/// for InfluxDB 2 or `http://localhost:8086/write?db=<db>` for InfluxDB 1. `token` is sent as
+/// `Authorization: Token <token>`. Points are sent in batches of 5000 lines.
+///
+/// # Returns
+///
+/// Returns the number of points written.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Http` if a request fails or is rejected.
+#[cfg(feature = "http")]
+pub fn post_line_protocol(results: &[Record], url: &str, token: Option<&str>) -> Result<usize> {
+    use crate::error::PipelineError;
+
+    for chunk in results.chunks(LINES_PER_REQUEST) {
+        let body: String = chunk
+            .iter()
+            .map(|record| line_protocol(record) + "\n")
+            .collect();
+        let mut request = ureq::post(url)
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .header("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        let mut response = request.send(body).map_err(|e| PipelineError::Http {
+            url: url.to_string(),
+            reason: e.to_string(),
+        })?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.body_mut().read_to_string().unwrap_or_default();
+            // InfluxDB 2 reports {"message": ...}, InfluxDB 1 {"error": ...}
+            let message = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|error| {
+                    error["message"]
+                        .as_str()
+                        .or(error["error"].as_str())
+                        .map(str::to_string)
+                })
+                .unwrap_or(body);
+            return Err(PipelineError::Http {
+                url: url.to_string(),
+                reason: format!("{}: {}", status, message),
+            });
+        }
+    }
+    Ok(results.len())
+}
+
+/// Escapes commas, equals signs, and spaces in a tag value.
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn timestamp_nanos(record: &Record) -> Option<i64> {
+    if record.year == 0 {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(record.year, record.month.max(1), record.day.unwrap_or(1))?
+        .and_hms_opt(0, 0, 0)?
+        .and_utc()
+        .timestamp_nanos_opt()
+}
@@ -0,0 +1,34 @@
+use crate::error::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit code of a run stopped by SIGINT or SIGTERM (128 + SIGINT, as shells report it)
+pub const EXIT_CODE: u8 = 130;
+
+/// Set by the first SIGINT / SIGTERM
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the SIGINT / SIGTERM (Ctrl-C on Windows) handler.
+///
+/// The first signal only asks the pipeline to stop: input scanning ends after the current
+/// batch and the groups aggregated so far are written as partial outputs. A second signal
+/// exits immediately after removing the directories archives were extracted to, leaving
+/// unfinished files to `clean`.
+///
+/// # Errors
+/// Returns `PipelineError::Signal` if a handler is already installed.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            #[cfg(feature = "archive")]
+            crate::archive::remove_extraction_dirs();
+            std::process::exit(i32::from(EXIT_CODE));
+        }
+        eprintln!("Interrupted: finishing the current batch (interrupt again to exit now)");
+    })?;
+    Ok(())
+}
+
+/// Whether an interrupt was requested.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
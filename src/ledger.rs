@@ -0,0 +1,302 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Ledger file name, created directly under the output root
+const LEDGER_FILE: &str = ".transformer-ledger";
+
+/// Suffix of in-progress output files, renamed into place once complete
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// A temporary artifact registered by a running pipeline
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LedgerEntry {
+    pub path: PathBuf,
+    pub pid: u32,
+}
+
+/// Append-only record of temporary artifacts under an output root.
+///
+/// Every temporary file is registered before it is created and released after it has been
+/// renamed into place, so anything still listed was left behind by an interrupted run and can
+/// be removed safely by `transformer clean`. Every read-modify-write of the ledger holds an
+/// advisory lock on the file, so concurrent runs under one output root never drop each other's
+/// entries.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    /// Opens (without creating) the ledger of an output root.
+    pub fn open(output_root: &Path) -> Self {
+        Self {
+            path: output_root.join(LEDGER_FILE),
+        }
+    }
+
+    /// Registers a temporary artifact owned by the current process.
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be written.
+    pub fn register(&self, artifact: &Path) -> Result<()> {
+        self.register_all(&[artifact.to_path_buf()])
+    }
+
+    /// Registers several temporary artifacts owned by the current process with a single write.
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be written.
+    pub fn register_all(&self, artifacts: &[PathBuf]) -> Result<()> {
+        let mut lines = String::new();
+        for artifact in artifacts {
+            let entry = LedgerEntry {
+                path: artifact.clone(),
+                pid: std::process::id(),
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        let mut file = self.lock()?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    /// Registers the `.partial` temporary file for `output_path` and returns its path.
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be written.
+    pub fn begin(&self, output_path: &Path) -> Result<PathBuf> {
+        let temp_path = partial_path(output_path);
+        self.register(&temp_path)?;
+        Ok(temp_path)
+    }
+
+    /// Registers the `.partial` temporary files of many outputs at once, see [`Ledger::begin`].
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be written.
+    pub fn begin_all(&self, output_paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let temp_paths: Vec<PathBuf> = output_paths.iter().map(|path| partial_path(path)).collect();
+        self.register_all(&temp_paths)?;
+        Ok(temp_paths)
+    }
+
+    /// Renames the completed `.partial` files of many outputs into place and releases them with
+    /// a single rewrite of the ledger, see [`Ledger::commit`].
+    ///
+    /// # Errors
+    /// Returns error if a rename fails or the ledger cannot be rewritten.
+    pub fn commit_all(&self, output_paths: &[PathBuf]) -> Result<()> {
+        let mut released = HashSet::with_capacity(output_paths.len());
+        for path in output_paths {
+            let temp_path = partial_path(path);
+            fs::rename(&temp_path, path)?;
+            released.insert(temp_path);
+        }
+        self.update(|entries| {
+            entries
+                .into_iter()
+                .filter(|e| !released.contains(&e.path))
+                .collect()
+        })
+    }
+
+    /// Renames the completed `.partial` file of `output_path` into place and releases it.
+    ///
+    /// # Errors
+    /// Returns error if the rename fails or the ledger cannot be rewritten.
+    pub fn commit(&self, output_path: &Path) -> Result<()> {
+        let temp_path = partial_path(output_path);
+        fs::rename(&temp_path, output_path)?;
+        self.release(&temp_path)
+    }
+
+    /// Renames the completed `.partial` file of `output_path` to `final_path` instead and
+    /// releases it.
+    ///
+    /// # Errors
+    /// Returns error if the rename fails or the ledger cannot be rewritten.
+    pub fn commit_as(&self, output_path: &Path, final_path: &Path) -> Result<()> {
+        let temp_path = partial_path(output_path);
+        fs::rename(&temp_path, final_path)?;
+        self.release(&temp_path)
+    }
+
+    /// Removes an artifact from the ledger once it no longer exists as a temporary file.
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be read or rewritten.
+    pub fn release(&self, artifact: &Path) -> Result<()> {
+        self.update(|entries| entries.into_iter().filter(|e| e.path != artifact).collect())
+    }
+
+    /// Lists all registered artifacts.
+    ///
+    /// # Errors
+    /// Returns error if the ledger exists but cannot be read or parsed.
+    pub fn entries(&self) -> Result<Vec<LedgerEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(&self.path)?;
+        file.lock_shared()?;
+        read_entries(&file)
+    }
+
+    /// Removes artifacts left behind by runs that are no longer alive.
+    ///
+    /// Entries owned by live processes are kept, as are all entries of other processes on
+    /// platforms where liveness cannot be checked. With `dry_run` nothing is deleted.
+    ///
+    /// # Returns
+    /// Returns the paths that were (or would be) removed.
+    ///
+    /// # Errors
+    /// Returns error if the ledger cannot be read or a file cannot be deleted.
+    pub fn clean(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = self.lock()?;
+        let (alive, orphaned): (Vec<LedgerEntry>, Vec<LedgerEntry>) = read_entries(&file)?
+            .into_iter()
+            .partition(|e| e.pid != std::process::id() && process_alive(e.pid));
+
+        let removed: Vec<PathBuf> = orphaned.into_iter().map(|e| e.path).collect();
+        if dry_run {
+            return Ok(removed);
+        }
+        for path in &removed {
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        write_entries(&file, &alive)?;
+        Ok(removed)
+    }
+
+    /// Opens the ledger and takes an exclusive advisory lock on it, released when the file is
+    /// dropped.
+    ///
+    /// The file is never removed, even once empty, so every run locks the same file.
+    fn lock(&self) -> Result<File> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&self.path)?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Replaces the entries with `update(entries)` under the ledger lock.
+    fn update(&self, update: impl FnOnce(Vec<LedgerEntry>) -> Vec<LedgerEntry>) -> Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let file = self.lock()?;
+        let entries = read_entries(&file)?;
+        write_entries(&file, &update(entries))
+    }
+}
+
+fn read_entries(mut file: &File) -> Result<Vec<LedgerEntry>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+fn write_entries(mut file: &File, entries: &[LedgerEntry]) -> Result<()> {
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// Writes an output through a registered `.partial` temporary file, renaming it into place
+/// only once `write` succeeds.
+///
+/// # Errors
+/// Returns the writer's error (leaving the temporary file registered for `clean`), or an
+/// I/O error if the rename fails.
+pub fn write_atomic<T>(
+    ledger: &Ledger,
+    output_path: &Path,
+    write: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    let temp_path = ledger.begin(output_path)?;
+    let value = write(&temp_path)?;
+    ledger.commit(output_path)?;
+    Ok(value)
+}
+
+/// Returns the temporary path used while `output_path` is being written.
+pub fn partial_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Whether a process is still running.
+///
+/// A process that exists but belongs to another user counts as running.
+#[cfg(unix)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 performs the existence and permission checks without sending anything.
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, ERROR_ACCESS_DENIED, GetLastError, STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle is checked before use and closed before returning.
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut code = 0;
+        let queried = GetExitCodeProcess(handle, &mut code) != 0;
+        CloseHandle(handle);
+        !queried || code == STILL_ACTIVE as u32
+    }
+}
+
+/// Reports every process as running on platforms without a liveness check, so nothing owned
+/// by another process is ever considered abandoned.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn process_alive(_pid: u32) -> bool {
+    true
+}
@@ -1,10 +1,136 @@
+pub mod anonymize;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod aridity;
+pub mod baseline;
+pub mod cache;
+mod check;
+pub mod config;
+pub mod countries;
+pub mod diff;
+mod distribution;
+pub mod enso;
 pub mod error;
+#[cfg(feature = "excel")]
+pub mod excel;
+pub mod expectations;
+pub mod extract;
+pub mod geo;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "gsheet")]
+pub mod gsheet;
+pub mod history;
+#[cfg(feature = "iceberg")]
+pub mod iceberg;
+pub mod influx;
+pub mod interrupt;
+pub mod ledger;
 pub mod load;
+pub mod lock;
+pub mod memory;
+pub mod online;
+mod outliers;
+pub mod partition;
+pub mod paths;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod priority;
+pub mod profile;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod provenance;
+pub mod records;
+#[cfg(feature = "http")]
+pub mod remote;
+pub mod retention;
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod season;
+mod strategy;
 pub mod structs;
+pub mod summary;
+#[cfg(feature = "template")]
+pub mod template;
+pub mod timeseries;
 pub mod transform;
+pub mod trends;
+pub mod units;
+#[cfg(feature = "self-update")]
+pub mod update;
+pub mod verify;
 
 // Re-export public API
+pub use anonymize::{anonymize, anonymize_all};
+#[cfg(feature = "archive")]
+pub use archive::{ExtractedArchive, expand_archives, extract_archive, is_archive};
+pub use aridity::de_martonne_index;
+pub use baseline::{above_baseline, attach_anomalies, attach_period_zscores, baseline_means};
+pub use cache::StatsCache;
+pub use config::{InputConfig, PipelineConfig};
+pub use countries::{CountryInfo, country_info};
+pub use diff::{compare_outputs, diff_records, read_previous_records};
+pub use enso::{EnsoPhase, EnsoTable};
 pub use error::{PipelineError, Result};
-pub use load::{write_csv, write_json, write_parquet};
-pub use structs::{Record, SimpleLogger, TemperatureUnit, TransformConfig};
-pub use transform::process_data;
+#[cfg(feature = "excel")]
+pub use excel::{ExcelWorkbook, write_excel};
+pub use expectations::{Expectation, ExpectationResult, ExpectationSet};
+pub use extract::{NdjsonBatches, list_countries, list_years, read_csv, read_ndjson};
+pub use geo::write_choropleth_geojson;
+#[cfg(feature = "gsheet")]
+pub use gsheet::write_gsheet;
+pub use history::{HISTORY_HEADER, HistoryRun, append_history};
+#[cfg(feature = "iceberg")]
+pub use iceberg::{IcebergCommit, write_iceberg};
+#[cfg(feature = "http")]
+pub use influx::post_line_protocol;
+pub use influx::{line_protocol, write_line_protocol};
+pub use ledger::{Ledger, LedgerEntry, partial_path, write_atomic};
+pub use load::{
+    CsvSink, NdjsonSink, OutputSizes, ParquetSink, RecordSink, output_sizes,
+    write_above_baseline_csv, write_csv, write_csv_stream, write_diff_csv, write_error_report,
+    write_json, write_manifest, write_ndjson_stream, write_output, write_parquet,
+    write_parquet_stream, write_stream,
+};
+pub use lock::{RunLock, is_locked};
+pub use online::{OnlineStats, P2Quantile};
+pub use partition::{PartitionTiming, write_country_partitions};
+pub use paths::{expand_input_paths, long_path, resolve_output, sanitize_file_name};
+#[cfg(feature = "plugins")]
+pub use plugin::{Plugin, pack_country};
+pub use priority::{LOW_PRIORITY_NICE, lower_priority};
+pub use profile::{Profile, ProfileEntry, SpanKind, write_profile};
+#[cfg(feature = "protobuf")]
+pub use protobuf::{PROTO_DEFINITION, WeatherStats, write_protobuf};
+pub use provenance::{run_id, stamp_provenance};
+pub use records::{Records, Selection};
+pub use retention::{prune_runs, timestamped_name};
+pub use schema::{Column, ColumnType, Value, arrow_schema_json, json_schema, output_columns};
+#[cfg(feature = "scripting")]
+pub use script::Script;
+pub use season::{Hemisphere, Season, season_year};
+pub use structs::{
+    AnonymizeConfig, BaselineExceedance, BaselinePeriod, CelsiusStats, ChangeType, ColumnMapping,
+    DailyCollapse, DedupePolicy, ErrorReport, ExecutionStrategy, Granularity, GroupDimension,
+    IcebergMode, InputFormat, InputSource, JsonCase, JsonLayout, Manifest, MonthSet, OutlierAction,
+    OutlierMethod, OutputConfig, OutputFormat, ParquetReport, ProcessingStats, QuantileMethod,
+    Record, RecordChange, RunSummary, RunTimings, SchemaVersion, SimpleLogger, StdDevMode,
+    TemperatureUnit, Throughput, TransformConfig, Warning, send_status_to_stderr,
+    tag_logs_with_run_id,
+};
+pub use summary::{
+    CountrySummary, MonthExtreme, SummaryDocument, country_summaries, write_country_summary,
+};
+#[cfg(feature = "template")]
+pub use template::{render_template, template_output_name};
+pub use timeseries::{
+    CountrySeries, Frequency, Gap, IntoTimeSeries, Period, Resampled, TimeSeries,
+};
+pub use transform::{
+    RecordIter, process_data, process_data_iter, process_data_with_stats, process_inputs_iter,
+    process_inputs_with_stats,
+};
+pub use trends::{CountryTrend, country_trends, write_trends_csv};
+pub use units::{LinearScale, TemperatureScale, scale_for};
+pub use verify::{check_parquet, verify_csv, verify_json, verify_output, verify_parquet};
@@ -1,58 +1,118 @@
 use crate::error::Result;
-use crate::structs::Record;
-use arrow_array::{Float64Array, Int32Array, RecordBatch, StringArray, UInt32Array};
+use crate::profile::{self, SpanKind};
+use crate::schema::{
+    Column, ColumnType, NestedRecords, RecordRow, Value, check_nested, json_columns,
+    nested_columns, output_columns,
+};
+use crate::structs::{
+    BaselineExceedance, ErrorReport, JsonLayout, Manifest, OutputConfig, OutputFormat, Record,
+    RecordChange,
+};
+use arrow_array::builder::{Float64Builder, Int32Builder, StringBuilder, UInt32Builder};
+use arrow_array::{ArrayRef, RecordBatch};
 use arrow_schema::{DataType, Field, Schema};
-use csv::Writer;
+use csv::{Writer, WriterBuilder};
 use parquet::arrow::ArrowWriter;
 use parquet::file::properties::WriterProperties;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{fs::File, path::Path, sync::Arc};
 
+/// Writes weather statistics in `format` with [`write_csv`], [`write_json`], or
+/// [`write_parquet`].
+///
+/// # Errors
+/// Returns the errors of the format's writer.
+pub fn write_output(
+    format: OutputFormat,
+    results: &[Record],
+    output_path: &Path,
+    config: &OutputConfig,
+) -> Result<()> {
+    match format {
+        OutputFormat::Csv => write_csv(results, output_path, config),
+        OutputFormat::Json => write_json(results, output_path, config),
+        OutputFormat::Parquet => write_parquet(results, output_path, config),
+    }
+}
+
 /// Writes weather statistics to a CSV file with formatted numeric values.
 ///
 /// # Arguments
 /// * `results` - Slice of Record structs containing weather statistics
 /// * `output_path` - Path where the CSV file will be created
+/// * `config` - Output configuration selecting the schema version
+///
+/// # Returns
+/// Returns `Ok(())` on success.
+///
+/// # Errors
+/// Returns error if file cannot be created or written to.
+pub fn write_csv(results: &[Record], output_path: &Path, config: &OutputConfig) -> Result<()> {
+    let _span = profile::span("write_csv", SpanKind::Io);
+    let mut sink = CsvSink::create(output_path, config)?;
+    sink.write_records(results)?;
+    Box::new(sink).finish()
+}
+
+/// Formats a single value for CSV output (floats are rounded to 2 decimals, with a decimal
+/// comma if `config.decimal_comma` is set, and nulls written as `config.null_value`).
+pub(crate) fn format_csv_value(value: Value, config: &OutputConfig) -> String {
+    match value {
+        Value::Utf8(v) => v,
+        Value::Int32(v) => v.to_string(),
+        Value::UInt32(v) => v.to_string(),
+        Value::Float64(v) if config.decimal_comma => format!("{:.2}", v).replace('.', ","),
+        Value::Float64(v) => format!("{:.2}", v),
+        Value::Null => config.null_value.clone().unwrap_or_default(),
+    }
+}
+
+/// Field delimiter of the CSV outputs: `;` alongside a decimal comma, `,` otherwise.
+pub(crate) fn csv_delimiter(config: &OutputConfig) -> u8 {
+    if config.decimal_comma { b';' } else { b',' }
+}
+
+fn csv_writer<W: Write>(writer: W, config: &OutputConfig) -> Writer<W> {
+    WriterBuilder::new()
+        .delimiter(csv_delimiter(config))
+        .from_writer(writer)
+}
+
+/// Writes the per-country months-above-baseline summary to a CSV file.
+///
+/// # Arguments
+/// * `summary` - Slice of per-country exceedance rows
+/// * `margin` - Margin above the baseline mean the summary was computed with
+/// * `output_path` - Path where the CSV file will be created
 ///
 /// # Returns
 /// Returns `Ok(())` on success.
 ///
 /// # Errors
 /// Returns error if file cannot be created or written to.
-pub fn write_csv(results: &[Record], output_path: &Path) -> Result<()> {
+pub fn write_above_baseline_csv(
+    summary: &[BaselineExceedance],
+    margin: f64,
+    output_path: &Path,
+) -> Result<()> {
     let file = File::create(output_path)?;
     let mut writer = Writer::from_writer(file);
 
     writer.write_record([
         "Country",
-        "Year",
-        "Month",
-        "Avg_Temp",
-        "Min_Temp",
-        "Max_Temp",
-        "Std_Dev",
-        "Median_Temp",
-        "Count",
-        "Percentile_25",
-        "Percentile_75",
-        "Percentile_90",
-        "Percentile_95",
+        "Margin",
+        "Months",
+        "Months_Above",
+        "Fraction_Above",
     ])?;
-
-    for stats in results {
+    for row in summary {
         writer.write_record(&[
-            stats.country.to_string(),
-            stats.year.to_string(),
-            stats.month.to_string(),
-            format!("{:.2}", stats.avg_temp),
-            format!("{:.2}", stats.min_temp),
-            format!("{:.2}", stats.max_temp),
-            format!("{:.2}", stats.std_dev),
-            format!("{:.2}", stats.median_temp),
-            stats.count.to_string(),
-            format!("{:.2}", stats.percentile_25),
-            format!("{:.2}", stats.percentile_75),
-            format!("{:.2}", stats.percentile_90),
-            format!("{:.2}", stats.percentile_95),
+            row.country.clone(),
+            format!("{:.2}", margin),
+            row.months.to_string(),
+            row.months_above.to_string(),
+            format!("{:.4}", row.fraction_above),
         ])?;
     }
 
@@ -60,20 +120,119 @@ pub fn write_csv(results: &[Record], output_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Writes records that differ from a previous run to a CSV file.
+///
+/// The columns are those of the regular CSV output, prefixed by a `Change_Type` column
+/// (`added`, `changed`, or `removed`).
+///
+/// # Arguments
+/// * `changes` - Changed records, see `diff_records`
+/// * `output_path` - Path where the CSV file will be created
+/// * `config` - Output configuration selecting the schema version
+///
+/// # Returns
+/// Returns `Ok(())` on success.
+///
+/// # Errors
+/// Returns error if file cannot be created or written to.
+pub fn write_diff_csv(
+    changes: &[RecordChange],
+    output_path: &Path,
+    config: &OutputConfig,
+) -> Result<()> {
+    let columns = output_columns(config);
+    let mut writer = csv_writer(BufWriter::new(File::create(output_path)?), config);
+
+    let mut header = vec!["Change_Type".to_string()];
+    header.extend(columns.iter().map(Column::csv_header));
+    writer.write_record(&header)?;
+    for change in changes {
+        let mut row = vec![change.change_type.to_string()];
+        row.extend(
+            columns
+                .iter()
+                .map(|column| format_csv_value(column.value(&change.record), config)),
+        );
+        writer.write_record(&row)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the run manifest (record count, output files, processing statistics) as JSON.
+///
+/// # Arguments
+/// * `manifest` - Manifest describing the run
+/// * `output_path` - Path where the JSON file will be created
+///
+/// # Returns
+/// Returns `Ok(())` on success.
+///
+/// # Errors
+/// Returns error if file cannot be created or serialization fails.
+pub fn write_manifest(manifest: &Manifest, output_path: &Path) -> Result<()> {
+    let file = BufWriter::new(File::create(output_path)?);
+    serde_json::to_writer_pretty(file, manifest)?;
+    Ok(())
+}
+
+/// Writes the error report of a failed run as pretty-printed JSON.
+pub fn write_error_report(report: &ErrorReport, output_path: &Path) -> Result<()> {
+    let file = BufWriter::new(File::create(output_path)?);
+    serde_json::to_writer_pretty(file, report)?;
+    Ok(())
+}
+
 /// Writes weather statistics to a pretty-formatted JSON file.
 ///
 /// # Arguments
 /// * `results` - Slice of Record structs containing weather statistics
 /// * `output_path` - Path where the JSON file will be created
+/// * `config` - Output configuration selecting the schema version, key casing, and layout
 ///
 /// # Returns
 /// Returns `Ok(())` on success.
 ///
 /// # Errors
-/// Returns error if file cannot be created or serialization fails.
-pub fn write_json(results: &[Record], output_path: &Path) -> Result<()> {
-    let file = File::create(output_path)?;
-    serde_json::to_writer_pretty(file, results)?;
+/// Returns error if file cannot be created or serialization fails, or `PipelineError::Data`
+/// if the nested layout is configured and the results hold several records of a country,
+/// year, and month.
+pub fn write_json(results: &[Record], output_path: &Path, config: &OutputConfig) -> Result<()> {
+    let _span = profile::span("write_json", SpanKind::Io);
+    let mut file = BufWriter::new(File::create(output_path)?);
+    write_json_document(&mut file, results, config)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes the JSON output document in the configured layout to `writer`.
+fn write_json_document<W: Write>(
+    writer: W,
+    results: &[Record],
+    config: &OutputConfig,
+) -> Result<()> {
+    if config.json_layout == JsonLayout::Nested {
+        check_nested(results, config)?;
+        let columns = nested_columns(config);
+        let nested = NestedRecords {
+            records: results,
+            columns: &columns,
+            null_value: config.json_null(),
+        };
+        serde_json::to_writer_pretty(writer, &nested)?;
+        return Ok(());
+    }
+    let columns = json_columns(config);
+    let rows: Vec<RecordRow> = results
+        .iter()
+        .map(|record| RecordRow {
+            record,
+            columns: &columns,
+            null_value: config.json_null(),
+        })
+        .collect();
+    serde_json::to_writer_pretty(writer, &rows)?;
     Ok(())
 }
 
@@ -84,68 +243,402 @@ pub fn write_json(results: &[Record], output_path: &Path) -> Result<()> {
 /// # Arguments
 /// * `results` - Slice of Record structs containing weather statistics
 /// * `output_path` - Path where the Parquet file will be created
+/// * `config` - Output configuration selecting the schema version
 ///
 /// # Returns
 /// Returns `Ok(())` on success.
 ///
 /// # Errors
 /// Returns error if file cannot be created, schema is invalid, or Arrow operations fail.
-pub fn write_parquet(results: &[Record], output_path: &Path) -> Result<()> {
-    let schema = Arc::new(Schema::new(vec![
-        Field::new("country", DataType::Utf8, false),
-        Field::new("year", DataType::Int32, false),
-        Field::new("month", DataType::UInt32, false),
-        Field::new("avg_temp", DataType::Float64, false),
-        Field::new("min_temp", DataType::Float64, false),
-        Field::new("max_temp", DataType::Float64, false),
-        Field::new("std_dev", DataType::Float64, false),
-        Field::new("median_temp", DataType::Float64, false),
-        Field::new("count", DataType::UInt32, false),
-        Field::new("percentile_25", DataType::Float64, false),
-        Field::new("percentile_75", DataType::Float64, false),
-        Field::new("percentile_90", DataType::Float64, false),
-        Field::new("percentile_95", DataType::Float64, false),
-    ]));
-
-    let countries: StringArray =
-        StringArray::from_iter_values(results.iter().map(|r| r.country.as_str()));
-    let years: Int32Array = results.iter().map(|r| r.year).collect();
-    let months: UInt32Array = results.iter().map(|r| r.month).collect();
-    let avg_temps: Float64Array = results.iter().map(|r| r.avg_temp).collect();
-    let min_temps: Float64Array = results.iter().map(|r| r.min_temp).collect();
-    let max_temps: Float64Array = results.iter().map(|r| r.max_temp).collect();
-    let std_devs: Float64Array = results.iter().map(|r| r.std_dev).collect();
-    let median_temps: Float64Array = results.iter().map(|r| r.median_temp).collect();
-    let counts: UInt32Array = results.iter().map(|r| r.count).collect();
-    let percentile_25: Float64Array = results.iter().map(|r| r.percentile_25).collect();
-    let percentile_75: Float64Array = results.iter().map(|r| r.percentile_75).collect();
-    let percentile_90: Float64Array = results.iter().map(|r| r.percentile_90).collect();
-    let percentile_95: Float64Array = results.iter().map(|r| r.percentile_95).collect();
-
-    let batch = RecordBatch::try_new(
-        schema.clone(),
-        vec![
-            Arc::new(countries),
-            Arc::new(years),
-            Arc::new(months),
-            Arc::new(avg_temps),
-            Arc::new(min_temps),
-            Arc::new(max_temps),
-            Arc::new(std_devs),
-            Arc::new(median_temps),
-            Arc::new(counts),
-            Arc::new(percentile_25),
-            Arc::new(percentile_75),
-            Arc::new(percentile_90),
-            Arc::new(percentile_95),
-        ],
-    )?;
+pub fn write_parquet(results: &[Record], output_path: &Path, config: &OutputConfig) -> Result<()> {
+    let _span = profile::span("write_parquet", SpanKind::Io);
+    let mut sink = ParquetSink::create(output_path, config)?;
+    sink.write_records(results)?;
+    Box::new(sink).finish()
+}
 
-    let file = File::create(output_path)?;
-    let props = WriterProperties::builder().build();
-    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
-    writer.write(&batch)?;
-    writer.close()?;
+/// Sizes in bytes of the CSV, JSON, and Parquet files of a set of records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutputSizes {
+    pub csv: u64,
+    pub json: u64,
+    pub parquet: u64,
+}
 
-    Ok(())
+impl OutputSizes {
+    /// Size of the file of `format`.
+    pub fn of(&self, format: OutputFormat) -> u64 {
+        match format {
+            OutputFormat::Csv => self.csv,
+            OutputFormat::Json => self.json,
+            OutputFormat::Parquet => self.parquet,
+        }
+    }
+}
+
+/// Computes the sizes the CSV, JSON, and Parquet files of `results` would have, without
+/// writing anything.
+///
+/// The files are rendered exactly as [`write_csv`], [`write_json`], and [`write_parquet`]
+/// render them, into a writer that only counts bytes, so the sizes match what a real run
+/// writes.
+///
+/// # Errors
+/// Returns error if serialization fails.
+pub fn output_sizes(results: &[Record], config: &OutputConfig) -> Result<OutputSizes> {
+    let csv = ByteCounter::default();
+    let mut sink = CsvSink::from_writer(csv.clone(), config)?;
+    sink.write_records(results)?;
+    Box::new(sink).finish()?;
+
+    let json = ByteCounter::default();
+    write_json_document(json.clone(), results, config)?;
+
+    let parquet = ByteCounter::default();
+    let mut sink = ParquetSink::from_writer(parquet.clone(), config)?;
+    sink.write_records(results)?;
+    Box::new(sink).finish()?;
+
+    Ok(OutputSizes {
+        csv: csv.bytes(),
+        json: json.bytes(),
+        parquet: parquet.bytes(),
+    })
+}
+
+/// Writer discarding its input, counting the bytes written through any of its clones
+#[derive(Debug, Clone, Default)]
+struct ByteCounter(Arc<AtomicU64>);
+
+impl ByteCounter {
+    fn bytes(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Number of records pulled from the iterator per chunk by the streaming writers
+pub const STREAM_CHUNK_RECORDS: usize = 8192;
+
+/// Destination for chunks of records, used by the streaming writers.
+pub trait RecordSink {
+    /// Appends a chunk of records.
+    fn write_records(&mut self, records: &[Record]) -> Result<()>;
+
+    /// Flushes buffered data and finalizes the file (e.g. the Parquet footer).
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// CSV sink writing the header on creation and one row per record.
+pub struct CsvSink<W: Write = File> {
+    writer: Writer<W>,
+    columns: Vec<Column>,
+    config: OutputConfig,
+}
+
+impl CsvSink {
+    /// Creates the CSV file and writes its header row.
+    ///
+    /// # Errors
+    /// Returns error if file cannot be created or written to.
+    pub fn create(output_path: &Path, config: &OutputConfig) -> Result<Self> {
+        Self::from_writer(File::create(output_path)?, config)
+    }
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Writes the CSV header row to `writer`.
+    ///
+    /// # Errors
+    /// Returns error if the header cannot be written.
+    pub fn from_writer(writer: W, config: &OutputConfig) -> Result<Self> {
+        let columns = output_columns(config);
+        let mut writer = csv_writer(writer, config);
+        writer.write_record(columns.iter().map(Column::csv_header))?;
+        Ok(Self {
+            writer,
+            columns,
+            config: config.clone(),
+        })
+    }
+}
+
+impl<W: Write> RecordSink for CsvSink<W> {
+    fn write_records(&mut self, records: &[Record]) -> Result<()> {
+        for stats in records {
+            self.writer.write_record(
+                self.columns
+                    .iter()
+                    .map(|c| format_csv_value(c.value(stats), &self.config)),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Newline-delimited JSON sink writing one compact object per line.
+pub struct NdjsonSink {
+    writer: BufWriter<File>,
+    columns: Vec<Column>,
+    null_value: Option<String>,
+}
+
+impl NdjsonSink {
+    /// Creates the NDJSON file.
+    ///
+    /// # Errors
+    /// Returns error if file cannot be created.
+    pub fn create(output_path: &Path, config: &OutputConfig) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(output_path)?),
+            columns: json_columns(config),
+            null_value: config.json_null().map(str::to_string),
+        })
+    }
+}
+
+impl RecordSink for NdjsonSink {
+    fn write_records(&mut self, records: &[Record]) -> Result<()> {
+        for record in records {
+            let row = RecordRow {
+                record,
+                columns: &self.columns,
+                null_value: self.null_value.as_deref(),
+            };
+            serde_json::to_writer(&mut self.writer, &row)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Parquet sink writing each chunk as a record batch.
+pub struct ParquetSink<W: Write + Send = File> {
+    writer: ArrowWriter<W>,
+    schema: Arc<Schema>,
+    columns: Vec<Column>,
+}
+
+impl ParquetSink {
+    /// Creates the Parquet file for the configured schema.
+    ///
+    /// # Errors
+    /// Returns error if file cannot be created or the schema is invalid.
+    pub fn create(output_path: &Path, config: &OutputConfig) -> Result<Self> {
+        Self::from_writer(File::create(output_path)?, config)
+    }
+}
+
+impl<W: Write + Send> ParquetSink<W> {
+    /// Starts a Parquet file for the configured schema in `writer`.
+    ///
+    /// # Errors
+    /// Returns error if the schema is invalid.
+    pub fn from_writer(writer: W, config: &OutputConfig) -> Result<Self> {
+        let columns = output_columns(config);
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|c| Field::new(&c.name, arrow_type(c.data_type), c.nullable))
+                .collect::<Vec<Field>>(),
+        ));
+
+        let props = WriterProperties::builder().build();
+        let writer = ArrowWriter::try_new(writer, schema.clone(), Some(props))?;
+        Ok(Self {
+            writer,
+            schema,
+            columns,
+        })
+    }
+}
+
+impl<W: Write + Send> RecordSink for ParquetSink<W> {
+    fn write_records(&mut self, records: &[Record]) -> Result<()> {
+        let arrays: Vec<ArrayRef> = self
+            .columns
+            .iter()
+            .map(|c| build_array(c, records))
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Streams records into one or more sinks in chunks of [`STREAM_CHUNK_RECORDS`].
+///
+/// Only one chunk is held in memory at a time, so the full result set never needs to be
+/// materialized. Pair with `process_data_iter` for a constant-memory output stage.
+///
+/// # Arguments
+/// * `records` - Iterator of records (e.g. a `RecordIter`)
+/// * `sinks` - Destinations receiving every chunk
+///
+/// # Returns
+/// Returns the number of records written.
+///
+/// # Errors
+/// Returns the first error yielded by the iterator or raised by a sink.
+pub fn write_stream<I>(records: I, sinks: Vec<Box<dyn RecordSink>>) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    let mut sinks = sinks;
+    let mut records = records.into_iter();
+    let mut chunk = Vec::with_capacity(STREAM_CHUNK_RECORDS);
+    let mut written = 0;
+
+    loop {
+        chunk.clear();
+        for record in records.by_ref().take(STREAM_CHUNK_RECORDS) {
+            chunk.push(record?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+        let _span = profile::span("write_records", SpanKind::Io);
+        for sink in sinks.iter_mut() {
+            sink.write_records(&chunk)?;
+        }
+        written += chunk.len();
+    }
+
+    let _span = profile::span("write_records", SpanKind::Io);
+    for sink in sinks {
+        sink.finish()?;
+    }
+    Ok(written)
+}
+
+/// Streams records into a CSV file. See [`write_stream`].
+///
+/// # Errors
+/// Returns error if file cannot be created or written to.
+pub fn write_csv_stream<I>(records: I, output_path: &Path, config: &OutputConfig) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    write_stream(
+        records,
+        vec![Box::new(CsvSink::create(output_path, config)?)],
+    )
+}
+
+/// Streams records into a newline-delimited JSON file. See [`write_stream`].
+///
+/// # Errors
+/// Returns error if file cannot be created or serialization fails.
+pub fn write_ndjson_stream<I>(
+    records: I,
+    output_path: &Path,
+    config: &OutputConfig,
+) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    write_stream(
+        records,
+        vec![Box::new(NdjsonSink::create(output_path, config)?)],
+    )
+}
+
+/// Streams records into a Parquet file. See [`write_stream`].
+///
+/// # Errors
+/// Returns error if file cannot be created, schema is invalid, or Arrow operations fail.
+pub fn write_parquet_stream<I>(
+    records: I,
+    output_path: &Path,
+    config: &OutputConfig,
+) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    write_stream(
+        records,
+        vec![Box::new(ParquetSink::create(output_path, config)?)],
+    )
+}
+
+/// Maps an output column type to its Arrow data type.
+pub(crate) fn arrow_type(data_type: ColumnType) -> DataType {
+    match data_type {
+        ColumnType::Utf8 => DataType::Utf8,
+        ColumnType::Int32 => DataType::Int32,
+        ColumnType::UInt32 => DataType::UInt32,
+        ColumnType::Float64 => DataType::Float64,
+    }
+}
+
+/// Builds the Arrow array holding one column's values for every record.
+fn build_array(column: &Column, results: &[Record]) -> ArrayRef {
+    let values = results.iter().map(|r| column.value(r));
+    match column.data_type {
+        ColumnType::Utf8 => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    Value::Utf8(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Int32 => {
+            let mut builder = Int32Builder::new();
+            for value in values {
+                match value {
+                    Value::Int32(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::UInt32 => {
+            let mut builder = UInt32Builder::new();
+            for value in values {
+                match value {
+                    Value::UInt32(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Float64 => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                match value {
+                    Value::Float64(v) => builder.append_value(v),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    }
 }
@@ -0,0 +1,107 @@
+use crate::error::{PipelineError, Result};
+use crate::ledger::process_alive;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Lock file name, created directly inside a locked output directory
+const LOCK_FILE: &str = ".transformer-lock";
+
+/// Exclusive claim of an output directory by the current run, released on drop.
+///
+/// The claim is an exclusive OS lock (`flock`/`LockFileEx`) on the lock file, held for the
+/// whole run, so a second invocation writing into the same directory fails instead of
+/// interleaving its files with the first. The operating system releases the lock when its
+/// owner exits, even after a crash, so a lock file left behind is simply locked again. The
+/// file also holds the owner's PID for error messages and [`is_locked`].
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl RunLock {
+    /// Locks `dir`, which must already exist.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Data` if another run holds the lock, or an I/O error if the
+    /// lock file cannot be created or locked.
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(LOCK_FILE);
+        loop {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&path)?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    return Err(PipelineError::Data(format!(
+                        "{} is locked by another run (pid {}); wait for it to finish or \
+                         choose a different --output or --namespace",
+                        dir.display(),
+                        lock_owner(&path)
+                            .map_or_else(|| "unknown".to_string(), |pid| pid.to_string())
+                    )));
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+            // The previous owner removes the file before unlocking it, so a lock taken on a
+            // file that is no longer at `path` claims nothing; start over with a new file.
+            if !is_current(&file, &path) {
+                continue;
+            }
+            file.set_len(0)?;
+            writeln!(file, "{}", std::process::id())?;
+            return Ok(Self { path, _file: file });
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Removed while still locked; the lock itself is released when the file is closed
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether `dir` is locked by a live run.
+///
+/// A lock file whose PID has not been written yet counts as locked, as do all lock files of
+/// other processes on platforms where liveness cannot be checked.
+pub fn is_locked(dir: &Path) -> bool {
+    let path = dir.join(LOCK_FILE);
+    path.exists()
+        && lock_owner(&path).is_none_or(|pid| pid != std::process::id() && process_alive(pid))
+}
+
+/// PID recorded in a lock file, if it exists and has been written.
+fn lock_owner(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `file` is still the file at `path`.
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `file` is still the file at `path`. Windows cannot open a file pending deletion,
+/// so an existing path is the file that was locked.
+#[cfg(not(unix))]
+fn is_current(_file: &File, path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(_) => true,
+        Err(e) => !matches!(
+            e.kind(),
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+        ),
+    }
+}
@@ -1,30 +1,175 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use lib::status;
 use lib::{
-    PipelineError, SimpleLogger, TemperatureUnit, TransformConfig, process_data, write_csv,
-    write_json, write_parquet,
+    AnonymizeConfig, BaselinePeriod, ChangeType, ColumnMapping, CsvSink, DailyCollapse, EnsoTable,
+    ErrorReport, ExecutionStrategy, ExpectationSet, Granularity, GroupDimension, Hemisphere,
+    InputFormat, InputSource, Ledger, LinearScale, Manifest, MonthSet, NdjsonSink, OutlierAction,
+    OutlierMethod, OutputConfig, OutputFormat, ParquetSink, PipelineConfig, PipelineError,
+    ProcessingStats, QuantileMethod, RecordSink, RunLock, RunSummary, RunTimings, SchemaVersion,
+    SimpleLogger, SpanKind, StatsCache, StdDevMode, TemperatureUnit, TransformConfig,
+    above_baseline, anonymize, anonymize_all, arrow_schema_json, attach_anomalies,
+    attach_period_zscores, baseline_means, check_parquet, compare_outputs, diff_records,
+    json_schema, line_protocol, list_countries, list_years, long_path, output_sizes,
+    process_inputs_iter, process_inputs_with_stats, prune_runs, read_previous_records,
+    resolve_output, sanitize_file_name, send_status_to_stderr, tag_logs_with_run_id,
+    timestamped_name, verify_output, write_above_baseline_csv, write_atomic,
+    write_choropleth_geojson, write_country_partitions, write_country_summary, write_diff_csv,
+    write_error_report, write_line_protocol, write_manifest, write_output, write_profile,
+    write_stream, write_trends_csv,
+};
+use lib::{
+    HistoryRun, JsonCase, JsonLayout, LOW_PRIORITY_NICE, append_history, interrupt, lower_priority,
+    memory, profile,
 };
 use log::debug;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::time::Instant;
 
 static LOGGER: SimpleLogger = SimpleLogger;
 
+/// Default root directory all output directories are created under
+const OUTPUT_ROOT: &str = "./output";
+
+/// File `--history` appends to when given without a path, relative to the output root
+const HISTORY_FILE: &str = "history.csv";
+
+/// Fence multiplier of `--outlier-method iqr` when `--iqr-multiplier` is not given
+const DEFAULT_IQR_MULTIPLIER: f64 = 1.5;
+
+/// File a failed run describes its error in, inside its output directory
+const ERROR_REPORT_FILE: &str = "error_report.json";
+
+/// Environment variable read for `--output-dir` when the flag is not given
+const OUTPUT_DIR_VAR: &str = "TRANSFORMER_OUTPUT_DIR";
+
+/// Cache directory for `--cache`, relative to the output root
+const CACHE_DIR: &str = ".cache";
+
+/// Download cache for URL inputs, relative to the output root
+#[cfg(feature = "http")]
+const DOWNLOAD_DIR: &str = ".cache/downloads";
+
+/// Environment variable holding the API token for `--influx-url`
+#[cfg(feature = "http")]
+const INFLUX_TOKEN_VAR: &str = "INFLUX_TOKEN";
+
+/// Environment variable holding the OAuth access token for `--gsheet`
+#[cfg(feature = "gsheet")]
+const GSHEET_TOKEN_VAR: &str = "GOOGLE_OAUTH_ACCESS_TOKEN";
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// input Parquet file (project root dir)
-    #[arg(short, long)]
-    input_file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input Parquet file(s) (project root dir); repeat or comma-separate to merge several files,
+    /// or give a directory or glob (e.g. "data/*.parquet") to read every matching file
+    #[arg(short, long, value_delimiter = ',', required_unless_present = "config")]
+    input_file: Vec<PathBuf>,
+
+    /// YAML pipeline configuration with additional inputs and per-file column mappings
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Column names of the --input-file files as KEY=COLUMN pairs (e.g.
+    /// date=obs_date,country=iso2,temp=t_mean); keys are date, country, temp, and precip
+    #[arg(long, value_name = "MAPPING")]
+    column_map: Option<ColumnMapping>,
 
-    /// Output base name (will create dir containing .csv, .json, and .parquet files)
+    /// Format of the --input-file files: auto (CSV for .csv files, NDJSON for .ndjson/.jsonl,
+    /// Parquet otherwise), parquet, csv, or ndjson
+    #[arg(long, value_enum, default_value = "auto")]
+    input_format: InputFormat,
+
+    /// Glob selecting the members of .zip/.tar/.tar.gz inputs to process (e.g. "2024/*.parquet");
+    /// defaults to every .parquet and .csv member
+    #[cfg(feature = "archive")]
+    #[arg(long, value_parser = parse_pattern)]
+    archive_pattern: Option<glob::Pattern>,
+
+    /// Inputs resolved from --input-file and --config
+    #[arg(skip)]
+    inputs: Vec<InputSource>,
+
+    /// Identifier of this run, generated at startup
+    #[arg(skip)]
+    run_id: String,
+
+    /// Extracted archive inputs, kept alive (and cleaned up on drop) for the whole run
+    #[cfg(feature = "archive")]
+    #[arg(skip)]
+    archives: Vec<lib::ExtractedArchive>,
+
+    /// Output base name (will create dir containing the .csv, .json, and .parquet files of --formats)
     #[arg(short, long, default_value = "output")]
     output: String,
 
+    /// Root directory the output directories, ledger, and caches are created under, e.g. a
+    /// writable volume when the working directory is read-only
+    #[arg(long, env = OUTPUT_DIR_VAR, default_value = OUTPUT_ROOT, global = true)]
+    output_dir: PathBuf,
+
+    /// Write the output directory under <output-dir>/<NAMESPACE>/ instead of <output-dir>/,
+    /// keeping the outputs of separate jobs or teams apart
+    #[arg(long, value_name = "NAMESPACE")]
+    namespace: Option<String>,
+
+    /// Record files to write, comma-separated: csv, json (NDJSON with --stream), parquet
+    #[arg(long, value_delimiter = ',', default_value = "csv,json,parquet")]
+    formats: Vec<OutputFormat>,
+
+    /// Also write each country's records to <output>/country=<code>/<name>.<ext> in every
+    /// format of --formats
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    split_by_country: bool,
+
+    /// Partitions of --split-by-country written concurrently
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    writer_threads: u32,
+
+    /// Write the CSV output to this path instead of <output>/<name>.csv
+    #[arg(long)]
+    csv_out: Option<PathBuf>,
+
+    /// Write the JSON output (NDJSON with --stream) to this path instead of <output>/<name>.json
+    #[arg(long)]
+    json_out: Option<PathBuf>,
+
+    /// Write the Parquet output to this path instead of <output>/<name>.parquet
+    #[arg(long)]
+    parquet_out: Option<PathBuf>,
+
+    /// Append the UTC start time to the output name (<output>_YYYYMMDDTHHMMSSZ)
+    #[arg(long, default_value_t = false)]
+    timestamp_output: bool,
+
+    /// Keep only the newest N timestamped output directories of this output name, deleting
+    /// older ones after a successful run; implies --timestamp-output
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    retain: Option<u32>,
+
+    /// Output name before --timestamp-output appended the timestamp
+    #[arg(skip)]
+    output_base: Option<String>,
+
     /// Country alpha-2 codes to filter data by (e.g., US,FR,CA). If not specified, processes all countries.
     #[arg(short, long, value_delimiter = ',')]
     countries: Vec<String>,
 
+    /// Country alpha-2 codes to drop (e.g., RU,CN), also when listed in --countries
+    #[arg(long, value_delimiter = ',')]
+    exclude_countries: Vec<String>,
+
     /// Start year (inclusive) for filtering (optional)
     #[arg(long)]
     start_year: Option<i32>,
@@ -33,30 +178,841 @@ struct Args {
     #[arg(long)]
     end_year: Option<i32>,
 
-    /// Temperature unit for output
-    #[arg(long, default_value = "celsius")]
-    unit: TemperatureUnit,
+    /// Calendar months to keep, as a list and/or ranges (e.g. 6,7,8 or 6-8 or 12,1-2); all
+    /// months if not specified
+    #[arg(long)]
+    months: Option<MonthSet>,
+
+    /// Temperature unit(s) for output; comma-separate several (e.g. celsius,fahrenheit) to also
+    /// write <output>_<unit> files of --formats from the same pass
+    #[arg(long, value_delimiter = ',', default_value = "celsius")]
+    unit: Vec<TemperatureUnit>,
+
+    /// Factor of --unit custom: value = celsius * scale + offset
+    #[arg(long, allow_hyphen_values = true)]
+    unit_scale: Option<f64>,
+
+    /// Offset of --unit custom: value = celsius * scale + offset
+    #[arg(long, allow_hyphen_values = true)]
+    unit_offset: Option<f64>,
+
+    /// Lowest plausible reading, in the first --unit; colder readings are rejected [default:
+    /// -100 °C]
+    #[arg(long, allow_negative_numbers = true)]
+    valid_min: Option<f64>,
+
+    /// Highest plausible reading, in the first --unit; warmer readings are rejected [default:
+    /// 70 °C]
+    #[arg(long, allow_negative_numbers = true)]
+    valid_max: Option<f64>,
 
     /// Outlier detection threshold (standard deviations)
     #[arg(long)]
     threshold: Option<f64>,
 
+    /// Outlier test: std-dev, grubbs (--threshold is the significance level), rolling (z-score
+    /// against the same month of neighbouring years), climatological (of all years), iqr
+    /// (Tukey's fences, see --iqr-multiplier), mad (median absolute deviations), or
+    /// z-score-modified (Iglewicz-Hoaglin)
+    #[arg(long, default_value = "std-dev")]
+    outlier_method: OutlierMethod,
+
+    /// Interquartile ranges beyond the quartiles at which --outlier-method iqr flags readings;
+    /// enables the iqr test without --threshold [default: 1.5]
+    #[arg(long, value_parser = parse_positive)]
+    iqr_multiplier: Option<f64>,
+
+    /// Years on either side of a record's year compared by --outlier-method rolling
+    #[arg(long, default_value_t = 5)]
+    outlier_window: u32,
+
+    /// Remove outliers beyond the threshold or clip them to it (winsorize)
+    #[arg(long, default_value = "remove")]
+    outlier_action: OutlierAction,
+
+    /// How percentiles between two readings are computed, as NumPy's and pandas' methods:
+    /// linear, nearest, lower, higher, or midpoint
+    #[arg(long, default_value = "linear")]
+    quantile_method: QuantileMethod,
+
+    /// Percentiles to emit instead of 25,75,90,95, comma-separated in 0-100 (e.g. 5,10,50,99),
+    /// as percentile_<p> columns (percentile_99_9 for 99.9)
+    #[arg(long, value_delimiter = ',', value_name = "P")]
+    percentiles: Vec<f64>,
+
+    /// Denominator of std_dev and the z-score outlier bounds: sample (n - 1) or population (n)
+    #[arg(long, default_value = "sample")]
+    stddev: StdDevMode,
+
     /// Aggregate all countries together instead of keeping them separate
     #[arg(long, default_value_t = false)]
     aggregate: bool,
 
+    /// Abort on rows with nulls or unparsable dates instead of skipping them
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Verify N randomly sampled groups against a naive reference implementation (default 100)
+    #[arg(long, num_args = 0..=1, default_missing_value = "100")]
+    self_check: Option<usize>,
+
+    /// Output schema version (1 = original 13 columns, 2 = adds unit and period dates)
+    #[arg(long, default_value = "1")]
+    output_schema_version: SchemaVersion,
+
+    /// Add country_name, the English name of each country from the bundled ISO 3166-1 table
+    #[arg(long, default_value_t = false)]
+    include_country_names: bool,
+
+    /// Tag each record with its ENSO phase (El Niño / La Niña / neutral)
+    #[arg(long, default_value_t = false)]
+    enso: bool,
+
+    /// CSV of ENSO episodes (start,end,phase) replacing the bundled table; implies --enso
+    #[arg(long)]
+    enso_table: Option<PathBuf>,
+
+    /// Collapse several readings of the same country and date to one (mean or median) first,
+    /// so dense station coverage does not skew monthly statistics
+    #[arg(long, default_value = "none")]
+    daily_collapse: DailyCollapse,
+
+    /// Add the number of distinct dates among each record's readings
+    #[arg(long, default_value_t = false)]
+    distinct_days: bool,
+
+    /// Add the 95% confidence interval of each record's mean (Student's t) as ci_low/ci_high
+    #[arg(long, default_value_t = false)]
+    confidence_interval: bool,
+
+    /// Add each record's range (max - min) and binned mode as range_temp/mode_temp
+    #[arg(long, default_value_t = false)]
+    mode_range: bool,
+
+    /// Bin width of the mode added by --mode-range, in the output unit
+    #[arg(long, default_value_t = 1.0)]
+    mode_bin_width: f64,
+
+    /// Add a trimmed mean dropping this fraction of readings from each end (e.g. 0.1)
+    #[arg(long)]
+    trimmed_mean: Option<f64>,
+
+    /// Add geometric and harmonic means, for records whose readings are all positive
+    #[arg(long, default_value_t = false)]
+    positive_means: bool,
+
+    /// Add zscore_vs_period: each avg_temp in standard deviations from its country's mean
+    /// avg_temp over the whole period
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    zscore: bool,
+
+    /// WebAssembly module (.wasm or .wat) exporting transform_row and/or post_aggregate, run on
+    /// every reading and record
+    #[cfg(feature = "plugins")]
+    #[arg(long)]
+    plugin: Option<PathBuf>,
+
+    /// Rhai script run on every reading; it sees country, date, year, month, day, and temp
+    /// (Celsius), may assign temp, and evaluates to false to drop the reading
+    #[cfg(feature = "scripting")]
+    #[arg(long)]
+    row_script: Option<PathBuf>,
+
+    /// Rhai script run on every output record (the map `record`), which it may modify, or
+    /// drop by evaluating to false
+    #[cfg(feature = "scripting")]
+    #[arg(long)]
+    record_script: Option<PathBuf>,
+
+    /// Add the De Martonne aridity index of each group, computed from the precipitation column
+    #[arg(long, default_value_t = false)]
+    aridity: bool,
+
+    /// Add source_file, run_id, and processed_at columns so records of merged runs stay
+    /// traceable
+    #[arg(long, default_value_t = false)]
+    provenance: bool,
+
+    /// Also emit the core statistics in Celsius (avg_temp_celsius ... percentile_95_celsius)
+    /// next to those in --unit
+    #[arg(long, default_value_t = false)]
+    keep_celsius: bool,
+
+    /// Round every statistic to N significant digits as it is computed (not only when
+    /// formatted), so outputs are bit-identical across platforms for hash-based verification
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=17))]
+    significant_digits: Option<u32>,
+
+    /// Dimensions to group statistics by (country, year, month, enso-phase or enso_phase,
+    /// season, day)
+    #[arg(long, value_delimiter = ',', default_value = "country,year,month")]
+    group_by: Vec<GroupDimension>,
+
+    /// Time granularity of the records: daily, monthly, seasonal, or yearly (a shorthand for
+    /// the matching --group-by dimensions)
+    #[arg(long, conflicts_with = "group_by")]
+    granularity: Option<Granularity>,
+
+    /// Hemisphere whose seasons --group-by season uses (auto picks each country's own)
+    #[arg(long, default_value = "north")]
+    hemisphere: Hemisphere,
+
+    /// Baseline period (e.g. 1981-2010) for the months-above-baseline summary
+    #[arg(long)]
+    baseline: Option<BaselinePeriod>,
+
+    /// Degrees above the baseline mean a month must exceed to count in the summary
+    #[arg(long, default_value_t = 0.0)]
+    baseline_margin: f64,
+
+    /// Add anomaly_c: each avg_temp minus the --baseline mean of its country and calendar
+    /// month, in degrees Celsius
+    #[arg(long, default_value_t = false, requires = "baseline")]
+    anomaly: bool,
+
+    /// Analysis strategy (auto picks one from the input size and available memory)
+    #[arg(long, default_value = "auto")]
+    strategy: ExecutionStrategy,
+
+    /// Aggregate each group in constant memory while scanning: exact count, mean, std_dev,
+    /// min, and max, but estimated median and percentiles (for inputs larger than memory)
+    #[arg(long, default_value_t = false)]
+    streaming_stats: bool,
+
+    /// Worker threads for parallel analysis (defaults to one per CPU)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+
+    /// MiB of readings a worker may hold for one country with --strategy per-country; denser
+    /// countries are analyzed in several passes
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    country_memory_mib: Option<u64>,
+
+    /// Lower the process priority to this Unix nice level (1-19) so long runs leave CPU time to
+    /// interactive work; on Windows, levels below 15 select the below-normal priority class and
+    /// higher ones the idle class
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=19))]
+    nice: Option<i32>,
+
+    /// Lower the process priority to nice level 10 (same as --nice 10)
+    #[arg(long, conflicts_with = "nice")]
+    low_priority: bool,
+
+    /// Experimental: sort groups of at least N readings on the GPU, falling back to the CPU
+    /// when no GPU is available (default N = 1000000)
+    #[cfg(feature = "gpu")]
+    #[arg(long, num_args = 0..=1, default_missing_value = "1000000")]
+    gpu: Option<usize>,
+
+    /// Bucket temperature statistics into ranges of this width (values become the range's
+    /// lower bound)
+    #[arg(long, value_parser = parse_positive)]
+    bucket_width: Option<f64>,
+
+    /// Suppress records aggregating fewer than K readings (k-anonymity style)
+    #[arg(long, value_name = "K")]
+    min_group_size: Option<u32>,
+
+    /// YAML file of data-quality expectations checked before any output is written; the run
+    /// fails with a report if any expectation is violated
+    #[arg(long, conflicts_with = "stream")]
+    expectations: Option<PathBuf>,
+
+    /// Previous output directory (or its JSON file) to compare against; writes
+    /// <output>_diff.csv with the added, changed, and removed records
+    #[arg(long, conflicts_with = "stream")]
+    diff_against: Option<PathBuf>,
+
+    /// Reuse results cached under <output-dir>/.cache for identical input content and filters
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    cache: bool,
+
+    /// Stream records to CSV, NDJSON, and Parquet in chunks instead of collecting them in memory
+    #[arg(long, default_value_t = false, conflicts_with = "baseline")]
+    stream: bool,
+
+    /// Re-read the CSV, JSON, and Parquet outputs (of --formats) and check counts and sampled
+    /// values
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    verify_outputs: bool,
+
+    /// Append one summary row per country of this run (timestamp, filters, counts, and mean,
+    /// min, and max temperatures) to a persistent history CSV [default:
+    /// <output-dir>/history.csv]
+    #[arg(long, num_args = 0..=1, conflicts_with = "stream")]
+    history: Option<Option<PathBuf>>,
+
+    /// Process the inputs but write no outputs; report the record count and the size of each
+    /// CSV, JSON, Parquet, line protocol, and protobuf file a real run would write
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    dry_run: bool,
+
+    /// Format CSV numbers with a decimal comma and delimit fields with `;` (for European Excel)
+    #[arg(long, default_value_t = false)]
+    decimal_comma: bool,
+
+    /// Casing of the JSON and NDJSON keys (snake: avg_temp, camel: avgTemp, pascal: AvgTemp)
+    #[arg(long, default_value = "snake")]
+    json_case: JsonCase,
+
+    /// Layout of the JSON output: an array of records, or objects nested by country, year, and
+    /// month (NDJSON stays flat)
+    #[arg(long, default_value = "flat")]
+    json_layout: JsonLayout,
+
+    /// Text written for missing values in every output, e.g. `NA` (default: empty in CSV and
+    /// Excel, null in JSON; `null` keeps JSON nulls)
+    #[arg(long, value_name = "TEXT")]
+    null_value: Option<String>,
+
+    /// Write <output>_choropleth.geojson with per-country annual means (and baseline anomalies)
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    geojson: bool,
+
+    /// Polygon GeoJSON (features with an ISO_A2 property) to join onto instead of the bundled
+    /// capital-city points; implies --geojson
+    #[arg(long, conflicts_with = "stream")]
+    geojson_base: Option<PathBuf>,
+
+    /// Write <output>_summary.json with compact per-country aggregates (mean, trend, hottest
+    /// and coldest month, counts) for web widgets
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    country_summary: bool,
+
+    /// Write <output>_trends.csv with a least-squares linear trend (degrees Celsius per decade)
+    /// of each country's monthly means
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    trends: bool,
+
+    /// Write <output>.lp with the statistics as InfluxDB line protocol
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    line_protocol: bool,
+
+    /// Post the statistics as line protocol to this InfluxDB write URL; an API token is read
+    /// from INFLUX_TOKEN if set
+    #[cfg(feature = "http")]
+    #[arg(long, conflicts_with = "stream")]
+    influx_url: Option<String>,
+
+    /// Write <output>.pb with length-delimited WeatherStats protobuf messages
+    /// (proto/weather_stats.proto)
+    #[cfg(feature = "protobuf")]
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    protobuf: bool,
+
+    /// Render the statistics through a Jinja2-style template into <output>_<template name>
+    /// (e.g. report.html.j2 -> <output>_report.html); repeat for several templates
+    #[cfg(feature = "template")]
+    #[arg(long, conflicts_with = "stream")]
+    template: Vec<PathBuf>,
+
+    /// Also write the statistics as an Excel workbook (.xlsx)
+    #[cfg(feature = "excel")]
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    excel: bool,
+
+    /// Write a single Excel workbook with sheets for stats, baseline summary, and data quality
+    #[cfg(feature = "excel")]
+    #[arg(long, default_value_t = false, conflicts_with = "stream")]
+    excel_combined: bool,
+
+    /// Commit the statistics to the Iceberg table in this directory (created if missing)
+    #[cfg(feature = "iceberg")]
+    #[arg(long, conflicts_with = "stream")]
+    iceberg: Option<PathBuf>,
+
+    /// How --iceberg commits: append data files, or overwrite the years being written
+    #[cfg(feature = "iceberg")]
+    #[arg(long, default_value = "append", requires = "iceberg")]
+    iceberg_mode: lib::IcebergMode,
+
+    /// How an --iceberg append handles records whose (country, year, month) is already in the
+    /// table: fail, replace them, or merge them with the new records (default: warn and keep both)
+    #[cfg(feature = "iceberg")]
+    #[arg(long, requires = "iceberg")]
+    dedupe_output: Option<lib::DedupePolicy>,
+
+    /// Replace a sheet of this Google Sheet (spreadsheet ID) with the statistics; the OAuth
+    /// access token is read from GOOGLE_OAUTH_ACCESS_TOKEN
+    #[cfg(feature = "gsheet")]
+    #[arg(long, conflicts_with = "stream")]
+    gsheet: Option<String>,
+
+    /// Sheet (tab) written by --gsheet
+    #[cfg(feature = "gsheet")]
+    #[arg(long, default_value = "Sheet1", requires = "gsheet")]
+    gsheet_tab: String,
+
+    /// Print the end-of-run summary (records, files, counters, timings, first warnings) as a
+    /// single JSON object on stdout; progress messages and logs go to stderr instead
+    #[arg(long, default_value_t = false)]
+    summary_json: bool,
+
+    /// Time the reading, decoding, analysis, and writing phases and write
+    /// <output>_profile.txt and a flamegraph-ready <output>_profile.folded into the output
+    /// directory, to tell whether a run is I/O or CPU bound
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+
     /// Log level for output
     #[arg(long, default_value = "false")]
     debug: bool,
 }
 
-fn main() -> Result<(), PipelineError> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Remove temporary and partial outputs left behind by interrupted runs
+    Clean {
+        /// List the files that would be removed without deleting them
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Compare two output directories record by record and report values that drifted
+    DiffOutputs {
+        /// First output directory (or its .json / .ndjson file), e.g. a golden run
+        left: PathBuf,
+
+        /// Second output directory (or its .json / .ndjson file)
+        right: PathBuf,
+
+        /// Largest absolute difference between numbers that is not reported
+        #[arg(long, default_value_t = 0.0)]
+        tolerance: f64,
+    },
+    /// List the distinct countries or years of the inputs, to discover valid --countries and
+    /// --start-year/--end-year values; Parquet statistics are used where they suffice
+    List {
+        /// Values to list
+        #[arg(value_enum)]
+        what: ListKind,
+
+        /// Input file(s), directories, or globs, like --input-file of a run
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        input_file: Vec<PathBuf>,
+
+        /// Column names of the input files (see --column-map of a run)
+        #[arg(long, value_name = "MAPPING")]
+        column_map: Option<ColumnMapping>,
+
+        /// Format of the input files (see --input-format of a run)
+        #[arg(long, value_enum, default_value = "auto")]
+        input_format: InputFormat,
+    },
+    /// Replace this binary with the latest GitHub release built for this platform
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// Only report whether a newer release is available
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+    /// Print the JSON Schema (or Arrow schema) of the records the pipeline writes
+    Schema {
+        /// Output schema version to describe
+        #[arg(long, default_value = "1")]
+        output_schema_version: SchemaVersion,
+
+        /// Describe the year column as nullable, as written when the grouping collapses years
+        /// (e.g. --group-by country,enso_phase)
+        #[arg(long, default_value_t = false)]
+        collapsed_year: bool,
+
+        /// Describe the month column as nullable, as written when the grouping collapses
+        /// months (e.g. --granularity yearly)
+        #[arg(long, default_value_t = false)]
+        collapsed_month: bool,
+
+        /// Include the country_name column added by --include-country-names
+        #[arg(long, default_value_t = false)]
+        include_country_names: bool,
+
+        /// Include the day column added by --granularity daily (or --group-by day)
+        #[arg(long, default_value_t = false)]
+        day: bool,
+
+        /// Include the enso_phase column added by --enso
+        #[arg(long, default_value_t = false)]
+        enso: bool,
+
+        /// Include the season column added by --group-by season
+        #[arg(long, default_value_t = false)]
+        season: bool,
+
+        /// Include the aridity_index column added by --aridity
+        #[arg(long, default_value_t = false)]
+        aridity: bool,
+
+        /// Include the distinct_days column added by --distinct-days
+        #[arg(long, default_value_t = false)]
+        distinct_days: bool,
+
+        /// Include the outliers_removed column added by outlier detection (--threshold); only
+        /// part of output schema version 2
+        #[arg(long, default_value_t = false)]
+        outliers_removed: bool,
+
+        /// Include the ci_low/ci_high columns added by --confidence-interval
+        #[arg(long, default_value_t = false)]
+        confidence_interval: bool,
+
+        /// Include the range_temp/mode_temp columns added by --mode-range
+        #[arg(long, default_value_t = false)]
+        mode_range: bool,
+
+        /// Include the trimmed_mean column added by --trimmed-mean
+        #[arg(long, default_value_t = false)]
+        trimmed_mean: bool,
+
+        /// Describe the percentile_<p> columns of --percentiles instead of percentile_25 ...
+        /// percentile_95
+        #[arg(long, value_delimiter = ',', value_name = "P")]
+        percentiles: Vec<f64>,
+
+        /// Include the geometric_mean/harmonic_mean columns added by --positive-means
+        #[arg(long, default_value_t = false)]
+        positive_means: bool,
+
+        /// Include the zscore_vs_period column added by --zscore
+        #[arg(long, default_value_t = false)]
+        zscore: bool,
+
+        /// Include the anomaly_c column added by --anomaly
+        #[arg(long, default_value_t = false)]
+        anomaly: bool,
+
+        /// Include the plugin_metric column added by a --plugin exporting post_aggregate
+        #[arg(long, default_value_t = false)]
+        plugin_metric: bool,
+
+        /// Include the source_file/run_id/processed_at columns added by --provenance
+        #[arg(long, default_value_t = false)]
+        provenance: bool,
+
+        /// Include the *_celsius columns added by --keep-celsius
+        #[arg(long, default_value_t = false)]
+        keep_celsius: bool,
+
+        /// Print the Arrow schema of the Parquet output instead of a JSON Schema
+        #[arg(long, default_value_t = false)]
+        arrow: bool,
+
+        /// Casing of the JSON keys the schema describes
+        #[arg(long, default_value = "snake")]
+        json_case: JsonCase,
+
+        /// Layout of the JSON output the schema describes
+        #[arg(long, default_value = "flat")]
+        json_layout: JsonLayout,
+
+        /// Null text of the JSON output the schema describes (see --null-value)
+        #[arg(long, value_name = "TEXT")]
+        null_value: Option<String>,
+
+        /// Write the schema to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check a Parquet output without rewriting it: it must decode cleanly, have the columns of
+    /// its output schema version, and be ordered by (country, year, month)
+    Verify {
+        /// Parquet file written by a run, e.g. output/run1/run1.parquet
+        file: PathBuf,
+
+        /// Output schema version the file must have; either is accepted when omitted
+        #[arg(long)]
+        output_schema_version: Option<SchemaVersion>,
+    },
+}
+
+/// Values printed by the `list` subcommand
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ListKind {
+    Countries,
+    Years,
+}
+
+impl Args {
+    /// Resolves the input files and their column mappings from the CLI and `--config`.
+    fn resolve_inputs(&mut self) -> Result<(), PipelineError> {
+        let pipeline_config = match &self.config {
+            Some(path) => PipelineConfig::from_yaml(path)?,
+            None => PipelineConfig::default(),
+        };
+        let files = lib::expand_input_paths(&self.input_file)?;
+        let columns = self.column_map.clone().unwrap_or_default();
+        self.inputs = pipeline_config.resolve_inputs(&files, &columns);
+        if self.input_format != InputFormat::Auto {
+            // Command-line files come first, followed by the configured inputs
+            for input in &mut self.inputs[..files.len()] {
+                input.format = self.input_format;
+            }
+        }
+        for input in self.inputs.iter_mut().filter(|input| input.is_remote()) {
+            input.path = download(&input.path.to_string_lossy(), &self.output_dir)?;
+        }
+        #[cfg(feature = "archive")]
+        {
+            let inputs = std::mem::take(&mut self.inputs);
+            (self.inputs, self.archives) = lib::expand_archives(
+                inputs,
+                self.archive_pattern.as_ref(),
+                &Ledger::open(&self.output_dir),
+            )?;
+        }
+        if self.inputs.is_empty() {
+            return Err(PipelineError::Data("no input files given".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Directory the output directory is resolved in: `--output-dir`, or
+    /// `<output-dir>/<namespace>`.
+    fn output_root(&self) -> PathBuf {
+        self.namespace.as_deref().map_or_else(
+            || self.output_dir.clone(),
+            |namespace| self.output_dir.join(sanitize_file_name(namespace)),
+        )
+    }
+
+    /// The `--csv-out`, `--json-out`, or `--parquet-out` path of an output suffix, if given.
+    fn output_override(&self, suffix: &str) -> Option<&Path> {
+        match suffix {
+            ".csv" => self.csv_out.as_deref(),
+            ".json" | ".ndjson" => self.json_out.as_deref(),
+            ".parquet" => self.parquet_out.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Input paths for display.
+    fn inputs_display(&self) -> String {
+        self.inputs
+            .iter()
+            .map(|input| input.path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn anonymize_config(&self) -> AnonymizeConfig {
+        AnonymizeConfig {
+            bucket_width: self.bucket_width,
+            min_group_size: self.min_group_size,
+        }
+    }
+}
+
+/// Downloads a URL input into the download cache, returning its local path.
+#[cfg(feature = "http")]
+fn download(url: &str, root: &Path) -> Result<PathBuf, PipelineError> {
+    lib::remote::fetch(url, &root.join(DOWNLOAD_DIR), &Ledger::open(root))
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &str, _root: &Path) -> Result<PathBuf, PipelineError> {
+    Err(PipelineError::Data(format!(
+        "cannot read {}: URL inputs require the 'http' feature",
+        url
+    )))
+}
+
+/// Compiles an optional `--row-script` / `--record-script`.
+#[cfg(feature = "scripting")]
+fn load_script(path: Option<&Path>) -> Result<Option<std::sync::Arc<lib::Script>>, PipelineError> {
+    path.map(lib::Script::load)
+        .transpose()
+        .map(|script| script.map(std::sync::Arc::new))
+}
+
+/// Parses a glob pattern for archive members.
+#[cfg(feature = "archive")]
+fn parse_pattern(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|e| e.to_string())
+}
+
+/// Parses a strictly positive number.
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn main() -> Result<ExitCode, PipelineError> {
+    let mut failure = FailureContext::default();
+    let result = run(&mut failure);
+    if let Err(error) = &result {
+        failure.report(error);
+    }
+    result
+}
+
+/// What is known about a pipeline run when it fails, for its error report
+#[derive(Default)]
+struct FailureContext {
+    /// Output directory of the run, once resolved; subcommands write no report
+    dir: Option<PathBuf>,
+    run_id: String,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    config_file: Option<PathBuf>,
+    stats: Option<ProcessingStats>,
+}
+
+impl FailureContext {
+    /// Writes `error_report.json` into the run's output directory. A report that cannot be
+    /// written is logged, leaving the original error to be returned.
+    fn report(&self, error: &PipelineError) {
+        let (Some(dir), Some(started_at)) = (&self.dir, self.started_at) else {
+            return;
+        };
+        let report = ErrorReport {
+            run_id: self.run_id.clone(),
+            started_at: started_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            failed_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            errors: ErrorReport::error_chain(error),
+            arguments: std::env::args().collect(),
+            config_file: self.config_file.clone(),
+            stats: self.stats.clone(),
+        };
+        let path = dir.join(ERROR_REPORT_FILE);
+        let written = fs::create_dir_all(dir)
+            .map_err(PipelineError::from)
+            .and_then(|_| write_error_report(&report, &path));
+        match written {
+            Ok(()) => status!("Wrote error report to {}", path.display()),
+            Err(e) => log::error!("Could not write error report {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Runs the pipeline or a subcommand. Everything the run created, including the extracted
+/// archive directories, is dropped before the exit code is returned.
+fn run(failure: &mut FailureContext) -> Result<ExitCode, PipelineError> {
     // Initialize timer and logger
     let total_start = Instant::now();
+    let started_at = chrono::Utc::now();
     log::set_logger(&LOGGER).unwrap();
 
     // Acquire CLI args
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(Command::Clean { dry_run }) = &args.command {
+        log::set_max_level(log::LevelFilter::Info);
+        return run_clean(*dry_run, &args.output_dir).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(Command::DiffOutputs {
+        left,
+        right,
+        tolerance,
+    }) = &args.command
+    {
+        log::set_max_level(log::LevelFilter::Info);
+        return run_diff_outputs(left, right, *tolerance).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(Command::List {
+        what,
+        input_file,
+        column_map,
+        input_format,
+    }) = &args.command
+    {
+        log::set_max_level(log::LevelFilter::Info);
+        let what = *what;
+        args.input_file = input_file.clone();
+        args.column_map = column_map.clone();
+        args.input_format = *input_format;
+        args.resolve_inputs()?;
+        return run_list(what, &args.inputs).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(Command::Verify {
+        file,
+        output_schema_version,
+    }) = &args.command
+    {
+        log::set_max_level(log::LevelFilter::Info);
+        return run_verify(file, *output_schema_version).map(|()| ExitCode::SUCCESS);
+    }
+    #[cfg(feature = "self-update")]
+    if let Some(Command::SelfUpdate { check }) = &args.command {
+        log::set_max_level(log::LevelFilter::Info);
+        return run_self_update(*check).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(Command::Schema {
+        output_schema_version,
+        collapsed_year,
+        collapsed_month,
+        include_country_names,
+        day,
+        enso,
+        season,
+        aridity,
+        distinct_days,
+        outliers_removed,
+        confidence_interval,
+        mode_range,
+        trimmed_mean,
+        percentiles,
+        positive_means,
+        zscore,
+        anomaly,
+        plugin_metric,
+        provenance,
+        keep_celsius,
+        arrow,
+        json_case,
+        json_layout,
+        null_value,
+        output,
+    }) = &args.command
+    {
+        let config = OutputConfig {
+            schema_version: *output_schema_version,
+            collapsed_year: *collapsed_year,
+            collapsed_month: *collapsed_month,
+            include_country_names: *include_country_names,
+            include_day: *day,
+            include_enso_phase: *enso,
+            include_season: *season,
+            include_aridity_index: *aridity,
+            include_distinct_days: *distinct_days,
+            include_outliers_removed: *outliers_removed,
+            include_confidence_interval: *confidence_interval,
+            include_mode_range: *mode_range,
+            include_trimmed_mean: *trimmed_mean,
+            include_positive_means: *positive_means,
+            include_zscore: *zscore,
+            include_anomaly: *anomaly,
+            include_plugin_metric: *plugin_metric,
+            include_provenance: *provenance,
+            include_celsius: *keep_celsius,
+            percentiles: check_percentiles(percentiles)?,
+            decimal_comma: false,
+            json_case: *json_case,
+            json_layout: *json_layout,
+            null_value: null_value.clone(),
+            std_dev: StdDevMode::Sample,
+            hemisphere: Hemisphere::North,
+        };
+        let schema = if *arrow {
+            arrow_schema_json(&config)
+        } else {
+            json_schema(&config)
+        };
+        let text = serde_json::to_string_pretty(&schema)? + "\n";
+        match output {
+            Some(path) => fs::write(path, text)?,
+            None => print!("{}", text),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    args.run_id = lib::run_id();
+    tag_logs_with_run_id(&args.run_id);
+    *failure = FailureContext {
+        dir: Some(resolve_output(&args.output_root(), &args.output).0),
+        run_id: args.run_id.clone(),
+        started_at: Some(started_at),
+        config_file: args.config.clone(),
+        stats: None,
+    };
+    args.resolve_inputs()?;
     let start_year = args.start_year.unwrap_or(1980);
     let end_year = args.end_year.unwrap_or(2024);
     if args.debug {
@@ -64,23 +1020,62 @@ fn main() -> Result<(), PipelineError> {
     } else {
         log::set_max_level(log::LevelFilter::Info);
     }
-    let countries_display = if args.countries.is_empty() {
+    let mut countries_display = if args.countries.is_empty() {
         "ALL".to_string()
     } else {
         args.countries.join(",")
     };
+    if !args.exclude_countries.is_empty() {
+        if !args.countries.is_empty()
+            && args
+                .countries
+                .iter()
+                .all(|country| args.exclude_countries.contains(country))
+        {
+            return Err(PipelineError::Data(
+                "every --countries code is excluded by --exclude-countries".to_string(),
+            ));
+        }
+        countries_display = format!(
+            "{} except {}",
+            countries_display,
+            args.exclude_countries.join(",")
+        );
+    }
+
+    if args.summary_json {
+        send_status_to_stderr();
+    }
+    // Before any thread is spawned, so that they inherit the priority
+    if let Some(nice) = args.nice.or(args.low_priority.then_some(LOW_PRIORITY_NICE)) {
+        lower_priority(nice)?;
+        debug!("Running at nice level {}", nice);
+    }
+    interrupt::install_handler()?;
+    if args.profile {
+        profile::enable();
+    }
+    if args.timestamp_output || args.retain.is_some() {
+        let stamped = timestamped_name(&args.output, started_at);
+        args.output_base = Some(std::mem::replace(&mut args.output, stamped));
+    }
+    failure.dir = Some(resolve_output(&args.output_root(), &args.output).0);
 
     // UI
-    println!("Transformer! Rust Weather Data Pipeline");
+    status!("Transformer! Rust Weather Data Pipeline");
+    status!("Run ID: {}", args.run_id);
     debug!(
         "Input file: {} | Countries: {}",
-        args.input_file.display(),
+        args.inputs_display(),
         countries_display
     );
     debug!(
         "Date range: {}-{} | Temperature unit: {:?}",
         start_year, end_year, args.unit
     );
+    if let Some(months) = args.months {
+        debug!("Months: {}", months);
+    }
     if let Some(threshold) = args.threshold {
         debug!("Outlier threshold: {} std devs", threshold);
     }
@@ -90,7 +1085,7 @@ fn main() -> Result<(), PipelineError> {
 
     debug!(
         "Processing {} for {} ({}-{})",
-        args.input_file.display(),
+        args.inputs_display(),
         countries_display,
         start_year,
         end_year
@@ -101,69 +1096,621 @@ fn main() -> Result<(), PipelineError> {
         "Creating transformation configuration | Unit={:?}, Threshold={:?}, Aggregate={}",
         args.unit, args.threshold, args.aggregate
     );
+    let enso = if let Some(path) = &args.enso_table {
+        Some(EnsoTable::from_csv(path)?)
+    } else if args.enso || args.group_by.contains(&GroupDimension::EnsoPhase) {
+        Some(EnsoTable::bundled())
+    } else {
+        None
+    };
+    let include_enso_phase = enso.is_some();
+
+    let mut extra_units = Vec::new();
+    for unit in &args.unit[1..] {
+        if *unit != args.unit[0] && !extra_units.contains(unit) {
+            extra_units.push(*unit);
+        }
+    }
+    let custom = args.unit.contains(&TemperatureUnit::Custom);
+    if !custom && (args.unit_scale.is_some() || args.unit_offset.is_some()) {
+        return Err(PipelineError::Data(
+            "--unit-scale and --unit-offset only apply to --unit custom".to_string(),
+        ));
+    }
+    if args.unit_scale == Some(0.0) {
+        return Err(PipelineError::Data(
+            "--unit-scale must not be zero".to_string(),
+        ));
+    }
+    if !(args.mode_bin_width > 0.0 && args.mode_bin_width.is_finite()) {
+        return Err(PipelineError::Data(
+            "--mode-bin-width must be a positive number".to_string(),
+        ));
+    }
+    if let Some(fraction) = args.trimmed_mean
+        && !(0.0..0.5).contains(&fraction)
+    {
+        return Err(PipelineError::Data(
+            "--trimmed-mean must be in [0, 0.5)".to_string(),
+        ));
+    }
+    let threshold = match args.outlier_method {
+        OutlierMethod::Iqr if args.threshold.is_some() => {
+            return Err(PipelineError::Data(
+                "--outlier-method iqr takes its multiplier from --iqr-multiplier, not --threshold"
+                    .to_string(),
+            ));
+        }
+        OutlierMethod::Iqr => Some(args.iqr_multiplier.unwrap_or(DEFAULT_IQR_MULTIPLIER)),
+        _ if args.iqr_multiplier.is_some() => {
+            return Err(PipelineError::Data(
+                "--iqr-multiplier only applies to --outlier-method iqr".to_string(),
+            ));
+        }
+        _ => args.threshold,
+    };
+    args.percentiles = check_percentiles(&args.percentiles)?;
+    if !args.percentiles.is_empty() && args.diff_against.is_some() {
+        return Err(PipelineError::Data(
+            "--diff-against compares the percentile_25 ... percentile_95 columns and cannot be \
+             combined with --percentiles"
+                .to_string(),
+        ));
+    }
+    let mut formats = Vec::with_capacity(args.formats.len());
+    for format in args.formats.drain(..) {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    args.formats = formats;
+    for format in OutputFormat::ALL {
+        if !args.formats.contains(&format) && args.output_override(format.suffix()).is_some() {
+            let name = format.suffix().trim_start_matches('.');
+            return Err(PipelineError::Data(format!(
+                "--{}-out given but --formats does not include {}",
+                name, name
+            )));
+        }
+    }
+    let custom_scale = LinearScale {
+        scale: args.unit_scale.unwrap_or(1.0),
+        offset: args.unit_offset.unwrap_or(0.0),
+    };
+    if custom {
+        debug!(
+            "Custom unit: value = celsius * {} + {}",
+            custom_scale.scale, custom_scale.offset
+        );
+    }
     let config = TransformConfig {
-        unit: args.unit,
-        threshold: args.threshold,
+        columns: args.column_map.clone().unwrap_or_default(),
+        unit: args.unit[0],
+        extra_units,
+        custom_scale,
+        valid_min: args.valid_min,
+        valid_max: args.valid_max,
+        threshold,
+        outlier_method: args.outlier_method,
+        outlier_window: args.outlier_window,
+        outlier_action: args.outlier_action,
+        quantile_method: args.quantile_method,
+        percentiles: args.percentiles.clone(),
+        std_dev: args.stddev,
         aggregate: args.aggregate,
+        strict: args.strict,
+        self_check: args.self_check,
+        group_by: args.group_by.clone(),
+        granularity: args.granularity,
+        enso,
+        hemisphere: args.hemisphere,
+        daily_collapse: args.daily_collapse,
+        aridity: args.aridity,
+        provenance: args.provenance,
+        keep_celsius: args.keep_celsius,
+        significant_digits: args.significant_digits,
+        streaming: args.streaming_stats,
+        distinct_days: args.distinct_days,
+        confidence_interval: args.confidence_interval,
+        mode_range: args.mode_range,
+        mode_bin_width: args.mode_bin_width,
+        trimmed_mean: args.trimmed_mean,
+        positive_means: args.positive_means,
+        strategy: args.strategy,
+        threads: args.threads.map(|threads| threads as usize),
+        country_memory_limit: args.country_memory_mib.map(|mib| mib * 1024 * 1024),
+        #[cfg(feature = "gpu")]
+        gpu_min_group: args.gpu,
+        #[cfg(not(feature = "gpu"))]
+        gpu_min_group: None,
+        #[cfg(feature = "plugins")]
+        plugin: args
+            .plugin
+            .as_deref()
+            .map(lib::Plugin::load)
+            .transpose()?
+            .map(std::sync::Arc::new),
+        #[cfg(feature = "scripting")]
+        row_script: load_script(args.row_script.as_deref())?,
+        #[cfg(feature = "scripting")]
+        record_script: load_script(args.record_script.as_deref())?,
     };
+    let group_by = config.dimensions();
+    debug!("Grouping by {:?}", group_by);
+
+    let output_config = OutputConfig {
+        schema_version: args.output_schema_version,
+        collapsed_year: !group_by.contains(&GroupDimension::Year),
+        collapsed_month: !group_by.contains(&GroupDimension::Month),
+        include_country_names: args.include_country_names,
+        include_day: group_by.contains(&GroupDimension::Day),
+        include_enso_phase,
+        include_season: group_by.contains(&GroupDimension::Season),
+        include_aridity_index: args.aridity,
+        include_distinct_days: args.distinct_days,
+        include_outliers_removed: config.threshold.is_some(),
+        include_confidence_interval: args.confidence_interval,
+        include_mode_range: args.mode_range,
+        include_trimmed_mean: args.trimmed_mean.is_some(),
+        include_positive_means: args.positive_means,
+        include_zscore: args.zscore,
+        include_anomaly: args.anomaly,
+        #[cfg(feature = "plugins")]
+        include_plugin_metric: config
+            .plugin
+            .as_ref()
+            .is_some_and(|plugin| plugin.has_post_aggregate()),
+        #[cfg(not(feature = "plugins"))]
+        include_plugin_metric: false,
+        include_provenance: args.provenance,
+        include_celsius: args.keep_celsius,
+        percentiles: args.percentiles.clone(),
+        decimal_comma: args.decimal_comma,
+        json_case: args.json_case,
+        json_layout: args.json_layout,
+        null_value: args.null_value.clone(),
+        std_dev: args.stddev,
+        hemisphere: args.hemisphere,
+    };
+    debug!("Output schema version: {:?}", output_config.schema_version);
+
+    if args.stream {
+        if !config.extra_units.is_empty() {
+            return Err(PipelineError::Data(
+                "--stream writes a single unit; pass one --unit".to_string(),
+            ));
+        }
+        return run_streaming(
+            &args,
+            &config,
+            &output_config,
+            start_year,
+            end_year,
+            total_start,
+            started_at,
+        );
+    }
 
     // Process data with comprehensive statistics
-    println!("Starting data processing...");
+    status!("Starting data processing...");
     let processing_start = Instant::now();
-    let results = process_data(
-        &args.input_file,
-        &args.countries,
-        start_year,
-        end_year,
-        &config,
-    )?;
+    let (mut results, mut stats) = process_cached(&args, start_year, end_year, &config)?;
+    failure.stats = Some(stats.clone());
+    if args.zscore {
+        attach_period_zscores(&mut results, config.std_dev);
+        if let Some(digits) = config.significant_digits {
+            for record in &mut results {
+                record.round_significant(digits);
+            }
+        }
+    }
+    if args.provenance {
+        lib::stamp_provenance(&mut results, &args.run_id, started_at);
+    }
+    let mut extra_results = split_units(&mut results, &config);
+    let anonymize_config = args.anonymize_config();
+    if anonymize_config.is_enabled() {
+        stats.suppressed_groups = anonymize_all(&mut results, &anonymize_config);
+        for (_, records) in &mut extra_results {
+            anonymize_all(records, &anonymize_config);
+        }
+        status!(
+            "Anonymized records | Suppressed {} below the minimum group size",
+            stats.suppressed_groups
+        );
+    }
     let processing_time = processing_start.elapsed();
-    println!(
+    status!(
         "Data processing completed in {:.2?} | Processed {} records",
         processing_time,
         results.len()
     );
+    if stats.interrupted {
+        return write_partial_outputs(&args, &results, stats, &output_config, processing_time);
+    }
 
-    // Create output directory
-    let output_dir = PathBuf::from(format!("./output/{}", args.output));
-    fs::create_dir_all(&output_dir)?;
-    println!(
-        "Created output directory: {} | Writing output files...",
-        output_dir.display()
-    );
+    if let Some(path) = &args.expectations {
+        check_expectations(path, &results)?;
+    }
+
+    if args.dry_run {
+        return report_dry_run(&args, &results, &extra_results, &output_config)
+            .map(|()| ExitCode::SUCCESS);
+    }
+
+    // Baseline means come first, as --anomaly adds them to the records before they are written
+    let baseline = match &args.baseline {
+        Some(period) => {
+            let means_start = Instant::now();
+            let means = baseline_means_for(&args, &config, &results, period, start_year, end_year)?;
+            if args.anomaly {
+                attach_anomalies(&mut results, &means, config.unit, &config.custom_scale);
+                for (_, records) in &mut extra_results {
+                    attach_anomalies(records, &means, config.unit, &config.custom_scale);
+                }
+                if let Some(digits) = config.significant_digits {
+                    for record in results.iter_mut().chain(
+                        extra_results
+                            .iter_mut()
+                            .flat_map(|(_, records)| records.iter_mut()),
+                    ) {
+                        record.round_significant(digits);
+                    }
+                }
+            }
+            status!(
+                "Baseline means ({} country-months) took {:.2?}",
+                means.len(),
+                means_start.elapsed()
+            );
+            Some(means)
+        }
+        None => None,
+    };
+
+    let target = create_output_dir(&args)?;
+    let ledger = &target.ledger;
     let io_start = Instant::now();
+    let mut primary_paths = Vec::with_capacity(args.formats.len());
+    for &format in &args.formats {
+        let path = target.primary(format.suffix(), &args)?;
+        let format_start = Instant::now();
+        write_atomic(ledger, &path, |path| {
+            write_output(format, &results, path, &output_config)
+        })?;
+        status!("{} write took {:.2?}", format, format_start.elapsed());
+        primary_paths.push((format, path));
+    }
+
+    let mut unit_paths = Vec::new();
+    for (unit, records) in &extra_results {
+        let unit_start = Instant::now();
+        for &format in &args.formats {
+            let path = target.file(&format!("_{}{}", unit, format.suffix()));
+            write_atomic(ledger, &path, |path| {
+                write_output(format, records, path, &output_config)
+            })?;
+            unit_paths.push(path);
+        }
+        status!(
+            "{} {} writes took {:.2?}",
+            unit,
+            format_names(&args.formats),
+            unit_start.elapsed()
+        );
+    }
+
+    let mut partition_paths = Vec::new();
+    if args.split_by_country {
+        let partitions_start = Instant::now();
+        let timings = write_country_partitions(
+            &results,
+            &target.dir,
+            &target.name,
+            &args.formats,
+            &output_config,
+            ledger,
+            args.writer_threads as usize,
+        )?;
+        for timing in &timings {
+            debug!(
+                "  {}: {} records in {:.2?}",
+                timing.partition, timing.records, timing.elapsed
+            );
+        }
+        let slowest = timings.iter().max_by_key(|timing| timing.elapsed);
+        status!(
+            "{} country partitions ({} writers) took {:.2?}{}",
+            timings.len(),
+            args.writer_threads,
+            partitions_start.elapsed(),
+            slowest.map_or(String::new(), |timing| format!(
+                " | slowest {} {:.2?}",
+                timing.partition, timing.elapsed
+            ))
+        );
+        partition_paths = timings
+            .into_iter()
+            .flat_map(|timing| timing.files)
+            .collect();
+    }
+
+    if args.verify_outputs {
+        let verify_start = Instant::now();
+        for (format, path) in &primary_paths {
+            verify_output(*format, &results, path, &output_config)?;
+        }
+        status!(
+            "Verified {} outputs in {:.2?}",
+            format_names(&args.formats),
+            verify_start.elapsed()
+        );
+    }
+
+    let mut diff_path = None;
+    if let Some(previous_path) = &args.diff_against {
+        let diff_start = Instant::now();
+        let previous = read_previous_records(previous_path, output_config.json_null())?;
+        let changes = diff_records(&previous, &results);
+        let path = target.file("_diff.csv");
+        write_atomic(ledger, &path, |path| {
+            write_diff_csv(&changes, path, &output_config)
+        })?;
+        let count = |change_type| {
+            changes
+                .iter()
+                .filter(|c| c.change_type == change_type)
+                .count()
+        };
+        status!(
+            "Diff against {}: {} added, {} changed, {} removed ({:.2?})",
+            previous_path.display(),
+            count(ChangeType::Added),
+            count(ChangeType::Changed),
+            count(ChangeType::Removed),
+            diff_start.elapsed()
+        );
+        diff_path = Some(path);
+    }
+
+    let mut baseline_path = None;
+    let mut baseline_summary = None;
+    if let Some(means) = &baseline {
+        let baseline_start = Instant::now();
+        let path = target.file("_above_baseline.csv");
+        let summary = above_baseline(&results, means, args.baseline_margin);
+        write_atomic(ledger, &path, |path| {
+            write_above_baseline_csv(&summary, args.baseline_margin, path)
+        })?;
+        status!(
+            "Baseline summary ({} countries) took {:.2?}",
+            summary.len(),
+            baseline_start.elapsed()
+        );
+        baseline_path = Some(path);
+        baseline_summary = Some(summary);
+    }
+
+    let mut geojson_path = None;
+    if args.geojson || args.geojson_base.is_some() {
+        let geojson_start = Instant::now();
+        let path = target.file("_choropleth.geojson");
+        let joined = write_atomic(ledger, &path, |path| {
+            write_choropleth_geojson(
+                &results,
+                baseline.as_ref(),
+                args.geojson_base.as_deref(),
+                path,
+            )
+        })?;
+        status!(
+            "GeoJSON write ({} countries joined) took {:.2?}",
+            joined,
+            geojson_start.elapsed()
+        );
+        geojson_path = Some(path);
+    }
+
+    let mut summary_path = None;
+    if args.country_summary {
+        let summary_start = Instant::now();
+        let path = target.file("_summary.json");
+        let countries = write_atomic(ledger, &path, |path| {
+            write_country_summary(&results, start_year, end_year, config.unit, path)
+        })?;
+        status!(
+            "Country summary ({} countries) took {:.2?}",
+            countries,
+            summary_start.elapsed()
+        );
+        summary_path = Some(path);
+    }
+
+    let mut trends_path = None;
+    if args.trends {
+        let trends_start = Instant::now();
+        let path = target.file("_trends.csv");
+        let fitted = write_atomic(ledger, &path, |path| {
+            write_trends_csv(&results, &config.custom_scale, path)
+        })?;
+        status!(
+            "Trends ({} countries fitted) took {:.2?}",
+            fitted,
+            trends_start.elapsed()
+        );
+        trends_path = Some(path);
+    }
 
-    // Extract just the directory name for the file names (remove path separators)
-    let output_name = args
-        .output
-        .split(['/', '\\'])
-        .next_back()
-        .unwrap_or(&args.output);
-    let csv_path = output_dir.join(format!("{}.csv", output_name));
-    let json_path = output_dir.join(format!("{}.json", output_name));
-    let parquet_path = output_dir.join(format!("{}.parquet", output_name));
+    let mut line_protocol_path = None;
+    if args.line_protocol {
+        let line_protocol_start = Instant::now();
+        let path = target.file(".lp");
+        write_atomic(ledger, &path, |path| write_line_protocol(&results, path))?;
+        status!(
+            "Line protocol write took {:.2?}",
+            line_protocol_start.elapsed()
+        );
+        line_protocol_path = Some(path);
+    }
+    #[cfg(feature = "protobuf")]
+    let protobuf_path = if args.protobuf {
+        let protobuf_start = Instant::now();
+        let path = target.file(".pb");
+        write_atomic(ledger, &path, |path| {
+            lib::write_protobuf(&results, path, &output_config)
+        })?;
+        status!("Protobuf write took {:.2?}", protobuf_start.elapsed());
+        Some(path)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "protobuf"))]
+    let protobuf_path: Option<PathBuf> = None;
+    #[cfg(feature = "http")]
+    if let Some(url) = &args.influx_url {
+        let influx_start = Instant::now();
+        let token = std::env::var(INFLUX_TOKEN_VAR).ok();
+        let points = lib::post_line_protocol(&results, url, token.as_deref())?;
+        status!(
+            "InfluxDB write ({} points) took {:.2?}",
+            points,
+            influx_start.elapsed()
+        );
+    }
 
-    let csv_start = Instant::now();
-    write_csv(&results, &csv_path)?;
-    println!("CSV write took {:.2?}", csv_start.elapsed());
+    #[cfg(feature = "template")]
+    let template_paths = render_templates(&args, &target, &results, &stats, &output_config)?;
+    #[cfg(not(feature = "template"))]
+    let template_paths: Vec<PathBuf> = Vec::new();
 
-    let json_start = Instant::now();
-    write_json(&results, &json_path)?;
-    println!("JSON write took {:.2?}", json_start.elapsed());
+    #[cfg(feature = "excel")]
+    let excel_paths = write_excel_outputs(
+        &args,
+        &target,
+        &results,
+        &stats,
+        baseline_summary.as_deref(),
+        &output_config,
+    )?;
+    #[cfg(not(feature = "excel"))]
+    let excel_paths: Vec<PathBuf> = {
+        let _ = baseline_summary;
+        Vec::new()
+    };
 
-    let parquet_start = Instant::now();
-    write_parquet(&results, &parquet_path)?;
-    println!("Parquet write took {:.2?}", parquet_start.elapsed());
+    #[cfg(feature = "iceberg")]
+    if let Some(table_dir) = &args.iceberg {
+        let iceberg_start = Instant::now();
+        let commit = lib::write_iceberg(
+            &results,
+            table_dir,
+            &output_config,
+            args.iceberg_mode,
+            args.dedupe_output,
+        )?;
+        status!(
+            "Iceberg {:?} commit of snapshot {} ({} files added, {} deleted) took {:.2?}",
+            args.iceberg_mode,
+            commit.snapshot_id,
+            commit.added_files,
+            commit.deleted_files,
+            iceberg_start.elapsed()
+        );
+        debug!("  - {}", commit.metadata_path.display());
+        if commit.overlapping_records > 0 {
+            match args.dedupe_output {
+                Some(policy) => status!(
+                    "Resolved {} records already in the Iceberg table with {:?}",
+                    commit.overlapping_records,
+                    policy
+                ),
+                None => status!(
+                    "Warning: {} records were already in the Iceberg table and are now duplicated; pass --dedupe-output to replace or merge them",
+                    commit.overlapping_records
+                ),
+            }
+        }
+    }
+
+    #[cfg(feature = "gsheet")]
+    if let Some(spreadsheet_id) = &args.gsheet {
+        let gsheet_start = Instant::now();
+        let token = std::env::var(GSHEET_TOKEN_VAR).map_err(|_| {
+            PipelineError::Data(format!(
+                "--gsheet requires an OAuth access token in {} (e.g. from `gcloud auth print-access-token`)",
+                GSHEET_TOKEN_VAR
+            ))
+        })?;
+        let cells = lib::write_gsheet(
+            &results,
+            spreadsheet_id,
+            &args.gsheet_tab,
+            &token,
+            &output_config,
+        )?;
+        status!(
+            "Google Sheet write ({} cells) took {:.2?}",
+            cells,
+            gsheet_start.elapsed()
+        );
+    }
+
+    let mut files: Vec<PathBuf> = primary_paths.iter().map(|(_, path)| path.clone()).collect();
+    files.extend(
+        unit_paths
+            .iter()
+            .chain(&partition_paths)
+            .chain(&baseline_path)
+            .chain(&geojson_path)
+            .chain(&summary_path)
+            .chain(&trends_path)
+            .chain(&line_protocol_path)
+            .chain(&protobuf_path)
+            .chain(&template_paths)
+            .chain(&excel_paths)
+            .cloned(),
+    );
+    let (manifest_path, manifest) = write_run_manifest(&target, results.len(), &files, &mut stats)?;
+
+    if let Some(path) = &args.history {
+        let path = path
+            .clone()
+            .unwrap_or_else(|| args.output_dir.join(HISTORY_FILE));
+        let run = HistoryRun {
+            timestamp: started_at,
+            output: args.output.clone(),
+            countries: countries_display.clone(),
+            start_year,
+            end_year,
+            unit: config.unit,
+        };
+        let rows = append_history(&path, &run, &results)?;
+        status!("Appended {} row(s) to run history {}", rows, path.display());
+    }
 
     let io_time = io_start.elapsed();
-    println!("All files took {:.2?}", io_time);
-    println!("\nWrote files to directory: {}", output_dir.display());
-    debug!("  - {}", csv_path.display());
-    debug!("  - {}", json_path.display());
-    debug!("  - {}", parquet_path.display());
+    status!("All files took {:.2?}", io_time);
+    status!("\nWrote files to directory: {}", target.dir.display());
+    for path in primary_paths
+        .iter()
+        .map(|(_, path)| path)
+        .chain(&unit_paths)
+        .chain(&diff_path)
+        .chain(&baseline_path)
+        .chain(&geojson_path)
+        .chain(&summary_path)
+        .chain(&trends_path)
+        .chain(&line_protocol_path)
+        .chain(&protobuf_path)
+        .chain(&template_paths)
+        .chain(&excel_paths)
+        .chain([&manifest_path])
+    {
+        debug!("  - {}", path.display());
+    }
 
     // Show summary
-    println!("\nProcessed {} records", results.len());
+    status!("\nProcessed {} records", results.len());
     if let Some(first) = results.first() {
         debug!(
             "Sample: {} {}/{} avg={:.1}°C count={}",
@@ -171,14 +1718,805 @@ fn main() -> Result<(), PipelineError> {
         );
     }
 
+    print_memory(&stats);
+    print_throughput(&stats);
+    write_run_profile(&args, &target)?;
+    print_warnings(&stats);
+
     let total_time = total_start.elapsed();
-    println!("Pipeline completed successfully in {:.2?}", total_time);
+    status!("Pipeline completed successfully in {:.2?}", total_time);
     debug!(
         "Performance breakdown: Processing={:.1}%, IO={:.1}%",
         (processing_time.as_secs_f64() / total_time.as_secs_f64()) * 100.0,
         (io_time.as_secs_f64() / total_time.as_secs_f64()) * 100.0
     );
 
-    println!("\nTotal runtime: {:.2?}", total_time);
+    status!("\nTotal runtime: {:.2?}", total_time);
+    prune_outputs(&args)?;
+    if args.summary_json {
+        let timings = RunTimings {
+            processing_secs: processing_time.as_secs_f64(),
+            io_secs: io_time.as_secs_f64(),
+            total_secs: total_time.as_secs_f64(),
+            throughput: stats.throughput(),
+        };
+        print_summary_json(manifest, &target, timings)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Processes the input, going through the on-disk cache when `--cache` is given.
+///
+/// Self-check runs always recompute so the sampled groups are actually verified.
+fn process_cached(
+    args: &Args,
+    start_year: i32,
+    end_year: i32,
+    config: &TransformConfig,
+) -> Result<(Vec<lib::Record>, ProcessingStats), PipelineError> {
+    let process = || {
+        process_inputs_with_stats(
+            &args.inputs,
+            &args.countries,
+            &args.exclude_countries,
+            start_year,
+            end_year,
+            args.months,
+            config,
+        )
+    };
+    if !args.cache {
+        return process();
+    }
+
+    let root = &args.output_dir;
+    let cache = StatsCache::open(&root.join(CACHE_DIR))?;
+    let key = StatsCache::key(
+        &args.inputs,
+        &args.countries,
+        &args.exclude_countries,
+        start_year,
+        end_year,
+        args.months,
+        config,
+    )?;
+    if config.self_check.is_none()
+        && let Some(cached) = cache.get(&key)
+    {
+        status!("Loaded {} records from cache ({})", cached.0.len(), key);
+        return Ok(cached);
+    }
+    let (results, stats) = process()?;
+    if stats.interrupted {
+        return Ok((results, stats));
+    }
+    cache.put(&Ledger::open(root), &key, &results, &stats)?;
+    debug!("Cached {} records under {}", results.len(), key);
+    Ok((results, stats))
+}
+
+/// Moves the records of `config.extra_units` out of `results`, grouped by unit.
+fn split_units(
+    results: &mut Vec<lib::Record>,
+    config: &TransformConfig,
+) -> Vec<(TemperatureUnit, Vec<lib::Record>)> {
+    if config.extra_units.is_empty() {
+        return Vec::new();
+    }
+    let (primary, extra): (Vec<_>, Vec<_>) = std::mem::take(results)
+        .into_iter()
+        .partition(|record| record.unit == config.unit);
+    *results = primary;
+    config
+        .extra_units
+        .iter()
+        .map(|unit| {
+            let records = extra.iter().filter(|r| r.unit == *unit).cloned().collect();
+            (*unit, records)
+        })
+        .collect()
+}
+
+/// Baseline means of `--baseline` per country and calendar month, from the results when the
+/// period lies within the requested years and from a second pass over the baseline years
+/// otherwise.
+fn baseline_means_for(
+    args: &Args,
+    config: &TransformConfig,
+    results: &[lib::Record],
+    period: &BaselinePeriod,
+    start_year: i32,
+    end_year: i32,
+) -> Result<std::collections::HashMap<(String, u32), f64>, PipelineError> {
+    if period.start_year >= start_year && period.end_year <= end_year {
+        return Ok(baseline_means(results, period));
+    }
+    debug!(
+        "Baseline {}-{} outside requested range, processing baseline years separately",
+        period.start_year, period.end_year
+    );
+    let (baseline_records, _) = process_inputs_with_stats(
+        &args.inputs,
+        &args.countries,
+        &args.exclude_countries,
+        period.start_year,
+        period.end_year,
+        args.months,
+        &TransformConfig {
+            extra_units: Vec::new(),
+            ..config.clone()
+        },
+    )?;
+    Ok(baseline_means(&baseline_records, period))
+}
+
+/// Output directory of a run together with the base name used for its files.
+struct OutputTarget {
+    dir: PathBuf,
+    name: String,
+    run_id: String,
+    ledger: Ledger,
+    /// Held until the run's outputs are written
+    _lock: RunLock,
+}
+
+impl OutputTarget {
+    /// Path of an output file: `<dir>/<name><suffix>`.
+    fn file(&self, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", self.name, suffix))
+    }
+
+    /// Path of the CSV, JSON / NDJSON, or Parquet output: the `--csv-out`, `--json-out`, or
+    /// `--parquet-out` override (whose directory is created if missing), or `<dir>/<name><suffix>`.
+    fn primary(&self, suffix: &str, args: &Args) -> Result<PathBuf, PipelineError> {
+        let Some(path) = args.output_override(suffix) else {
+            return Ok(self.file(suffix));
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(long_path(path))
+    }
+}
+
+/// Validates `--percentiles`, in 0-100, dropping repeated ones.
+fn check_percentiles(percentiles: &[f64]) -> Result<Vec<f64>, PipelineError> {
+    let mut checked: Vec<f64> = Vec::with_capacity(percentiles.len());
+    for &percent in percentiles {
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(PipelineError::Data(format!(
+                "--percentiles must be in [0, 100], got {}",
+                percent
+            )));
+        }
+        if !checked.contains(&percent) {
+            checked.push(percent);
+        }
+    }
+    Ok(checked)
+}
+
+/// Prints the files a run would write with their sizes, for `--dry-run`.
+fn report_dry_run(
+    args: &Args,
+    results: &[lib::Record],
+    extra_results: &[(TemperatureUnit, Vec<lib::Record>)],
+    output_config: &OutputConfig,
+) -> Result<(), PipelineError> {
+    let (output_dir, output_name) = resolve_output(&args.output_root(), &args.output);
+    let file = |suffix: &str| {
+        args.output_override(suffix).map_or_else(
+            || output_dir.join(format!("{}{}", output_name, suffix)),
+            Path::to_path_buf,
+        )
+    };
+    let mut files = Vec::new();
+    let mut record_files = |suffix: &str, records: &[lib::Record]| -> Result<(), PipelineError> {
+        let sizes = output_sizes(records, output_config)?;
+        for &format in &args.formats {
+            let path = file(&format!("{}{}", suffix, format.suffix()));
+            files.push((path, records.len(), sizes.of(format)));
+        }
+        Ok(())
+    };
+    record_files("", results)?;
+    for (unit, records) in extra_results {
+        record_files(&format!("_{}", unit), records)?;
+    }
+    if args.line_protocol {
+        let bytes = results
+            .iter()
+            .map(|record| line_protocol(record).len() as u64 + 1)
+            .sum();
+        files.push((file(".lp"), results.len(), bytes));
+    }
+    #[cfg(feature = "protobuf")]
+    if args.protobuf {
+        let columns = lib::output_columns(output_config);
+        let bytes = results
+            .iter()
+            .map(|record| {
+                let message = lib::WeatherStats::from_record(record, &columns);
+                prost::Message::encoded_len(&message) as u64
+                    + prost::length_delimiter_len(prost::Message::encoded_len(&message)) as u64
+            })
+            .sum();
+        files.push((file(".pb"), results.len(), bytes));
+    }
+
+    status!("Dry run: nothing was written");
+    for (path, records, bytes) in &files {
+        status!(
+            "  {} | {} records | {}",
+            path.display(),
+            records,
+            memory::format_mib(*bytes)
+        );
+    }
+    status!(
+        "Total: {} files, {}; other outputs (manifest, baseline, diff, GeoJSON, country summary, Excel, templates, and remote destinations) are not estimated",
+        files.len(),
+        memory::format_mib(files.iter().map(|(_, _, bytes)| bytes).sum())
+    );
+    Ok(())
+}
+
+/// Creates and locks `<output-dir>/[<namespace>/]<output>` and returns it with the base name used
+/// for the files.
+fn create_output_dir(args: &Args) -> Result<OutputTarget, PipelineError> {
+    let (output_dir, output_name) = resolve_output(&args.output_root(), &args.output);
+    fs::create_dir_all(&output_dir)?;
+    let lock = RunLock::acquire(&output_dir)?;
+    status!(
+        "Created output directory: {} | Writing output files...",
+        output_dir.display()
+    );
+    Ok(OutputTarget {
+        dir: output_dir,
+        name: output_name,
+        run_id: args.run_id.clone(),
+        ledger: Ledger::open(&args.output_dir),
+        _lock: lock,
+    })
+}
+
+/// Refreshes the peak RSS and writes `<name>_manifest.json` describing the run.
+fn write_run_manifest(
+    target: &OutputTarget,
+    records: usize,
+    files: &[PathBuf],
+    stats: &mut ProcessingStats,
+) -> Result<(PathBuf, Manifest), PipelineError> {
+    stats.peak_rss_bytes = memory::peak_rss_bytes().or(stats.peak_rss_bytes);
+    let manifest = Manifest {
+        run_id: target.run_id.clone(),
+        records,
+        files: files
+            .iter()
+            .filter_map(|path| match path.strip_prefix(&target.dir) {
+                Ok(relative) => Some(relative.as_os_str()),
+                Err(_) => path.file_name(),
+            })
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect(),
+        stats: stats.clone(),
+    };
+    let path = target.file("_manifest.json");
+    write_atomic(&target.ledger, &path, |path| {
+        write_manifest(&manifest, path)
+    })?;
+    Ok((path, manifest))
+}
+
+/// Applies `--retain`, deleting the oldest timestamped runs of the output name.
+fn prune_outputs(args: &Args) -> Result<(), PipelineError> {
+    let (Some(keep), Some(base)) = (args.retain, &args.output_base) else {
+        return Ok(());
+    };
+    let (base, name) = resolve_output(&args.output_root(), base);
+    let Some(parent) = base.parent() else {
+        return Ok(());
+    };
+    let removed = prune_runs(parent, &name, keep as usize)?;
+    for path in &removed {
+        debug!("  - removed {}", path.display());
+    }
+    if !removed.is_empty() {
+        status!(
+            "Retention: removed {} older output director{} (keeping {})",
+            removed.len(),
+            if removed.len() == 1 { "y" } else { "ies" },
+            keep
+        );
+    }
+    Ok(())
+}
+
+/// Prefix of the output files of an interrupted run
+const PARTIAL_PREFIX: &str = "partial_";
+
+/// Writes the records aggregated before an interrupt as `partial_<name>` files of `--formats`,
+/// skipping every other output, and returns [`interrupt::EXIT_CODE`].
+fn write_partial_outputs(
+    args: &Args,
+    results: &[lib::Record],
+    mut stats: ProcessingStats,
+    output_config: &OutputConfig,
+    processing_time: std::time::Duration,
+) -> Result<ExitCode, PipelineError> {
+    let mut target = create_output_dir(args)?;
+    target.name = format!("{}{}", PARTIAL_PREFIX, target.name);
+    let io_start = Instant::now();
+    let mut files = Vec::with_capacity(args.formats.len());
+    for &format in &args.formats {
+        let file = target.file(format.suffix());
+        write_atomic(&target.ledger, &file, |path| {
+            write_output(format, results, path, output_config)
+        })?;
+        files.push(file);
+    }
+    let (_, manifest) = write_run_manifest(&target, results.len(), &files, &mut stats)?;
+    let io_time = io_start.elapsed();
+    status!(
+        "\nInterrupted: wrote {} records aggregated so far to {}",
+        results.len(),
+        files[0].with_extension("*").display()
+    );
+    write_run_profile(args, &target)?;
+    print_warnings(&stats);
+    if args.summary_json {
+        let timings = RunTimings {
+            processing_secs: processing_time.as_secs_f64(),
+            io_secs: io_time.as_secs_f64(),
+            total_secs: processing_time.as_secs_f64() + io_time.as_secs_f64(),
+            throughput: stats.throughput(),
+        };
+        print_summary_json(manifest, &target, timings)?;
+    }
+    Ok(ExitCode::from(interrupt::EXIT_CODE))
+}
+
+/// Maximum number of warnings listed by `--summary-json`
+const SUMMARY_WARNINGS: usize = 10;
+
+/// Prints the `--summary-json` object of a finished run on stdout.
+fn print_summary_json(
+    mut manifest: Manifest,
+    target: &OutputTarget,
+    timings: RunTimings,
+) -> Result<(), PipelineError> {
+    let warning_count = manifest.stats.warnings.len();
+    manifest.stats.warnings.truncate(SUMMARY_WARNINGS);
+    let summary = RunSummary {
+        manifest,
+        output_dir: target.dir.clone(),
+        warning_count,
+        timings,
+    };
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
+
+/// Evaluates an expectations file against the results and prints a report.
+///
+/// # Errors
+/// Returns `PipelineError::Expectations` if any expectation failed.
+fn check_expectations(path: &Path, results: &[lib::Record]) -> Result<(), PipelineError> {
+    let outcomes = ExpectationSet::from_yaml(path)?.evaluate(results);
+    let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+    status!(
+        "Expectations: {} passed, {} failed",
+        outcomes.len() - failed,
+        failed
+    );
+    for outcome in outcomes.iter().filter(|outcome| !outcome.passed()) {
+        status!(
+            "  FAIL {} ({} of {} records)",
+            outcome.name,
+            outcome.violations,
+            outcome.checked
+        );
+        for failure in &outcome.failures {
+            status!("    - {}", failure);
+        }
+    }
+    if failed > 0 {
+        return Err(PipelineError::Expectations {
+            failed,
+            total: outcomes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Prints the memory footprint recorded during processing.
+fn print_memory(stats: &ProcessingStats) {
+    let buffers = memory::format_mib((stats.group_map_bytes + stats.group_buffer_bytes) as u64);
+    match stats.peak_rss_bytes {
+        Some(peak) => status!(
+            "Peak memory: {} (group buffers ~{})",
+            memory::format_mib(peak),
+            buffers
+        ),
+        None => status!("Group buffers: ~{}", buffers),
+    }
+}
+
+/// Prints the throughput of the extraction and analysis phases.
+/// Names of the `--formats` for status lines, e.g. `CSV/Parquet`.
+fn format_names(formats: &[OutputFormat]) -> String {
+    formats
+        .iter()
+        .map(|format| format.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn print_throughput(stats: &ProcessingStats) {
+    let throughput = stats.throughput();
+    status!(
+        "Throughput: extraction {:.0} rows/s, {:.1} MB/s ({:.2}s) | analysis {:.0} groups/s ({:.2}s)",
+        throughput.rows_per_sec,
+        throughput.mb_per_sec,
+        stats.extract_secs,
+        throughput.groups_per_sec,
+        stats.analyze_secs
+    );
+}
+
+/// Writes the `--profile` report into the output directory and prints where the time went.
+fn write_run_profile(args: &Args, target: &OutputTarget) -> Result<(), PipelineError> {
+    if !args.profile {
+        return Ok(());
+    }
+    let (profile, [table_path, folded_path]) = write_profile(&target.dir, &target.name)?;
+    let (io, cpu) = (
+        profile.own_time(SpanKind::Io),
+        profile.own_time(SpanKind::Cpu),
+    );
+    match profile.io_share() {
+        Some(share) => status!(
+            "Profile: I/O {:.2?} ({:.0}%), CPU {:.2?} ({:.0}%) across threads, {} bound",
+            io,
+            share * 100.0,
+            cpu,
+            (1.0 - share) * 100.0,
+            if share >= 0.5 { "I/O" } else { "CPU" }
+        ),
+        None => status!("Profile: no phase was timed (results loaded from the cache?)"),
+    }
+    debug!("  - {}", table_path.display());
+    debug!("  - {}", folded_path.display());
+    Ok(())
+}
+
+/// Prints warnings collected during processing.
+fn print_warnings(stats: &ProcessingStats) {
+    if !stats.warnings.is_empty() {
+        status!("\n{} warning(s):", stats.warnings.len());
+        for warning in &stats.warnings {
+            status!("  - {}", warning);
+        }
+    }
+}
+
+/// Suffix of a format's streamed output; JSON streams as NDJSON.
+fn stream_suffix(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Json => ".ndjson",
+        format => format.suffix(),
+    }
+}
+
+/// Runs the pipeline with the iterator API, streaming records into the CSV, NDJSON, and
+/// Parquet files of `--formats`.
+fn run_streaming(
+    args: &Args,
+    config: &TransformConfig,
+    output_config: &OutputConfig,
+    start_year: i32,
+    end_year: i32,
+    total_start: Instant,
+    started_at: chrono::DateTime<chrono::Utc>,
+) -> Result<ExitCode, PipelineError> {
+    status!("Starting streaming data processing...");
+    let processing_start = Instant::now();
+    let mut records = process_inputs_iter(
+        &args.inputs,
+        &args.countries,
+        &args.exclude_countries,
+        start_year,
+        end_year,
+        args.months,
+        config,
+    )?;
+    let processing_time = processing_start.elapsed();
+    status!("Data extraction completed in {:.2?}", processing_time);
+
+    let mut target = create_output_dir(args)?;
+    let io_start = Instant::now();
+    let mut paths = Vec::with_capacity(args.formats.len());
+    let mut sinks: Vec<Box<dyn RecordSink>> = Vec::with_capacity(args.formats.len());
+    for &format in &args.formats {
+        let suffix = stream_suffix(format);
+        let path = target.primary(suffix, args)?;
+        let staged = target.ledger.begin(&path)?;
+        sinks.push(match format {
+            OutputFormat::Csv => Box::new(CsvSink::create(&staged, output_config)?),
+            OutputFormat::Json => Box::new(NdjsonSink::create(&staged, output_config)?),
+            OutputFormat::Parquet => Box::new(ParquetSink::create(&staged, output_config)?),
+        });
+        paths.push((suffix, path));
+    }
+    let anonymize_config = args.anonymize_config();
+    let mut suppressed = 0;
+    let anonymized = records.by_ref().filter_map(|record| match record {
+        Ok(mut record) => {
+            if args.provenance {
+                lib::stamp_provenance(std::slice::from_mut(&mut record), &args.run_id, started_at);
+            }
+            let kept = anonymize(record, &anonymize_config);
+            suppressed += usize::from(kept.is_none());
+            kept.map(Ok)
+        }
+        Err(e) => Some(Err(e)),
+    });
+    let written = write_stream(anonymized, sinks)?;
+    let mut stats = records.into_stats();
+    stats.suppressed_groups = suppressed;
+    let files = if stats.interrupted {
+        // Partial outputs stay in the output directory, even with per-format output paths
+        target.name = format!("{}{}", PARTIAL_PREFIX, target.name);
+        paths
+            .iter()
+            .map(|(suffix, _)| target.file(suffix))
+            .collect()
+    } else {
+        paths
+            .iter()
+            .map(|(_, path)| path.clone())
+            .collect::<Vec<_>>()
+    };
+    for ((_, path), file) in paths.iter().zip(&files) {
+        target.ledger.commit_as(path, file)?;
+    }
+    let (manifest_path, manifest) = write_run_manifest(&target, written, &files, &mut stats)?;
+    let io_time = io_start.elapsed();
+    status!("Analysis and streaming writes took {:.2?}", io_time);
+    status!("\nWrote files to directory: {}", target.dir.display());
+    for path in files.iter().chain([&manifest_path]) {
+        debug!("  - {}", path.display());
+    }
+
+    status!("\nProcessed {} records", written);
+    print_memory(&stats);
+    print_throughput(&stats);
+    write_run_profile(args, &target)?;
+    print_warnings(&stats);
+
+    let total_time = total_start.elapsed();
+    if stats.interrupted {
+        status!("Interrupted: the streamed outputs are partial");
+    } else {
+        status!("Pipeline completed successfully in {:.2?}", total_time);
+    }
+    status!("\nTotal runtime: {:.2?}", total_time);
+    if !stats.interrupted {
+        prune_outputs(args)?;
+    }
+    if args.summary_json {
+        let timings = RunTimings {
+            processing_secs: processing_time.as_secs_f64(),
+            io_secs: io_time.as_secs_f64(),
+            total_secs: total_time.as_secs_f64(),
+            throughput: stats.throughput(),
+        };
+        print_summary_json(manifest, &target, timings)?;
+    }
+    if stats.interrupted {
+        return Ok(ExitCode::from(interrupt::EXIT_CODE));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Renders every `--template` into `<output>_<template name>`.
+#[cfg(feature = "template")]
+fn render_templates(
+    args: &Args,
+    target: &OutputTarget,
+    results: &[lib::Record],
+    stats: &ProcessingStats,
+    output_config: &OutputConfig,
+) -> Result<Vec<PathBuf>, PipelineError> {
+    use lib::{render_template, template_output_name};
+
+    let mut paths = Vec::with_capacity(args.template.len());
+    for template in &args.template {
+        let template_start = Instant::now();
+        let path = target.file(&format!("_{}", template_output_name(template)));
+        write_atomic(&target.ledger, &path, |path| {
+            render_template(template, results, stats, output_config, path)
+        })?;
+        status!(
+            "Template {} rendered in {:.2?}",
+            template.display(),
+            template_start.elapsed()
+        );
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Writes the Excel outputs: one workbook per report, or a single combined workbook.
+#[cfg(feature = "excel")]
+fn write_excel_outputs(
+    args: &Args,
+    target: &OutputTarget,
+    results: &[lib::Record],
+    stats: &ProcessingStats,
+    baseline_summary: Option<&[lib::BaselineExceedance]>,
+    output_config: &OutputConfig,
+) -> Result<Vec<PathBuf>, PipelineError> {
+    use lib::{ExcelWorkbook, write_excel};
+
+    let ledger = &target.ledger;
+    if !args.excel && !args.excel_combined {
+        return Ok(Vec::new());
+    }
+    let excel_start = Instant::now();
+    let stats_path = target.file(".xlsx");
+    let mut paths = vec![stats_path.clone()];
+
+    if args.excel_combined {
+        let mut workbook = ExcelWorkbook::new();
+        workbook.add_records_sheet("Stats", results, output_config)?;
+        if let Some(summary) = baseline_summary {
+            workbook.add_baseline_sheet(summary, args.baseline_margin)?;
+        }
+        workbook.add_quality_sheet(stats)?;
+        write_atomic(ledger, &stats_path, |path| workbook.save(path))?;
+    } else {
+        write_atomic(ledger, &stats_path, |path| {
+            write_excel(results, path, output_config)
+        })?;
+        if let Some(summary) = baseline_summary {
+            let path = target.file("_above_baseline.xlsx");
+            write_atomic(ledger, &path, |path| {
+                ExcelWorkbook::new()
+                    .add_baseline_sheet(summary, args.baseline_margin)?
+                    .save(path)
+            })?;
+            paths.push(path);
+        }
+        let quality_path = target.file("_quality.xlsx");
+        write_atomic(ledger, &quality_path, |path| {
+            ExcelWorkbook::new().add_quality_sheet(stats)?.save(path)
+        })?;
+        paths.push(quality_path);
+    }
+
+    status!("Excel write took {:.2?}", excel_start.elapsed());
+    Ok(paths)
+}
+
+/// Removes orphaned temporary outputs registered in the output root's ledger.
+/// Prints how two output runs differ, failing if they differ beyond the tolerance.
+fn run_diff_outputs(left: &Path, right: &Path, tolerance: f64) -> Result<(), PipelineError> {
+    let comparison = compare_outputs(left, right, tolerance)?;
+    println!(
+        "Compared {} matching records | {} only in {} | {} only in {} | {} drifted fields (tolerance {})",
+        comparison.matched,
+        comparison.only_left.len(),
+        left.display(),
+        comparison.only_right.len(),
+        right.display(),
+        comparison.drifts.len(),
+        tolerance
+    );
+    for record in &comparison.only_left {
+        println!("  - {} only in {}", record, left.display());
+    }
+    for record in &comparison.only_right {
+        println!("  + {} only in {}", record, right.display());
+    }
+    for drift in &comparison.drifts {
+        let delta = match (drift.left.as_f64(), drift.right.as_f64()) {
+            (Some(a), Some(b)) => format!(" (delta {:e})", b - a),
+            _ => String::new(),
+        };
+        println!(
+            "  ~ {} {}: {} -> {}{}",
+            drift.record, drift.field, drift.left, drift.right, delta
+        );
+    }
+    match comparison.differences() {
+        0 => Ok(()),
+        differences => Err(PipelineError::Comparison { differences }),
+    }
+}
+
+/// Prints the distinct countries or years of the inputs, one per line.
+fn run_list(what: ListKind, inputs: &[InputSource]) -> Result<(), PipelineError> {
+    let values: Vec<String> = match what {
+        ListKind::Countries => list_countries(inputs)?.into_iter().collect(),
+        ListKind::Years => list_years(inputs)?
+            .into_iter()
+            .map(|year| year.to_string())
+            .collect(),
+    };
+    for value in &values {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+/// Prints the row counts of a Parquet output that passes [`check_parquet`].
+fn run_verify(file: &Path, version: Option<SchemaVersion>) -> Result<(), PipelineError> {
+    let report = check_parquet(file, version)?;
+    let years = match report.years {
+        Some((first, last)) => format!("{}-{}", first, last),
+        None => "none".to_string(),
+    };
+    println!(
+        "{} is valid | schema version {} | {} columns | {} rows in {} row group(s) | {} countries | years {}",
+        file.display(),
+        report.schema_version,
+        report.columns,
+        report.rows,
+        report.row_groups,
+        report.countries,
+        years
+    );
+    Ok(())
+}
+
+#[cfg(feature = "self-update")]
+fn run_self_update(check: bool) -> Result<(), PipelineError> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = lib::update::latest_release()?;
+    if !release.is_newer_than(current) {
+        println!(
+            "Transformer {} is up to date (latest release {})",
+            current, release.tag_name
+        );
+        return Ok(());
+    }
+    println!(
+        "Transformer {} is available (installed {}): {}",
+        release.tag_name, current, release.html_url
+    );
+    if check {
+        return Ok(());
+    }
+    let asset = release.platform_asset().ok_or_else(|| {
+        PipelineError::Data(format!(
+            "release {} has no binary for {}-{}; download it from {}",
+            release.tag_name,
+            std::env::consts::ARCH,
+            std::env::consts::OS,
+            release.html_url
+        ))
+    })?;
+    let exe = lib::update::install(asset)?;
+    println!("Updated {} to {}", exe.display(), release.tag_name);
+    Ok(())
+}
+
+fn run_clean(dry_run: bool, root: &Path) -> Result<(), PipelineError> {
+    let ledger = Ledger::open(root);
+    let removed = ledger.clean(dry_run)?;
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    for path in &removed {
+        println!("{} {}", verb, path.display());
+    }
+    println!(
+        "{} {} orphaned artifact(s) under {}",
+        verb,
+        removed.len(),
+        root.display()
+    );
     Ok(())
 }
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Returns the peak resident set size of the current process in bytes.
+///
+/// Reads `VmHWM` from `/proc/self/status`; returns `None` on platforms without procfs.
+pub fn peak_rss_bytes() -> Option<u64> {
+    proc_status_kib("VmHWM:").map(|kib| kib * 1024)
+}
+
+/// Returns the current resident set size of the current process in bytes.
+pub fn current_rss_bytes() -> Option<u64> {
+    proc_status_kib("VmRSS:").map(|kib| kib * 1024)
+}
+
+/// Returns the memory available to new allocations in bytes.
+///
+/// Reads `MemAvailable` from `/proc/meminfo`; returns `None` on platforms without procfs.
+pub fn available_memory_bytes() -> Option<u64> {
+    proc_field_kib("/proc/meminfo", "MemAvailable:").map(|kib| kib * 1024)
+}
+
+fn proc_status_kib(field: &str) -> Option<u64> {
+    proc_field_kib("/proc/self/status", field)
+}
+
+fn proc_field_kib(path: &str, field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string(path).ok()?;
+    status
+        .lines()
+        .find(|line| line.starts_with(field))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Approximates the heap footprint of a grouping map of `Vec<f64>` buffers.
+///
+/// # Returns
+///
+/// Returns `(map_bytes, buffer_bytes)`: the hash table itself (slots of key + value plus one
+/// control byte each) and the allocated capacity of all value buffers.
+pub fn group_map_bytes<K>(map: &HashMap<K, Vec<f64>>) -> (usize, usize) {
+    let map_bytes = map.capacity() * (size_of::<K>() + size_of::<Vec<f64>>() + 1);
+    let buffer_bytes = map
+        .values()
+        .map(|temps| temps.capacity() * size_of::<f64>())
+        .sum();
+    (map_bytes, buffer_bytes)
+}
+
+/// Formats a byte count as MiB for display.
+pub fn format_mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
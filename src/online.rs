@@ -0,0 +1,196 @@
+use crate::structs::StdDevMode;
+
+/// Percentiles estimated by [`OnlineStats`]: the median and the percentiles of the records
+pub const PERCENTILES: [f64; 5] = [50.0, 25.0, 75.0, 90.0, 95.0];
+
+/// Readings [`OnlineStats`] keeps to compute exact percentiles, e.g. of monthly groups of daily
+/// readings; larger groups fall back to the P² estimates
+pub const EXACT_READINGS: usize = 64;
+
+/// Streaming estimate of one quantile with the P² algorithm (Jain & Chlamtac, 1985).
+///
+/// Five markers track the minimum, the quantile, the maximum, and two midpoints; each reading
+/// moves the markers towards their desired positions with a piecewise-parabolic interpolation,
+/// so the estimate takes constant memory however many readings are added. Up to five readings
+/// the quantile is exact.
+#[derive(Debug, Clone)]
+pub struct P2Quantile {
+    p: f64,
+    count: usize,
+    /// Marker heights; the first `count` readings until there are five
+    heights: [f64; 5],
+    /// Actual marker positions (0-based)
+    positions: [f64; 5],
+    /// Desired marker positions and their increments per reading
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    /// Estimator of the quantile `p`, between 0 and 1.
+    pub fn new(p: f64) -> Self {
+        Self {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    pub fn push(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        let q = &mut self.heights;
+        let k = if x < q[0] {
+            q[0] = x;
+            0
+        } else if x >= q[4] {
+            q[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < q[i + 1]).unwrap_or(3)
+        };
+        for position in &mut self.positions[k + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        let n = &mut self.positions;
+        for i in 1..4 {
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                q[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                n[i] += d;
+            }
+        }
+    }
+
+    /// Current estimate, `None` before the first reading.
+    ///
+    /// Up to five readings this is the linearly interpolated quantile of the batch analysis.
+    pub fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..=5 => Some(interpolate(&self.heights[..self.count], self.p)),
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// Linearly interpolated quantile `p` of `readings`, as in the batch analysis.
+fn interpolate(readings: &[f64], p: f64) -> f64 {
+    let mut sorted = readings.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let index = p * (sorted.len() - 1) as f64;
+    let (lower, upper) = (index.floor() as usize, index.ceil() as usize);
+    let weight = index - lower as f64;
+    sorted[lower] * (1.0 - weight) + sorted[upper] * weight
+}
+
+/// Statistics of a stream of readings in constant memory.
+///
+/// Count, mean (Welford's algorithm), sample standard deviation, minimum, and maximum are
+/// exact; the median and the percentiles of [`PERCENTILES`] are exact up to
+/// [`EXACT_READINGS`] readings and P² estimates beyond.
+#[derive(Debug, Clone)]
+pub struct OnlineStats {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    /// The readings while there are at most `EXACT_READINGS`
+    readings: Vec<f64>,
+    quantiles: [P2Quantile; 5],
+}
+
+impl Default for OnlineStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            readings: Vec::new(),
+            quantiles: PERCENTILES.map(|percentile| P2Quantile::new(percentile / 100.0)),
+        }
+    }
+}
+
+impl OnlineStats {
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        if self.count as usize <= EXACT_READINGS {
+            self.readings.push(x);
+        } else if !self.readings.is_empty() {
+            self.readings = Vec::new();
+        }
+        for quantile in &mut self.quantiles {
+            quantile.push(x);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Standard deviation with the `mode` denominator, 0 with fewer than two readings.
+    pub fn std_dev(&self, mode: StdDevMode) -> f64 {
+        if self.count > 1 {
+            (self.m2 / mode.divisor(self.count as usize)).sqrt()
+        } else {
+            0.0
+        }
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The [`PERCENTILES`] in order, exact up to [`EXACT_READINGS`] readings; 0 before the
+    /// first reading.
+    pub fn percentiles(&self) -> [f64; 5] {
+        if self.count > 0 && self.count as usize <= EXACT_READINGS {
+            return PERCENTILES.map(|percentile| interpolate(&self.readings, percentile / 100.0));
+        }
+        self.quantiles
+            .each_ref()
+            .map(|quantile| quantile.estimate().unwrap_or(0.0))
+    }
+}
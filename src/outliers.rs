@@ -0,0 +1,388 @@
+use crate::distribution::student_t_upper_quantile;
+use crate::structs::{QuantileMethod, StdDevMode};
+use crate::transform::{GroupKey, sorted_median, sorted_percentile};
+use crate::units::TemperatureScale;
+use std::collections::{BTreeMap, HashMap};
+
+/// Accepted range of a group's readings: values at most `radius` away from `center`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Bounds {
+    pub center: f64,
+    pub radius: f64,
+}
+
+impl Bounds {
+    /// Whether `value` is inside the bounds (i.e. not an outlier).
+    pub fn contains(&self, value: f64) -> bool {
+        (value - self.center).abs() <= self.radius
+    }
+
+    /// Converts bounds computed in Celsius into `scale`.
+    pub fn convert(&self, scale: &dyn TemperatureScale) -> Self {
+        let factor = (scale.convert_celsius(1.0) - scale.convert_celsius(0.0)).abs();
+        Self {
+            center: scale.convert_celsius(self.center),
+            radius: self.radius * factor,
+        }
+    }
+}
+
+/// Bounds of the standard deviation method: readings more than `threshold` standard deviations
+/// (with the `mode` denominator) from the group mean are outliers.
+///
+/// Common threshold values:
+/// - 1.0: Removes ~32% of data (aggressive)
+/// - 2.0: Removes ~5% of data (moderate)
+/// - 3.0: Removes ~0.3% of data (conservative)
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 2 values or a constant group, which are kept unchanged (see
+/// [`is_constant`]).
+pub(crate) fn std_dev_bounds(data: &[f64], threshold: f64, mode: StdDevMode) -> Option<Bounds> {
+    if data.len() < 2 || is_constant(data) {
+        return None;
+    }
+    let (mean, std_dev) = mean_std_dev(data, mode);
+    Some(Bounds {
+        center: mean,
+        radius: threshold * std_dev,
+    })
+}
+
+/// Bounds of Grubbs' test at significance level `alpha`.
+///
+/// A reading is an outlier when its Grubbs statistic `|x - mean| / s` exceeds the two-sided
+/// critical value for the group size, which assumes the group is normally distributed. Unlike
+/// the standard deviation method the cut-off grows with the group size, so large groups are not
+/// trimmed more aggressively than small ones.
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 3 values, for which the test is undefined, and for a constant
+/// group, whose statistic is `0 / 0`; both are kept unchanged.
+pub(crate) fn grubbs_bounds(data: &[f64], alpha: f64) -> Option<Bounds> {
+    if data.len() < 3 || is_constant(data) {
+        return None;
+    }
+    let (mean, std_dev) = mean_std_dev(data, StdDevMode::Sample);
+    Some(Bounds {
+        center: mean,
+        radius: grubbs_critical_value(data.len(), alpha) * std_dev,
+    })
+}
+
+/// Bounds of Tukey's fences: readings more than `multiplier` interquartile ranges below the
+/// first or above the third quartile (computed with `method`) are outliers.
+///
+/// The fences need not be centered on the mean, so unlike the standard deviation method a
+/// long tail on one side does not widen the range on the other. Common multipliers are 1.5
+/// (outliers) and 3.0 (far outliers).
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 2 values or a zero interquartile range, for which the fences
+/// would collapse onto the quartiles; such groups are kept unchanged.
+pub(crate) fn iqr_bounds(data: &[f64], multiplier: f64, method: QuantileMethod) -> Option<Bounds> {
+    if data.len() < 2 {
+        return None;
+    }
+    let sorted = sorted(data);
+    let q1 = sorted_percentile(&sorted, 25.0, method);
+    let q3 = sorted_percentile(&sorted, 75.0, method);
+    let iqr = q3 - q1;
+    if iqr <= 0.0 {
+        return None;
+    }
+    Some(Bounds {
+        center: (q1 + q3) / 2.0,
+        radius: iqr / 2.0 + multiplier * iqr,
+    })
+}
+
+/// Bounds of the median absolute deviation method: readings more than `threshold` MADs from
+/// the group median are outliers.
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 2 values or a zero MAD (more than half the readings equal),
+/// which are kept unchanged.
+pub(crate) fn mad_bounds(data: &[f64], threshold: f64) -> Option<Bounds> {
+    if data.len() < 2 {
+        return None;
+    }
+    let (median, mad) = median_mad(data);
+    if mad <= 0.0 {
+        return None;
+    }
+    Some(Bounds {
+        center: median,
+        radius: threshold * mad,
+    })
+}
+
+/// Bounds of Iglewicz and Hoaglin's modified z-score: readings whose `0.6745 (x - median) /
+/// MAD` exceeds `threshold` in magnitude are outliers (3.5 is the usual cut-off).
+///
+/// When the MAD is zero the score is `(x - median) / (1.253314 MeanAD)` with the mean
+/// absolute deviation from the median instead, so groups dominated by one repeated reading
+/// still have their stray readings flagged.
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 2 values or a constant group, which are kept unchanged.
+pub(crate) fn modified_zscore_bounds(data: &[f64], threshold: f64) -> Option<Bounds> {
+    if data.len() < 2 || is_constant(data) {
+        return None;
+    }
+    let (median, mad) = median_mad(data);
+    let scale = if mad > 0.0 {
+        mad / 0.6745
+    } else {
+        let mean_ad = data.iter().map(|x| (x - median).abs()).sum::<f64>() / data.len() as f64;
+        1.253314 * mean_ad
+    };
+    Some(Bounds {
+        center: median,
+        radius: threshold * scale,
+    })
+}
+
+/// Readings sorted ascending
+fn sorted(data: &[f64]) -> Vec<f64> {
+    let mut sorted = data.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+/// Median and median absolute deviation from it
+fn median_mad(data: &[f64]) -> (f64, f64) {
+    let median = sorted_median(&sorted(data));
+    let deviations: Vec<f64> = data.iter().map(|x| (x - median).abs()).collect();
+    (median, sorted_median(&sorted(&deviations)))
+}
+
+/// Whether every reading equals the first one.
+///
+/// A constant group has no outliers: its standard deviation is zero, so its bounds would have
+/// a zero radius around a mean that rounding can move off the readings themselves, removing
+/// (or clipping) every one of them. Outlier handling is skipped for such groups instead.
+fn is_constant(data: &[f64]) -> bool {
+    data.windows(2).all(|pair| pair[0] == pair[1])
+}
+
+/// Removes the readings outside `bounds`.
+pub(crate) fn remove_outliers(data: &[f64], bounds: Bounds) -> Vec<f64> {
+    data.iter()
+        .filter(|&&x| bounds.contains(x))
+        .copied()
+        .collect()
+}
+
+/// Clips the readings outside `bounds` to the nearest bound instead of dropping them.
+///
+/// # Returns
+///
+/// Returns the winsorized values and the number of clipped values.
+pub(crate) fn winsorize(mut data: Vec<f64>, bounds: Bounds) -> (Vec<f64>, usize) {
+    let mut clipped = 0;
+    for x in &mut data {
+        if !bounds.contains(*x) {
+            *x = x.clamp(bounds.center - bounds.radius, bounds.center + bounds.radius);
+            clipped += 1;
+        }
+    }
+    (data, clipped)
+}
+
+/// Mean and standard deviation with the `mode` denominator
+fn mean_std_dev(data: &[f64], mode: StdDevMode) -> (f64, f64) {
+    let mean = data.iter().sum::<f64>() / data.len() as f64;
+    let variance = data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / mode.divisor(data.len());
+    (mean, variance.sqrt())
+}
+
+/// Count, sum, sum of squares, and range of readings
+#[derive(Debug, Clone, Copy)]
+struct Moments {
+    count: usize,
+    sum: f64,
+    sum_sq: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Moments {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_sq += value * value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn merge(&mut self, other: &Moments) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Per-year moments of every country and calendar month, used to judge a reading against
+/// the same month of neighbouring years rather than against its own group only.
+#[derive(Debug, Default)]
+pub(crate) struct SeasonalTable {
+    months: HashMap<(String, u32), BTreeMap<i32, Moments>>,
+}
+
+impl SeasonalTable {
+    /// Builds the table from groups keyed by country, year, and month.
+    pub fn from_groups(groups: &HashMap<GroupKey, Vec<f64>>) -> Self {
+        let mut table = Self::default();
+        for (key, temps) in groups {
+            let moments = table.entry(key);
+            for &temp in temps {
+                moments.add(temp);
+            }
+        }
+        table
+    }
+
+    /// Adds one reading of the group `key`.
+    pub fn add(&mut self, key: &GroupKey, temp: f64) {
+        self.entry(key).add(temp);
+    }
+
+    /// Bounds of the seasonal z-score: readings more than `threshold` standard deviations (with
+    /// the `mode` denominator) from the mean of the same country and calendar month are
+    /// outliers. The distribution covers the years within `window` of the group's year, or
+    /// every year when `window` is `None`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the window holds fewer than 2 readings or they are all equal, like
+    /// [`std_dev_bounds`] for a constant group; the group's own readings are part of the
+    /// window, so they are kept unchanged.
+    pub fn bounds(
+        &self,
+        key: &GroupKey,
+        window: Option<u32>,
+        threshold: f64,
+        mode: StdDevMode,
+    ) -> Option<Bounds> {
+        let years = self.months.get(&(key.country.clone(), key.month))?;
+        let mut total = Moments::default();
+        let in_window: Box<dyn Iterator<Item = &Moments>> = match window {
+            Some(window) => {
+                let window = window as i32;
+                let range = key.year.saturating_sub(window)..=key.year.saturating_add(window);
+                Box::new(years.range(range).map(|(_, moments)| moments))
+            }
+            None => Box::new(years.values()),
+        };
+        for moments in in_window {
+            total.merge(moments);
+        }
+        if total.count < 2 || total.min == total.max {
+            return None;
+        }
+        let n = total.count as f64;
+        let mean = total.sum / n;
+        let variance = ((total.sum_sq - total.sum * mean) / mode.divisor(total.count)).max(0.0);
+        Some(Bounds {
+            center: mean,
+            radius: threshold * variance.sqrt(),
+        })
+    }
+
+    fn entry(&mut self, key: &GroupKey) -> &mut Moments {
+        self.months
+            .entry((key.country.clone(), key.month))
+            .or_default()
+            .entry(key.year)
+            .or_default()
+    }
+}
+
+/// Two-sided critical value of Grubbs' test for `n` readings at significance level `alpha`:
+/// `G = (n - 1) / sqrt(n) * sqrt(t² / (n - 2 + t²))`, where `t` is the upper `alpha / (2n)`
+/// quantile of Student's t distribution with `n - 2` degrees of freedom.
+fn grubbs_critical_value(n: usize, alpha: f64) -> f64 {
+    let n = n as f64;
+    let t = student_t_upper_quantile(alpha / (2.0 * n), n - 2.0);
+    (n - 1.0) / n.sqrt() * (t * t / (n - 2.0 + t * t)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READINGS: [f64; 5] = [9.0, 10.0, 11.0, 10.0, 30.0];
+
+    fn bounds() -> Bounds {
+        Bounds {
+            center: 10.0,
+            radius: 2.0,
+        }
+    }
+
+    #[test]
+    fn remove_drops_readings_outside_the_bounds() {
+        assert_eq!(
+            remove_outliers(&READINGS, bounds()),
+            vec![9.0, 10.0, 11.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn winsorize_clips_readings_to_the_nearest_bound() {
+        let (clipped, count) = winsorize(vec![5.0, 9.0, 10.0, 30.0], bounds());
+        assert_eq!(clipped, vec![8.0, 9.0, 10.0, 12.0]);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn winsorize_keeps_the_reading_count() {
+        let (clipped, count) = winsorize(READINGS.to_vec(), bounds());
+        let removed = remove_outliers(&READINGS, bounds());
+        assert_eq!(clipped.len(), READINGS.len());
+        assert_eq!(count, READINGS.len() - removed.len());
+        assert!(clipped.iter().all(|&x| bounds().contains(x)));
+    }
+
+    #[test]
+    fn constant_groups_have_no_bounds_under_every_method() {
+        // 0.1 has no exact binary representation, so the mean of the readings can differ
+        // from the readings themselves
+        let constant = [0.1; 7];
+        assert_eq!(std_dev_bounds(&constant, 2.0, StdDevMode::Sample), None);
+        assert_eq!(std_dev_bounds(&constant, 2.0, StdDevMode::Population), None);
+        assert_eq!(grubbs_bounds(&constant, 0.05), None);
+        assert_eq!(iqr_bounds(&constant, 1.5, QuantileMethod::Linear), None);
+        assert_eq!(mad_bounds(&constant, 3.0), None);
+        assert_eq!(modified_zscore_bounds(&constant, 3.5), None);
+    }
+
+    #[test]
+    fn stray_readings_in_mostly_constant_groups_are_still_flagged() {
+        let readings = [10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 25.0];
+        let bounds = modified_zscore_bounds(&readings, 3.5).unwrap();
+        assert!(!bounds.contains(25.0));
+        assert!(bounds.contains(10.0));
+        assert!(std_dev_bounds(&readings, 2.0, StdDevMode::Sample).is_some());
+    }
+}
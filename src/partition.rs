@@ -0,0 +1,94 @@
+use crate::error::{PipelineError, Result};
+use crate::ledger::Ledger;
+use crate::load::write_output;
+use crate::paths::sanitize_file_name;
+use crate::structs::{OutputConfig, OutputFormat, Record};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Files written for one partition and how long they took
+#[derive(Debug, Clone)]
+pub struct PartitionTiming {
+    /// Partition directory name, e.g. `country=DE`
+    pub partition: String,
+    pub records: usize,
+    pub files: Vec<PathBuf>,
+    pub elapsed: Duration,
+}
+
+/// Writes the records of each country to `<dir>/country=<code>/<name>.<ext>` in every format,
+/// writing up to `threads` partitions at a time.
+///
+/// Many small partitions are dominated by per-file latency (creating, flushing, and renaming
+/// files) rather than by encoding, so they are written concurrently by a dedicated pool of
+/// `threads` writers. The files are registered in the ledger with a single write before the
+/// writers start and renamed into place after all of them have finished, so the ledger is
+/// never updated concurrently.
+///
+/// # Returns
+///
+/// Returns the timing of every partition, sorted by country.
+///
+/// # Errors
+/// Returns `PipelineError::ThreadPool` if the pool cannot be created, or the first error of a
+/// writer (leaving the temporary files registered for `clean`).
+pub fn write_country_partitions(
+    results: &[Record],
+    dir: &Path,
+    name: &str,
+    formats: &[OutputFormat],
+    config: &OutputConfig,
+    ledger: &Ledger,
+    threads: usize,
+) -> Result<Vec<PartitionTiming>> {
+    let mut countries: BTreeMap<&str, Vec<Record>> = BTreeMap::new();
+    for record in results {
+        countries
+            .entry(record.country.as_str())
+            .or_default()
+            .push(record.clone());
+    }
+
+    let mut partitions = Vec::with_capacity(countries.len());
+    let mut paths = Vec::with_capacity(countries.len() * formats.len());
+    for (country, records) in countries {
+        let partition = format!("country={}", sanitize_file_name(country));
+        let partition_dir = dir.join(&partition);
+        std::fs::create_dir_all(&partition_dir)?;
+        for format in formats {
+            paths.push(partition_dir.join(format!("{}{}", name, format.suffix())));
+        }
+        partitions.push((partition, records));
+    }
+    let staged = ledger.begin_all(&paths)?;
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(PipelineError::from)?;
+    let timings = pool.install(|| {
+        partitions
+            .par_iter()
+            .zip(paths.par_chunks(formats.len()))
+            .zip(staged.par_chunks(formats.len()))
+            .map(|(((partition, records), paths), staged)| {
+                let start = Instant::now();
+                for (format, staged) in formats.iter().zip(staged) {
+                    write_output(*format, records, staged, config)?;
+                }
+                Ok(PartitionTiming {
+                    partition: partition.clone(),
+                    records: records.len(),
+                    files: paths.to_vec(),
+                    elapsed: start.elapsed(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    ledger.commit_all(&paths)?;
+    Ok(timings)
+}
@@ -0,0 +1,166 @@
+use crate::error::{PipelineError, Result};
+use crate::structs::DATA_EXTENSIONS;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Characters that are invalid in Windows file names (plus `\`, a separator there)
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '\\', '/'];
+
+/// Device names Windows reserves regardless of extension (e.g. `NUL.csv`)
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes `name` usable as a file or directory name on every platform.
+///
+/// Invalid and control characters are replaced by `_`, trailing dots and spaces (which Windows
+/// strips) are removed, and reserved device names get a `_` suffix. Returns `_` if nothing is
+/// left.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        sanitized.insert(stem.len(), '_');
+    }
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+    sanitized
+}
+
+/// Resolves an output name (e.g. `runs/daily`) to a directory inside `root`.
+///
+/// The name is split into path components by the platform's rules and each one is sanitized
+/// with [`sanitize_file_name`]. Roots, drive or UNC prefixes, and `..` are dropped so the
+/// directory can never escape `root`.
+///
+/// # Returns
+///
+/// Returns the directory and its final component, used as the base name of the output files.
+pub fn resolve_output(root: &Path, output: &str) -> (PathBuf, String) {
+    let names: Vec<String> = Path::new(output)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(sanitize_file_name(&name.to_string_lossy())),
+            _ => None,
+        })
+        .collect();
+    let name = names
+        .last()
+        .cloned()
+        .unwrap_or_else(|| "output".to_string());
+    let mut dir = root.to_path_buf();
+    if names.is_empty() {
+        dir.push(&name);
+    }
+    dir.extend(&names);
+    (long_path(&dir), name)
+}
+
+/// Expands the directories and glob patterns among the input paths into the files they name.
+///
+/// A directory stands for every file with one of the [`DATA_EXTENSIONS`] below it, e.g. a dataset
+/// partitioned into `year=2020/` subdirectories, skipping hidden entries; a path containing
+/// `*`, `?`, or `[` is matched as a glob (e.g. `data/*.parquet`). Both expand to their files
+/// sorted by path, in place of the argument. Other paths, including URLs, are kept as given.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if a pattern is invalid or a directory or pattern names no
+/// file, and `PipelineError::Io` if a directory cannot be read.
+pub fn expand_input_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(paths.len());
+    for path in paths {
+        let text = path.to_string_lossy();
+        let files = if text.starts_with("http://") || text.starts_with("https://") {
+            expanded.push(path.clone());
+            continue;
+        } else if path.is_dir() {
+            let mut files = Vec::new();
+            collect_data_files(path, &mut files)?;
+            files
+        } else if text.contains(['*', '?', '[']) {
+            let matches = glob::glob(&text).map_err(|e| {
+                PipelineError::Data(format!("invalid input pattern '{}': {}", text, e))
+            })?;
+            matches
+                .filter_map(|entry| entry.ok())
+                .filter(|file| file.is_file())
+                .collect()
+        } else {
+            expanded.push(path.clone());
+            continue;
+        };
+        if files.is_empty() {
+            return Err(PipelineError::Data(format!(
+                "no input files found for '{}'",
+                text
+            )));
+        }
+        let start = expanded.len();
+        expanded.extend(files);
+        expanded[start..].sort();
+    }
+    Ok(expanded)
+}
+
+/// Adds the files with one of the [`DATA_EXTENSIONS`] below `dir` to `files`, skipping hidden
+/// entries.
+fn collect_data_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect_data_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| {
+            DATA_EXTENSIONS
+                .iter()
+                .any(|data| ext.eq_ignore_ascii_case(data))
+        }) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns `path` in a form not limited to `MAX_PATH` (260 characters) on Windows.
+///
+/// Long paths are made absolute and given the verbatim `\\?\` prefix (`\\?\UNC\` for
+/// `\\server\share` paths); shorter paths are unchanged.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    const MAX_PATH: usize = 260;
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let text = absolute.to_string_lossy();
+    if text.len() < MAX_PATH || text.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+    match text.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", text)),
+    }
+}
+
+/// Returns `path` unchanged: only Windows limits path lengths.
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
@@ -0,0 +1,210 @@
+use crate::cache::fnv1a;
+use crate::error::{PipelineError, Result};
+use crate::structs::Record;
+use chrono::{Datelike, NaiveDate};
+use log::debug;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wasmi::{Engine, Linker, Module, Store, TypedFunc};
+
+/// `transform_row(country, year, month, day, temp) -> temp`
+type TransformRow = TypedFunc<(i32, i32, i32, i32, f64), f64>;
+
+/// `post_aggregate(country, year, month, count, avg, min, max, std_dev, median) -> metric`
+type PostAggregate = TypedFunc<(i32, i32, i32, i32, f64, f64, f64, f64, f64), f64>;
+
+/// User-supplied WebAssembly module with custom transforms.
+///
+/// The module (binary `.wasm` or text `.wat`) must not import anything and exports one or
+/// both of:
+///
+/// - `transform_row(country: i32, year: i32, month: i32, day: i32, temp: f64) -> f64`, called
+///   for every reading that passed the filters and range checks with its temperature in
+///   Celsius. It returns the temperature to aggregate, or NaN to drop the reading. Returned
+///   temperatures that are infinite or outside the valid range are rejected and counted.
+/// - `post_aggregate(country: i32, year: i32, month: i32, count: i32, avg_temp: f64,
+///   min_temp: f64, max_temp: f64, std_dev: f64, median_temp: f64) -> f64`, called for every
+///   record with its statistics in the record's unit. The result becomes the record's
+///   `plugin_metric`; NaN leaves it null.
+///
+/// `country` is the group's country code with its first four bytes packed little-endian
+/// (`"US"` is `0x5355`), see [`pack_country`]. Calls are serialized, so a module may keep state
+/// in globals, but the order readings and groups are visited in is unspecified.
+pub struct Plugin {
+    path: PathBuf,
+    checksum: u64,
+    instance: Mutex<Instance>,
+    has_transform_row: bool,
+    has_post_aggregate: bool,
+}
+
+struct Instance {
+    store: Store<()>,
+    transform_row: Option<TransformRow>,
+    post_aggregate: Option<PostAggregate>,
+}
+
+impl Plugin {
+    /// Loads and instantiates the module at `path`.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` if the file cannot be read, and `PipelineError::Plugin` if
+    /// it is not a valid module, imports anything, exports neither function, or exports one
+    /// with a different signature.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let error = |reason: String| PipelineError::Plugin {
+            file: path.to_path_buf(),
+            reason,
+        };
+        let engine = Engine::default();
+        let module = Module::new(&engine, &bytes).map_err(|e| error(e.to_string()))?;
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::<()>::new(&engine)
+            .instantiate_and_start(&mut store, &module)
+            .map_err(|e| error(e.to_string()))?;
+        let has_transform_row = instance.get_func(&store, "transform_row").is_some();
+        let has_post_aggregate = instance.get_func(&store, "post_aggregate").is_some();
+        if !has_transform_row && !has_post_aggregate {
+            return Err(error(
+                "exports neither transform_row nor post_aggregate".to_string(),
+            ));
+        }
+        let transform_row = has_transform_row
+            .then(|| instance.get_typed_func(&store, "transform_row"))
+            .transpose()
+            .map_err(|e| error(format!("transform_row: {}", e)))?;
+        let post_aggregate = has_post_aggregate
+            .then(|| instance.get_typed_func(&store, "post_aggregate"))
+            .transpose()
+            .map_err(|e| error(format!("post_aggregate: {}", e)))?;
+        debug!(
+            "Loaded plugin {} (transform_row: {}, post_aggregate: {})",
+            path.display(),
+            has_transform_row,
+            has_post_aggregate
+        );
+        Ok(Self {
+            path: path.to_path_buf(),
+            checksum: fnv1a(crate::cache::FNV_OFFSET, &bytes),
+            instance: Mutex::new(Instance {
+                store,
+                transform_row,
+                post_aggregate,
+            }),
+            has_transform_row,
+            has_post_aggregate,
+        })
+    }
+
+    /// FNV-1a checksum of the module, part of the cache key.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// Whether the module exports `transform_row`.
+    pub fn has_transform_row(&self) -> bool {
+        self.has_transform_row
+    }
+
+    /// Whether the module exports `post_aggregate`, i.e. records get a `plugin_metric`.
+    pub fn has_post_aggregate(&self) -> bool {
+        self.has_post_aggregate
+    }
+
+    /// Runs `transform_row` on one reading, returning `None` if the module returns NaN to drop
+    /// the reading. Other results, infinities included, are returned for the caller to
+    /// validate.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Plugin` if the module traps.
+    pub fn transform_row(&self, country: &str, date: NaiveDate, temp: f64) -> Result<Option<f64>> {
+        let mut instance = self.lock();
+        let Instance {
+            store,
+            transform_row: Some(func),
+            ..
+        } = &mut *instance
+        else {
+            return Ok(Some(temp));
+        };
+        let temp = func
+            .call(
+                store,
+                (
+                    pack_country(country),
+                    date.year(),
+                    date.month() as i32,
+                    date.day() as i32,
+                    temp,
+                ),
+            )
+            .map_err(|e| self.error(format!("transform_row: {}", e)))?;
+        Ok((!temp.is_nan()).then_some(temp))
+    }
+
+    /// Runs `post_aggregate` on a record, returning its `plugin_metric`.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Plugin` if the module traps.
+    pub fn post_aggregate(&self, record: &Record) -> Result<Option<f64>> {
+        let mut instance = self.lock();
+        let Instance {
+            store,
+            post_aggregate: Some(func),
+            ..
+        } = &mut *instance
+        else {
+            return Ok(None);
+        };
+        let metric = func
+            .call(
+                store,
+                (
+                    pack_country(&record.country),
+                    record.year,
+                    record.month as i32,
+                    record.count as i32,
+                    record.avg_temp,
+                    record.min_temp,
+                    record.max_temp,
+                    record.std_dev,
+                    record.median_temp,
+                ),
+            )
+            .map_err(|e| self.error(format!("post_aggregate: {}", e)))?;
+        Ok((!metric.is_nan()).then_some(metric))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Instance> {
+        // A trap leaves the instance usable, so a poisoned lock is recovered
+        self.instance
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn error(&self, reason: String) -> PipelineError {
+        PipelineError::Plugin {
+            file: self.path.clone(),
+            reason,
+        }
+    }
+}
+
+impl std::fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Plugin")
+            .field("path", &self.path)
+            .field("checksum", &format_args!("{:016x}", self.checksum))
+            .finish()
+    }
+}
+
+/// Packs the first four bytes of a country code into an `i32`, first byte lowest.
+pub fn pack_country(country: &str) -> i32 {
+    let mut bytes = [0; 4];
+    for (slot, byte) in bytes.iter_mut().zip(country.bytes()) {
+        *slot = byte;
+    }
+    i32::from_le_bytes(bytes)
+}
@@ -0,0 +1,54 @@
+use crate::error::Result;
+
+/// Nice level used by `--low-priority`
+pub const LOW_PRIORITY_NICE: i32 = 10;
+
+/// Lowers the scheduling priority of the process to the Unix nice level `nice` (1 to 19).
+///
+/// On Linux the nice level belongs to each thread and is inherited by the threads it spawns,
+/// so this must run on the main thread before the interrupt handler and the rayon pools start
+/// their threads. A process already running at `nice` or lower priority is left unchanged,
+/// since unprivileged users cannot raise it back. On Windows the process is moved to the
+/// below-normal priority class, or the idle class for levels of 15 and above.
+///
+/// # Errors
+/// Returns `PipelineError::Io` if the operating system rejects the change.
+#[cfg(unix)]
+pub fn lower_priority(nice: i32) -> Result<()> {
+    // SAFETY: getpriority and setpriority only read and update the calling thread's
+    // scheduling attributes.
+    unsafe {
+        if libc::getpriority(libc::PRIO_PROCESS, 0) >= nice {
+            return Ok(());
+        }
+        if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn lower_priority(nice: i32) -> Result<()> {
+    use windows_sys::Win32::System::Threading::{
+        BELOW_NORMAL_PRIORITY_CLASS, GetCurrentProcess, IDLE_PRIORITY_CLASS, SetPriorityClass,
+    };
+
+    let class = if nice >= 15 {
+        IDLE_PRIORITY_CLASS
+    } else {
+        BELOW_NORMAL_PRIORITY_CLASS
+    };
+    // SAFETY: the pseudo handle of the current process is always valid.
+    if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Leaves the priority unchanged on platforms without process priorities.
+#[cfg(not(any(unix, windows)))]
+pub fn lower_priority(_nice: i32) -> Result<()> {
+    crate::status!("Warning: process priority cannot be changed on this platform");
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use crate::error::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set by `--profile`; spans are free when unset
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Accumulated time of every span stack, keyed by its `;`-joined span names
+static SPANS: Mutex<Option<HashMap<String, SpanTotal>>> = Mutex::new(None);
+
+thread_local! {
+    /// Names of the open spans of the current thread, outermost first
+    static STACK: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// What a span spends its own time on, used to tell I/O-bound from CPU-bound runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// Reading and decoding inputs, or writing outputs
+    Io,
+    /// Cleaning, grouping, and analyzing readings
+    Cpu,
+    /// Waiting for worker threads, whose spans are counted on their own threads
+    Wait,
+}
+
+impl std::fmt::Display for SpanKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SpanKind::Io => "io",
+            SpanKind::Cpu => "cpu",
+            SpanKind::Wait => "wait",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpanTotal {
+    kind: SpanKind,
+    calls: u64,
+    total: Duration,
+}
+
+/// Enables span timing for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--profile` enabled span timing.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Open span, timed until dropped; see [`span`].
+pub struct Span {
+    started: Option<Instant>,
+    kind: SpanKind,
+}
+
+/// Starts timing `name`, nested in the spans open on the current thread.
+///
+/// Spans opened on rayon workers start their own stacks on those threads.
+pub fn span(name: &'static str, kind: SpanKind) -> Span {
+    if !enabled() {
+        return Span {
+            started: None,
+            kind,
+        };
+    }
+    STACK.with(|stack| stack.borrow_mut().push(name));
+    Span {
+        started: Some(Instant::now()),
+        kind,
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(started) = self.started else {
+            return;
+        };
+        let elapsed = started.elapsed();
+        let key = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let key = stack.join(";");
+            stack.pop();
+            key
+        });
+        let mut spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+        let total = spans
+            .get_or_insert_with(HashMap::new)
+            .entry(key)
+            .or_insert(SpanTotal {
+                kind: self.kind,
+                calls: 0,
+                total: Duration::ZERO,
+            });
+        total.calls += 1;
+        total.total += elapsed;
+    }
+}
+
+/// Times every `next` call of `iter` as a `name` span.
+pub fn timed_iter<I: Iterator>(
+    name: &'static str,
+    kind: SpanKind,
+    iter: I,
+) -> impl Iterator<Item = I::Item> {
+    let mut iter = iter;
+    std::iter::from_fn(move || {
+        let _span = span(name, kind);
+        iter.next()
+    })
+}
+
+/// Own time (excluding nested spans) of one span stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEntry {
+    /// Span names, outermost first, joined by `;`
+    pub stack: String,
+    pub kind: SpanKind,
+    pub calls: u64,
+    pub total: Duration,
+    pub own: Duration,
+}
+
+/// Span timings of a run, written by [`write_profile`]
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// Entries sorted by descending own time
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    /// Collects the timings of the spans closed so far.
+    pub fn collect() -> Self {
+        let spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(spans) = spans.as_ref() else {
+            return Profile::default();
+        };
+        let mut entries: Vec<ProfileEntry> = spans
+            .iter()
+            .map(|(stack, total)| {
+                let nested: Duration = spans
+                    .iter()
+                    .filter(|(child, _)| {
+                        child
+                            .strip_prefix(stack.as_str())
+                            .and_then(|rest| rest.strip_prefix(';'))
+                            .is_some_and(|rest| !rest.contains(';'))
+                    })
+                    .map(|(_, child)| child.total)
+                    .sum();
+                ProfileEntry {
+                    stack: stack.clone(),
+                    kind: total.kind,
+                    calls: total.calls,
+                    total: total.total,
+                    own: total.total.saturating_sub(nested),
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| b.own.cmp(&a.own).then_with(|| a.stack.cmp(&b.stack)));
+        Profile { entries }
+    }
+
+    /// Own time of the spans of `kind`, summed across threads.
+    pub fn own_time(&self, kind: SpanKind) -> Duration {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == kind)
+            .map(|entry| entry.own)
+            .sum()
+    }
+
+    /// Share of the I/O and CPU time spent on I/O, or `None` if no span was timed.
+    pub fn io_share(&self) -> Option<f64> {
+        let io = self.own_time(SpanKind::Io).as_secs_f64();
+        let busy = io + self.own_time(SpanKind::Cpu).as_secs_f64();
+        (busy > 0.0).then(|| io / busy)
+    }
+}
+
+/// Writes the span timings of the run to `<dir>/<name>_profile.txt` (a table of the spans)
+/// and `<dir>/<name>_profile.folded`, whose `stack microseconds` lines are the collapsed-stack
+/// format read by `flamegraph.pl` and `inferno-flamegraph`.
+///
+/// # Returns
+/// Returns the profile and the paths of the table and folded files.
+///
+/// # Errors
+/// Returns `PipelineError::Io` if a file cannot be written.
+pub fn write_profile(dir: &Path, name: &str) -> Result<(Profile, [PathBuf; 2])> {
+    let profile = Profile::collect();
+    let table_path = dir.join(format!("{}_profile.txt", name));
+    let folded_path = dir.join(format!("{}_profile.folded", name));
+
+    let mut table = BufWriter::new(File::create(&table_path)?);
+    writeln!(
+        table,
+        "{:<56} {:>5} {:>10} {:>12} {:>12}",
+        "span", "kind", "calls", "total_ms", "own_ms"
+    )?;
+    for entry in &profile.entries {
+        writeln!(
+            table,
+            "{:<56} {:>5} {:>10} {:>12.3} {:>12.3}",
+            entry.stack,
+            entry.kind.to_string(),
+            entry.calls,
+            entry.total.as_secs_f64() * 1000.0,
+            entry.own.as_secs_f64() * 1000.0
+        )?;
+    }
+    writeln!(table)?;
+    for kind in [SpanKind::Io, SpanKind::Cpu, SpanKind::Wait] {
+        writeln!(
+            table,
+            "{} own time: {:.3} ms",
+            kind,
+            profile.own_time(kind).as_secs_f64() * 1000.0
+        )?;
+    }
+    table.flush()?;
+
+    let mut folded = BufWriter::new(File::create(&folded_path)?);
+    for entry in &profile.entries {
+        let micros = entry.own.as_micros();
+        if micros > 0 {
+            writeln!(folded, "{} {}", entry.stack, micros)?;
+        }
+    }
+    folded.flush()?;
+    Ok((profile, [table_path, folded_path]))
+}
@@ -0,0 +1,194 @@
+use crate::error::Result;
+use crate::schema::{Column, Value, output_columns};
+use crate::structs::{OutputConfig, Record};
+use prost::Message;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Protobuf definition of [`WeatherStats`], shipped for consumers to generate readers from
+pub const PROTO_DEFINITION: &str = include_str!("../proto/weather_stats.proto");
+
+/// `transformer.v1.WeatherStats` message (see `proto/weather_stats.proto`)
+#[derive(Clone, PartialEq, Message)]
+pub struct WeatherStats {
+    #[prost(string, tag = "1")]
+    pub country: String,
+    #[prost(int32, tag = "2")]
+    pub year: i32,
+    #[prost(uint32, tag = "3")]
+    pub month: u32,
+    #[prost(double, tag = "4")]
+    pub avg_temp: f64,
+    #[prost(double, tag = "5")]
+    pub min_temp: f64,
+    #[prost(double, tag = "6")]
+    pub max_temp: f64,
+    #[prost(double, tag = "7")]
+    pub std_dev: f64,
+    #[prost(double, tag = "8")]
+    pub median_temp: f64,
+    #[prost(uint32, tag = "9")]
+    pub count: u32,
+    #[prost(double, tag = "10")]
+    pub percentile_25: f64,
+    #[prost(double, tag = "11")]
+    pub percentile_75: f64,
+    #[prost(double, tag = "12")]
+    pub percentile_90: f64,
+    #[prost(double, tag = "13")]
+    pub percentile_95: f64,
+    #[prost(string, optional, tag = "14")]
+    pub unit: Option<String>,
+    #[prost(string, optional, tag = "15")]
+    pub period_start: Option<String>,
+    #[prost(string, optional, tag = "16")]
+    pub period_end: Option<String>,
+    #[prost(string, optional, tag = "17")]
+    pub enso_phase: Option<String>,
+    #[prost(double, optional, tag = "18")]
+    pub aridity_index: Option<f64>,
+    #[prost(uint32, optional, tag = "19")]
+    pub distinct_days: Option<u32>,
+    #[prost(double, optional, tag = "20")]
+    pub ci_low: Option<f64>,
+    #[prost(double, optional, tag = "21")]
+    pub ci_high: Option<f64>,
+    #[prost(double, optional, tag = "22")]
+    pub range_temp: Option<f64>,
+    #[prost(double, optional, tag = "23")]
+    pub mode_temp: Option<f64>,
+    #[prost(double, optional, tag = "24")]
+    pub trimmed_mean: Option<f64>,
+    #[prost(double, optional, tag = "25")]
+    pub geometric_mean: Option<f64>,
+    #[prost(double, optional, tag = "26")]
+    pub harmonic_mean: Option<f64>,
+    #[prost(double, optional, tag = "27")]
+    pub zscore_vs_period: Option<f64>,
+    #[prost(string, optional, tag = "28")]
+    pub country_name: Option<String>,
+    #[prost(string, optional, tag = "29")]
+    pub season: Option<String>,
+    #[prost(double, optional, tag = "30")]
+    pub plugin_metric: Option<f64>,
+    #[prost(string, optional, tag = "31")]
+    pub source_file: Option<String>,
+    #[prost(string, optional, tag = "32")]
+    pub run_id: Option<String>,
+    #[prost(string, optional, tag = "33")]
+    pub processed_at: Option<String>,
+    #[prost(uint32, optional, tag = "34")]
+    pub day: Option<u32>,
+    #[prost(double, optional, tag = "35")]
+    pub avg_temp_celsius: Option<f64>,
+    #[prost(double, optional, tag = "36")]
+    pub min_temp_celsius: Option<f64>,
+    #[prost(double, optional, tag = "37")]
+    pub max_temp_celsius: Option<f64>,
+    #[prost(double, optional, tag = "38")]
+    pub std_dev_celsius: Option<f64>,
+    #[prost(double, optional, tag = "39")]
+    pub median_temp_celsius: Option<f64>,
+    #[prost(double, optional, tag = "40")]
+    pub percentile_25_celsius: Option<f64>,
+    #[prost(double, optional, tag = "41")]
+    pub percentile_75_celsius: Option<f64>,
+    #[prost(double, optional, tag = "42")]
+    pub percentile_90_celsius: Option<f64>,
+    #[prost(double, optional, tag = "43")]
+    pub percentile_95_celsius: Option<f64>,
+    #[prost(uint32, optional, tag = "44")]
+    pub outliers_removed: Option<u32>,
+    #[prost(double, optional, tag = "45")]
+    pub anomaly_c: Option<f64>,
+}
+
+impl WeatherStats {
+    /// Builds the message from the configured output columns, leaving fields of columns that
+    /// are not emitted unset.
+    pub fn from_record(record: &Record, columns: &[Column]) -> Self {
+        let mut message = Self::default();
+        for column in columns {
+            match (column.name.as_str(), column.value(record)) {
+                ("country", Value::Utf8(v)) => message.country = v,
+                ("year", Value::Int32(v)) => message.year = v,
+                ("month", Value::UInt32(v)) => message.month = v,
+                ("avg_temp", Value::Float64(v)) => message.avg_temp = v,
+                ("min_temp", Value::Float64(v)) => message.min_temp = v,
+                ("max_temp", Value::Float64(v)) => message.max_temp = v,
+                ("std_dev", Value::Float64(v)) => message.std_dev = v,
+                ("median_temp", Value::Float64(v)) => message.median_temp = v,
+                ("count", Value::UInt32(v)) => message.count = v,
+                ("percentile_25", Value::Float64(v)) => message.percentile_25 = v,
+                ("percentile_75", Value::Float64(v)) => message.percentile_75 = v,
+                ("percentile_90", Value::Float64(v)) => message.percentile_90 = v,
+                ("percentile_95", Value::Float64(v)) => message.percentile_95 = v,
+                ("unit", Value::Utf8(v)) => message.unit = Some(v),
+                ("period_start", Value::Utf8(v)) => message.period_start = Some(v),
+                ("period_end", Value::Utf8(v)) => message.period_end = Some(v),
+                ("enso_phase", Value::Utf8(v)) => message.enso_phase = Some(v),
+                ("aridity_index", Value::Float64(v)) => message.aridity_index = Some(v),
+                ("distinct_days", Value::UInt32(v)) => message.distinct_days = Some(v),
+                ("ci_low", Value::Float64(v)) => message.ci_low = Some(v),
+                ("ci_high", Value::Float64(v)) => message.ci_high = Some(v),
+                ("range_temp", Value::Float64(v)) => message.range_temp = Some(v),
+                ("mode_temp", Value::Float64(v)) => message.mode_temp = Some(v),
+                ("trimmed_mean", Value::Float64(v)) => message.trimmed_mean = Some(v),
+                ("geometric_mean", Value::Float64(v)) => message.geometric_mean = Some(v),
+                ("harmonic_mean", Value::Float64(v)) => message.harmonic_mean = Some(v),
+                ("zscore_vs_period", Value::Float64(v)) => message.zscore_vs_period = Some(v),
+                ("country_name", Value::Utf8(v)) => message.country_name = Some(v),
+                ("season", Value::Utf8(v)) => message.season = Some(v),
+                ("plugin_metric", Value::Float64(v)) => message.plugin_metric = Some(v),
+                ("source_file", Value::Utf8(v)) => message.source_file = Some(v),
+                ("run_id", Value::Utf8(v)) => message.run_id = Some(v),
+                ("processed_at", Value::Utf8(v)) => message.processed_at = Some(v),
+                ("day", Value::UInt32(v)) => message.day = Some(v),
+                ("avg_temp_celsius", Value::Float64(v)) => message.avg_temp_celsius = Some(v),
+                ("min_temp_celsius", Value::Float64(v)) => message.min_temp_celsius = Some(v),
+                ("max_temp_celsius", Value::Float64(v)) => message.max_temp_celsius = Some(v),
+                ("std_dev_celsius", Value::Float64(v)) => message.std_dev_celsius = Some(v),
+                ("median_temp_celsius", Value::Float64(v)) => message.median_temp_celsius = Some(v),
+                ("percentile_25_celsius", Value::Float64(v)) => {
+                    message.percentile_25_celsius = Some(v)
+                }
+                ("percentile_75_celsius", Value::Float64(v)) => {
+                    message.percentile_75_celsius = Some(v)
+                }
+                ("percentile_90_celsius", Value::Float64(v)) => {
+                    message.percentile_90_celsius = Some(v)
+                }
+                ("percentile_95_celsius", Value::Float64(v)) => {
+                    message.percentile_95_celsius = Some(v)
+                }
+                ("outliers_removed", Value::UInt32(v)) => message.outliers_removed = Some(v),
+                ("anomaly_c", Value::Float64(v)) => message.anomaly_c = Some(v),
+                _ => {}
+            }
+        }
+        message
+    }
+}
+
+/// Writes the records as length-delimited `WeatherStats` protobuf messages.
+///
+/// Each message is preceded by its encoded length as a varint, the framing read by
+/// `parseDelimitedFrom` (Java) and `ParseDelimitedFrom` (C++/Go/Python helpers).
+///
+/// # Errors
+/// Returns error if the file cannot be written.
+pub fn write_protobuf(results: &[Record], output_path: &Path, config: &OutputConfig) -> Result<()> {
+    let columns = output_columns(config);
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    let mut buffer = Vec::new();
+    for record in results {
+        buffer.clear();
+        WeatherStats::from_record(record, &columns)
+            .encode_length_delimited(&mut buffer)
+            .expect("Vec<u8> has unlimited capacity");
+        writer.write_all(&buffer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use crate::structs::Record;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// New identifier of a run: a random (version 4) UUID such as
+/// `67e55044-10b1-426f-9247-bb680e5fe0c8`, unique across hosts and concurrent runs, which
+/// correlates the run's log lines, manifest, and `--provenance` columns.
+pub fn run_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Sets the run columns of `--provenance` on every record: `run_id` and `processed_at`, the
+/// run's start time in RFC 3339.
+///
+/// `source_file` is set during the analysis (see `TransformConfig::provenance`), as it depends
+/// on the inputs each group was read from.
+pub fn stamp_provenance(records: &mut [Record], run_id: &str, started_at: DateTime<Utc>) {
+    let processed_at = started_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+    for record in records {
+        record.run_id = Some(run_id.to_string());
+        record.processed_at = Some(processed_at.clone());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{PipelineError, Result};
+use crate::schema::{Column, RecordRow, Value, all_columns};
+use crate::structs::{Record, TemperatureUnit};
+use crate::timeseries::{IntoTimeSeries, TimeSeries};
+use serde_json::Value as Json;
+use std::ops::Deref;
+
+/// Analysis results with chainable filters, for slicing them without a dataframe library, e.g.
+/// `Records::from(results).filter_country("DE").between(2000, 2010).select_columns(&["year",
+/// "month", "avg_temp"])`.
+///
+/// Every filter keeps the records' order. `Records` dereferences to `[Record]`, so slice methods
+/// such as `len` and `iter` are available directly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Records(Vec<Record>);
+
+impl Records {
+    pub fn new(records: Vec<Record>) -> Self {
+        Self(records)
+    }
+
+    /// Keeps the records matching `predicate`.
+    pub fn filter(mut self, predicate: impl FnMut(&Record) -> bool) -> Self {
+        self.0.retain(predicate);
+        self
+    }
+
+    /// Keeps the records of one country (alpha-2 code, case-insensitive).
+    pub fn filter_country(self, country: &str) -> Self {
+        self.filter(|record| record.country.eq_ignore_ascii_case(country))
+    }
+
+    /// Keeps the records from `start_year` to `end_year`, both inclusive.
+    pub fn between(self, start_year: i32, end_year: i32) -> Self {
+        self.filter(|record| (start_year..=end_year).contains(&record.year))
+    }
+
+    /// Keeps the records of one month (1 to 12).
+    pub fn filter_month(self, month: u32) -> Self {
+        self.filter(|record| record.month == month)
+    }
+
+    /// Keeps the records in one unit, e.g. of results with extra units.
+    pub fn filter_unit(self, unit: TemperatureUnit) -> Self {
+        self.filter(|record| record.unit == unit)
+    }
+
+    /// Projects the records onto the named output columns (as in the JSON output, e.g.
+    /// `avg_temp`), in the given order.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Data` if a name is not an output column or is given twice.
+    pub fn select_columns(self, names: &[&str]) -> Result<Selection> {
+        let mut available = all_columns();
+        let mut columns = Vec::with_capacity(names.len());
+        for name in names {
+            if columns.iter().any(|column: &Column| column.name == *name) {
+                return Err(PipelineError::Data(format!(
+                    "column '{}' is selected twice",
+                    name
+                )));
+            }
+            let Some(index) = available.iter().position(|column| column.name == *name) else {
+                return Err(PipelineError::Data(format!(
+                    "unknown column '{}' (expected one of {})",
+                    name,
+                    all_columns()
+                        .iter()
+                        .map(|column| column.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            };
+            columns.push(available.swap_remove(index));
+        }
+        Ok(Selection {
+            columns,
+            records: self.0,
+        })
+    }
+
+    pub fn into_vec(self) -> Vec<Record> {
+        self.0
+    }
+}
+
+impl IntoTimeSeries for Records {
+    fn into_time_series(self) -> TimeSeries {
+        TimeSeries::new(self.0)
+    }
+}
+
+impl From<Vec<Record>> for Records {
+    fn from(records: Vec<Record>) -> Self {
+        Self(records)
+    }
+}
+
+impl FromIterator<Record> for Records {
+    fn from_iter<I: IntoIterator<Item = Record>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Records {
+    type Item = Record;
+    type IntoIter = std::vec::IntoIter<Record>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl Deref for Records {
+    type Target = [Record];
+
+    fn deref(&self) -> &[Record] {
+        &self.0
+    }
+}
+
+/// Records projected onto a subset of the output columns by [`Records::select_columns`].
+pub struct Selection {
+    columns: Vec<Column>,
+    records: Vec<Record>,
+}
+
+impl Selection {
+    /// Names of the selected columns, in order.
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Values of each row, in column order.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<Value>> + '_ {
+        self.records.iter().map(|record| {
+            self.columns
+                .iter()
+                .map(|column| column.value(record))
+                .collect()
+        })
+    }
+
+    /// Values of one selected column, if selected.
+    pub fn column(&self, name: &str) -> Option<Vec<Value>> {
+        let column = self.columns.iter().find(|column| column.name == name)?;
+        Some(
+            self.records
+                .iter()
+                .map(|record| column.value(record))
+                .collect(),
+        )
+    }
+
+    /// Rows as an array of JSON objects holding the selected columns, like the JSON output.
+    pub fn to_json(&self) -> Json {
+        Json::Array(
+            self.records
+                .iter()
+                .map(|record| {
+                    serde_json::to_value(RecordRow {
+                        record,
+                        columns: &self.columns,
+                        null_value: None,
+                    })
+                    .unwrap_or(Json::Null)
+                })
+                .collect(),
+        )
+    }
+
+    /// The filtered records with all their fields.
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+}
+
+impl std::fmt::Debug for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Selection")
+            .field("columns", &self.column_names())
+            .field("rows", &self.records.len())
+            .finish()
+    }
+}
@@ -0,0 +1,101 @@
+use crate::cache::fnv1a_str;
+use crate::error::{PipelineError, Result};
+use crate::ledger::{Ledger, write_atomic};
+use crate::status;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Validators of a downloaded file, stored next to it as `<file>.meta.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DownloadMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Downloads `url` into `cache_dir`, reusing the cached copy while the server reports it
+/// unchanged.
+///
+/// Requests are conditional on the `ETag` (and `Last-Modified`) of the cached copy, so an
+/// unchanged file costs a single `304 Not Modified` round trip. If the server cannot be
+/// reached, an existing cached copy is used.
+///
+/// # Returns
+///
+/// Returns the path of the local copy, which keeps the URL's file name (and thereby its
+/// extension, so `.csv` and archive URLs are read accordingly).
+///
+/// # Errors
+///
+/// Returns `PipelineError::Http` if the download fails without a cached copy, or
+/// `PipelineError::Io` if the cache cannot be written.
+pub fn fetch(url: &str, cache_dir: &Path, ledger: &Ledger) -> Result<PathBuf> {
+    fs::create_dir_all(cache_dir)?;
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download");
+    let path = cache_dir.join(format!("{:016x}-{}", fnv1a_str(url), file_name));
+    let meta_path = PathBuf::from(format!("{}.meta.json", path.display()));
+    let meta: DownloadMeta = match (path.is_file(), File::open(&meta_path)) {
+        (true, Ok(file)) => serde_json::from_reader(file).unwrap_or_default(),
+        _ => DownloadMeta::default(),
+    };
+
+    let mut request = ureq::get(url);
+    if let Some(etag) = &meta.etag {
+        request = request.header("If-None-Match", etag);
+    }
+    if let Some(last_modified) = &meta.last_modified {
+        request = request.header("If-Modified-Since", last_modified);
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(e) if path.is_file() => {
+            status!("Using cached copy of {} (download failed: {})", url, e);
+            return Ok(path);
+        }
+        Err(e) => return Err(http_error(url, e)),
+    };
+
+    if response.status() == 304 {
+        debug!("{} not modified, using {}", url, path.display());
+        return Ok(path);
+    }
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let meta = DownloadMeta {
+        url: url.to_string(),
+        etag: header("etag"),
+        last_modified: header("last-modified"),
+    };
+
+    status!("Downloading {}", url);
+    let mut body = response.into_body().into_reader();
+    let bytes = write_atomic(ledger, &path, |partial| {
+        Ok(io::copy(
+            &mut body,
+            &mut BufWriter::new(File::create(partial)?),
+        )?)
+    })?;
+    serde_json::to_writer(File::create(&meta_path)?, &meta)?;
+    debug!("Downloaded {} bytes to {}", bytes, path.display());
+    Ok(path)
+}
+
+fn http_error(url: &str, error: ureq::Error) -> PipelineError {
+    PipelineError::Http {
+        url: url.to_string(),
+        reason: error.to_string(),
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::Result;
+use crate::lock::is_locked;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Format of the timestamp suffix of a run directory, e.g. `20251203T141500Z`
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Name of a timestamped run: `<name>_<UTC timestamp>`, which sorts chronologically.
+pub fn timestamped_name(name: &str, now: DateTime<Utc>) -> String {
+    format!("{}_{}", name, now.format(TIMESTAMP_FORMAT))
+}
+
+/// Deletes all but the newest `keep` timestamped runs of `name` inside `parent`.
+///
+/// Only directories named exactly like [`timestamped_name`] output are considered, so other
+/// runs and files under `parent` are never touched. Runs still locked by another live process
+/// are skipped and do not count towards `keep`; where liveness cannot be checked, every
+/// directory with a lock file of another process is skipped (see [`is_locked`]).
+///
+/// # Returns
+///
+/// Returns the deleted directories, oldest first.
+///
+/// # Errors
+/// Returns error if `parent` cannot be listed or a directory cannot be removed.
+pub fn prune_runs(parent: &Path, name: &str, keep: usize) -> Result<Vec<PathBuf>> {
+    let prefix = format!("{}_", name);
+    let mut runs: Vec<(String, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(parent)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let is_run = file_name.strip_prefix(&prefix).is_some_and(|timestamp| {
+            NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).is_ok()
+        });
+        if is_run && entry.file_type()?.is_dir() && !is_locked(&entry.path()) {
+            runs.push((file_name, entry.path()));
+        }
+    }
+    runs.sort();
+    let excess = runs.len().saturating_sub(keep);
+    let mut removed = Vec::with_capacity(excess);
+    for (_, path) in runs.into_iter().take(excess) {
+        fs::remove_dir_all(&path)?;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "transformer-retention-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run_dir(parent: &Path, timestamp: &str) -> PathBuf {
+        let dir = parent.join(format!("stats_{}", timestamp));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn keeps_newest_runs_and_other_entries() {
+        let parent = scratch("newest");
+        let oldest = run_dir(&parent, "20250101T000000Z");
+        let middle = run_dir(&parent, "20250102T000000Z");
+        let newest = run_dir(&parent, "20250103T000000Z");
+        fs::create_dir_all(parent.join("stats_latest")).unwrap();
+
+        let removed = prune_runs(&parent, "stats", 1).unwrap();
+
+        assert_eq!(removed, vec![oldest, middle]);
+        assert!(newest.exists());
+        assert!(parent.join("stats_latest").exists());
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn skips_runs_locked_by_a_live_process() {
+        let parent = scratch("locked");
+        let locked = run_dir(&parent, "20250101T000000Z");
+        let unlocked = run_dir(&parent, "20250102T000000Z");
+        let newest = run_dir(&parent, "20250103T000000Z");
+        // A lock file whose PID has not been written yet belongs to a run that is starting
+        fs::write(locked.join(".transformer-lock"), "").unwrap();
+
+        let removed = prune_runs(&parent, "stats", 1).unwrap();
+
+        assert_eq!(removed, vec![unlocked]);
+        assert!(locked.exists());
+        assert!(newest.exists());
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn prunes_runs_whose_lock_owner_has_exited() {
+        let parent = scratch("stale");
+        let stale = run_dir(&parent, "20250101T000000Z");
+        let newest = run_dir(&parent, "20250102T000000Z");
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .arg("--list")
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        fs::write(stale.join(".transformer-lock"), format!("{}\n", pid)).unwrap();
+
+        let removed = prune_runs(&parent, "stats", 1).unwrap();
+
+        assert_eq!(removed, vec![stale]);
+        assert!(newest.exists());
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
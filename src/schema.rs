@@ -0,0 +1,655 @@
+use crate::countries::country_info;
+use crate::error::{PipelineError, Result};
+use crate::season::Hemisphere;
+use crate::structs::{
+    CelsiusStats, JsonCase, JsonLayout, OutputConfig, Record, SchemaVersion, StdDevMode,
+};
+use chrono::{Months, NaiveDate};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value as Json, json};
+
+/// Arrow-compatible type of an output column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Utf8,
+    Int32,
+    UInt32,
+    Float64,
+}
+
+/// A single cell value produced for an output column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Utf8(String),
+    Int32(i32),
+    UInt32(u32),
+    Float64(f64),
+    Null,
+}
+
+type Getter = Box<dyn Fn(&Record) -> Value + Send + Sync>;
+
+/// Output column definition shared by the CSV, JSON, and Parquet writers.
+pub struct Column {
+    pub name: String,
+    pub data_type: ColumnType,
+    pub nullable: bool,
+    getter: Getter,
+}
+
+impl Column {
+    fn new(
+        name: &str,
+        data_type: ColumnType,
+        nullable: bool,
+        getter: impl Fn(&Record) -> Value + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type,
+            nullable,
+            getter: Box::new(getter),
+        }
+    }
+
+    /// Extracts this column's value from a record.
+    pub fn value(&self, record: &Record) -> Value {
+        (self.getter)(record)
+    }
+
+    /// Header used by the CSV writer (e.g. `avg_temp` -> `Avg_Temp`).
+    pub fn csv_header(&self) -> String {
+        self.name
+            .split('_')
+            .map(|part| {
+                let mut chars = part.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("_")
+    }
+}
+
+/// Column of a grouping dimension, nullable and always null when the grouping collapses it.
+fn collapsible(
+    name: &str,
+    data_type: ColumnType,
+    collapsed: bool,
+    getter: fn(&Record) -> Value,
+) -> Column {
+    if collapsed {
+        Column::new(name, data_type, true, |_| Value::Null)
+    } else {
+        Column::new(name, data_type, false, getter)
+    }
+}
+
+/// Reads one of the Celsius statistics of `--keep-celsius`
+type CelsiusStat = fn(&CelsiusStats) -> f64;
+
+/// Returns the ordered list of output columns for the configured schema version.
+///
+/// # Schema Versions
+///
+/// - **V1**: The original 13 columns (country, year, month, summary statistics, percentiles)
+/// - **V2**: All V1 columns followed by `unit`, `period_start`, and `period_end`
+///
+/// Columns are only ever appended in newer versions so that V1 consumers keep working.
+/// `year` and `month` are nullable, and null, when the grouping collapses them.
+/// Opt-in columns (e.g. `enso_phase`, `aridity_index`, `distinct_days`) are appended after the versioned columns when enabled.
+/// `outliers_removed` is only ever emitted from V2 on, since V1 has a fixed layout.
+/// With `config.percentiles`, one `percentile_<p>` column per requested percentile replaces
+/// `percentile_25` ... `percentile_95` (and likewise their `*_celsius` columns).
+/// The exceptions are `country_name`, which follows `country` so the code and name stay adjacent,
+/// and `day`, which follows `month`.
+pub fn output_columns(config: &OutputConfig) -> Vec<Column> {
+    use ColumnType::*;
+
+    let mut columns = vec![
+        Column::new("country", Utf8, false, |r| Value::Utf8(r.country.clone())),
+        collapsible("year", Int32, config.collapsed_year, |r| {
+            Value::Int32(r.year)
+        }),
+        collapsible("month", UInt32, config.collapsed_month, |r| {
+            Value::UInt32(r.month)
+        }),
+        Column::new("avg_temp", Float64, false, |r| Value::Float64(r.avg_temp)),
+        Column::new("min_temp", Float64, false, |r| Value::Float64(r.min_temp)),
+        Column::new("max_temp", Float64, false, |r| Value::Float64(r.max_temp)),
+        Column::new("std_dev", Float64, false, |r| Value::Float64(r.std_dev)),
+        Column::new("median_temp", Float64, false, |r| {
+            Value::Float64(r.median_temp)
+        }),
+        Column::new("count", UInt32, false, |r| Value::UInt32(r.count)),
+    ];
+    if config.percentiles.is_empty() {
+        columns.extend([
+            Column::new("percentile_25", Float64, false, |r| {
+                Value::Float64(r.percentile_25)
+            }),
+            Column::new("percentile_75", Float64, false, |r| {
+                Value::Float64(r.percentile_75)
+            }),
+            Column::new("percentile_90", Float64, false, |r| {
+                Value::Float64(r.percentile_90)
+            }),
+            Column::new("percentile_95", Float64, false, |r| {
+                Value::Float64(r.percentile_95)
+            }),
+        ]);
+    }
+    for (index, &percent) in config.percentiles.iter().enumerate() {
+        columns.push(Column::new(
+            &Record::percentile_name(percent),
+            Float64,
+            false,
+            move |r| requested_percentile(&r.percentiles, index),
+        ));
+    }
+
+    if config.include_day {
+        columns.insert(
+            3,
+            Column::new("day", UInt32, true, |r| {
+                r.day.map_or(Value::Null, Value::UInt32)
+            }),
+        );
+    }
+
+    if config.include_country_names {
+        columns.insert(
+            1,
+            Column::new("country_name", Utf8, true, |r| {
+                country_info(&r.country).map_or(Value::Null, |info| Value::Utf8(info.name.clone()))
+            }),
+        );
+    }
+
+    if config.schema_version >= SchemaVersion::V2 {
+        columns.push(Column::new("unit", Utf8, false, |r| {
+            Value::Utf8(r.unit.to_string())
+        }));
+        let hemisphere = config.hemisphere;
+        columns.push(Column::new("period_start", Utf8, true, move |r| {
+            period_bounds(r, hemisphere)
+                .map_or(Value::Null, |(start, _)| Value::Utf8(start.to_string()))
+        }));
+        columns.push(Column::new("period_end", Utf8, true, move |r| {
+            period_bounds(r, hemisphere)
+                .map_or(Value::Null, |(_, end)| Value::Utf8(end.to_string()))
+        }));
+    }
+
+    if config.include_enso_phase {
+        columns.push(Column::new("enso_phase", Utf8, true, |r| {
+            r.enso_phase
+                .map_or(Value::Null, |phase| Value::Utf8(phase.to_string()))
+        }));
+    }
+
+    if config.include_season {
+        columns.push(Column::new("season", Utf8, true, |r| {
+            r.season
+                .map_or(Value::Null, |season| Value::Utf8(season.to_string()))
+        }));
+    }
+
+    if config.include_aridity_index {
+        columns.push(Column::new("aridity_index", Float64, true, |r| {
+            r.aridity_index.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_distinct_days {
+        columns.push(Column::new("distinct_days", UInt32, true, |r| {
+            r.distinct_days.map_or(Value::Null, Value::UInt32)
+        }));
+    }
+
+    if config.include_outliers_removed && config.schema_version >= SchemaVersion::V2 {
+        columns.push(Column::new("outliers_removed", UInt32, false, |r| {
+            Value::UInt32(r.outliers_removed)
+        }));
+    }
+
+    if config.include_confidence_interval {
+        columns.push(Column::new("ci_low", Float64, true, |r| {
+            r.ci_low.map_or(Value::Null, Value::Float64)
+        }));
+        columns.push(Column::new("ci_high", Float64, true, |r| {
+            r.ci_high.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_mode_range {
+        columns.push(Column::new("range_temp", Float64, true, |r| {
+            r.range_temp.map_or(Value::Null, Value::Float64)
+        }));
+        columns.push(Column::new("mode_temp", Float64, true, |r| {
+            r.mode_temp.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_trimmed_mean {
+        columns.push(Column::new("trimmed_mean", Float64, true, |r| {
+            r.trimmed_mean.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_positive_means {
+        columns.push(Column::new("geometric_mean", Float64, true, |r| {
+            r.geometric_mean.map_or(Value::Null, Value::Float64)
+        }));
+        columns.push(Column::new("harmonic_mean", Float64, true, |r| {
+            r.harmonic_mean.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_zscore {
+        columns.push(Column::new("zscore_vs_period", Float64, true, |r| {
+            r.zscore_vs_period.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_anomaly {
+        columns.push(Column::new("anomaly_c", Float64, true, |r| {
+            r.anomaly_c.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_plugin_metric {
+        columns.push(Column::new("plugin_metric", Float64, true, |r| {
+            r.plugin_metric.map_or(Value::Null, Value::Float64)
+        }));
+    }
+
+    if config.include_celsius {
+        let stats: [(&str, CelsiusStat); 9] = [
+            ("avg_temp_celsius", |c| c.avg_temp),
+            ("min_temp_celsius", |c| c.min_temp),
+            ("max_temp_celsius", |c| c.max_temp),
+            ("std_dev_celsius", |c| c.std_dev),
+            ("median_temp_celsius", |c| c.median_temp),
+            ("percentile_25_celsius", |c| c.percentile_25),
+            ("percentile_75_celsius", |c| c.percentile_75),
+            ("percentile_90_celsius", |c| c.percentile_90),
+            ("percentile_95_celsius", |c| c.percentile_95),
+        ];
+        let fixed = if config.percentiles.is_empty() { 9 } else { 5 };
+        for (name, stat) in stats.into_iter().take(fixed) {
+            columns.push(Column::new(name, Float64, true, move |r| {
+                r.celsius
+                    .as_ref()
+                    .map_or(Value::Null, |c| Value::Float64(stat(c)))
+            }));
+        }
+        for (index, &percent) in config.percentiles.iter().enumerate() {
+            let name = format!("{}_celsius", Record::percentile_name(percent));
+            columns.push(Column::new(&name, Float64, true, move |r| {
+                r.celsius
+                    .as_ref()
+                    .map_or(Value::Null, |c| requested_percentile(&c.percentiles, index))
+            }));
+        }
+    }
+
+    if config.include_provenance {
+        columns.push(Column::new("source_file", Utf8, true, |r| {
+            r.source_file.clone().map_or(Value::Null, Value::Utf8)
+        }));
+        columns.push(Column::new("run_id", Utf8, true, |r| {
+            r.run_id.clone().map_or(Value::Null, Value::Utf8)
+        }));
+        columns.push(Column::new("processed_at", Utf8, true, |r| {
+            r.processed_at.clone().map_or(Value::Null, Value::Utf8)
+        }));
+    }
+
+    columns
+}
+
+/// Value of the `index`-th requested percentile, null if it was not computed.
+fn requested_percentile(percentiles: &[(String, f64)], index: usize) -> Value {
+    percentiles
+        .get(index)
+        .map_or(Value::Null, |&(_, value)| Value::Float64(value))
+}
+
+/// Output columns named as the keys of the JSON and NDJSON output, i.e. in
+/// `config.json_case`.
+pub fn json_columns(config: &OutputConfig) -> Vec<Column> {
+    let mut columns = output_columns(config);
+    for column in &mut columns {
+        column.name = config.json_case.apply(&column.name);
+    }
+    columns
+}
+
+/// Columns keying the nested JSON layout, in nesting order
+const NESTED_KEYS: [&str; 3] = ["country", "year", "month"];
+
+/// JSON columns of the statistics objects of the nested layout: every column but the
+/// [`NESTED_KEYS`].
+pub(crate) fn nested_columns(config: &OutputConfig) -> Vec<Column> {
+    let keys = NESTED_KEYS.map(|key| config.json_case.apply(key));
+    json_columns(config)
+        .into_iter()
+        .filter(|column| !keys.contains(&column.name))
+        .collect()
+}
+
+/// Checks that `records` can be written in the nested layout: grouped by year and month, and
+/// sorted by country, year, and month with one record each.
+///
+/// # Errors
+/// Returns `PipelineError::Data` if the grouping collapses the year or month, or naming the
+/// first record that repeats or precedes the key of its predecessor.
+pub(crate) fn check_nested(records: &[Record], config: &OutputConfig) -> Result<()> {
+    if config.collapsed_year || config.collapsed_month {
+        return Err(PipelineError::Data(
+            "the nested JSON layout keys records by year and month, which the grouping \
+             collapses; use --json-layout flat"
+                .to_string(),
+        ));
+    }
+    for pair in records.windows(2) {
+        let [a, b] = [&pair[0], &pair[1]];
+        if (a.country.as_str(), a.year, a.month) >= (b.country.as_str(), b.year, b.month) {
+            return Err(PipelineError::Data(format!(
+                "the nested JSON layout needs records sorted by country, year, and month with one \
+                 record each, but {} {}-{} follows {} {}-{}",
+                b.country, b.year, b.month, a.country, a.year, a.month
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Serializes records as nested objects keyed by country, year, and month, each holding a
+/// [`RecordRow`] of `columns` (see [`nested_columns`]).
+///
+/// The records must pass [`check_nested`].
+pub struct NestedRecords<'a> {
+    pub records: &'a [Record],
+    pub columns: &'a [Column],
+    pub null_value: Option<&'a str>,
+}
+
+impl Serialize for NestedRecords<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        NestedLevel {
+            records: self.records,
+            columns: self.columns,
+            null_value: self.null_value,
+            depth: 0,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Records sharing the keys above `depth` (0 = country, 1 = year, 2 = month, 3 = leaf)
+struct NestedLevel<'a> {
+    records: &'a [Record],
+    columns: &'a [Column],
+    null_value: Option<&'a str>,
+    depth: usize,
+}
+
+impl Serialize for NestedLevel<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if self.depth == NESTED_KEYS.len() {
+            return RecordRow {
+                record: &self.records[0],
+                columns: self.columns,
+                null_value: self.null_value,
+            }
+            .serialize(serializer);
+        }
+        let key = |record: &Record| match self.depth {
+            0 => record.country.clone(),
+            1 => record.year.to_string(),
+            _ => record.month.to_string(),
+        };
+        let mut map = serializer.serialize_map(None)?;
+        for group in self.records.chunk_by(|a, b| key(a) == key(b)) {
+            let level = NestedLevel {
+                records: group,
+                columns: self.columns,
+                null_value: self.null_value,
+                depth: self.depth + 1,
+            };
+            map.serialize_entry(&key(&group[0]), &level)?;
+        }
+        map.end()
+    }
+}
+
+/// Rows of a JSON output document in either layout, adding the country, year, and month keys
+/// (in `case`) back to the statistics objects of the nested layout. Nested rows are returned
+/// in record order, i.e. with years and months sorted numerically.
+pub(crate) fn json_rows(document: Json, case: JsonCase) -> Vec<Json> {
+    let countries = match document {
+        Json::Array(rows) => return rows,
+        Json::Object(countries) => countries,
+        _ => return Vec::new(),
+    };
+    let [country_key, year_key, month_key] = NESTED_KEYS.map(|key| case.apply(key));
+    let mut rows = Vec::new();
+    for (country, years) in countries {
+        let Json::Object(years) = years else { continue };
+        for (year, months) in numeric_order(years) {
+            let Json::Object(months) = months else {
+                continue;
+            };
+            for (month, stats) in numeric_order(months) {
+                let Json::Object(mut row) = stats else {
+                    continue;
+                };
+                row.insert(country_key.clone(), Json::from(country.clone()));
+                row.insert(year_key.clone(), json_number(&year));
+                row.insert(month_key.clone(), json_number(&month));
+                rows.push(Json::Object(row));
+            }
+        }
+    }
+    rows
+}
+
+/// Entries of a nested level sorted by their numeric key (`"2"` before `"10"`).
+fn numeric_order(level: Map<String, Json>) -> Vec<(String, Json)> {
+    let mut entries: Vec<_> = level.into_iter().collect();
+    entries.sort_by_key(|(key, _)| key.parse::<i64>().unwrap_or(i64::MAX));
+    entries
+}
+
+fn json_number(key: &str) -> Json {
+    key.parse::<i64>()
+        .map_or_else(|_| Json::from(key), Json::from)
+}
+
+/// Every column the writers can emit, e.g. for checks and selections naming any of them.
+pub(crate) fn all_columns() -> Vec<Column> {
+    output_columns(&OutputConfig {
+        schema_version: SchemaVersion::V2,
+        collapsed_year: false,
+        collapsed_month: false,
+        include_country_names: true,
+        include_day: true,
+        include_enso_phase: true,
+        include_season: true,
+        include_aridity_index: true,
+        include_distinct_days: true,
+        include_outliers_removed: true,
+        include_confidence_interval: true,
+        include_mode_range: true,
+        include_trimmed_mean: true,
+        include_positive_means: true,
+        include_zscore: true,
+        include_anomaly: true,
+        include_plugin_metric: true,
+        include_provenance: true,
+        include_celsius: true,
+        percentiles: Vec::new(),
+        decimal_comma: false,
+        json_case: JsonCase::Snake,
+        json_layout: JsonLayout::Flat,
+        null_value: None,
+        std_dev: StdDevMode::Sample,
+        hemisphere: Hemisphere::North,
+    })
+}
+
+/// Returns a JSON Schema (draft 2020-12) of the JSON output for the configured schema.
+///
+/// The document describes the `.json` file (an array of records, or with
+/// `JsonLayout::Nested` an object of countries, years, and months holding `$defs/stats`);
+/// `$defs/record` describes a single record, i.e. one line of the NDJSON stream output. It is
+/// generated from [`output_columns`], so it always matches what the writers emit.
+pub fn json_schema(config: &OutputConfig) -> Json {
+    let null_value = config.json_null();
+    let record = object_schema(&json_columns(config), null_value);
+    let title = format!(
+        "Transformer weather statistics (output schema version {})",
+        schema_number(config)
+    );
+    match config.json_layout {
+        JsonLayout::Flat => json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": title,
+            "type": "array",
+            "items": {"$ref": "#/$defs/record"},
+            "$defs": {"record": record},
+        }),
+        JsonLayout::Nested => json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": title,
+            "type": "object",
+            "additionalProperties": {
+                "type": "object",
+                "propertyNames": {"pattern": "^-?[0-9]+$"},
+                "additionalProperties": {
+                    "type": "object",
+                    "propertyNames": {"pattern": "^[0-9]+$"},
+                    "additionalProperties": {"$ref": "#/$defs/stats"},
+                },
+            },
+            "$defs": {
+                "record": record,
+                "stats": object_schema(&nested_columns(config), null_value),
+            },
+        }),
+    }
+}
+
+/// JSON Schema of an object holding exactly `columns`, whose nulls are `null` or the constant
+/// `null_value`.
+fn object_schema(columns: &[Column], null_value: Option<&str>) -> Json {
+    let mut properties = Map::new();
+    for column in columns {
+        let mut property = match column.data_type {
+            ColumnType::Utf8 => json!({"type": "string"}),
+            ColumnType::Int32 => {
+                json!({"type": "integer", "minimum": i32::MIN, "maximum": i32::MAX})
+            }
+            ColumnType::UInt32 => json!({"type": "integer", "minimum": 0, "maximum": u32::MAX}),
+            ColumnType::Float64 => json!({"type": "number"}),
+        };
+        match null_value {
+            Some(text) if column.nullable => {
+                property = json!({"anyOf": [property, {"const": text}]});
+            }
+            _ if column.nullable => property["type"] = json!([property["type"], "null"]),
+            _ => {}
+        }
+        properties.insert(column.name.clone(), property);
+    }
+    let required: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Returns the Arrow schema of the Parquet output as JSON (`fields` with `name`, `data_type`,
+/// and `nullable`, plus schema `metadata`).
+pub fn arrow_schema_json(config: &OutputConfig) -> Json {
+    let fields: Vec<Json> = output_columns(config)
+        .iter()
+        .map(|column| {
+            let data_type = match column.data_type {
+                ColumnType::Utf8 => "Utf8",
+                ColumnType::Int32 => "Int32",
+                ColumnType::UInt32 => "UInt32",
+                ColumnType::Float64 => "Float64",
+            };
+            json!({"name": column.name, "data_type": data_type, "nullable": column.nullable})
+        })
+        .collect();
+    json!({
+        "fields": fields,
+        "metadata": {"output_schema_version": schema_number(config).to_string()},
+    })
+}
+
+fn schema_number(config: &OutputConfig) -> u32 {
+    match config.schema_version {
+        SchemaVersion::V1 => 1,
+        SchemaVersion::V2 => 2,
+    }
+}
+
+/// Computes the first and last calendar day covered by a record: its day when grouped by day,
+/// its month, the three months of its season (starting in the December before, for a season
+/// counted in the following year by [`season_year`](crate::season::season_year)), or its
+/// whole year when months are collapsed. Records whose year is collapsed (0) cover no single
+/// period.
+fn period_bounds(record: &Record, hemisphere: Hemisphere) -> Option<(NaiveDate, NaiveDate)> {
+    let (year, month) = (record.year, record.month);
+    if year == 0 {
+        return None;
+    }
+    if let Some(day) = record.day {
+        let date = NaiveDate::from_ymd_opt(year, month, day)?;
+        return Some((date, date));
+    }
+    let (start, months) = match (month, record.season) {
+        (0, Some(season)) => {
+            let first = hemisphere.first_month(&record.country, season);
+            let start_year = if first == 12 { year - 1 } else { year };
+            (NaiveDate::from_ymd_opt(start_year, first, 1)?, 3)
+        }
+        (0, None) => (NaiveDate::from_ymd_opt(year, 1, 1)?, 12),
+        _ => (NaiveDate::from_ymd_opt(year, month, 1)?, 1),
+    };
+    let end = start.checked_add_months(Months::new(months))?.pred_opt()?;
+    Some((start, end))
+}
+
+/// Serializes a record as a JSON object containing exactly the configured columns; nulls are
+/// written as `null_value` when set (see [`OutputConfig::json_null`]).
+pub struct RecordRow<'a> {
+    pub record: &'a Record,
+    pub columns: &'a [Column],
+    pub null_value: Option<&'a str>,
+}
+
+impl Serialize for RecordRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for column in self.columns {
+            match column.value(self.record) {
+                Value::Utf8(v) => map.serialize_entry(&column.name, &v)?,
+                Value::Int32(v) => map.serialize_entry(&column.name, &v)?,
+                Value::UInt32(v) => map.serialize_entry(&column.name, &v)?,
+                Value::Float64(v) => map.serialize_entry(&column.name, &v)?,
+                Value::Null => map.serialize_entry(&column.name, &self.null_value)?,
+            }
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,127 @@
+use crate::cache::{FNV_OFFSET, fnv1a};
+use crate::error::{PipelineError, Result};
+use crate::structs::Record;
+use chrono::{Datelike, NaiveDate};
+use rhai::{AST, Dynamic, Engine, Scope};
+use std::path::{Path, PathBuf};
+
+/// Compiled Rhai script run on every reading (`--row-script`) or record (`--record-script`).
+///
+/// A row script sees the variables `country`, `date` (`YYYY-MM-DD`), `year`, `month`, `day`,
+/// and `temp` (Celsius, after the range checks). It may assign a new `temp` or evaluate to a
+/// number to replace the temperature, and evaluates to `false` to drop the reading.
+///
+/// A record script sees the record as the object map `record`, with the same fields as the
+/// JSON output of the record (e.g. `record.avg_temp`, `record.country`). It may modify or
+/// add to the map's fields, keeping their types (floats stay floats), and evaluates to
+/// `false` to drop the record.
+///
+/// Each call starts from a fresh scope, so scripts cannot carry state between readings.
+pub struct Script {
+    path: PathBuf,
+    checksum: u64,
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// Reads and compiles the script at `path`.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Io` if the file cannot be read, and `PipelineError::Script` if
+    /// it does not compile.
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        let engine = Engine::new();
+        let ast = engine.compile(&source).map_err(|e| PipelineError::Script {
+            file: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            checksum: fnv1a(FNV_OFFSET, source.as_bytes()),
+            engine,
+            ast,
+        })
+    }
+
+    /// FNV-1a checksum of the script source, part of the cache key.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// Runs the script as a row script, returning the reading's temperature or `None` if the
+    /// script drops it. The temperature is returned as the script left it, non-finite or
+    /// not, for the caller to validate.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Script` if the script fails or sets `temp` to a non-number.
+    pub fn row(&self, country: &str, date: NaiveDate, temp: f64) -> Result<Option<f64>> {
+        let mut scope = Scope::new();
+        scope.push("country", country.to_string());
+        scope.push("date", date.to_string());
+        scope.push("year", date.year() as i64);
+        scope.push("month", date.month() as i64);
+        scope.push("day", date.day() as i64);
+        scope.push("temp", temp);
+        let result = self.eval(&mut scope)?;
+        if result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+        let temp = as_number(&result)
+            .or_else(|| scope.get("temp").and_then(as_number))
+            .ok_or_else(|| self.error("temp must be a number".to_string()))?;
+        Ok(Some(temp))
+    }
+
+    /// Runs the script as a record script, returning the modified record or `None` if the
+    /// script drops it.
+    ///
+    /// # Errors
+    /// Returns `PipelineError::Script` if the script fails or leaves a field of the wrong type.
+    pub fn record(&self, record: &Record) -> Result<Option<Record>> {
+        let mut scope = Scope::new();
+        let map = rhai::serde::to_dynamic(record).map_err(|e| self.error(e.to_string()))?;
+        scope.push("record", map);
+        let result = self.eval(&mut scope)?;
+        if result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+        let map = scope
+            .get("record")
+            .ok_or_else(|| self.error("record was removed from the scope".to_string()))?;
+        rhai::serde::from_dynamic(map)
+            .map(Some)
+            .map_err(|e| self.error(format!("invalid record: {}", e)))
+    }
+
+    fn eval(&self, scope: &mut Scope) -> Result<Dynamic> {
+        self.engine
+            .eval_ast_with_scope(scope, &self.ast)
+            .map_err(|e| self.error(e.to_string()))
+    }
+
+    fn error(&self, reason: String) -> PipelineError {
+        PipelineError::Script {
+            file: self.path.clone(),
+            reason,
+        }
+    }
+}
+
+impl std::fmt::Debug for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Script")
+            .field("path", &self.path)
+            .field("checksum", &format_args!("{:016x}", self.checksum))
+            .finish()
+    }
+}
+
+/// Value of a float or integer, as scripts may write `temp = 20` as well as `temp = 20.0`
+fn as_number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|int| int as f64))
+}
@@ -0,0 +1,80 @@
+use crate::countries::country_info;
+use serde::{Deserialize, Serialize};
+
+/// Meteorological season: three-month blocks starting in March, June, September, and December
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Autumn,
+}
+
+impl std::fmt::Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Season::Winter => "winter",
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Autumn => "autumn",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Hemisphere whose seasons are used when grouping by season
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Hemisphere {
+    /// December-February is winter everywhere
+    #[default]
+    North,
+    /// December-February is summer everywhere
+    South,
+    /// Pick each country's hemisphere from the latitude of its capital
+    Auto,
+}
+
+impl Hemisphere {
+    /// Season of `month` for a reading of `country`.
+    ///
+    /// With `Hemisphere::Auto`, countries whose capital lies south of the equator use the
+    /// southern seasons; countries missing from the bundled table use the northern ones.
+    pub fn season(self, country: &str, month: u32) -> Season {
+        let southern = match self {
+            Hemisphere::North => false,
+            Hemisphere::South => true,
+            Hemisphere::Auto => country_info(country).is_some_and(|info| info.lat < 0.0),
+        };
+        let northern = match month {
+            12 | 1 | 2 => Season::Winter,
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            _ => Season::Autumn,
+        };
+        if !southern {
+            return northern;
+        }
+        match northern {
+            Season::Winter => Season::Summer,
+            Season::Spring => Season::Autumn,
+            Season::Summer => Season::Winter,
+            Season::Autumn => Season::Spring,
+        }
+    }
+
+    /// First calendar month of `season` for a reading of `country`: 12 for the season spanning
+    /// December to February, which [`season_year`] counts in the following year.
+    pub fn first_month(self, country: &str, season: Season) -> u32 {
+        [12, 3, 6, 9]
+            .into_iter()
+            .find(|&month| self.season(country, month) == season)
+            .unwrap_or(12)
+    }
+}
+
+/// Year a month's season is counted in: December belongs to the season that continues into
+/// the following January and February, so it is counted in the next year.
+pub fn season_year(year: i32, month: u32) -> i32 {
+    if month == 12 { year + 1 } else { year }
+}
@@ -0,0 +1,427 @@
+use crate::enso::EnsoPhase;
+use crate::error::{PipelineError, Result};
+use crate::memory;
+use crate::season::Season;
+use crate::structs::{ExecutionStrategy, InputFormat, InputSource, TransformConfig};
+use crate::transform::GroupKey;
+use log::debug;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Inputs with fewer rows than this run sequentially under `Auto`
+const SEQUENTIAL_MAX_ROWS: u64 = 100_000;
+
+/// Approximate bytes held per buffered reading (an `f64` plus `Vec` growth slack)
+const BYTES_PER_READING: u64 = 16;
+
+/// Approximate size of a CSV input row, used to estimate the row count of CSV inputs
+const CSV_BYTES_PER_ROW: u64 = 64;
+
+/// Approximate size of an NDJSON input row, whose field names repeat on every line
+const NDJSON_BYTES_PER_ROW: u64 = 96;
+
+/// Partition count used when spilling without knowing the available memory
+const DEFAULT_SPILL_PARTITIONS: usize = 16;
+
+/// Upper bound on spill partitions (open file handles)
+const MAX_SPILL_PARTITIONS: usize = 256;
+
+/// Resolved execution strategy for one run
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExecutionPlan {
+    pub strategy: ExecutionStrategy,
+    /// Number of spill partitions (only used by `Spill`)
+    pub partitions: usize,
+}
+
+/// Resolves `config.strategy` against the input size and available memory.
+///
+/// `Auto` spills when the estimated group buffers exceed half of the available memory,
+/// runs sequentially for small inputs, and runs in parallel otherwise.
+///
+/// # Errors
+///
+/// Returns `PipelineError` if the Parquet metadata or size of an input cannot be read.
+pub(crate) fn resolve_plan(
+    inputs: &[InputSource],
+    config: &TransformConfig,
+) -> Result<ExecutionPlan> {
+    let mut rows = 0;
+    for input in inputs {
+        rows += match input.resolved_format() {
+            InputFormat::Csv => fs::metadata(&input.path)?.len() / CSV_BYTES_PER_ROW,
+            InputFormat::Ndjson => fs::metadata(&input.path)?.len() / NDJSON_BYTES_PER_ROW,
+            _ => SerializedFileReader::new(File::open(&input.path)?)?
+                .metadata()
+                .file_metadata()
+                .num_rows()
+                .max(0) as u64,
+        };
+    }
+    let estimated = rows * BYTES_PER_READING;
+    let available = memory::available_memory_bytes();
+    debug!(
+        "Input has {} rows (~{} of group buffers), available memory: {}",
+        rows,
+        memory::format_mib(estimated),
+        available.map_or("unknown".to_string(), memory::format_mib)
+    );
+
+    // Keep each partition within a quarter of the available memory
+    let partitions = available.map_or(DEFAULT_SPILL_PARTITIONS, |available| {
+        (estimated.div_ceil((available / 4).max(1)) as usize).clamp(2, MAX_SPILL_PARTITIONS)
+    });
+    let strategy = match config.strategy {
+        ExecutionStrategy::Auto => match available {
+            Some(available) if estimated > available / 2 => ExecutionStrategy::Spill,
+            _ if rows < SEQUENTIAL_MAX_ROWS => ExecutionStrategy::Sequential,
+            _ => ExecutionStrategy::Parallel,
+        },
+        strategy => strategy,
+    };
+    Ok(ExecutionPlan {
+        strategy,
+        partitions,
+    })
+}
+
+/// Builds the thread pool a plan runs in, or `None` to use rayon's global pool.
+///
+/// # Errors
+///
+/// Returns `PipelineError::ThreadPool` if the pool cannot be created.
+pub(crate) fn thread_pool(
+    plan: &ExecutionPlan,
+    config: &TransformConfig,
+) -> Result<Option<ThreadPool>> {
+    let threads = match plan.strategy {
+        ExecutionStrategy::Sequential => Some(1),
+        _ => config.threads,
+    };
+    threads
+        .map(|threads| ThreadPoolBuilder::new().num_threads(threads).build())
+        .transpose()
+        .map_err(PipelineError::from)
+}
+
+/// Runs `op` inside `pool` if given, on the current (global) pool otherwise.
+pub(crate) fn install<T: Send>(pool: Option<&ThreadPool>, op: impl FnOnce() -> T + Send) -> T {
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+/// Temporary files that readings are partitioned into by group key hash.
+///
+/// The directory is removed when the partitions are dropped.
+pub(crate) struct SpillPartitions {
+    dir: PathBuf,
+    writers: Vec<BufWriter<File>>,
+}
+
+impl SpillPartitions {
+    /// Creates `count` empty partition files in a fresh temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the directory or files cannot be created.
+    pub fn create(count: usize) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("transformer-spill-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        debug!(
+            "Spilling readings to {} partitions in {}",
+            count,
+            dir.display()
+        );
+        let mut partitions = Self {
+            dir,
+            writers: Vec::with_capacity(count),
+        };
+        for index in 0..count {
+            let file = File::create(partitions.partition_path(index))?;
+            partitions.writers.push(BufWriter::new(file));
+        }
+        Ok(partitions)
+    }
+
+    /// Number of partitions.
+    pub fn len(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Appends one reading to the partition its key hashes to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the write fails.
+    pub fn push(&mut self, key: &GroupKey, temp: f64) -> Result<()> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let partition = (hasher.finish() % self.writers.len() as u64) as usize;
+        write_reading(&mut self.writers[partition], key, temp)
+    }
+
+    /// Flushes all partitions so they can be read back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if a flush fails.
+    pub fn finish_writing(&mut self) -> Result<()> {
+        for writer in &mut self.writers {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Reads one partition back and groups its readings by key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the partition cannot be read, or `PipelineError::Data`
+    /// if it is corrupt.
+    pub fn read_partition(&self, index: usize) -> Result<HashMap<GroupKey, Vec<f64>>> {
+        read_readings(&self.partition_path(index), |_| true)
+    }
+
+    fn partition_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("partition-{:03}.bin", index))
+    }
+}
+
+impl Drop for SpillPartitions {
+    fn drop(&mut self) {
+        self.writers.clear();
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            debug!(
+                "Failed to remove spill directory {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Write buffer of each country's spill file, bounding the memory held during the scan
+const COUNTRY_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Spilled readings of one country
+struct CountryFile {
+    index: usize,
+    writer: BufWriter<File>,
+    readings: u64,
+}
+
+/// Temporary files holding the readings of one country each, so that countries can be
+/// analyzed independently of each other.
+///
+/// The directory is removed when the partitions are dropped.
+pub(crate) struct CountryPartitions {
+    dir: PathBuf,
+    files: BTreeMap<String, CountryFile>,
+}
+
+impl CountryPartitions {
+    /// Creates a fresh temporary directory for the country files.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the directory cannot be created.
+    pub fn create() -> Result<Self> {
+        let dir =
+            std::env::temp_dir().join(format!("transformer-countries-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        debug!("Spilling readings by country to {}", dir.display());
+        Ok(Self {
+            dir,
+            files: BTreeMap::new(),
+        })
+    }
+
+    /// Appends one reading to the file of its country, creating the file on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the file cannot be created or written.
+    pub fn push(&mut self, key: &GroupKey, temp: f64) -> Result<()> {
+        if !self.files.contains_key(&key.country) {
+            let index = self.files.len();
+            let file = File::create(self.country_path(index))?;
+            self.files.insert(
+                key.country.clone(),
+                CountryFile {
+                    index,
+                    writer: BufWriter::with_capacity(COUNTRY_BUFFER_BYTES, file),
+                    readings: 0,
+                },
+            );
+        }
+        let file = self.files.get_mut(&key.country).expect("inserted above");
+        file.readings += 1;
+        write_reading(&mut file.writer, key, temp)
+    }
+
+    /// Flushes all country files so they can be read back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if a flush fails.
+    pub fn finish_writing(&mut self) -> Result<()> {
+        for file in self.files.values_mut() {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Countries with spilled readings, in sorted order.
+    pub fn countries(&self) -> Vec<String> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// Number of passes needed to analyze `country` with at most `limit` bytes of group
+    /// buffers in memory at once (one pass without a limit).
+    pub fn passes(&self, country: &str, limit: Option<u64>) -> usize {
+        let readings = self.files.get(country).map_or(0, |file| file.readings);
+        limit.map_or(1, |limit| {
+            (readings * BYTES_PER_READING).div_ceil(limit.max(1)).max(1) as usize
+        })
+    }
+
+    /// Reads the groups of `country` whose key hashes to `pass` out of `passes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Io` if the file cannot be read, or `PipelineError::Data` if it
+    /// is corrupt.
+    pub fn read_pass(
+        &self,
+        country: &str,
+        pass: usize,
+        passes: usize,
+    ) -> Result<HashMap<GroupKey, Vec<f64>>> {
+        let Some(file) = self.files.get(country) else {
+            return Ok(HashMap::new());
+        };
+        read_readings(&self.country_path(file.index), |key| {
+            passes == 1 || {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                (hasher.finish() % passes as u64) as usize == pass
+            }
+        })
+    }
+
+    fn country_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("country-{:03}.bin", index))
+    }
+}
+
+impl Drop for CountryPartitions {
+    fn drop(&mut self) {
+        self.files.clear();
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            debug!(
+                "Failed to remove spill directory {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Appends one reading to a spill file.
+fn write_reading(writer: &mut impl Write, key: &GroupKey, temp: f64) -> Result<()> {
+    let country = key.country.as_bytes();
+    writer.write_all(&(country.len() as u16).to_le_bytes())?;
+    writer.write_all(country)?;
+    writer.write_all(&key.year.to_le_bytes())?;
+    writer.write_all(&key.month.to_le_bytes())?;
+    writer.write_all(&[
+        key.day as u8,
+        encode_phase(key.enso_phase),
+        encode_season(key.season),
+    ])?;
+    writer.write_all(&temp.to_le_bytes())?;
+    Ok(())
+}
+
+/// Reads a spill file back and groups the readings of the keys accepted by `keep`.
+fn read_readings(
+    path: &Path,
+    mut keep: impl FnMut(&GroupKey) -> bool,
+) -> Result<HashMap<GroupKey, Vec<f64>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut groups: HashMap<GroupKey, Vec<f64>> = HashMap::new();
+    let mut len = [0u8; 2];
+    loop {
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut country = vec![0u8; u16::from_le_bytes(len) as usize];
+        reader.read_exact(&mut country)?;
+        let mut fields = [0u8; 19];
+        reader.read_exact(&mut fields)?;
+        let country = String::from_utf8(country).map_err(|_| {
+            PipelineError::Data(format!("corrupt spill partition {}", path.display()))
+        })?;
+        let key = GroupKey {
+            country,
+            year: i32::from_le_bytes(fields[0..4].try_into().unwrap()),
+            month: u32::from_le_bytes(fields[4..8].try_into().unwrap()),
+            day: u32::from(fields[8]),
+            enso_phase: decode_phase(fields[9]),
+            season: decode_season(fields[10]),
+        };
+        let temp = f64::from_le_bytes(fields[11..19].try_into().unwrap());
+        if keep(&key) {
+            groups.entry(key).or_default().push(temp);
+        }
+    }
+    Ok(groups)
+}
+
+fn encode_phase(phase: Option<EnsoPhase>) -> u8 {
+    match phase {
+        None => 0,
+        Some(EnsoPhase::ElNino) => 1,
+        Some(EnsoPhase::LaNina) => 2,
+        Some(EnsoPhase::Neutral) => 3,
+    }
+}
+
+fn decode_phase(byte: u8) -> Option<EnsoPhase> {
+    match byte {
+        1 => Some(EnsoPhase::ElNino),
+        2 => Some(EnsoPhase::LaNina),
+        3 => Some(EnsoPhase::Neutral),
+        _ => None,
+    }
+}
+
+fn encode_season(season: Option<Season>) -> u8 {
+    match season {
+        None => 0,
+        Some(Season::Winter) => 1,
+        Some(Season::Spring) => 2,
+        Some(Season::Summer) => 3,
+        Some(Season::Autumn) => 4,
+    }
+}
+
+fn decode_season(byte: u8) -> Option<Season> {
+    match byte {
+        1 => Some(Season::Winter),
+        2 => Some(Season::Spring),
+        3 => Some(Season::Summer),
+        4 => Some(Season::Autumn),
+        _ => None,
+    }
+}
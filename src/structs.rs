@@ -1,5 +1,45 @@
+use crate::enso::{EnsoPhase, EnsoTable};
+use crate::season::{Hemisphere, Season};
+use crate::units::LinearScale;
 use log::{Log, Metadata, Record as LogRecord};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether progress messages go to stderr, keeping stdout for machine-readable output
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Sends progress messages and log output to stderr instead of stdout.
+pub fn send_status_to_stderr() {
+    STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Whether [`send_status_to_stderr`] was called.
+pub fn status_to_stderr() -> bool {
+    STATUS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Run ID prefixed to every log line, see [`tag_logs_with_run_id`]
+static LOG_RUN_ID: OnceLock<String> = OnceLock::new();
+
+/// Prefixes every line of [`SimpleLogger`] with `run_id`, so the logs of concurrent runs can be
+/// told apart and matched to their outputs. Only the first call has an effect.
+pub fn tag_logs_with_run_id(run_id: &str) {
+    let _ = LOG_RUN_ID.set(run_id.to_string());
+}
+
+/// Prints a progress message to stdout, or to stderr after [`send_status_to_stderr`].
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::structs::status_to_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// Simple logger implementation
 pub struct SimpleLogger;
@@ -10,52 +50,1254 @@ impl Log for SimpleLogger {
     }
 
     fn log(&self, record: &LogRecord) {
-        println!("[{}] {}", record.level(), record.args());
+        match LOG_RUN_ID.get() {
+            Some(run_id) => crate::status!("[{}] [{}] {}", record.level(), run_id, record.args()),
+            None => crate::status!("[{}] {}", record.level(), record.args()),
+        }
     }
 
     fn flush(&self) {}
 }
 
+/// Core temperature statistics of a record in degrees Celsius, kept alongside the statistics
+/// in the record's unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CelsiusStats {
+    pub avg_temp: f64,
+    pub min_temp: f64,
+    pub max_temp: f64,
+    pub std_dev: f64,
+    pub median_temp: f64,
+    pub percentile_25: f64,
+    pub percentile_75: f64,
+    pub percentile_90: f64,
+    pub percentile_95: f64,
+    /// The requested percentiles of `--percentiles` (see [`Record::percentiles`])
+    #[serde(default)]
+    pub percentiles: Vec<(String, f64)>,
+}
+
 /// Weather data record with comprehensive statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Record {
     pub country: String,
     pub year: i32,
     pub month: u32,
+    /// Day of the month, when grouped by day
+    #[serde(default)]
+    pub day: Option<u32>,
     pub avg_temp: f64,
     pub min_temp: f64,
     pub max_temp: f64,
     pub std_dev: f64,
     pub median_temp: f64,
     pub count: u32,
+    /// Number of distinct dates among the readings, when counted
+    #[serde(default)]
+    pub distinct_days: Option<u32>,
+    /// Number of readings removed (or, with winsorizing, clipped) by outlier detection
+    #[serde(default)]
+    pub outliers_removed: u32,
     pub percentile_25: f64,
     pub percentile_75: f64,
     pub percentile_90: f64,
     pub percentile_95: f64,
+    /// Requested percentiles as (column name, value) in the requested order, emitted instead
+    /// of the four above when `--percentiles` is given (see [`Record::percentile_name`])
+    #[serde(default)]
+    pub percentiles: Vec<(String, f64)>,
+    /// Bounds of the 95% confidence interval of `avg_temp`, when computed
+    #[serde(default)]
+    pub ci_low: Option<f64>,
+    #[serde(default)]
+    pub ci_high: Option<f64>,
+    /// Spread `max_temp - min_temp` and center of the most populated bin, when computed
+    #[serde(default)]
+    pub range_temp: Option<f64>,
+    #[serde(default)]
+    pub mode_temp: Option<f64>,
+    /// Mean after dropping the configured fraction of readings from each end, when computed
+    #[serde(default)]
+    pub trimmed_mean: Option<f64>,
+    /// Geometric and harmonic means, when computed and every reading is strictly positive
+    #[serde(default)]
+    pub geometric_mean: Option<f64>,
+    #[serde(default)]
+    pub harmonic_mean: Option<f64>,
+    /// `avg_temp` as a z-score against all of the country's records in the results, when computed
+    #[serde(default)]
+    pub zscore_vs_period: Option<f64>,
+    /// `avg_temp` minus the baseline mean of the country and calendar month, in degrees
+    /// Celsius, with `--anomaly`
+    #[serde(default)]
+    pub anomaly_c: Option<f64>,
+    /// Value returned by the plugin's `post_aggregate`, when a plugin exports it
+    #[serde(default)]
+    pub plugin_metric: Option<f64>,
+    /// File names of the inputs the record was aggregated from (`;`-separated), with `--provenance`
+    #[serde(default)]
+    pub source_file: Option<String>,
+    /// Identifier of the run that produced the record, with `--provenance`
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// Start time of the run that produced the record (RFC 3339), with `--provenance`
+    #[serde(default)]
+    pub processed_at: Option<String>,
+    /// The core statistics in degrees Celsius, with `--keep-celsius`
+    #[serde(default)]
+    pub celsius: Option<CelsiusStats>,
+    #[serde(default)]
+    pub unit: TemperatureUnit,
+    #[serde(default)]
+    pub enso_phase: Option<EnsoPhase>,
+    /// Season of the record, when grouped by season
+    #[serde(default)]
+    pub season: Option<Season>,
+    #[serde(default)]
+    pub aridity_index: Option<f64>,
+}
+
+impl Record {
+    /// Rounds every floating-point statistic (including the Celsius ones) to `digits`
+    /// significant digits.
+    ///
+    /// Rounding goes through Rust's correctly rounded decimal formatting rather than `log10`
+    /// and `powi`, whose last bits may differ between platforms' math libraries, so records
+    /// that agree to `digits` digits become bit-identical.
+    ///
+    /// ```
+    /// use lib::Record;
+    /// assert_eq!(Record::round_value(21.299999237060547, 6), 21.3);
+    /// assert_eq!(Record::round_value(-0.000123456, 2), -0.00012);
+    /// ```
+    pub fn round_significant(&mut self, digits: u32) {
+        let round = |value: &mut f64| *value = Self::round_value(*value, digits);
+        for value in [
+            &mut self.avg_temp,
+            &mut self.min_temp,
+            &mut self.max_temp,
+            &mut self.std_dev,
+            &mut self.median_temp,
+            &mut self.percentile_25,
+            &mut self.percentile_75,
+            &mut self.percentile_90,
+            &mut self.percentile_95,
+        ] {
+            round(value);
+        }
+        for value in [
+            &mut self.ci_low,
+            &mut self.ci_high,
+            &mut self.range_temp,
+            &mut self.mode_temp,
+            &mut self.trimmed_mean,
+            &mut self.geometric_mean,
+            &mut self.harmonic_mean,
+            &mut self.zscore_vs_period,
+            &mut self.anomaly_c,
+            &mut self.plugin_metric,
+            &mut self.aridity_index,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            round(value);
+        }
+        for (_, value) in &mut self.percentiles {
+            round(value);
+        }
+        if let Some(celsius) = &mut self.celsius {
+            for value in [
+                &mut celsius.avg_temp,
+                &mut celsius.min_temp,
+                &mut celsius.max_temp,
+                &mut celsius.std_dev,
+                &mut celsius.median_temp,
+                &mut celsius.percentile_25,
+                &mut celsius.percentile_75,
+                &mut celsius.percentile_90,
+                &mut celsius.percentile_95,
+            ] {
+                round(value);
+            }
+            for (_, value) in &mut celsius.percentiles {
+                round(value);
+            }
+        }
+    }
+
+    /// Column name of a requested percentile, with any decimal point written as `_`.
+    ///
+    /// ```
+    /// use lib::Record;
+    /// assert_eq!(Record::percentile_name(5.0), "percentile_5");
+    /// assert_eq!(Record::percentile_name(99.9), "percentile_99_9");
+    /// ```
+    pub fn percentile_name(percent: f64) -> String {
+        format!("percentile_{}", percent.to_string().replace('.', "_"))
+    }
+
+    /// `value` rounded to `digits` (at least 1) significant digits; non-finite values are
+    /// returned unchanged.
+    pub fn round_value(value: f64, digits: u32) -> f64 {
+        if !value.is_finite() {
+            return value;
+        }
+        let precision = digits.max(1) as usize - 1;
+        // Adding 0.0 normalizes -0.0, as the CSV writer would print "-0.00"
+        format!("{:.*e}", precision, value)
+            .parse::<f64>()
+            .map_or(value, |rounded| rounded + 0.0)
+    }
+}
+
+/// Names of the input columns the pipeline reads
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnMapping {
+    pub date: String,
+    pub country: String,
+    pub temp: String,
+    pub precip: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            date: "date".to_string(),
+            country: "country_alpha2".to_string(),
+            temp: "temp_mean_c_approx".to_string(),
+            precip: "rain_mm".to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for ColumnMapping {
+    type Err = String;
+
+    /// Parses comma-separated `key=column` pairs (e.g. `date=obs_date,country=iso2,temp=t_mean`);
+    /// keys are `date`, `country`, `temp`, and `precip`, and keys left out keep their default
+    /// column names.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut mapping = Self::default();
+        for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (key, column) = pair.split_once('=').ok_or_else(|| {
+                format!(
+                    "invalid column mapping '{}' (expected KEY=COLUMN, e.g. temp=t_mean)",
+                    pair
+                )
+            })?;
+            let column = column.trim();
+            if column.is_empty() {
+                return Err(format!("empty column name for '{}'", key.trim()));
+            }
+            let slot = match key.trim() {
+                "date" => &mut mapping.date,
+                "country" => &mut mapping.country,
+                "temp" => &mut mapping.temp,
+                "precip" => &mut mapping.precip,
+                other => {
+                    return Err(format!(
+                        "unknown column key '{}' (expected date, country, temp, or precip)",
+                        other
+                    ));
+                }
+            };
+            *slot = column.to_string();
+        }
+        Ok(mapping)
+    }
+}
+
+/// Extensions of the files read from directories and archives, in any case
+pub const DATA_EXTENSIONS: [&str; 4] = ["parquet", "csv", "ndjson", "jsonl"];
+
+/// File format of an input
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// CSV for files ending in `.csv`, NDJSON for `.ndjson` and `.jsonl`, Parquet otherwise
+    #[default]
+    Auto,
+    Parquet,
+    Csv,
+    /// Newline-delimited JSON, one object per row
+    Ndjson,
+}
+
+impl std::fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InputFormat::Auto => "auto",
+            InputFormat::Parquet => "Parquet",
+            InputFormat::Csv => "CSV",
+            InputFormat::Ndjson => "NDJSON",
+        })
+    }
+}
+
+/// An input file together with the column names it uses
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSource {
+    pub path: PathBuf,
+    pub columns: ColumnMapping,
+    pub format: InputFormat,
+}
+
+impl InputSource {
+    /// Input using the default column names, with its format detected by extension.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            columns: ColumnMapping::default(),
+            format: InputFormat::Auto,
+        }
+    }
+
+    /// Whether the input is an `http://` or `https://` URL to download before processing.
+    pub fn is_remote(&self) -> bool {
+        self.path
+            .to_str()
+            .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+    }
+
+    /// Format the input is read in: the forced format, or the one its extension names
+    /// (never `InputFormat::Auto`).
+    pub fn resolved_format(&self) -> InputFormat {
+        if self.format != InputFormat::Auto {
+            return self.format;
+        }
+        let ext = self.path.extension().map(|ext| ext.to_ascii_lowercase());
+        match ext.as_ref().and_then(|ext| ext.to_str()) {
+            Some("csv") => InputFormat::Csv,
+            Some("ndjson" | "jsonl") => InputFormat::Ndjson,
+            _ => InputFormat::Parquet,
+        }
+    }
+
+    /// Whether the input is read as CSV (forced, or by its `.csv` extension).
+    pub fn is_csv(&self) -> bool {
+        self.resolved_format() == InputFormat::Csv
+    }
 }
 
 /// Configuration for data transformation
 #[derive(Debug, Clone)]
 pub struct TransformConfig {
+    /// Column names of the input read by [`crate::process_data`] and the other single-file
+    /// entry points; an [`InputSource`] carries its own
+    pub columns: ColumnMapping,
     pub unit: TemperatureUnit,
+    /// Additional units computed in the same pass; their records follow the `unit` record of
+    /// each group and are told apart by `Record::unit`
+    pub extra_units: Vec<TemperatureUnit>,
+    /// Scale of `TemperatureUnit::Custom`
+    pub custom_scale: LinearScale,
+    /// Lowest and highest plausible reading in `unit`; readings outside are rejected while
+    /// cleaning. Either defaults to the bound of [`crate::transform::VALID_CELSIUS`]
+    pub valid_min: Option<f64>,
+    pub valid_max: Option<f64>,
     pub threshold: Option<f64>,
+    /// How outliers are detected; `threshold` is the significance level for `Grubbs` and the
+    /// fence multiplier for `Iqr`
+    pub outlier_method: OutlierMethod,
+    /// Years on either side of a group's year compared by `OutlierMethod::Rolling`
+    pub outlier_window: u32,
+    /// What happens to readings beyond `threshold`
+    pub outlier_action: OutlierAction,
+    /// How the 25th, 75th, 90th, and 95th percentiles (and those of `percentiles`) are computed;
+    /// the median always averages the two middle readings of an even group
+    pub quantile_method: QuantileMethod,
+    /// Percentiles (0-100) computed into [`Record::percentiles`]; empty for only the fixed set
+    pub percentiles: Vec<f64>,
+    /// Denominator of `std_dev` and of the `StdDev`, `Rolling`, and `Climatological` outlier
+    /// bounds; Grubbs' test always uses the sample standard deviation its critical values assume
+    pub std_dev: StdDevMode,
     pub aggregate: bool,
+    pub strict: bool,
+    /// Number of randomly sampled groups to verify against a reference implementation
+    pub self_check: Option<usize>,
+    /// Dimensions records are grouped by; dimensions left out are collapsed. Ignored when
+    /// `granularity` is set
+    pub group_by: Vec<GroupDimension>,
+    /// Time granularity of the records, replacing `group_by` with
+    /// [`Granularity::group_by`] when set (see [`TransformConfig::dimensions`])
+    pub granularity: Option<Granularity>,
+    /// ENSO episode table used to tag records with their phase
+    pub enso: Option<EnsoTable>,
+    /// Hemisphere whose seasons are used when grouping by `GroupDimension::Season`
+    pub hemisphere: Hemisphere,
+    /// Collapse the readings of each country and date to one value before grouping
+    pub daily_collapse: DailyCollapse,
+    /// Count each group's distinct reading dates
+    pub distinct_days: bool,
+    /// Compute the 95% confidence interval of each record's mean
+    pub confidence_interval: bool,
+    /// Compute each record's range and binned mode
+    pub mode_range: bool,
+    /// Width of the bins of the binned mode, in the record's unit
+    pub mode_bin_width: f64,
+    /// Fraction of readings dropped from each end for the trimmed mean, if computed
+    pub trimmed_mean: Option<f64>,
+    /// Compute each record's geometric and harmonic means
+    pub positive_means: bool,
+    /// Compute each group's aridity index from the mapped precipitation column
+    pub aridity: bool,
+    /// Record the file names of the inputs each group was aggregated from
+    pub provenance: bool,
+    /// Keep each record's core statistics in Celsius as well (see [`Record::celsius`])
+    pub keep_celsius: bool,
+    /// Significant digits every statistic is rounded to as its record is produced (see
+    /// [`Record::round_significant`]), for outputs that hash identically across platforms
+    pub significant_digits: Option<u32>,
+    /// Aggregate with constant memory per group: exact count, mean, standard deviation, and
+    /// extremes, but P² estimates of the median and percentiles (see [`crate::online`])
+    pub streaming: bool,
+    /// How the analysis is executed; `Auto` picks one from input size and available memory
+    pub strategy: ExecutionStrategy,
+    /// Worker threads for parallel analysis (rayon's default when `None`)
+    pub threads: Option<usize>,
+    /// Bytes of group buffers a worker may hold for one country under
+    /// `ExecutionStrategy::PerCountry`; denser countries are analyzed in several passes
+    pub country_memory_limit: Option<u64>,
+    /// Sort groups of at least this many readings on the GPU (with the `gpu` feature)
+    pub gpu_min_group: Option<usize>,
+    /// WebAssembly module transforming readings and deriving a metric per record
+    #[cfg(feature = "plugins")]
+    pub plugin: Option<std::sync::Arc<crate::plugin::Plugin>>,
+    /// Rhai script run on every cleaned reading
+    #[cfg(feature = "scripting")]
+    pub row_script: Option<std::sync::Arc<crate::script::Script>>,
+    /// Rhai script run on every record
+    #[cfg(feature = "scripting")]
+    pub record_script: Option<std::sync::Arc<crate::script::Script>>,
+}
+
+/// How several readings of the same country and date are combined before grouping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DailyCollapse {
+    /// Keep every reading
+    #[default]
+    None,
+    /// Average the day's readings
+    Mean,
+    /// Take the median of the day's readings
+    Median,
+}
+
+/// Test deciding which readings of a group are outliers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutlierMethod {
+    /// Readings more than `threshold` standard deviations from the group mean
+    #[default]
+    StdDev,
+    /// Grubbs' test at significance level `threshold` (e.g. 0.05), assuming normal readings
+    Grubbs,
+    /// Z-score (`threshold` standard deviations) against the same country and calendar month
+    /// over a rolling window of years, so a reading is judged against its season
+    Rolling,
+    /// Z-score (`threshold` standard deviations) against the same country and calendar month
+    /// over every year, which catches sensor errors in months with few readings
+    Climatological,
+    /// Tukey's fences: readings more than `threshold` interquartile ranges (e.g. 1.5) below the
+    /// first or above the third quartile, robust to skewed groups
+    Iqr,
+    /// Readings more than `threshold` median absolute deviations from the group median
+    Mad,
+    /// Iglewicz and Hoaglin's modified z-score `0.6745 (x - median) / MAD` above `threshold`
+    /// (e.g. 3.5), falling back to the mean absolute deviation when the MAD is zero
+    ZScoreModified,
+}
+
+/// How percentiles falling between two readings are computed, as NumPy's `method` (and pandas'
+/// `interpolation`) choices of the same names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum QuantileMethod {
+    /// Interpolate linearly between the two readings
+    #[default]
+    Linear,
+    /// The nearer of the two readings (the even-indexed one when halfway)
+    Nearest,
+    /// The lower reading
+    Lower,
+    /// The higher reading
+    Higher,
+    /// The mean of the two readings
+    Midpoint,
+}
+
+/// Denominator of the variances behind `std_dev` and the z-score outlier bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum StdDevMode {
+    /// Sample standard deviation, dividing by `n - 1` (Bessel's correction)
+    #[default]
+    Sample,
+    /// Population standard deviation, dividing by `n`
+    Population,
+}
+
+impl StdDevMode {
+    /// Divisor of the sum of squared deviations of `count` readings.
+    ///
+    /// ```
+    /// use lib::StdDevMode;
+    /// assert_eq!(StdDevMode::Sample.divisor(4), 3.0);
+    /// assert_eq!(StdDevMode::Population.divisor(4), 4.0);
+    /// ```
+    pub fn divisor(self, count: usize) -> f64 {
+        match self {
+            StdDevMode::Sample => count as f64 - 1.0,
+            StdDevMode::Population => count as f64,
+        }
+    }
+
+    /// Converts a standard deviation of `count` readings computed with this mode into the
+    /// sample standard deviation, e.g. for a t interval.
+    pub fn to_sample(self, std_dev: f64, count: usize) -> f64 {
+        match self {
+            StdDevMode::Sample => std_dev,
+            StdDevMode::Population => std_dev * (count as f64 / (count as f64 - 1.0)).sqrt(),
+        }
+    }
+}
+
+/// Handling of readings flagged by outlier detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutlierAction {
+    /// Drop outliers from the group
+    #[default]
+    Remove,
+    /// Clip outliers to the threshold bound, keeping the group's count
+    Winsorize,
+}
+
+/// Dimension that statistics can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupDimension {
+    Country,
+    Year,
+    Month,
+    /// ENSO phase of the month, see `TransformConfig::enso`
+    #[value(alias = "enso_phase")]
+    EnsoPhase,
+    /// Meteorological season of the month, see `TransformConfig::hemisphere`
+    Season,
+    /// Day of the month; requires grouping by month
+    Day,
+}
+
+/// Time granularity of the records, a shorthand for the matching [`GroupDimension`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Granularity {
+    /// One record per country and day
+    Daily,
+    /// One record per country and month, the default grouping
+    Monthly,
+    /// One record per country and meteorological season; December counts towards the winter
+    /// of the following year
+    Seasonal,
+    /// One record per country and year
+    Yearly,
+}
+
+impl Granularity {
+    /// Dimensions records are grouped by at this granularity.
+    pub fn group_by(self) -> Vec<GroupDimension> {
+        use GroupDimension::*;
+        match self {
+            Self::Daily => vec![Country, Year, Month, Day],
+            Self::Monthly => vec![Country, Year, Month],
+            Self::Seasonal => vec![Country, Year, Season],
+            Self::Yearly => vec![Country, Year],
+        }
+    }
+}
+
+/// Execution strategy of the analysis stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionStrategy {
+    /// Pick a strategy from the input size and available memory
+    #[default]
+    Auto,
+    /// Single-threaded analysis, avoids thread overhead on small inputs
+    Sequential,
+    /// Rayon parallel analysis with all groups held in memory
+    Parallel,
+    /// Partition readings into temporary files and analyze one partition at a time
+    Spill,
+    /// Spill readings to one temporary file per country and analyze countries in parallel,
+    /// one country per worker
+    #[serde(rename = "per-country")]
+    PerCountry,
+}
+
+impl std::fmt::Display for ExecutionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ExecutionStrategy::Auto => "auto",
+            ExecutionStrategy::Sequential => "sequential",
+            ExecutionStrategy::Parallel => "parallel",
+            ExecutionStrategy::Spill => "spill",
+            ExecutionStrategy::PerCountry => "per-country",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Record file written by a run (`--formats`), see [`crate::write_output`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    /// A JSON document, or NDJSON when streaming
+    Json,
+    Parquet,
+}
+
+impl OutputFormat {
+    /// Every format, in the order they are written
+    pub const ALL: [OutputFormat; 3] =
+        [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Parquet];
+
+    /// File name suffix of the format's output, e.g. `.csv`
+    pub fn suffix(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => ".csv",
+            OutputFormat::Json => ".json",
+            OutputFormat::Parquet => ".parquet",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OutputFormat::Csv => "CSV",
+            OutputFormat::Json => "JSON",
+            OutputFormat::Parquet => "Parquet",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How a run is committed to an Iceberg table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum IcebergMode {
+    /// Add the new data files to the table
+    #[default]
+    Append,
+    /// Replace the data files of every year present in the results
+    Overwrite,
+}
+
+/// How an Iceberg append handles records whose (country, year, month) is already in the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DedupePolicy {
+    /// Fail the commit without changing the table
+    Error,
+    /// Replace the existing records with the new ones
+    KeepLatest,
+    /// Combine the existing and new records into one
+    Merge,
 }
 
 /// Temperature unit conversion
-#[derive(Debug, Clone, clap::ValueEnum)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
 pub enum TemperatureUnit {
+    #[default]
     Celsius,
     Fahrenheit,
     Kelvin,
+    Rankine,
+    /// Linear transform of Celsius given by `TransformConfig::custom_scale`
+    Custom,
+}
+
+impl std::fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TemperatureUnit::Celsius => "celsius",
+            TemperatureUnit::Fahrenheit => "fahrenheit",
+            TemperatureUnit::Kelvin => "kelvin",
+            TemperatureUnit::Rankine => "rankine",
+            TemperatureUnit::Custom => "custom",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Output schema version emitted by the writers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, clap::ValueEnum)]
+pub enum SchemaVersion {
+    /// Original 13-column layout
+    #[default]
+    #[value(name = "1", alias = "v1")]
+    V1,
+    /// V1 columns plus unit and period dates
+    #[value(name = "2", alias = "v2")]
+    V2,
+}
+
+impl std::fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = match self {
+            SchemaVersion::V1 => "1",
+            SchemaVersion::V2 => "2",
+        };
+        f.write_str(number)
+    }
+}
+
+/// Casing of the keys of the JSON and NDJSON output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum JsonCase {
+    /// Column names as they are (`avg_temp`, `percentile_25`)
+    #[default]
+    Snake,
+    /// `avgTemp`, `percentile25`
+    Camel,
+    /// `AvgTemp`, `Percentile25`
+    Pascal,
+}
+
+impl JsonCase {
+    /// Key of the snake_case column `name` in this casing.
+    pub fn apply(self, name: &str) -> String {
+        if self == JsonCase::Snake {
+            return name.to_string();
+        }
+        name.split('_')
+            .enumerate()
+            .map(|(i, part)| {
+                let mut chars = part.chars();
+                match chars.next() {
+                    Some(first) if i > 0 || self == JsonCase::Pascal => {
+                        first.to_uppercase().chain(chars).collect()
+                    }
+                    _ => part.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Layout of the JSON output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum JsonLayout {
+    /// Array of records
+    #[default]
+    Flat,
+    /// Object keyed by country, then year, then month, holding each record's other columns
+    Nested,
+}
+
+/// Configuration for output writers
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    pub schema_version: SchemaVersion,
+    /// The grouping collapses years (e.g. `--group-by country,enso_phase`): the `year` column
+    /// is nullable and written as null
+    pub collapsed_year: bool,
+    /// The grouping collapses months (e.g. seasonal or yearly granularity): the `month` column
+    /// is nullable and written as null
+    pub collapsed_month: bool,
+    /// Emit the `country_name` column after `country`
+    pub include_country_names: bool,
+    /// Emit the `day` column after `month`
+    pub include_day: bool,
+    /// Emit the `enso_phase` column
+    pub include_enso_phase: bool,
+    /// Emit the `season` column
+    pub include_season: bool,
+    /// Emit the `distinct_days` column
+    pub include_distinct_days: bool,
+    /// Emit the `outliers_removed` column (schema version 2 and later only)
+    pub include_outliers_removed: bool,
+    /// Emit the `ci_low` and `ci_high` columns
+    pub include_confidence_interval: bool,
+    /// Emit the `range_temp` and `mode_temp` columns
+    pub include_mode_range: bool,
+    /// Emit the `trimmed_mean` column
+    pub include_trimmed_mean: bool,
+    /// Emit the `geometric_mean` and `harmonic_mean` columns
+    pub include_positive_means: bool,
+    /// Emit the `zscore_vs_period` column
+    pub include_zscore: bool,
+    /// Emit the `anomaly_c` column
+    pub include_anomaly: bool,
+    /// Emit the `plugin_metric` column
+    pub include_plugin_metric: bool,
+    /// Emit the `aridity_index` column
+    pub include_aridity_index: bool,
+    /// Emit the `source_file`, `run_id`, and `processed_at` columns
+    pub include_provenance: bool,
+    /// Emit the `*_celsius` columns
+    pub include_celsius: bool,
+    /// Requested percentiles whose `percentile_<p>` columns replace `percentile_25` ...
+    /// `percentile_95`; empty for the fixed columns
+    pub percentiles: Vec<f64>,
+    /// Write CSV numbers with a decimal comma, delimiting fields with `;` instead of `,`
+    pub decimal_comma: bool,
+    /// Casing of the JSON and NDJSON keys
+    pub json_case: JsonCase,
+    /// Layout of the JSON output (NDJSON is always flat)
+    pub json_layout: JsonLayout,
+    /// Text written for null values by every writer; `None` leaves CSV and Excel cells empty
+    /// and writes JSON `null` (as does `"null"` in JSON)
+    pub null_value: Option<String>,
+    /// Denominator the `std_dev` column was computed with, used to pool merged rows
+    pub std_dev: StdDevMode,
+    /// Hemisphere the `season` column was computed for, used for the period dates of
+    /// seasonal records
+    pub hemisphere: Hemisphere,
+}
+
+impl OutputConfig {
+    /// Text replacing JSON `null`, if any (see [`OutputConfig::null_value`]).
+    pub fn json_null(&self) -> Option<&str> {
+        self.null_value.as_deref().filter(|text| *text != "null")
+    }
+}
+
+impl TransformConfig {
+    /// Dimensions records are actually grouped by: those of `granularity` if set, otherwise
+    /// `group_by`.
+    pub fn dimensions(&self) -> Vec<GroupDimension> {
+        self.granularity
+            .map_or_else(|| self.group_by.clone(), Granularity::group_by)
+    }
 }
 
 impl Default for TransformConfig {
     fn default() -> Self {
         Self {
+            columns: ColumnMapping::default(),
             unit: TemperatureUnit::Celsius,
+            extra_units: Vec::new(),
+            custom_scale: LinearScale::default(),
+            valid_min: None,
+            valid_max: None,
             threshold: Some(3.0),
+            outlier_method: OutlierMethod::StdDev,
+            outlier_window: 5,
+            outlier_action: OutlierAction::Remove,
+            quantile_method: QuantileMethod::Linear,
+            percentiles: Vec::new(),
+            std_dev: StdDevMode::Sample,
             aggregate: false,
+            strict: false,
+            self_check: None,
+            group_by: vec![
+                GroupDimension::Country,
+                GroupDimension::Year,
+                GroupDimension::Month,
+            ],
+            granularity: None,
+            enso: None,
+            hemisphere: Hemisphere::North,
+            daily_collapse: DailyCollapse::None,
+            distinct_days: false,
+            confidence_interval: false,
+            mode_range: false,
+            mode_bin_width: 1.0,
+            trimmed_mean: None,
+            positive_means: false,
+            aridity: false,
+            provenance: false,
+            keep_celsius: false,
+            significant_digits: None,
+            streaming: false,
+            strategy: ExecutionStrategy::Auto,
+            threads: None,
+            country_memory_limit: None,
+            gpu_min_group: None,
+            #[cfg(feature = "plugins")]
+            plugin: None,
+            #[cfg(feature = "scripting")]
+            row_script: None,
+            #[cfg(feature = "scripting")]
+            record_script: None,
+        }
+    }
+}
+
+/// Settings for publishing aggregated outputs under data-sharing constraints
+#[derive(Debug, Clone, Default)]
+pub struct AnonymizeConfig {
+    /// Width of the ranges temperature statistics are bucketed into
+    pub bucket_width: Option<f64>,
+    /// Records aggregating fewer readings than this are suppressed (k-anonymity style)
+    pub min_group_size: Option<u32>,
+}
+
+impl AnonymizeConfig {
+    /// Whether any anonymization is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.bucket_width.is_some() || self.min_group_size.is_some()
+    }
+}
+
+/// Recoverable issue detected during processing (does not abort the run)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warning {
+    pub file: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(file: Option<&Path>, message: String) -> Self {
+        Self {
+            file: file.map(Path::to_path_buf),
+            message,
         }
     }
 }
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}: {}", file.display(), self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Counters and warnings collected while processing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessingStats {
+    /// Rows decoded from the input
+    pub total_rows: usize,
+    /// Parquet row groups skipped without decoding because their date statistics lie outside
+    /// the year range, and the rows they hold
+    #[serde(default)]
+    pub pruned_row_groups: usize,
+    #[serde(default)]
+    pub pruned_rows: usize,
+    /// Rows outside the country and year filters dropped by the Parquet row filter while
+    /// decoding; they are not counted in `total_rows`
+    #[serde(default)]
+    pub row_filtered_rows: usize,
+    /// Rows matching the country and year filters
+    pub filtered_rows: usize,
+    /// Rows skipped because of nulls or unparsable dates
+    pub skipped_rows: usize,
+    /// Matched rows rejected by temperature validation
+    pub invalid_temps: usize,
+    /// Matched readings dropped by the plugin's `transform_row` or the row script
+    #[serde(default)]
+    pub hook_dropped: usize,
+    /// Matched readings rejected because the plugin's `transform_row` or the row script
+    /// returned a non-finite temperature or one outside the valid range
+    #[serde(default)]
+    pub hook_rejected: usize,
+    /// Matched readings of seasons only partly inside the year range, which are left out
+    /// when grouping seasons by year
+    #[serde(default)]
+    pub partial_seasons: usize,
+    /// Readings removed by outlier detection
+    pub outliers_removed: usize,
+    /// Readings clipped to the threshold bound by `OutlierAction::Winsorize`
+    pub outliers_winsorized: usize,
+    /// Groups analyzed (before dropping groups emptied by outlier removal)
+    pub groups: usize,
+    /// Groups verified by the self-check
+    pub self_checked: usize,
+    /// Records suppressed by the minimum group size
+    pub suppressed_groups: usize,
+    /// Strategy the analysis actually ran with
+    pub strategy: Option<ExecutionStrategy>,
+    /// Peak resident set size of the process, where the platform reports it
+    pub peak_rss_bytes: Option<u64>,
+    /// Approximate size of the grouping hash table after extraction
+    pub group_map_bytes: usize,
+    /// Approximate size of all buffered temperature readings after extraction
+    pub group_buffer_bytes: usize,
+    pub warnings: Vec<Warning>,
+    /// Whether SIGINT / SIGTERM stopped the input scan before every row was read
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Bytes of input read: the compressed column chunks decoded from Parquet inputs and the
+    /// whole file of CSV and NDJSON inputs
+    #[serde(default)]
+    pub bytes_read: u64,
+    /// Seconds spent reading and grouping the inputs (including streaming aggregation)
+    #[serde(default)]
+    pub extract_secs: f64,
+    /// Seconds spent computing the records of the groups
+    #[serde(default)]
+    pub analyze_secs: f64,
+}
+
+impl ProcessingStats {
+    /// Throughput of the extraction and analysis phases.
+    pub fn throughput(&self) -> Throughput {
+        let per_sec = |amount: f64, secs: f64| if secs > 0.0 { amount / secs } else { 0.0 };
+        Throughput {
+            rows_per_sec: per_sec(self.total_rows as f64, self.extract_secs),
+            mb_per_sec: per_sec(self.bytes_read as f64 / 1e6, self.extract_secs),
+            groups_per_sec: per_sec(self.groups as f64, self.analyze_secs),
+        }
+    }
+}
+
+/// Throughput of a run's phases, comparable across machines and versions; zero for a phase
+/// that took no measurable time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Throughput {
+    /// Rows decoded per second of extraction
+    pub rows_per_sec: f64,
+    /// Megabytes (10^6 bytes) of input read per second of extraction
+    pub mb_per_sec: f64,
+    /// Groups analyzed per second of analysis
+    pub groups_per_sec: f64,
+}
+
+/// Inclusive range of years used as the climatological baseline (e.g. `1981-2010`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaselinePeriod {
+    pub start_year: i32,
+    pub end_year: i32,
+}
+
+impl std::str::FromStr for BaselinePeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid baseline '{}' (expected START-END, e.g. 1981-2010)",
+                s
+            )
+        };
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start_year = start.trim().parse().map_err(|_| invalid())?;
+        let end_year = end.trim().parse().map_err(|_| invalid())?;
+        if start_year > end_year {
+            return Err(invalid());
+        }
+        Ok(Self {
+            start_year,
+            end_year,
+        })
+    }
+}
+
+/// Set of calendar months (1-12) readings are restricted to, e.g. `6-8` for northern summer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MonthSet(u16);
+
+impl MonthSet {
+    /// Set of the given months; returns `None` if a month is outside 1-12 or none is given.
+    ///
+    /// ```
+    /// use lib::MonthSet;
+    /// let summer = MonthSet::new([6, 7, 8]).unwrap();
+    /// assert!(summer.contains(7) && !summer.contains(9));
+    /// assert!(MonthSet::new([13]).is_none());
+    /// ```
+    pub fn new(months: impl IntoIterator<Item = u32>) -> Option<Self> {
+        let mut bits = 0u16;
+        for month in months {
+            if !(1..=12).contains(&month) {
+                return None;
+            }
+            bits |= 1 << month;
+        }
+        (bits != 0).then_some(Self(bits))
+    }
+
+    /// Whether `month` (1-12) is in the set.
+    pub fn contains(self, month: u32) -> bool {
+        (1..=12).contains(&month) && self.0 & (1 << month) != 0
+    }
+
+    /// Months of the set in calendar order.
+    pub fn months(self) -> impl Iterator<Item = u32> {
+        (1..=12).filter(move |&month| self.contains(month))
+    }
+}
+
+impl std::str::FromStr for MonthSet {
+    type Err = String;
+
+    /// Parses comma-separated months and inclusive ranges, e.g. `6,7,8`, `6-8`, or `12,1-2`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid months '{}' (expected months 1-12 or ranges, e.g. 6,7,8 or 6-8)",
+                s
+            )
+        };
+        let mut months = Vec::new();
+        for part in s.split(',') {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+            let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            months.extend(start..=end);
+        }
+        Self::new(months).ok_or_else(invalid)
+    }
+}
+
+impl std::fmt::Display for MonthSet {
+    /// Formats runs of consecutive months as ranges, e.g. `1-3,12`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let months: Vec<u32> = self.months().collect();
+        let mut parts = Vec::new();
+        for run in months.chunk_by(|a, b| b - a == 1) {
+            match run {
+                [month] => parts.push(month.to_string()),
+                [first, .., last] => parts.push(format!("{}-{}", first, last)),
+                [] => {}
+            }
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+/// Per-country share of months exceeding the baseline mean
+#[derive(Debug, Clone, Serialize)]
+pub struct BaselineExceedance {
+    pub country: String,
+    pub months: u32,
+    pub months_above: u32,
+    pub fraction_above: f64,
+}
+
+/// Kind of difference between a record and the previous run's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl std::fmt::Display for ChangeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChangeType::Added => "added",
+            ChangeType::Changed => "changed",
+            ChangeType::Removed => "removed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Record that differs from the previous run (the previous values for removed records)
+#[derive(Debug, Clone)]
+pub struct RecordChange {
+    pub change_type: ChangeType,
+    pub record: Record,
+}
+
+/// Field whose value differs between the same record of two output runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDrift {
+    /// Record identity, e.g. `AD/1995/1`
+    pub record: String,
+    pub field: String,
+    /// Value in the first and second run (`null` if the field is missing)
+    pub left: serde_json::Value,
+    pub right: serde_json::Value,
+}
+
+/// Record-by-record comparison of two output runs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputComparison {
+    /// Records present in both runs
+    pub matched: usize,
+    /// Records present in only one of the runs
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+    pub drifts: Vec<FieldDrift>,
+}
+
+impl OutputComparison {
+    /// Number of missing records and drifted fields.
+    pub fn differences(&self) -> usize {
+        self.only_left.len() + self.only_right.len() + self.drifts.len()
+    }
+}
+
+/// Summary of a Parquet output checked by [`crate::check_parquet`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParquetReport {
+    pub schema_version: SchemaVersion,
+    pub columns: usize,
+    pub rows: usize,
+    pub row_groups: usize,
+    /// Distinct countries and the first and last year, if there are rows with a year
+    pub countries: usize,
+    pub years: Option<(i32, i32)>,
+}
+
+/// Machine-readable description of a run, written next to its outputs
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    /// Identifier of the run (see [`crate::run_id`]), also found in its log lines
+    pub run_id: String,
+    pub records: usize,
+    /// Output files written by the run (file names relative to the output directory)
+    pub files: Vec<String>,
+    pub stats: ProcessingStats,
+}
+
+/// Description of a failed run, written to `error_report.json` in its output directory so
+/// failed scheduled runs can be triaged without re-running them with `--debug`
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    pub run_id: String,
+    /// Start and failure time of the run (RFC 3339)
+    pub started_at: String,
+    pub failed_at: String,
+    /// Message of the error followed by those of the errors that caused it
+    pub errors: Vec<String>,
+    /// Command-line arguments of the run, which with `config_file` make up its configuration
+    pub arguments: Vec<String>,
+    pub config_file: Option<PathBuf>,
+    /// Processing counters of the run, if it failed after processing the inputs
+    pub stats: Option<ProcessingStats>,
+}
+
+impl ErrorReport {
+    /// Messages of `error` and its chain of sources, outermost first.
+    pub fn error_chain(error: &dyn std::error::Error) -> Vec<String> {
+        let mut errors = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(cause) = source {
+            errors.push(cause.to_string());
+            source = cause.source();
+        }
+        errors
+    }
+}
+
+/// End-of-run summary printed as a single JSON object by `--summary-json`
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// Record count, output files, and processing counters as in the run manifest, with at
+    /// most the first few warnings
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    pub output_dir: PathBuf,
+    /// Number of warnings, including those left out of `stats.warnings`
+    pub warning_count: usize,
+    pub timings: RunTimings,
+}
+
+/// Wall-clock time of the run phases, in seconds
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RunTimings {
+    pub processing_secs: f64,
+    pub io_secs: f64,
+    pub total_secs: f64,
+    pub throughput: Throughput,
+}
@@ -0,0 +1,163 @@
+use crate::countries::country_info;
+use crate::error::Result;
+use crate::structs::{Record, TemperatureUnit};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Compact per-country aggregates of a run, written by `--country-summary`
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryDocument {
+    pub start_year: i32,
+    pub end_year: i32,
+    pub unit: TemperatureUnit,
+    pub countries: Vec<CountrySummary>,
+}
+
+/// Aggregates of one country's records over the whole range; temperatures are rounded to two
+/// decimals.
+#[derive(Debug, Clone, Serialize)]
+pub struct CountrySummary {
+    pub country: String,
+    /// English name from the bundled country table
+    pub name: Option<String>,
+    pub records: usize,
+    pub readings: u64,
+    /// First and last `YYYY-MM` with a record (`YYYY` when not grouped by month)
+    pub first_period: String,
+    pub last_period: String,
+    /// Mean of `avg_temp` weighted by each record's reading count
+    pub mean_temp: f64,
+    /// Least-squares slope of the annual means in degrees per decade, with two years or more
+    pub trend_per_decade: Option<f64>,
+    /// Month with the highest and lowest `avg_temp`, when grouped by month
+    pub hottest_month: Option<MonthExtreme>,
+    pub coldest_month: Option<MonthExtreme>,
+}
+
+/// A record's period and mean temperature
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthExtreme {
+    pub year: i32,
+    pub month: u32,
+    pub avg_temp: f64,
+}
+
+/// Summarizes `results` per country, sorted by country code.
+///
+/// Annual means for the trend are the reading-weighted means of each year's records; records
+/// not grouped by year are left out of the trend.
+pub fn country_summaries(results: &[Record]) -> Vec<CountrySummary> {
+    let mut by_country: BTreeMap<&str, Vec<&Record>> = BTreeMap::new();
+    for record in results {
+        by_country.entry(&record.country).or_default().push(record);
+    }
+    by_country
+        .into_iter()
+        .map(|(country, records)| summarize(country, &records))
+        .collect()
+}
+
+fn summarize(country: &str, records: &[&Record]) -> CountrySummary {
+    let readings: u64 = records.iter().map(|r| u64::from(r.count)).sum();
+    let weighted: f64 = records
+        .iter()
+        .map(|r| r.avg_temp * f64::from(r.count))
+        .sum();
+
+    let mut annual: BTreeMap<i32, (f64, u64)> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.year != 0) {
+        let year = annual.entry(record.year).or_default();
+        year.0 += record.avg_temp * f64::from(record.count);
+        year.1 += u64::from(record.count);
+    }
+    let points: Vec<(f64, f64)> = annual
+        .into_iter()
+        .filter(|(_, (_, count))| *count > 0)
+        .map(|(year, (sum, count))| (f64::from(year), sum / count as f64))
+        .collect();
+
+    let monthly = || records.iter().filter(|r| r.month != 0);
+    let extreme = |record: &&Record| MonthExtreme {
+        year: record.year,
+        month: record.month,
+        avg_temp: round2(record.avg_temp),
+    };
+    let period = |record: &Record| match record.month {
+        0 => record.year.to_string(),
+        month => format!("{}-{:02}", record.year, month),
+    };
+    let first = records
+        .iter()
+        .min_by_key(|r| (r.year, r.month))
+        .map(|r| period(r));
+    let last = records
+        .iter()
+        .max_by_key(|r| (r.year, r.month))
+        .map(|r| period(r));
+
+    CountrySummary {
+        country: country.to_string(),
+        name: country_info(country).map(|info| info.name.clone()),
+        records: records.len(),
+        readings,
+        first_period: first.unwrap_or_default(),
+        last_period: last.unwrap_or_default(),
+        mean_temp: round2(weighted / readings.max(1) as f64),
+        trend_per_decade: slope(&points).map(|slope| round2(slope * 10.0)),
+        hottest_month: monthly()
+            .max_by(|a, b| a.avg_temp.total_cmp(&b.avg_temp))
+            .map(extreme),
+        coldest_month: monthly()
+            .min_by(|a, b| a.avg_temp.total_cmp(&b.avg_temp))
+            .map(extreme),
+    }
+}
+
+/// Least-squares slope of `points`, or `None` with fewer than two distinct x values.
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    (sxx > 0.0).then(|| sxy / sxx)
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Writes the per-country summary of `results` as compact JSON, small enough to power a web
+/// widget without loading the full output.
+///
+/// # Returns
+///
+/// Returns the number of countries summarized.
+///
+/// # Errors
+/// Returns error if the file cannot be created or serialization fails.
+pub fn write_country_summary(
+    results: &[Record],
+    start_year: i32,
+    end_year: i32,
+    unit: TemperatureUnit,
+    output_path: &Path,
+) -> Result<usize> {
+    let document = SummaryDocument {
+        start_year,
+        end_year,
+        unit,
+        countries: country_summaries(results),
+    };
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    serde_json::to_writer(&mut writer, &document)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(document.countries.len())
+}
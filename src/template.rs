@@ -0,0 +1,83 @@
+use crate::error::{PipelineError, Result};
+use crate::schema::{RecordRow, output_columns};
+use crate::structs::{OutputConfig, ProcessingStats, Record};
+use minijinja::syntax::SyntaxConfig;
+use minijinja::value::Serde;
+use minijinja::{Environment, UndefinedBehavior, context};
+use std::fs;
+use std::path::Path;
+
+/// Template file extensions stripped to derive the rendered file's name
+const TEMPLATE_EXTENSIONS: [&str; 4] = [".tera", ".j2", ".jinja", ".jinja2"];
+
+/// Name of the file a template renders to: its file name without the template extension
+/// (`report.html.j2` -> `report.html`).
+pub fn template_output_name(template_path: &Path) -> String {
+    let name = template_path
+        .file_name()
+        .map_or("template".to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+    TEMPLATE_EXTENSIONS
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext).filter(|stem| !stem.is_empty()))
+        .map_or(name.clone(), str::to_string)
+}
+
+/// Renders the results through a user-supplied Jinja2-style template.
+///
+/// The template sees `records` (one map per record holding the configured output columns),
+/// `columns` (the output column names in order), `stats` (the run's processing statistics),
+/// and `generated_at` (RFC 3339 UTC timestamp). Output is HTML-escaped when the rendered name
+/// ends in `.html`, `.htm`, or `.xml`, and JSON-escaped for `.json`/`.yaml`; undefined
+/// variables are errors so typos do not silently render as empty text.
+///
+/// # Errors
+///
+/// Returns `PipelineError::Template` if the template has a syntax error or fails to render,
+/// or `PipelineError::Io` if a file cannot be read or written.
+pub fn render_template(
+    template_path: &Path,
+    results: &[Record],
+    stats: &ProcessingStats,
+    config: &OutputConfig,
+    output_path: &Path,
+) -> Result<()> {
+    let source = fs::read_to_string(template_path).map_err(|e| {
+        PipelineError::Data(format!(
+            "cannot read template {}: {}",
+            template_path.display(),
+            e
+        ))
+    })?;
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_syntax(
+        SyntaxConfig::builder()
+            .keep_trailing_newline(true)
+            .build()?,
+    );
+
+    let columns = output_columns(config);
+    let records: Vec<RecordRow> = results
+        .iter()
+        .map(|record| RecordRow {
+            record,
+            columns: &columns,
+            null_value: None,
+        })
+        .collect();
+    let column_names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    let rendered = env.render_named_str(
+        &template_output_name(template_path),
+        &source,
+        context! {
+            records => Serde(&records),
+            columns => Serde(&column_names),
+            stats => Serde(stats),
+            generated_at => chrono::Utc::now().to_rfc3339(),
+        },
+    )?;
+    fs::write(output_path, rendered)?;
+    Ok(())
+}
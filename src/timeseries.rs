@@ -0,0 +1,275 @@
+use crate::structs::{Record, TemperatureUnit};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Year and month of a record; month `0` stands for the whole year, as in records that are not
+/// grouped by month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Period {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl Period {
+    pub fn new(year: i32, month: u32) -> Self {
+        Self { year, month }
+    }
+
+    /// Period of a record.
+    pub fn of(record: &Record) -> Self {
+        Self::new(record.year, record.month)
+    }
+
+    /// The following period: the next month, or the next year for whole-year periods.
+    pub fn succ(self) -> Self {
+        match self.month {
+            0 => Self::new(self.year + 1, 0),
+            12 => Self::new(self.year + 1, 1),
+            month => Self::new(self.year, month + 1),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.month == 0 {
+            write!(f, "{}", self.year)
+        } else {
+            write!(f, "{}-{:02}", self.year, self.month)
+        }
+    }
+}
+
+/// Run of consecutive periods without records between two periods that have some.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// First missing period
+    pub start: Period,
+    /// Last missing period (inclusive)
+    pub end: Period,
+    /// Number of missing periods
+    pub periods: u32,
+}
+
+/// Bucket size of [`CountrySeries::resample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Quarterly,
+    Yearly,
+}
+
+/// Statistics of the records of one country, unit, and resampling bucket.
+///
+/// Only the statistics that can be combined exactly from the records are kept: the mean is
+/// weighted by each record's reading count, and the extremes are those of the records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resampled {
+    pub country: String,
+    pub unit: TemperatureUnit,
+    /// First period of the bucket
+    pub start: Period,
+    /// Last period of the bucket (inclusive)
+    pub end: Period,
+    /// Number of records merged into the bucket
+    pub records: usize,
+    /// Total readings of the merged records
+    pub count: u64,
+    pub avg_temp: f64,
+    pub min_temp: f64,
+    pub max_temp: f64,
+}
+
+/// Records of one country in chronological order.
+///
+/// Several records may share a period when the results hold more than one unit or are grouped
+/// by ENSO phase or season; they are kept adjacent in the usual record order.
+#[derive(Debug, Clone)]
+pub struct CountrySeries {
+    country: String,
+    records: Vec<Record>,
+}
+
+impl CountrySeries {
+    pub fn country(&self) -> &str {
+        &self.country
+    }
+
+    /// All records of the country, in chronological order.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// First period with a record.
+    pub fn first_period(&self) -> Option<Period> {
+        self.records.first().map(Period::of)
+    }
+
+    /// Last period with a record.
+    pub fn last_period(&self) -> Option<Period> {
+        self.records.last().map(Period::of)
+    }
+
+    /// Distinct periods with records, in chronological order.
+    pub fn periods(&self) -> impl Iterator<Item = Period> + '_ {
+        self.records
+            .chunk_by(|a, b| Period::of(a) == Period::of(b))
+            .map(|records| Period::of(&records[0]))
+    }
+
+    /// Records of `period` (empty if it has none).
+    pub fn at(&self, period: Period) -> &[Record] {
+        self.range(period, period)
+    }
+
+    /// Records from `start` to `end`, both inclusive.
+    pub fn range(&self, start: Period, end: Period) -> &[Record] {
+        let from = self.records.partition_point(|r| Period::of(r) < start);
+        let to = self.records.partition_point(|r| Period::of(r) <= end);
+        &self.records[from..to.max(from)]
+    }
+
+    /// Runs of missing periods between the first and last period with records, e.g. months
+    /// without valid readings.
+    pub fn gaps(&self) -> impl Iterator<Item = Gap> + '_ {
+        let periods: Vec<Period> = self.periods().collect();
+        (1..periods.len()).filter_map(move |i| {
+            let start = periods[i - 1].succ();
+            let (mut end, mut missing) = (start, 0);
+            while end.succ() < periods[i] {
+                end = end.succ();
+                missing += 1;
+            }
+            (start < periods[i]).then_some(Gap {
+                start,
+                end,
+                periods: missing + 1,
+            })
+        })
+    }
+
+    /// Merges the records into calendar quarters or years, one bucket per unit.
+    ///
+    /// Records that are not grouped by month are resampled to whole years at either frequency.
+    /// Buckets are returned in chronological order, then by unit.
+    pub fn resample(&self, frequency: Frequency) -> Vec<Resampled> {
+        let mut buckets: BTreeMap<(Period, String), Resampled> = BTreeMap::new();
+        for record in &self.records {
+            let (start, end) = bucket_bounds(Period::of(record), frequency);
+            let weight = f64::from(record.count);
+            let bucket = buckets
+                .entry((start, record.unit.to_string()))
+                .or_insert_with(|| Resampled {
+                    country: self.country.clone(),
+                    unit: record.unit,
+                    start,
+                    end,
+                    records: 0,
+                    count: 0,
+                    avg_temp: 0.0,
+                    min_temp: f64::INFINITY,
+                    max_temp: f64::NEG_INFINITY,
+                });
+            bucket.records += 1;
+            bucket.count += u64::from(record.count);
+            // Running sum of avg_temp * count, divided once every record is merged
+            bucket.avg_temp += record.avg_temp * weight;
+            bucket.min_temp = bucket.min_temp.min(record.min_temp);
+            bucket.max_temp = bucket.max_temp.max(record.max_temp);
+        }
+        buckets
+            .into_values()
+            .map(|mut bucket| {
+                bucket.avg_temp /= bucket.count.max(1) as f64;
+                bucket
+            })
+            .collect()
+    }
+}
+
+/// First and last period of the bucket containing `period`.
+fn bucket_bounds(period: Period, frequency: Frequency) -> (Period, Period) {
+    let year = period.year;
+    match (frequency, period.month) {
+        (Frequency::Quarterly, month) if month > 0 => {
+            let first = (month - 1) / 3 * 3 + 1;
+            (Period::new(year, first), Period::new(year, first + 2))
+        }
+        (_, 0) => (Period::new(year, 0), Period::new(year, 0)),
+        _ => (Period::new(year, 1), Period::new(year, 12)),
+    }
+}
+
+/// Records grouped by country, with chronological access to each country's series.
+#[derive(Debug, Clone, Default)]
+pub struct TimeSeries {
+    countries: BTreeMap<String, CountrySeries>,
+}
+
+impl TimeSeries {
+    /// Groups `records` by country, sorting each country's records chronologically.
+    pub fn new(records: Vec<Record>) -> Self {
+        let mut countries: BTreeMap<String, CountrySeries> = BTreeMap::new();
+        for record in records {
+            countries
+                .entry(record.country.clone())
+                .or_insert_with(|| CountrySeries {
+                    country: record.country.clone(),
+                    records: Vec::new(),
+                })
+                .records
+                .push(record);
+        }
+        for series in countries.values_mut() {
+            series.records.sort_by(|a, b| {
+                Period::of(a)
+                    .cmp(&Period::of(b))
+                    .then_with(|| a.enso_phase.cmp(&b.enso_phase))
+                    .then_with(|| a.season.cmp(&b.season))
+            });
+        }
+        Self { countries }
+    }
+
+    /// Number of countries.
+    pub fn len(&self) -> usize {
+        self.countries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.countries.is_empty()
+    }
+
+    /// Country codes, sorted.
+    pub fn countries(&self) -> impl Iterator<Item = &str> {
+        self.countries.keys().map(String::as_str)
+    }
+
+    /// Series of one country.
+    pub fn get(&self, country: &str) -> Option<&CountrySeries> {
+        self.countries.get(country)
+    }
+
+    /// Series of every country, sorted by country code.
+    pub fn iter(&self) -> impl Iterator<Item = &CountrySeries> {
+        self.countries.values()
+    }
+
+    /// Flattens the series back into records, sorted by country and then chronologically.
+    pub fn into_records(self) -> Vec<Record> {
+        self.countries
+            .into_values()
+            .flat_map(|series| series.records)
+            .collect()
+    }
+}
+
+/// Conversion of analysis results into a [`TimeSeries`], e.g. `results.into_time_series()`.
+pub trait IntoTimeSeries {
+    fn into_time_series(self) -> TimeSeries;
+}
+
+impl IntoTimeSeries for Vec<Record> {
+    fn into_time_series(self) -> TimeSeries {
+        TimeSeries::new(self)
+    }
+}
@@ -1,13 +1,179 @@
+use crate::aridity::ClimateTotals;
+use crate::check;
+use crate::distribution::student_t_upper_quantile;
+use crate::enso::EnsoPhase;
 use crate::error::{PipelineError, Result};
-use crate::structs::{Record, TemperatureUnit, TransformConfig};
-use arrow_array::{Float64Array, RecordBatch, StringArray};
+use crate::extract::{BatchPlan, RowGroups, check_countries, plan_batches};
+use crate::interrupt;
+use crate::memory;
+use crate::online::OnlineStats;
+use crate::outliers::{self, Bounds, SeasonalTable};
+use crate::profile::{self, SpanKind};
+use crate::season::{Season, season_year};
+use crate::status;
+use crate::strategy::{self, CountryPartitions, ExecutionPlan, SpillPartitions};
+use crate::structs::{
+    CelsiusStats, ColumnMapping, DailyCollapse, ExecutionStrategy, GroupDimension, InputSource,
+    MonthSet, OutlierAction, OutlierMethod, ProcessingStats, QuantileMethod, Record, StdDevMode,
+    TemperatureUnit, TransformConfig, Warning,
+};
+use crate::units::scale_for;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Date32Type, Float64Type};
+use arrow_array::{Array, ArrayRef, Date32Array, Float64Array, RecordBatch, StringArray};
+use arrow_buffer::{BooleanBuffer, NullBuffer};
+use arrow_schema::{ArrowError, DataType};
 use chrono::{Datelike, NaiveDate};
 use log::debug;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use rayon::prelude::*;
-use std::{collections::HashMap, fs::File, path::Path};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::time::Instant;
 
-const DATE_FORMAT: &str = "%Y-%m-%d";
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Two-sided confidence level of `Record::ci_low`/`ci_high`
+const CONFIDENCE_LEVEL: f64 = 0.95;
+
+/// Grouping key of the intermediate aggregation; collapsed dimensions hold placeholder values
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct GroupKey {
+    pub country: String,
+    pub year: i32,
+    pub month: u32,
+    /// Day of the month, `0` when not grouped by day
+    pub day: u32,
+    pub enso_phase: Option<EnsoPhase>,
+    pub season: Option<Season>,
+}
+
+/// Per-group values collected during the scan besides the temperatures themselves
+#[derive(Debug, Default)]
+struct GroupExtras {
+    /// Precipitation totals, collected when the aridity index is computed
+    climate: ClimateTotals,
+    /// Dates of the group's readings, collected when distinct days are counted
+    days: HashSet<NaiveDate>,
+    /// Indices of the inputs with readings of the group, collected with provenance
+    inputs: BTreeSet<usize>,
+}
+
+type ExtrasMap = HashMap<GroupKey, GroupExtras>;
+
+/// Countries kept by a scan: those of `include` (every country when empty) that are not in
+/// `exclude`, so a country both listed and excluded is dropped
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CountryFilter<'a> {
+    pub include: &'a [String],
+    pub exclude: &'a [String],
+}
+
+impl CountryFilter<'_> {
+    /// Filter keeping every country
+    pub const ALL: Self = Self {
+        include: &[],
+        exclude: &[],
+    };
+
+    /// Whether the filter keeps every country.
+    pub fn is_all(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether readings of `country` pass the filter.
+    pub fn contains(&self, country: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|c| c == country))
+            && !self.exclude.iter().any(|c| c == country)
+    }
+
+    /// Rows of `countries` whose country passes the filter, compared with the Arrow comparison
+    /// kernels; the rows of null values are unspecified.
+    pub fn mask(&self, countries: &StringArray) -> std::result::Result<BooleanBuffer, ArrowError> {
+        let any = |codes: &[String]| {
+            let mut any = BooleanBuffer::new_unset(countries.len());
+            for code in codes {
+                let is_country =
+                    arrow_ord::cmp::eq(countries, &StringArray::new_scalar(code.as_str()))?;
+                any = &any | is_country.values();
+            }
+            Ok::<_, ArrowError>(any)
+        };
+        let mut matched = BooleanBuffer::new_set(countries.len());
+        if !self.include.is_empty() {
+            matched = &matched & &any(self.include)?;
+        }
+        if !self.exclude.is_empty() {
+            matched = &matched & &!&any(self.exclude)?;
+        }
+        Ok(matched)
+    }
+}
+
+/// Dates kept by a scan: years from `start_year` to `end_year`, in `months` if given
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DateFilter {
+    pub start_year: i32,
+    pub end_year: i32,
+    pub months: Option<MonthSet>,
+}
+
+impl DateFilter {
+    /// Filter keeping every date
+    pub const ALL: Self = Self {
+        start_year: i32::MIN,
+        end_year: i32::MAX,
+        months: None,
+    };
+
+    /// Whether a reading of `year` and `month` passes the filter.
+    pub fn contains(&self, year: i32, month: u32) -> bool {
+        (self.start_year..=self.end_year).contains(&year)
+            && self.months.is_none_or(|months| months.contains(month))
+    }
+
+    /// Whether the season of a reading of `year` and `month` reaches beyond the year range:
+    /// the December to February season begun in December of the last year, or ended in
+    /// January and February of the first year (see [`season_year`]).
+    pub fn cuts_season(&self, year: i32, month: u32) -> bool {
+        (month == 12 && year == self.end_year) || (month <= 2 && year == self.start_year)
+    }
+
+    /// Rows of `days` (days since 1970-01-01) whose date passes the filter, compared with the
+    /// Arrow comparison kernels; the rows of null values are unspecified.
+    pub fn mask(&self, days: &Date32Array) -> std::result::Result<BooleanBuffer, ArrowError> {
+        let day = |year: i32| {
+            NaiveDate::from_ymd_opt(year, 1, 1)
+                .map(|date| (date - UNIX_EPOCH_DATE).num_days() as i32)
+        };
+        let mut matched = BooleanBuffer::new_set(days.len());
+        if let Some(start) = day(self.start_year) {
+            let after_start = arrow_ord::cmp::gt_eq(days, &Date32Array::new_scalar(start))?;
+            matched = &matched & after_start.values();
+        }
+        if let Some(end) = self.end_year.checked_add(1).and_then(day) {
+            let before_end = arrow_ord::cmp::lt(days, &Date32Array::new_scalar(end))?;
+            matched = &matched & before_end.values();
+        }
+        if let Some(months) = self.months {
+            let values = days.values();
+            let in_months = BooleanBuffer::collect_bool(days.len(), |i| {
+                UNIX_EPOCH_DATE
+                    .checked_add_signed(chrono::TimeDelta::days(values[i].into()))
+                    .is_some_and(|date| months.contains(date.month()))
+            });
+            matched = &matched & &in_months;
+        }
+        Ok(matched)
+    }
+}
+
+/// Default range of plausible readings in °C, covering extreme Earth temperatures from
+/// Antarctica to Death Valley
+pub const VALID_CELSIUS: (f64, f64) = (-100.0, 70.0);
+
+/// Fraction of rows that may be skipped or rejected before a warning is raised
+const WARNING_FRACTION: f64 = 0.01;
 
 /// Processes weather data from a Parquet file with comprehensive statistical analysis.
 ///
@@ -20,9 +186,12 @@ const DATE_FORMAT: &str = "%Y-%m-%d";
 ///
 /// * `file_path` - Path to the input Parquet file containing weather data
 /// * `target_countries` - Slice of country alpha-2 codes to filter data by (empty slice means all countries)
+/// * `excluded_countries` - Country codes to drop, even when listed in `target_countries`
 /// * `start_year` - Inclusive start year for date filtering
 /// * `end_year` - Inclusive end year for date filtering  
-/// * `config` - Transform configuration containing unit preferences, outlier thresholds, and aggregation settings
+/// * `months` - Calendar months to keep (e.g. summer months only); `None` keeps every month
+/// * `config` - Transform configuration containing unit preferences, outlier thresholds, and aggregation settings;
+///   `config.columns` names the date, country, and temperature columns of the file
 ///
 /// # Returns
 ///
@@ -35,166 +204,2106 @@ const DATE_FORMAT: &str = "%Y-%m-%d";
 /// - File cannot be opened or read
 /// - Parquet file is malformed or missing required columns
 /// - Arrow array operations fail
-/// ```
 pub fn process_data(
     file_path: &Path,
     target_countries: &[String],
+    excluded_countries: &[String],
     start_year: i32,
     end_year: i32,
+    months: Option<MonthSet>,
     config: &TransformConfig,
 ) -> Result<Vec<Record>> {
-    debug!("Reading Parquet file: {}", file_path.display());
-    let mut monthly_data: HashMap<(String, i32, u32), Vec<f64>> = HashMap::new();
-    let file = File::open(file_path)?;
-    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-    let reader = builder.build()?;
-
-    let mut total_rows = 0;
-    let mut filtered_rows = 0;
-
-    // Extract and collect raw data
-    println!("Starting data extraction from Parquet batches");
-    for batch_result in reader {
-        let batch = batch_result.map_err(PipelineError::Arrow)?;
-        let date_col = get_column_str(&batch, "date")?;
-        let country_col = get_column_str(&batch, "country_alpha2")?;
-        let temp_col = get_column_f64(&batch, "temp_mean_c_approx")?;
-
-        total_rows += batch.num_rows();
-
-        for i in 0..batch.num_rows() {
-            let date_str = date_col.value(i);
-            let country = country_col.value(i);
-            let temp = temp_col.value(i);
-
-            // Parse date
-            let date = match NaiveDate::parse_from_str(date_str, DATE_FORMAT) {
-                Ok(dt) => dt,
-                Err(_) => continue,
-            };
-            let year = date.year();
-            let month = date.month();
+    process_data_with_stats(
+        file_path,
+        target_countries,
+        excluded_countries,
+        start_year,
+        end_year,
+        months,
+        config,
+    )
+    .map(|(results, _)| results)
+}
+
+/// Processes weather data like [`process_data`], additionally returning run statistics.
+///
+/// The returned `ProcessingStats` contains row counters and any recoverable `Warning`s
+/// (e.g. a large share of unparsable dates) detected along the way.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_data`].
+pub fn process_data_with_stats(
+    file_path: &Path,
+    target_countries: &[String],
+    excluded_countries: &[String],
+    start_year: i32,
+    end_year: i32,
+    months: Option<MonthSet>,
+    config: &TransformConfig,
+) -> Result<(Vec<Record>, ProcessingStats)> {
+    process_inputs_with_stats(
+        &[InputSource {
+            columns: config.columns.clone(),
+            ..InputSource::new(file_path)
+        }],
+        target_countries,
+        excluded_countries,
+        start_year,
+        end_year,
+        months,
+        config,
+    )
+}
 
-            // Apply filters - efficient: check countries only if list is not empty
-            let country_match =
-                target_countries.is_empty() || target_countries.contains(&country.to_string());
-            if country_match && year >= start_year && year <= end_year {
-                filtered_rows += 1;
-                // Data validation and cleaning
-                let cleaned_temp = clean_temp(temp, config);
-                if let Some(valid_temp) = cleaned_temp {
-                    let converted_temp = convert_temp(valid_temp, &config.unit);
-                    let key = if config.aggregate {
-                        let countries_key = if target_countries.is_empty() {
-                            "ALL".to_string()
-                        } else {
-                            target_countries.join(",")
-                        };
-                        (countries_key, year, month)
-                    } else {
-                        (country.to_string(), year, month)
-                    };
-                    monthly_data.entry(key).or_default().push(converted_temp);
+/// Processes several input files in a single aggregation pass.
+///
+/// Readings of all inputs are merged into the same groups; each input may use its own column
+/// names (see [`InputSource`]), so archives whose exports renamed a column can be processed
+/// together.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_data`] for any of the inputs.
+pub fn process_inputs_with_stats(
+    inputs: &[InputSource],
+    target_countries: &[String],
+    excluded_countries: &[String],
+    start_year: i32,
+    end_year: i32,
+    months: Option<MonthSet>,
+    config: &TransformConfig,
+) -> Result<(Vec<Record>, ProcessingStats)> {
+    let countries = CountryFilter {
+        include: target_countries,
+        exclude: excluded_countries,
+    };
+    let dates = DateFilter {
+        start_year,
+        end_year,
+        months,
+    };
+    let started = Instant::now();
+    let mut stats = ProcessingStats::default();
+    let mut plan = strategy::resolve_plan(inputs, config)?;
+    if config.streaming {
+        debug!("Streaming statistics aggregate while scanning, running sequentially");
+        plan.strategy = ExecutionStrategy::Sequential;
+    }
+    status!("Execution strategy: {}", plan.strategy);
+    stats.strategy = Some(plan.strategy);
+    let pool = strategy::thread_pool(&plan, config)?;
+
+    let mut extras = ExtrasMap::new();
+    let scan = input_scan(inputs, countries, dates, config, &mut extras);
+    let (mut results, self_checked) = strategy::install(pool.as_ref(), || match plan.strategy {
+        _ if config.streaming => analyze_online(scan, config, &mut stats),
+        ExecutionStrategy::Spill => analyze_spilled(scan, &plan, config, &mut stats),
+        ExecutionStrategy::PerCountry => analyze_by_country(scan, config, &mut stats),
+        _ => analyze_in_memory(scan, config, &mut stats),
+    })?;
+    stats.analyze_secs = (started.elapsed().as_secs_f64() - stats.extract_secs).max(0.0);
+    if uses_extras(config) {
+        let sources = source_names(inputs);
+        for record in &mut results {
+            attach_extras(record, &extras, &sources, config);
+        }
+    }
+    let primary = results.iter().filter(|r| r.unit == config.unit).count();
+    finish_analysis(&mut stats, primary, self_checked, config);
+
+    // Sort by country, then year, then month
+    debug!("Sorting {} results", results.len());
+    results.sort_by(record_order);
+
+    debug!("Transform processing completed successfully");
+    Ok((results, stats))
+}
+
+/// Order of the results: by country, year, month, day, ENSO phase, and season. The sort is
+/// stable, so the records of one group keep their unit order.
+fn record_order(a: &Record, b: &Record) -> std::cmp::Ordering {
+    a.country
+        .cmp(&b.country)
+        .then_with(|| a.year.cmp(&b.year))
+        .then_with(|| a.month.cmp(&b.month))
+        .then_with(|| a.day.cmp(&b.day))
+        .then_with(|| a.enso_phase.cmp(&b.enso_phase))
+        .then_with(|| a.season.cmp(&b.season))
+}
+
+/// Processes weather data lazily, yielding records in sorted order as they are analyzed.
+///
+/// Extraction still happens up front, but groups are analyzed in parallel chunks only as the
+/// iterator is consumed and each group's readings are released once its record is produced.
+/// Combined with the streaming writers in `load.rs` this avoids holding the full `Vec<Record>`.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_data`]; self-check failures are yielded by the iterator.
+pub fn process_data_iter(
+    file_path: &Path,
+    target_countries: &[String],
+    excluded_countries: &[String],
+    start_year: i32,
+    end_year: i32,
+    months: Option<MonthSet>,
+    config: &TransformConfig,
+) -> Result<RecordIter> {
+    process_inputs_iter(
+        &[InputSource {
+            columns: config.columns.clone(),
+            ..InputSource::new(file_path)
+        }],
+        target_countries,
+        excluded_countries,
+        start_year,
+        end_year,
+        months,
+        config,
+    )
+}
+
+/// Processes several input files lazily, like [`process_data_iter`].
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_inputs_with_stats`].
+pub fn process_inputs_iter(
+    inputs: &[InputSource],
+    target_countries: &[String],
+    excluded_countries: &[String],
+    start_year: i32,
+    end_year: i32,
+    months: Option<MonthSet>,
+    config: &TransformConfig,
+) -> Result<RecordIter> {
+    let countries = CountryFilter {
+        include: target_countries,
+        exclude: excluded_countries,
+    };
+    let dates = DateFilter {
+        start_year,
+        end_year,
+        months,
+    };
+    let mut stats = ProcessingStats::default();
+    let mut plan = strategy::resolve_plan(inputs, config)?;
+    if config.streaming {
+        debug!("Streaming statistics aggregate while scanning, running sequentially");
+        plan.strategy = ExecutionStrategy::Sequential;
+    }
+    if plan.strategy == ExecutionStrategy::Spill {
+        debug!("The iterator API keeps groups in memory, running in parallel instead of spilling");
+        plan.strategy = ExecutionStrategy::Parallel;
+    }
+    status!("Execution strategy: {}", plan.strategy);
+    stats.strategy = Some(plan.strategy);
+    let pool = strategy::thread_pool(&plan, config)?;
+    if config.streaming {
+        let mut extras = ExtrasMap::new();
+        let scan = input_scan(inputs, countries, dates, config, &mut extras);
+        let (mut records, _) = analyze_online(scan, config, &mut stats)?;
+        records.sort_by(record_order);
+        let primary = records.iter().filter(|r| r.unit == config.unit).count();
+        return Ok(RecordIter {
+            groups: Vec::new().into_iter().enumerate(),
+            countries: None,
+            config: config.clone(),
+            sampled: HashSet::new(),
+            self_checked: 0,
+            pool,
+            extras,
+            sources: source_names(inputs),
+            seasonal: SeasonalTable::default(),
+            buffer: records.into(),
+            records: primary,
+            stats,
+            finished: false,
+        });
+    }
+    if plan.strategy == ExecutionStrategy::PerCountry {
+        let mut extras = ExtrasMap::new();
+        let scan = input_scan(inputs, countries, dates, config, &mut extras);
+        let (partitions, seasonal) = partition_by_country(scan, config, &mut stats)?;
+        log_threshold(config);
+        let countries = partitions.countries();
+        let queue = CountryQueue {
+            sample_size: country_sample_size(config, countries.len()),
+            pending: countries.into_iter(),
+            partitions,
+        };
+        return Ok(RecordIter {
+            groups: Vec::new().into_iter().enumerate(),
+            countries: Some(queue),
+            config: config.clone(),
+            sampled: HashSet::new(),
+            self_checked: 0,
+            pool,
+            extras,
+            sources: source_names(inputs),
+            seasonal,
+            buffer: VecDeque::new(),
+            records: 0,
+            stats,
+            finished: false,
+        });
+    }
+    let (monthly_data, extras) = strategy::install(pool.as_ref(), || {
+        extract_groups(inputs, countries, dates, config, &mut stats)
+    })?;
+
+    let seasonal = seasonal_table(config, &monthly_data);
+    let mut entries: Vec<_> = monthly_data
+        .into_iter()
+        .filter(|(_, temps)| !temps.is_empty())
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let sampled = config
+        .self_check
+        .map(|sample_size| check::sample_groups(entries.len(), sample_size))
+        .unwrap_or_default();
+    stats.groups = entries.len();
+
+    Ok(RecordIter {
+        groups: entries.into_iter().enumerate(),
+        countries: None,
+        config: config.clone(),
+        sampled,
+        self_checked: 0,
+        pool,
+        extras,
+        sources: source_names(inputs),
+        seasonal,
+        buffer: VecDeque::new(),
+        records: 0,
+        stats,
+        finished: false,
+    })
+}
+
+/// Number of groups analyzed in parallel per refill of a [`RecordIter`]
+const ITER_CHUNK_GROUPS: usize = 1024;
+
+/// Spilled countries still to be analyzed by a [`RecordIter`]
+struct CountryQueue {
+    partitions: CountryPartitions,
+    pending: std::vec::IntoIter<String>,
+    sample_size: Option<usize>,
+}
+
+/// Iterator over analyzed records returned by [`process_data_iter`].
+///
+/// With `ExecutionStrategy::PerCountry` the groups are read from the country spill files one
+/// batch of countries (one per worker) at a time, and each batch's records are yielded as soon
+/// as it is analyzed.
+pub struct RecordIter {
+    groups: std::iter::Enumerate<std::vec::IntoIter<(GroupKey, Vec<f64>)>>,
+    countries: Option<CountryQueue>,
+    config: TransformConfig,
+    sampled: HashSet<usize>,
+    /// Groups self-checked by the per-country analysis
+    self_checked: usize,
+    pool: Option<rayon::ThreadPool>,
+    extras: ExtrasMap,
+    /// File names of the inputs, indexed like `GroupExtras::inputs`
+    sources: Vec<String>,
+    seasonal: SeasonalTable,
+    buffer: VecDeque<Record>,
+    records: usize,
+    stats: ProcessingStats,
+    finished: bool,
+}
+
+impl RecordIter {
+    /// Statistics collected so far; complete once the iterator is exhausted.
+    pub fn stats(&self) -> &ProcessingStats {
+        &self.stats
+    }
+
+    /// Consumes the iterator, returning the collected statistics.
+    pub fn into_stats(self) -> ProcessingStats {
+        self.stats
+    }
+}
+
+impl Iterator for RecordIter {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mut record) = self.buffer.pop_front() {
+                if uses_extras(&self.config) {
+                    attach_extras(&mut record, &self.extras, &self.sources, &self.config);
+                }
+                return Some(Ok(record));
+            }
+            if self.finished {
+                return None;
+            }
+            if let Some(queue) = &mut self.countries {
+                let workers = self
+                    .pool
+                    .as_ref()
+                    .map_or_else(rayon::current_num_threads, |pool| {
+                        pool.current_num_threads()
+                    });
+                let batch: Vec<String> = queue.pending.by_ref().take(workers).collect();
+                if !batch.is_empty() {
+                    let started = Instant::now();
+                    let (config, seasonal) = (&self.config, &self.seasonal);
+                    let (partitions, sample_size) = (&queue.partitions, queue.sample_size);
+                    let analyzed = strategy::install(self.pool.as_ref(), || {
+                        batch
+                            .par_iter()
+                            .map(|country| {
+                                analyze_country(partitions, country, sample_size, config, seasonal)
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    });
+                    self.stats.analyze_secs += started.elapsed().as_secs_f64();
+                    match analyzed {
+                        Ok(analyzed) => {
+                            for analysis in analyzed {
+                                add_country_stats(&mut self.stats, &analysis, &self.config);
+                                self.self_checked += analysis.self_checked;
+                                self.records += analysis
+                                    .records
+                                    .iter()
+                                    .filter(|record| record.unit == self.config.unit)
+                                    .count();
+                                self.buffer.extend(analysis.records);
+                            }
+                        }
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            let chunk: Vec<_> = self.groups.by_ref().take(ITER_CHUNK_GROUPS).collect();
+            if chunk.is_empty() {
+                self.finished = true;
+                finish_analysis(
+                    &mut self.stats,
+                    self.records,
+                    self.sampled.len() + self.self_checked,
+                    &self.config,
+                );
+                return None;
+            }
+
+            let started = Instant::now();
+            let (config, sampled, seasonal) = (&self.config, &self.sampled, &self.seasonal);
+            let analyzed = strategy::install(self.pool.as_ref(), || {
+                chunk
+                    .into_par_iter()
+                    .map(|(index, (key, temps))| {
+                        analyze_group(key, temps, config, seasonal, sampled.contains(&index))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            });
+            self.stats.analyze_secs += started.elapsed().as_secs_f64();
+            match analyzed {
+                Ok(analyzed) => {
+                    for (records, outliers) in analyzed {
+                        count_outliers(&mut self.stats, outliers, &self.config);
+                        self.records += records
+                            .iter()
+                            .filter(|record| record.unit == self.config.unit)
+                            .count();
+                        self.buffer.extend(records);
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
                 }
             }
         }
     }
+}
 
-    println!(
-        "Processed {} total rows, {} matched filters",
-        total_rows, filtered_rows
-    );
+/// Row scan feeding the cleaned readings to a callback, see [`scan_inputs`]
+type Scan<'a> = dyn FnOnce(&mut ProcessingStats, &mut GroupEmit<'_>) -> Result<()> + Send + 'a;
+
+/// Callback receiving readings of one group, in input order for that group
+type GroupEmit<'a> = dyn FnMut(GroupKey, &[f64]) -> Result<()> + 'a;
+
+/// Analyzes all groups held in memory at once.
+fn analyze_in_memory(
+    scan: Box<Scan<'_>>,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(Vec<Record>, usize)> {
+    let mut monthly_data: HashMap<GroupKey, Vec<f64>> = HashMap::new();
+    scan(stats, &mut |key, temps| {
+        monthly_data
+            .entry(key)
+            .or_default()
+            .extend_from_slice(temps);
+        Ok(())
+    })?;
     debug!(
         "Found {} unique country-month combinations",
         monthly_data.len()
     );
+    (stats.group_map_bytes, stats.group_buffer_bytes) = memory::group_map_bytes(&monthly_data);
+    log_threshold(config);
+    let seasonal = seasonal_table(config, &monthly_data);
+
+    // Transform data with comprehensive statistics (parallelized)
+    status!("Starting statistical analysis");
+    let entries: Vec<_> = monthly_data.into_iter().collect();
+    let sampled = config
+        .self_check
+        .map(|sample_size| check::sample_groups(entries.len(), sample_size))
+        .unwrap_or_default();
+    let records = analyze_entries(entries, &sampled, config, &seasonal, stats)?;
+    Ok((records, sampled.len()))
+}
+
+/// Partitions readings into temporary files and analyzes one partition at a time.
+///
+/// Only the groups of a single partition are held in memory; the self-check sample is
+/// spread evenly over the partitions.
+fn analyze_spilled(
+    scan: Box<Scan<'_>>,
+    plan: &ExecutionPlan,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(Vec<Record>, usize)> {
+    let mut partitions = SpillPartitions::create(plan.partitions)?;
+    let mut seasonal = SeasonalTable::default();
+    let by_season = uses_seasonal_bounds(config);
+    scan(stats, &mut |key, temps| {
+        for &temp in temps {
+            if by_season {
+                seasonal.add(&key, temp);
+            }
+            partitions.push(&key, temp)?;
+        }
+        Ok(())
+    })?;
+    partitions.finish_writing()?;
+    log_threshold(config);
+
+    status!(
+        "Starting statistical analysis of {} spill partitions",
+        partitions.len()
+    );
+    let mut records = Vec::new();
+    let mut self_checked = 0;
+    for index in 0..partitions.len() {
+        let groups = partitions.read_partition(index)?;
+        let (map_bytes, buffer_bytes) = memory::group_map_bytes(&groups);
+        stats.group_map_bytes = stats.group_map_bytes.max(map_bytes);
+        stats.group_buffer_bytes = stats.group_buffer_bytes.max(buffer_bytes);
+
+        let entries: Vec<_> = groups.into_iter().collect();
+        let sampled = config
+            .self_check
+            .map(|sample_size| {
+                check::sample_groups(entries.len(), sample_size.div_ceil(partitions.len()))
+            })
+            .unwrap_or_default();
+        self_checked += sampled.len();
+        records.extend(analyze_entries(
+            entries, &sampled, config, &seasonal, stats,
+        )?);
+    }
+    Ok((records, self_checked))
+}
+
+/// Spills readings to one file per country and analyzes the countries in parallel, one
+/// country per worker.
+///
+/// Each worker holds the groups of a single country (or, for countries over
+/// `config.country_memory_limit`, of one pass over it) at a time, so a dense country cannot
+/// inflate the memory of the others.
+fn analyze_by_country(
+    scan: Box<Scan<'_>>,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(Vec<Record>, usize)> {
+    let (partitions, seasonal) = partition_by_country(scan, config, stats)?;
+    log_threshold(config);
+
+    let countries = partitions.countries();
+    status!(
+        "Starting statistical analysis of {} countries",
+        countries.len()
+    );
+    let sample_size = country_sample_size(config, countries.len());
+    let analyzed = countries
+        .par_iter()
+        .map(|country| analyze_country(&partitions, country, sample_size, config, &seasonal))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut records = Vec::new();
+    let mut self_checked = 0;
+    for analysis in analyzed {
+        add_country_stats(stats, &analysis, config);
+        self_checked += analysis.self_checked;
+        records.extend(analysis.records);
+    }
+    Ok((records, self_checked))
+}
+
+/// Runs the scan into per-country spill files, collecting the seasonal table if needed.
+fn partition_by_country(
+    scan: Box<Scan<'_>>,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(CountryPartitions, SeasonalTable)> {
+    let mut partitions = CountryPartitions::create()?;
+    let mut seasonal = SeasonalTable::default();
+    let by_season = uses_seasonal_bounds(config);
+    scan(stats, &mut |key, temps| {
+        for &temp in temps {
+            if by_season {
+                seasonal.add(&key, temp);
+            }
+            partitions.push(&key, temp)?;
+        }
+        Ok(())
+    })?;
+    partitions.finish_writing()?;
+    Ok((partitions, seasonal))
+}
+
+/// Self-check sample of each country, spreading `config.self_check` evenly over them.
+fn country_sample_size(config: &TransformConfig, countries: usize) -> Option<usize> {
+    config
+        .self_check
+        .map(|sample_size| sample_size.div_ceil(countries.max(1)))
+}
+
+/// Records and counters of one country analyzed by [`analyze_country`]
+#[derive(Default)]
+struct CountryAnalysis {
+    records: Vec<Record>,
+    groups: usize,
+    outliers: usize,
+    self_checked: usize,
+    map_bytes: usize,
+    buffer_bytes: usize,
+}
+
+/// Analyzes the groups of one spilled country on the calling worker, in as many passes as
+/// `config.country_memory_limit` requires.
+///
+/// The records are returned in result order.
+fn analyze_country(
+    partitions: &CountryPartitions,
+    country: &str,
+    sample_size: Option<usize>,
+    config: &TransformConfig,
+    seasonal: &SeasonalTable,
+) -> Result<CountryAnalysis> {
+    let passes = partitions.passes(country, config.country_memory_limit);
+    if passes > 1 {
+        debug!("Analyzing {} in {} passes", country, passes);
+    }
+    let mut analysis = CountryAnalysis::default();
+    for pass in 0..passes {
+        let groups = partitions.read_pass(country, pass, passes)?;
+        let (map_bytes, buffer_bytes) = memory::group_map_bytes(&groups);
+        analysis.map_bytes = analysis.map_bytes.max(map_bytes);
+        analysis.buffer_bytes = analysis.buffer_bytes.max(buffer_bytes);
+
+        let mut entries: Vec<_> = groups
+            .into_iter()
+            .filter(|(_, temps)| !temps.is_empty())
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let sampled = sample_size
+            .map(|sample_size| check::sample_groups(entries.len(), sample_size.div_ceil(passes)))
+            .unwrap_or_default();
+        analysis.self_checked += sampled.len();
+        analysis.groups += entries.len();
+        for (index, (key, temps)) in entries.into_iter().enumerate() {
+            let (records, outliers) =
+                analyze_group(key, temps, config, seasonal, sampled.contains(&index))?;
+            analysis.outliers += outliers;
+            analysis.records.extend(records);
+        }
+    }
+    if passes > 1 {
+        analysis.records.sort_by(record_order);
+    }
+    Ok(analysis)
+}
+
+/// Adds the group, outlier, and memory counters of an analyzed country to `stats`.
+fn add_country_stats(
+    stats: &mut ProcessingStats,
+    analysis: &CountryAnalysis,
+    config: &TransformConfig,
+) {
+    stats.groups += analysis.groups;
+    count_outliers(stats, analysis.outliers, config);
+    stats.group_map_bytes = stats.group_map_bytes.max(analysis.map_bytes);
+    stats.group_buffer_bytes = stats.group_buffer_bytes.max(analysis.buffer_bytes);
+}
+
+/// Analyzes groups in parallel, verifying those whose index is in `sampled`.
+///
+/// Group and outlier counts are added to `stats`.
+fn analyze_entries(
+    entries: Vec<(GroupKey, Vec<f64>)>,
+    sampled: &HashSet<usize>,
+    config: &TransformConfig,
+    seasonal: &SeasonalTable,
+    stats: &mut ProcessingStats,
+) -> Result<Vec<Record>> {
+    let analyzed: Vec<(Vec<Record>, usize)> = entries
+        .into_par_iter()
+        .enumerate()
+        .filter(|(_, (_, temps))| !temps.is_empty())
+        .map(|(index, (key, temps))| {
+            analyze_group(key, temps, config, seasonal, sampled.contains(&index))
+        })
+        .collect::<Result<_>>()?;
+
+    stats.groups += analyzed.len();
+    let outliers = analyzed.iter().map(|(_, outliers)| outliers).sum::<usize>();
+    count_outliers(stats, outliers, config);
+    Ok(analyzed
+        .into_iter()
+        .flat_map(|(records, _)| records)
+        .collect())
+}
+
+/// Whether outliers are judged against the same month of other years, which needs a
+/// [`SeasonalTable`] of all groups before any group is analyzed.
+fn uses_seasonal_bounds(config: &TransformConfig) -> bool {
+    config.threshold.is_some()
+        && matches!(
+            config.outlier_method,
+            OutlierMethod::Rolling | OutlierMethod::Climatological
+        )
+}
+
+/// Builds the seasonal table of `groups` if the configured outlier method needs one.
+fn seasonal_table(config: &TransformConfig, groups: &HashMap<GroupKey, Vec<f64>>) -> SeasonalTable {
+    if uses_seasonal_bounds(config) {
+        SeasonalTable::from_groups(groups)
+    } else {
+        SeasonalTable::default()
+    }
+}
+
+/// Adds the outliers found in analyzed groups to the counter of the configured action.
+fn count_outliers(stats: &mut ProcessingStats, outliers: usize, config: &TransformConfig) {
+    match config.outlier_action {
+        OutlierAction::Remove => stats.outliers_removed += outliers,
+        OutlierAction::Winsorize => stats.outliers_winsorized += outliers,
+    }
+}
 
+fn log_threshold(config: &TransformConfig) {
     if config.threshold.is_some() {
         debug!(
-            "Outlier detection enabled with threshold: {:?}",
-            config.threshold
+            "Outlier detection enabled with threshold: {:?} ({:?}, {:?})",
+            config.threshold, config.outlier_method, config.outlier_action
         );
     }
+}
 
-    // Transform data with comprehensive statistics (parallelized)
-    println!("Starting statistical analysis");
+/// Row scan over `inputs` that adds each reading's extras to `extras` before emitting it.
+fn input_scan<'a>(
+    inputs: &'a [InputSource],
+    countries: CountryFilter<'a>,
+    dates: DateFilter,
+    config: &'a TransformConfig,
+    extras: &'a mut ExtrasMap,
+) -> Box<Scan<'a>> {
+    Box::new(move |stats, emit| {
+        scan_inputs(inputs, countries, dates, config, stats, emit)
+            .map(|collected| *extras = collected)
+    })
+}
 
-    let entries: Vec<_> = monthly_data.into_iter().collect();
-    let mut results: Vec<Record> = entries
-        .into_par_iter()
-        .filter_map(|((country, year, month), temps)| {
-            if temps.is_empty() {
-                return None;
+/// Reads the input files and groups cleaned temperatures by `GroupKey`, together with the
+/// groups' extras (see [`collect_extras`]).
+///
+/// Row counters and data-quality warnings are recorded in `stats`.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_data`].
+fn extract_groups(
+    inputs: &[InputSource],
+    countries: CountryFilter<'_>,
+    dates: DateFilter,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(HashMap<GroupKey, Vec<f64>>, ExtrasMap)> {
+    let mut monthly_data: HashMap<GroupKey, Vec<f64>> = HashMap::new();
+    let extras = scan_inputs(
+        inputs,
+        countries,
+        dates,
+        config,
+        stats,
+        &mut |key, temps| {
+            monthly_data
+                .entry(key)
+                .or_default()
+                .extend_from_slice(temps);
+            Ok(())
+        },
+    )?;
+    debug!(
+        "Found {} unique country-month combinations",
+        monthly_data.len()
+    );
+    (stats.group_map_bytes, stats.group_buffer_bytes) = memory::group_map_bytes(&monthly_data);
+    Ok((monthly_data, extras))
+}
+
+/// Whether any opt-in value derived from the scan (aridity index, distinct days, source files)
+/// is computed.
+fn uses_extras(config: &TransformConfig) -> bool {
+    config.aridity || config.distinct_days || config.provenance
+}
+
+/// File name of each input, for the records' `source_file`.
+fn source_names(inputs: &[InputSource]) -> Vec<String> {
+    inputs
+        .iter()
+        .map(|input| {
+            input.path.file_name().map_or_else(
+                || input.path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Adds a reading's precipitation and date to the extras of its group.
+fn collect_extras(extras: &mut ExtrasMap, reading: &Reading, config: &TransformConfig) {
+    if !uses_extras(config) {
+        return;
+    }
+    let group = extras.entry(reading.key.clone()).or_default();
+    if let Some(precip) = reading.precip {
+        group.climate.add(reading.temp, precip);
+    }
+    if config.distinct_days {
+        group.days.insert(reading.date);
+    }
+    if config.provenance {
+        group.inputs.extend(reading.inputs);
+    }
+}
+
+/// Sets a record's aridity index, distinct day count, and source files from the extras of its
+/// group; `sources` are the file names of the inputs.
+fn attach_extras(
+    record: &mut Record,
+    extras: &ExtrasMap,
+    sources: &[String],
+    config: &TransformConfig,
+) {
+    let key = GroupKey {
+        country: record.country.clone(),
+        year: record.year,
+        month: record.month,
+        day: record.day.unwrap_or(0),
+        enso_phase: record.enso_phase,
+        season: record.season,
+    };
+    let group = extras.get(&key);
+    if config.aridity {
+        record.aridity_index = group.and_then(|group| group.climate.aridity_index());
+    }
+    if config.distinct_days {
+        record.distinct_days = Some(group.map_or(0, |group| group.days.len() as u32));
+    }
+    if config.provenance {
+        record.source_file = group.map(|group| {
+            group
+                .inputs
+                .iter()
+                .map(|&input| sources[input].as_str())
+                .collect::<Vec<_>>()
+                .join(";")
+        });
+    }
+}
+
+/// One cleaned reading produced by [`scan_rows`]
+struct Reading<'a> {
+    key: GroupKey,
+    country: &'a str,
+    date: NaiveDate,
+    /// Temperature in Celsius
+    temp: f64,
+    /// Precipitation, read when `config.aridity` is set and the value is present
+    precip: Option<f64>,
+    /// Indices of the inputs the reading was read from (several for collapsed days), set by
+    /// [`scan_inputs`]
+    inputs: &'a [usize],
+}
+
+/// Readings of one country and date, collapsed by `--daily-collapse`
+#[derive(Default)]
+struct DayReadings {
+    temps: Vec<f64>,
+    precip_sum: f64,
+    precip_count: usize,
+    /// Indices of the inputs with readings of the day, in scan order
+    inputs: Vec<usize>,
+}
+
+impl DayReadings {
+    fn add(&mut self, temp: f64, precip: Option<f64>, input: usize) {
+        self.temps.push(temp);
+        if !self.inputs.contains(&input) {
+            self.inputs.push(input);
+        }
+        if let Some(precip) = precip {
+            self.precip_sum += precip;
+            self.precip_count += 1;
+        }
+    }
+
+    /// Collapses the day to a single temperature and (mean) precipitation.
+    fn collapse(self, mode: DailyCollapse) -> (f64, Option<f64>) {
+        let temp = match mode {
+            DailyCollapse::Median => calculate_median(&self.temps),
+            DailyCollapse::Mean | DailyCollapse::None => {
+                self.temps.iter().sum::<f64>() / self.temps.len() as f64
             }
+        };
+        let precip = (self.precip_count > 0).then(|| self.precip_sum / self.precip_count as f64);
+        (temp, precip)
+    }
+}
 
-            // Apply outlier detection if enabled
-            let cleaned_temps = if let Some(threshold) = config.threshold {
-                let original_count = temps.len();
-                let cleaned = remove_outliers(&temps, threshold);
-                let removed = original_count - cleaned.len();
-                if removed > 0 {
-                    debug!(
-                        "Removed {} outliers for {}/{}/{}",
-                        removed, country, year, month
-                    );
-                }
-                cleaned
+/// Scans every input with [`scan_rows`], passing each reading's temperature to `emit`, and
+/// returns the extras of the groups (see [`collect_extras`]).
+///
+/// With `config.daily_collapse` the readings of each country and date (across all inputs) are
+/// first collapsed to one value, which holds every matched reading in memory until the inputs
+/// are read. Without extras to collect, collapsing, or row hooks, the inputs are read with
+/// [`scan_rows_grouped`] instead, which decodes Parquet row groups in parallel.
+///
+/// # Errors
+///
+/// Returns the same errors as [`scan_rows`].
+fn scan_inputs(
+    inputs: &[InputSource],
+    countries: CountryFilter<'_>,
+    dates: DateFilter,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+    emit: &mut GroupEmit<'_>,
+) -> Result<ExtrasMap> {
+    let _span = profile::span("extract", SpanKind::Cpu);
+    check_countries(inputs, countries)?;
+    let mut extras = ExtrasMap::new();
+    let per_reading = uses_extras(config) || has_row_hooks(config);
+    if config.daily_collapse == DailyCollapse::None && !per_reading {
+        for input in inputs {
+            scan_rows_grouped(input, countries, dates, config, stats, emit)?;
+        }
+        note_interrupt(stats);
+        return Ok(extras);
+    }
+    if config.daily_collapse == DailyCollapse::None {
+        for (index, input) in inputs.iter().enumerate() {
+            scan_rows(input, countries, dates, config, stats, &mut |reading| {
+                let reading = Reading {
+                    inputs: std::slice::from_ref(&index),
+                    ..reading
+                };
+                collect_extras(&mut extras, &reading, config);
+                emit(reading.key, std::slice::from_ref(&reading.temp))
+            })?;
+        }
+        note_interrupt(stats);
+        return Ok(extras);
+    }
+
+    let mut days: HashMap<(GroupKey, String, NaiveDate), DayReadings> = HashMap::new();
+    let mut readings = 0;
+    for (index, input) in inputs.iter().enumerate() {
+        scan_rows(input, countries, dates, config, stats, &mut |reading| {
+            readings += 1;
+            days.entry((reading.key, reading.country.to_string(), reading.date))
+                .or_default()
+                .add(reading.temp, reading.precip, index);
+            Ok(())
+        })?;
+    }
+    note_interrupt(stats);
+    debug!(
+        "Collapsed {} readings to {} country-days ({:?})",
+        readings,
+        days.len(),
+        config.daily_collapse
+    );
+    // Sorted so that group readings (and thus floating-point sums) do not depend on hash order
+    let mut days: Vec<_> = days.into_iter().collect();
+    days.sort_by(|a, b| a.0.cmp(&b.0));
+    for ((key, country, date), mut day) in days {
+        let inputs = std::mem::take(&mut day.inputs);
+        let (temp, precip) = day.collapse(config.daily_collapse);
+        let reading = Reading {
+            key,
+            country: &country,
+            date,
+            temp,
+            precip,
+            inputs: &inputs,
+        };
+        collect_extras(&mut extras, &reading, config);
+        emit(reading.key, &[temp])?;
+    }
+    Ok(extras)
+}
+
+/// Marks `stats` as interrupted if a signal stopped the scan.
+fn note_interrupt(stats: &mut ProcessingStats) {
+    if interrupt::requested() {
+        stats.interrupted = true;
+        stats.warnings.push(Warning::new(
+            None,
+            format!(
+                "Interrupted after {} rows: the groups aggregated so far are written, the last ones may be incomplete",
+                stats.total_rows
+            ),
+        ));
+    }
+}
+
+/// Groups, row counters, and row groups of a chunk decoded by [`scan_rows_grouped`]
+type DecodedChunk = (HashMap<GroupKey, Vec<f64>>, RowCounts, RowGroups);
+
+/// Grouping and cleaning settings of one input's scan, shared by all of its batches
+struct RowScan<'a> {
+    file_path: &'a Path,
+    columns: &'a ColumnMapping,
+    countries: CountryFilter<'a>,
+    dates: DateFilter,
+    config: &'a TransformConfig,
+    /// Plausible readings in Celsius, see [`valid_bounds`]
+    bounds: (f64, f64),
+    by_country: bool,
+    by_year: bool,
+    by_month: bool,
+    by_season: bool,
+    by_day: bool,
+    /// Whether seasons are counted per year, so the seasons cut by the year range are dropped
+    /// instead of being reported from part of their months
+    complete_seasons: bool,
+    /// Whether groups carry their ENSO phase: when grouping by phase, or by year and month,
+    /// whose groups all lie within a single phase. Otherwise the phase is collapsed like any
+    /// other dimension left out of the grouping.
+    tag_phase: bool,
+    /// Country of the groups when countries are collapsed
+    aggregate_label: String,
+}
+
+/// Row counters of a scan, added to `ProcessingStats` once an input is read
+#[derive(Debug, Default)]
+struct RowCounts {
+    total: usize,
+    filtered: usize,
+    skipped: usize,
+    invalid_temps: usize,
+    hook_dropped: usize,
+    hook_rejected: usize,
+    partial_seasons: usize,
+}
+
+impl RowCounts {
+    fn add(&mut self, other: &RowCounts) {
+        self.total += other.total;
+        self.filtered += other.filtered;
+        self.skipped += other.skipped;
+        self.invalid_temps += other.invalid_temps;
+        self.hook_dropped += other.hook_dropped;
+        self.hook_rejected += other.hook_rejected;
+        self.partial_seasons += other.partial_seasons;
+    }
+}
+
+impl<'a> RowScan<'a> {
+    /// Validates the grouping and outlier settings for a scan of `input`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PipelineError::Data` if the grouping dimensions, outlier method, or valid
+    /// temperature range are inconsistent.
+    fn new(
+        input: &'a InputSource,
+        countries: CountryFilter<'a>,
+        dates: DateFilter,
+        config: &'a TransformConfig,
+    ) -> Result<Self> {
+        let group_by = config.dimensions();
+        let by_country = !config.aggregate && group_by.contains(&GroupDimension::Country);
+        let by_year = group_by.contains(&GroupDimension::Year);
+        let by_month = group_by.contains(&GroupDimension::Month);
+        let by_phase = group_by.contains(&GroupDimension::EnsoPhase);
+        let by_season = group_by.contains(&GroupDimension::Season);
+        let by_day = group_by.contains(&GroupDimension::Day);
+        if by_day && !by_month {
+            return Err(PipelineError::Data(
+                "Grouping by day requires grouping by month".to_string(),
+            ));
+        }
+        if by_phase && config.enso.is_none() {
+            return Err(PipelineError::Data(
+                "Grouping by enso_phase requires an ENSO table".to_string(),
+            ));
+        }
+        if uses_seasonal_bounds(config)
+            && config.outlier_method == OutlierMethod::Rolling
+            && !(by_year && by_month)
+        {
+            return Err(PipelineError::Data(
+                "The rolling outlier method requires grouping by year and month".to_string(),
+            ));
+        }
+        if uses_seasonal_bounds(config)
+            && config.outlier_method == OutlierMethod::Climatological
+            && !by_month
+        {
+            return Err(PipelineError::Data(
+                "The climatological outlier method requires grouping by month".to_string(),
+            ));
+        }
+        if config.outlier_method == OutlierMethod::Grubbs
+            && config
+                .threshold
+                .is_some_and(|alpha| !(alpha > 0.0 && alpha < 1.0))
+        {
+            return Err(PipelineError::Data(
+                "With the Grubbs outlier method the threshold is a significance level in (0, 1)"
+                    .to_string(),
+            ));
+        }
+        Ok(Self {
+            file_path: input.path.as_path(),
+            columns: &input.columns,
+            countries,
+            dates,
+            config,
+            bounds: valid_bounds(config)?,
+            by_country,
+            by_year,
+            by_month,
+            by_season,
+            by_day,
+            complete_seasons: by_season && by_year && !by_month,
+            tag_phase: config.enso.is_some() && (by_phase || (by_year && by_month)),
+            aggregate_label: if countries.is_all() {
+                "ALL".to_string()
+            } else if countries.include.is_empty() {
+                format!("ALL-EXCEPT-{}", countries.exclude.join(","))
             } else {
-                temps
+                let kept: Vec<&str> = countries
+                    .include
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|&country| countries.contains(country))
+                    .collect();
+                kept.join(",")
+            },
+        })
+    }
+
+    /// Plans the decoding of `input`, recording the row groups skipped by their statistics.
+    fn plan(&self, input: &'a InputSource, stats: &mut ProcessingStats) -> Result<BatchPlan<'a>> {
+        debug!("Reading Parquet file: {}", self.file_path.display());
+        let plan = plan_batches(input, self.config, self.countries, self.dates)?;
+        if plan.pruned > 0 {
+            status!(
+                "Skipped {} row groups ({} rows) outside the filters by their date or country statistics",
+                plan.pruned,
+                plan.pruned_rows
+            );
+            stats.pruned_row_groups += plan.pruned;
+            stats.pruned_rows += plan.pruned_rows;
+        }
+        Ok(plan)
+    }
+
+    /// Reads every batch of `plan` in order, passing each cleaned reading to `emit`.
+    fn read_all(
+        &self,
+        plan: &BatchPlan,
+        counts: &mut RowCounts,
+        emit: &mut dyn FnMut(Reading<'_>) -> Result<()>,
+    ) -> Result<RowGroups> {
+        let (reader, row_groups) = plan.open()?;
+        status!("Starting data extraction from {} batches", plan.format());
+        let batches = profile::timed_iter("read_decode", SpanKind::Io, reader);
+        for (batch_index, batch_result) in batches.enumerate() {
+            if interrupt::requested() {
+                break;
+            }
+            let batch = batch_result.map_err(PipelineError::Arrow)?;
+            self.scan_batch(&batch, batch_index, &row_groups, counts, emit)?;
+        }
+        Ok(row_groups)
+    }
+
+    /// Cleans and groups the rows of one batch, passing each reading to `emit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_index` - Index of the batch in its reader, reported by `config.strict`
+    /// * `row_groups` - Row groups of the batch's reader, locating rows reported by
+    ///   `config.strict`
+    /// * `counts` - Row counters, whose `total` is the number of rows read before the batch
+    fn scan_batch(
+        &self,
+        batch: &RecordBatch,
+        batch_index: usize,
+        row_groups: &RowGroups,
+        counts: &mut RowCounts,
+        emit: &mut dyn FnMut(Reading<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let (file_path, columns, config) = (self.file_path, self.columns, self.config);
+        let (countries, dates) = (self.countries, self.dates);
+        let date_col = get_date_column(batch, &columns.date, file_path)?;
+        let country_col = get_column_str(batch, &columns.country, file_path)?;
+        let temp_col = get_column_f64(batch, &columns.temp, file_path)?;
+        let precip_col = if config.aridity {
+            Some(get_column_f64(batch, &columns.precip, file_path)?)
+        } else {
+            None
+        };
+
+        let batch_offset = counts.total;
+        counts.total += batch.num_rows();
+
+        let selection = select_rows(&date_col, country_col, &temp_col, countries, dates);
+        let rows: Box<dyn Iterator<Item = usize>> = match &selection {
+            Some(selection) => Box::new(selection.rows.set_indices()),
+            None => Box::new(0..batch.num_rows()),
+        };
+        for i in rows {
+            let prefiltered = selection.as_ref().and_then(|s| s.date(i));
+            let row = match prefiltered {
+                Some(date) => Ok((date, country_col.value(i), temp_col.value(i))),
+                None => parse_row(&date_col, country_col, &temp_col, i),
+            };
+            let (date, country, temp) = match row {
+                Ok(row) => row,
+                Err(reason) if config.strict => {
+                    let (row_group, row) = row_groups.locate(batch_offset + i);
+                    return Err(PipelineError::BadRow {
+                        file: file_path.to_path_buf(),
+                        row_group,
+                        batch: batch_index,
+                        row,
+                        reason,
+                    });
+                }
+                Err(_) => {
+                    counts.skipped += 1;
+                    continue;
+                }
+            };
+            let year = date.year();
+            let month = date.month();
+
+            // Apply filters - rows selected by `select_rows` are known to match
+            let matched = prefiltered.is_some()
+                || (countries.contains(country) && dates.contains(year, month));
+            if !matched {
+                continue;
+            }
+            counts.filtered += 1;
+            if self.complete_seasons && dates.cuts_season(year, month) {
+                counts.partial_seasons += 1;
+                continue;
+            }
+            // Data validation and cleaning
+            let cleaned_temp = clean_temp(temp, self.bounds);
+            #[cfg(any(feature = "plugins", feature = "scripting"))]
+            let cleaned_temp = match cleaned_temp {
+                Some(temp) => match row_hooks(country, date, temp, self.bounds, config)? {
+                    HookOutcome::Kept(temp) => Some(temp),
+                    HookOutcome::Dropped => {
+                        counts.hook_dropped += 1;
+                        continue;
+                    }
+                    HookOutcome::Rejected => {
+                        counts.hook_rejected += 1;
+                        continue;
+                    }
+                },
+                None => None,
             };
+            let Some(valid_temp) = cleaned_temp else {
+                counts.invalid_temps += 1;
+                continue;
+            };
+            let key = GroupKey {
+                country: if self.by_country {
+                    country.to_string()
+                } else {
+                    self.aggregate_label.clone()
+                },
+                year: match (self.by_year, self.by_season && !self.by_month) {
+                    (false, _) => 0,
+                    (true, true) => season_year(year, month),
+                    (true, false) => year,
+                },
+                month: if self.by_month { month } else { 0 },
+                day: if self.by_day { date.day() } else { 0 },
+                enso_phase: config
+                    .enso
+                    .as_ref()
+                    .filter(|_| self.tag_phase)
+                    .map(|table| table.phase(year, month)),
+                season: self
+                    .by_season
+                    .then(|| config.hemisphere.season(country, month)),
+            };
+            // Negative or missing precipitation leaves the day out of the totals
+            let precip = precip_col
+                .as_deref()
+                .filter(|col| col.is_valid(i))
+                .map(|col| col.value(i))
+                .filter(|precip| *precip >= 0.0);
+            emit(Reading {
+                key,
+                country,
+                date,
+                temp: valid_temp,
+                precip,
+                inputs: &[],
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Adds the counters of the finished scan to `stats` and raises its data-quality warnings.
+    ///
+    /// `row_groups` are the row groups of every reader of the input, `started` is when the scan
+    /// began.
+    fn finish(
+        &self,
+        counts: RowCounts,
+        row_groups: &[RowGroups],
+        stats: &mut ProcessingStats,
+        started: Instant,
+    ) {
+        let file_path = self.file_path;
+        status!(
+            "Processed {} total rows, {} matched filters",
+            counts.total,
+            counts.filtered
+        );
+        let row_filtered: usize = row_groups.iter().map(RowGroups::row_filtered).sum();
+        if row_filtered > 0 {
+            debug!(
+                "{} rows outside the filters dropped while decoding",
+                row_filtered
+            );
+            stats.row_filtered_rows += row_filtered;
+        }
+        stats.bytes_read += row_groups.iter().map(|groups| groups.bytes).sum::<u64>();
+        stats.total_rows += counts.total;
+        stats.filtered_rows += counts.filtered;
+        stats.skipped_rows += counts.skipped;
+        stats.invalid_temps += counts.invalid_temps;
+        if counts.hook_dropped > 0 {
+            status!(
+                "{} readings dropped by the plugin or row script",
+                counts.hook_dropped
+            );
+            stats.hook_dropped += counts.hook_dropped;
+        }
+        if counts.hook_rejected > 0 {
+            stats.warnings.push(Warning::new(
+                Some(file_path),
+                format!(
+                    "{} readings rejected because the plugin or row script returned a \
+                     non-finite temperature or one outside {}..{}°C",
+                    counts.hook_rejected,
+                    (self.bounds.0 * 100.0).round() / 100.0,
+                    (self.bounds.1 * 100.0).round() / 100.0
+                ),
+            ));
+            stats.hook_rejected += counts.hook_rejected;
+        }
+        if counts.partial_seasons > 0 {
+            status!(
+                "{} readings left out of the seasons that begin before --start-year or end after \
+                 --end-year",
+                counts.partial_seasons
+            );
+            stats.partial_seasons += counts.partial_seasons;
+        }
+        let (total, skipped) = (counts.total, counts.skipped);
+        if total > 0 && skipped as f64 / total as f64 > WARNING_FRACTION {
+            stats.warnings.push(Warning::new(
+                Some(file_path),
+                format!(
+                    "{:.1}% of rows had nulls or unparsable dates ({} rows skipped)",
+                    skipped as f64 / total as f64 * 100.0,
+                    skipped
+                ),
+            ));
+        }
+        let (filtered, invalid) = (counts.filtered, counts.invalid_temps);
+        if filtered > 0 && invalid as f64 / filtered as f64 > WARNING_FRACTION {
+            stats.warnings.push(Warning::new(
+                Some(file_path),
+                format!(
+                    "{:.1}% of matched rows had temperatures outside {}..{}°C ({} rows rejected)",
+                    invalid as f64 / filtered as f64 * 100.0,
+                    (self.bounds.0 * 100.0).round() / 100.0,
+                    (self.bounds.1 * 100.0).round() / 100.0,
+                    invalid
+                ),
+            ));
+        }
+        stats.extract_secs += started.elapsed().as_secs_f64();
+    }
+}
+
+/// Reads one input file and passes every cleaned reading to `emit`.
+///
+/// Row counters are added to `stats`; data-quality warnings are raised per file.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_data`], plus any error returned by `emit`.
+fn scan_rows(
+    input: &InputSource,
+    countries: CountryFilter<'_>,
+    dates: DateFilter,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+    emit: &mut dyn FnMut(Reading<'_>) -> Result<()>,
+) -> Result<()> {
+    let started = Instant::now();
+    let scan = RowScan::new(input, countries, dates, config)?;
+    let plan = scan.plan(input, stats)?;
+    let mut counts = RowCounts::default();
+    let row_groups = scan.read_all(&plan, &mut counts, emit)?;
+    scan.finish(counts, &[row_groups], stats, started);
+    Ok(())
+}
+
+/// Reads one input file like [`scan_rows`], passing the cleaned temperatures to `emit` group
+/// by group.
+///
+/// The Parquet row groups to decode are read on the rayon worker threads, one row group per
+/// worker at a time, and grouped into a hash map per row group. The maps of each round of
+/// row groups are then merged in file order, so every group receives its readings in the
+/// same order as a sequential scan and the results do not depend on the thread count. Inputs
+/// with a single row group to decode, CSV and NDJSON inputs, single-threaded runs, and
+/// `config.strict` (which locates bad rows by batch) are read sequentially.
+///
+/// # Errors
+///
+/// Returns the same errors as [`scan_rows`]; of several failing row groups, the first in file
+/// order is reported.
+fn scan_rows_grouped(
+    input: &InputSource,
+    countries: CountryFilter<'_>,
+    dates: DateFilter,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+    emit: &mut GroupEmit<'_>,
+) -> Result<()> {
+    let started = Instant::now();
+    let scan = RowScan::new(input, countries, dates, config)?;
+    let plan = scan.plan(input, stats)?;
+    let workers = rayon::current_num_threads();
+    let chunks = plan.chunks();
+    let mut counts = RowCounts::default();
+    if chunks < 2 || workers < 2 || config.strict {
+        let row_groups = scan.read_all(&plan, &mut counts, &mut |reading| {
+            emit(reading.key, std::slice::from_ref(&reading.temp))
+        })?;
+        scan.finish(counts, &[row_groups], stats, started);
+        return Ok(());
+    }
+
+    status!(
+        "Starting data extraction from {} Parquet row groups on {} threads",
+        chunks,
+        workers
+    );
+    let mut row_groups = Vec::with_capacity(chunks);
+    for round in (0..chunks).step_by(workers) {
+        if interrupt::requested() {
+            break;
+        }
+        let waiting = profile::span("wait_workers", SpanKind::Wait);
+        let decoded: Vec<Result<DecodedChunk>> = (round..chunks.min(round + workers))
+            .into_par_iter()
+            .map(|chunk| {
+                let _span = profile::span("decode_row_group", SpanKind::Cpu);
+                let (reader, chunk_groups) = plan.open_chunks(chunk..chunk + 1)?;
+                let mut groups: HashMap<GroupKey, Vec<f64>> = HashMap::new();
+                let mut chunk_counts = RowCounts::default();
+                let batches = profile::timed_iter("read_decode", SpanKind::Io, reader);
+                for (batch_index, batch_result) in batches.enumerate() {
+                    if interrupt::requested() {
+                        break;
+                    }
+                    let batch = batch_result.map_err(PipelineError::Arrow)?;
+                    scan.scan_batch(
+                        &batch,
+                        batch_index,
+                        &chunk_groups,
+                        &mut chunk_counts,
+                        &mut |reading| {
+                            groups.entry(reading.key).or_default().push(reading.temp);
+                            Ok(())
+                        },
+                    )?;
+                }
+                Ok((groups, chunk_counts, chunk_groups))
+            })
+            .collect();
+        drop(waiting);
+        for result in decoded {
+            let (groups, chunk_counts, chunk_groups) = result?;
+            counts.add(&chunk_counts);
+            row_groups.push(chunk_groups);
+            for (key, temps) in groups {
+                emit(key, &temps)?;
+            }
+        }
+    }
+    scan.finish(counts, &row_groups, stats, started);
+    Ok(())
+}
+
+/// Whether a plugin's `transform_row` or a row script runs on every reading.
+fn has_row_hooks(config: &TransformConfig) -> bool {
+    #[cfg(feature = "plugins")]
+    if config
+        .plugin
+        .as_ref()
+        .is_some_and(|plugin| plugin.has_transform_row())
+    {
+        return true;
+    }
+    #[cfg(feature = "scripting")]
+    if config.row_script.is_some() {
+        return true;
+    }
+    let _ = config;
+    false
+}
+
+/// What the row hooks made of a reading
+#[cfg(any(feature = "plugins", feature = "scripting"))]
+enum HookOutcome {
+    /// Temperature to aggregate, within the valid range
+    Kept(f64),
+    /// A hook dropped the reading
+    Dropped,
+    /// A hook returned a non-finite temperature or one outside the valid range
+    Rejected,
+}
+
+/// Runs the plugin's `transform_row` and then the row script on a cleaned reading.
+///
+/// Every temperature a hook returns is validated against `bounds` like the input readings
+/// (see [`clean_temp`]), so hooks cannot smuggle implausible values into the aggregates.
+#[cfg(any(feature = "plugins", feature = "scripting"))]
+fn row_hooks(
+    country: &str,
+    date: NaiveDate,
+    temp: f64,
+    bounds: (f64, f64),
+    config: &TransformConfig,
+) -> Result<HookOutcome> {
+    let outcome = |value: Option<f64>| match value {
+        Some(value) => clean_temp(value, bounds).map_or(HookOutcome::Rejected, HookOutcome::Kept),
+        None => HookOutcome::Dropped,
+    };
+    let mut temp = temp;
+    #[cfg(feature = "plugins")]
+    if let Some(plugin) = &config.plugin
+        && plugin.has_transform_row()
+    {
+        match outcome(plugin.transform_row(country, date, temp)?) {
+            HookOutcome::Kept(value) => temp = value,
+            other => return Ok(other),
+        }
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &config.row_script {
+        match outcome(script.row(country, date, temp)?) {
+            HookOutcome::Kept(value) => temp = value,
+            other => return Ok(other),
+        }
+    }
+    Ok(HookOutcome::Kept(temp))
+}
+
+/// Rows of a batch chosen by vectorized filtering, see [`select_rows`]
+struct RowSelection {
+    /// Dates parsed by the Arrow cast kernel (null where unparsable)
+    dates: Date32Array,
+    /// Rows without nulls whose date parsed and whose country and year match the filters
+    matched: BooleanBuffer,
+    /// Rows to visit: the matched rows plus every row the kernels could not vouch for
+    rows: BooleanBuffer,
+}
+
+impl RowSelection {
+    /// Date of row `i` if it is a matched row, `None` if it needs the row-by-row checks.
+    fn date(&self, i: usize) -> Option<NaiveDate> {
+        if self.matched.value(i) {
+            self.dates.value_as_date(i)
+        } else {
+            None
+        }
+    }
+}
+
+/// Filters a batch by country and date with Arrow compute kernels.
+///
+/// Dates are parsed by the cast kernel and compared against the year range as day numbers,
+/// countries are compared against each listed and excluded code with the comparison kernels, and the masks are
+/// combined bitwise, so rows outside the filters are never visited one by one. Rows with nulls
+/// or dates the kernel could not parse (or did not parse strictly as `YYYY-MM-DD`) are kept for
+/// the row loop, which reports and counts them exactly as before.
+///
+/// # Returns
+///
+/// Returns `None` when a kernel does not support the batch, in which case every row goes
+/// through the row loop.
+fn select_rows(
+    date_col: &DateColumn,
+    country_col: &StringArray,
+    temp_col: &Float64Array,
+    countries: CountryFilter<'_>,
+    filter: DateFilter,
+) -> Option<RowSelection> {
+    let len = date_col.len();
+    let (dates, strict_dates) = date_col.days().ok()?;
+    let valid = |nulls: Option<&NullBuffer>| {
+        nulls.map_or_else(
+            || BooleanBuffer::new_set(len),
+            |nulls| nulls.inner().clone(),
+        )
+    };
+    let complete = &(&(&valid(dates.nulls()) & &valid(country_col.nulls()))
+        & &valid(temp_col.nulls()))
+        & &strict_dates;
+
+    let mut matched = &complete & &filter.mask(&dates).ok()?;
+    if !countries.is_all() {
+        matched = &matched & &countries.mask(country_col).ok()?;
+    }
+    let rows = &matched | &!&complete;
+    Some(RowSelection {
+        dates,
+        matched,
+        rows,
+    })
+}
+
+/// Date column of a batch
+pub(crate) enum DateColumn<'a> {
+    /// Strings formatted as `DATE_FORMAT`
+    Text(&'a StringArray),
+    /// `Date32`, `Date64`, or `Timestamp` values as days since 1970-01-01; timestamps are taken
+    /// at their UTC date
+    Days(Date32Array),
+}
+
+impl<'a> DateColumn<'a> {
+    /// Wraps a Utf8 or native date column, `None` for a column of any other type.
+    pub(crate) fn new(column: &'a ArrayRef) -> Option<Self> {
+        if let Some(strings) = column.as_any().downcast_ref::<StringArray>() {
+            return Some(DateColumn::Text(strings));
+        }
+        match column.data_type() {
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(..) => {
+                let days = arrow_cast::cast(column, &DataType::Date32).ok()?;
+                Some(DateColumn::Days(days.as_primitive::<Date32Type>().clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            DateColumn::Text(strings) => strings.len(),
+            DateColumn::Days(days) => days.len(),
+        }
+    }
+
+    fn is_null(&self, i: usize) -> bool {
+        match self {
+            DateColumn::Text(strings) => strings.is_null(i),
+            DateColumn::Days(days) => days.is_null(i),
+        }
+    }
+
+    /// Date of row `i` (which must not be null), or why it has none.
+    fn date(&self, i: usize) -> std::result::Result<NaiveDate, String> {
+        match self {
+            DateColumn::Text(strings) => {
+                let date_str = strings.value(i);
+                NaiveDate::parse_from_str(date_str, DATE_FORMAT)
+                    .map_err(|e| format!("unparsable date '{}': {}", date_str, e))
+            }
+            DateColumn::Days(days) => days.value_as_date(i).ok_or_else(|| {
+                format!(
+                    "date {} days from 1970-01-01 is out of range",
+                    days.value(i)
+                )
+            }),
+        }
+    }
+
+    /// Dates of every row (null where the cast kernel could not parse a string), with the rows
+    /// whose date can be trusted: strings of exactly `YYYY-MM-DD`, as the kernel accepts a few
+    /// more layouts than `DATE_FORMAT`, and every native date.
+    pub(crate) fn days(&self) -> std::result::Result<(Date32Array, BooleanBuffer), ArrowError> {
+        match self {
+            DateColumn::Text(strings) => {
+                let parsed = arrow_cast::cast(strings, &DataType::Date32)?;
+                let strict = strings
+                    .offsets()
+                    .windows(2)
+                    .map(|bounds| (bounds[1] - bounds[0]) as usize == DATE_FORMAT_LEN)
+                    .collect();
+                Ok((parsed.as_primitive::<Date32Type>().clone(), strict))
+            }
+            DateColumn::Days(days) => Ok((days.clone(), BooleanBuffer::new_set(days.len()))),
+        }
+    }
+}
+
+/// Length of a date formatted with `DATE_FORMAT` (`YYYY-MM-DD`)
+pub(crate) const DATE_FORMAT_LEN: usize = 10;
+
+/// 1970-01-01, day zero of `Date32` values
+pub(crate) const UNIX_EPOCH_DATE: NaiveDate = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
+/// Computes the records of one group: one in `config.unit`, followed by one per extra unit.
+///
+/// # Arguments
+///
+/// * `key` - Group identifiers
+/// * `temps` - Cleaned temperatures of the group in Celsius
+/// * `config` - Transform configuration (outlier threshold, units)
+/// * `seasonal` - Seasonal table of all groups, used by the rolling and climatological outlier
+///   methods
+/// * `verify` - Whether to verify the records against the self-check reference implementation
+///
+/// # Returns
+///
+/// Returns the records (none if outlier removal emptied the group) and the number of outliers
+/// removed or winsorized in `config.unit`.
+fn analyze_group(
+    key: GroupKey,
+    temps: Vec<f64>,
+    config: &TransformConfig,
+    seasonal: &SeasonalTable,
+    verify: bool,
+) -> Result<(Vec<Record>, usize)> {
+    let _span = profile::span("analyze_group", SpanKind::Cpu);
+    let seasonal = config
+        .threshold
+        .and_then(|threshold| match config.outlier_method {
+            OutlierMethod::Rolling => {
+                seasonal.bounds(&key, Some(config.outlier_window), threshold, config.std_dev)
+            }
+            OutlierMethod::Climatological => seasonal.bounds(&key, None, threshold, config.std_dev),
+            OutlierMethod::StdDev
+            | OutlierMethod::Grubbs
+            | OutlierMethod::Iqr
+            | OutlierMethod::Mad
+            | OutlierMethod::ZScoreModified => None,
+        });
+
+    let mut extra = Vec::with_capacity(config.extra_units.len());
+    for unit in &config.extra_units {
+        let scale = scale_for(*unit, &config.custom_scale);
+        let converted = temps.iter().map(|&t| scale.convert_celsius(t)).collect();
+        let bounds = seasonal.map(|bounds| bounds.convert(scale));
+        extra.extend(analyze_in_unit(key.clone(), converted, *unit, config, bounds, verify)?.0);
+    }
+
+    let scale = scale_for(config.unit, &config.custom_scale);
+    let mut temps = temps;
+    for temp in &mut temps {
+        *temp = scale.convert_celsius(*temp);
+    }
+    let bounds = seasonal.map(|bounds| bounds.convert(scale));
+    let (record, outliers) = analyze_in_unit(key, temps, config.unit, config, bounds, verify)?;
+    Ok((record.into_iter().chain(extra).collect(), outliers))
+}
+
+/// Applies outlier handling to one group and computes its record.
+///
+/// # Arguments
+///
+/// * `key` - Group identifiers
+/// * `temps` - Cleaned temperatures of the group in `unit`
+/// * `unit` - Unit the temperatures are expressed in
+/// * `config` - Transform configuration (outlier threshold, method, and action)
+/// * `seasonal` - Bounds of the rolling or climatological outlier method in `unit`, if they
+///   could be computed
+/// * `verify` - Whether to verify the record against the self-check reference implementation
+///
+/// # Returns
+///
+/// Returns the record (or `None` if outlier removal emptied the group) and the number of
+/// removed or winsorized outliers.
+fn analyze_in_unit(
+    key: GroupKey,
+    temps: Vec<f64>,
+    unit: TemperatureUnit,
+    config: &TransformConfig,
+    seasonal: Option<Bounds>,
+    verify: bool,
+) -> Result<(Option<Record>, usize)> {
+    let mut outliers = 0;
+
+    // Apply outlier detection if enabled
+    let bounds = config
+        .threshold
+        .and_then(|threshold| match config.outlier_method {
+            OutlierMethod::StdDev => outliers::std_dev_bounds(&temps, threshold, config.std_dev),
+            OutlierMethod::Grubbs => outliers::grubbs_bounds(&temps, threshold),
+            OutlierMethod::Iqr => outliers::iqr_bounds(&temps, threshold, config.quantile_method),
+            OutlierMethod::Mad => outliers::mad_bounds(&temps, threshold),
+            OutlierMethod::ZScoreModified => outliers::modified_zscore_bounds(&temps, threshold),
+            OutlierMethod::Rolling | OutlierMethod::Climatological => seasonal,
+        });
+    let cleaned_temps = if let Some(bounds) = bounds {
+        let original_count = temps.len();
+        let cleaned = match config.outlier_action {
+            OutlierAction::Remove => {
+                let cleaned = outliers::remove_outliers(&temps, bounds);
+                outliers = original_count - cleaned.len();
+                cleaned
+            }
+            OutlierAction::Winsorize => {
+                let (clipped, count) = outliers::winsorize(temps, bounds);
+                outliers = count;
+                clipped
+            }
+        };
+        if outliers > 0 {
+            debug!(
+                "{:?}: {} outliers for {}/{}/{}",
+                config.outlier_action, outliers, key.country, key.year, key.month
+            );
+        }
+        cleaned
+    } else {
+        temps
+    };
+
+    if cleaned_temps.is_empty() {
+        return Ok((None, outliers));
+    }
+
+    let celsius_key = config.keep_celsius.then(|| key.clone());
+    let mut record = analyze_temps(key, &cleaned_temps, unit, config);
+    record.outliers_removed = outliers as u32;
+    if config.confidence_interval
+        && let Some((low, high)) = mean_confidence_interval(
+            record.avg_temp,
+            record.std_dev,
+            record.count,
+            config.std_dev,
+        )
+    {
+        record.ci_low = Some(low);
+        record.ci_high = Some(high);
+    }
+    if config.mode_range {
+        record.range_temp = Some(record.max_temp - record.min_temp);
+        record.mode_temp = Some(binned_mode(&cleaned_temps, config.mode_bin_width));
+    }
+    if let Some(fraction) = config.trimmed_mean {
+        record.trimmed_mean = Some(trimmed_mean(&cleaned_temps, fraction));
+    }
+    if config.positive_means && cleaned_temps.iter().all(|&x| x > 0.0) {
+        let n = cleaned_temps.len() as f64;
+        record.geometric_mean = Some((cleaned_temps.iter().map(|x| x.ln()).sum::<f64>() / n).exp());
+        record.harmonic_mean = Some(n / cleaned_temps.iter().map(|x| x.recip()).sum::<f64>());
+    }
+    if let Some(key) = celsius_key {
+        record.celsius = Some(celsius_stats(key, &cleaned_temps, unit, config));
+    }
+    if verify {
+        check::verify_record(
+            &record,
+            &cleaned_temps,
+            config.quantile_method,
+            &config.percentiles,
+            config.std_dev,
+        )?;
+    }
+    Ok((finish_record(record, config)?, outliers))
+}
+
+/// Runs the plugin's `post_aggregate` and the record script on an analyzed record, then
+/// rounds it to `config.significant_digits`; the script may drop it.
+fn finish_record(record: Record, config: &TransformConfig) -> Result<Option<Record>> {
+    #[cfg(feature = "plugins")]
+    let record = match &config.plugin {
+        Some(plugin) => Record {
+            plugin_metric: plugin.post_aggregate(&record)?,
+            ..record
+        },
+        None => record,
+    };
+    #[cfg(feature = "scripting")]
+    let record = match &config.record_script {
+        Some(script) => script.record(&record)?,
+        None => Some(record),
+    };
+    #[cfg(not(feature = "scripting"))]
+    let record = Some(record);
+    Ok(record.map(|mut record| {
+        if let Some(digits) = config.significant_digits {
+            record.round_significant(digits);
+        }
+        record
+    }))
+}
+
+/// Aggregates every group in constant memory with [`OnlineStats`] while scanning, instead of
+/// buffering its readings (see `TransformConfig::streaming`).
+///
+/// # Errors
+///
+/// Returns `PipelineError::Data` if an enabled option needs every reading of a group, or if a
+/// unit does not increase with Celsius.
+fn analyze_online(
+    scan: Box<Scan<'_>>,
+    config: &TransformConfig,
+    stats: &mut ProcessingStats,
+) -> Result<(Vec<Record>, usize)> {
+    check_online(config)?;
+    let mut groups: HashMap<GroupKey, OnlineStats> = HashMap::new();
+    scan(stats, &mut |key, temps| {
+        let group = groups.entry(key).or_default();
+        for &temp in temps {
+            group.push(temp);
+        }
+        Ok(())
+    })?;
+    debug!("Found {} unique country-month combinations", groups.len());
+    stats.group_map_bytes =
+        groups.capacity() * (size_of::<GroupKey>() + size_of::<OnlineStats>() + 1);
+
+    status!("Starting statistical analysis");
+    stats.groups += groups.len();
+    let mut records = Vec::with_capacity(groups.len() * (1 + config.extra_units.len()));
+    for (key, group) in groups {
+        records.extend(online_records(key, &group, config)?);
+    }
+    Ok((records, 0))
+}
+
+/// Rejects the options streaming statistics cannot compute.
+fn check_online(config: &TransformConfig) -> Result<()> {
+    let unsupported = [
+        (config.threshold.is_some(), "outlier detection"),
+        (config.trimmed_mean.is_some(), "trimmed means"),
+        (config.mode_range, "modes and ranges"),
+        (config.positive_means, "geometric and harmonic means"),
+        (config.self_check.is_some(), "self-checks"),
+        (!config.percentiles.is_empty(), "custom percentiles"),
+        (
+            config.quantile_method != QuantileMethod::Linear,
+            "quantile methods other than linear",
+        ),
+    ];
+    if let Some((_, option)) = unsupported.iter().find(|(enabled, _)| *enabled) {
+        return Err(PipelineError::Data(format!(
+            "Streaming statistics do not support {}, which needs every reading of a group",
+            option
+        )));
+    }
+    for unit in std::iter::once(&config.unit).chain(&config.extra_units) {
+        let scale = scale_for(*unit, &config.custom_scale);
+        if scale.convert_celsius(1.0) <= scale.convert_celsius(0.0) {
+            return Err(PipelineError::Data(format!(
+                "Streaming statistics need units increasing with Celsius, but {} does not",
+                unit
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Records of one group aggregated by [`analyze_online`], in the configured unit followed by
+/// the extra units.
+///
+/// The statistics are accumulated in Celsius and converted afterwards, which is exact for the
+/// linear units as long as they increase with Celsius (see [`check_online`]).
+fn online_records(
+    key: GroupKey,
+    group: &OnlineStats,
+    config: &TransformConfig,
+) -> Result<Vec<Record>> {
+    let [median, p25, p75, p90, p95] = group.percentiles();
+    let mut records = Vec::with_capacity(1 + config.extra_units.len());
+    for unit in std::iter::once(config.unit).chain(config.extra_units.iter().copied()) {
+        let scale = scale_for(unit, &config.custom_scale);
+        let convert = |celsius| scale.convert_celsius(celsius);
+        // Start from the record of an empty group and fill in the streamed statistics
+        let mut record = analyze_temps(key.clone(), &[], unit, config);
+        record.count = group.count() as u32;
+        record.avg_temp = convert(group.mean());
+        record.min_temp = convert(group.min());
+        record.max_temp = convert(group.max());
+        record.std_dev = group.std_dev(config.std_dev) * (convert(1.0) - convert(0.0));
+        record.median_temp = convert(median);
+        record.percentile_25 = convert(p25);
+        record.percentile_75 = convert(p75);
+        record.percentile_90 = convert(p90);
+        record.percentile_95 = convert(p95);
+        if config.confidence_interval
+            && let Some((low, high)) = mean_confidence_interval(
+                record.avg_temp,
+                record.std_dev,
+                record.count,
+                config.std_dev,
+            )
+        {
+            record.ci_low = Some(low);
+            record.ci_high = Some(high);
+        }
+        if config.keep_celsius {
+            record.celsius = Some(CelsiusStats {
+                avg_temp: group.mean(),
+                min_temp: group.min(),
+                max_temp: group.max(),
+                std_dev: group.std_dev(config.std_dev),
+                median_temp: median,
+                percentile_25: p25,
+                percentile_75: p75,
+                percentile_90: p90,
+                percentile_95: p95,
+                percentiles: Vec::new(),
+            });
+        }
+        records.extend(finish_record(record, config)?);
+    }
+    Ok(records)
+}
+
+/// Core statistics in Celsius of a group's cleaned readings, given in `unit`.
+///
+/// The readings are converted back rather than the statistics, as the percentiles of a unit
+/// with a negative `--unit-scale` mirror those of Celsius.
+fn celsius_stats(
+    key: GroupKey,
+    temps: &[f64],
+    unit: TemperatureUnit,
+    config: &TransformConfig,
+) -> CelsiusStats {
+    let scale = scale_for(unit, &config.custom_scale);
+    let celsius: Vec<f64> = temps.iter().map(|&t| scale.to_celsius(t)).collect();
+    let record = analyze_temps(key, &celsius, TemperatureUnit::Celsius, config);
+    CelsiusStats {
+        avg_temp: record.avg_temp,
+        min_temp: record.min_temp,
+        max_temp: record.max_temp,
+        std_dev: record.std_dev,
+        median_temp: record.median_temp,
+        percentile_25: record.percentile_25,
+        percentile_75: record.percentile_75,
+        percentile_90: record.percentile_90,
+        percentile_95: record.percentile_95,
+        percentiles: record.percentiles,
+    }
+}
 
-            if cleaned_temps.is_empty() {
-                return None;
-            }
+/// Records end-of-analysis statistics and warnings shared by the batch and iterator APIs.
+fn finish_analysis(
+    stats: &mut ProcessingStats,
+    records: usize,
+    self_checked: usize,
+    config: &TransformConfig,
+) {
+    stats.self_checked = self_checked;
+    stats.peak_rss_bytes = memory::peak_rss_bytes();
+    if config.self_check.is_some() {
+        status!("Self-check passed for {} sampled groups", self_checked);
+    }
 
-            Some(analyze_temps(country, year, month, &cleaned_temps))
-        })
-        .collect();
+    let emptied = stats.groups - records;
+    if emptied > 0 {
+        stats.warnings.push(Warning::new(
+            None,
+            format!("{} groups had every reading removed as outliers", emptied),
+        ));
+    }
 
     if config.threshold.is_some() {
         debug!("Outlier detection completed");
     }
-
-    // Sort by country, then year, then month
-    debug!("Sorting {} results", results.len());
-    results.sort_by(|a, b| {
-        a.country
-            .cmp(&b.country)
-            .then_with(|| a.year.cmp(&b.year))
-            .then_with(|| a.month.cmp(&b.month))
-    });
-
-    debug!("Transform processing completed successfully");
-    Ok(results)
 }
 
-/// Extracts a Float64 column from an Arrow RecordBatch by name.
+/// Extracts a numeric column from an Arrow RecordBatch by name as Float64.
 ///
-/// This function safely retrieves a column from a RecordBatch and downcasts it to a Float64Array.
+/// This function safely retrieves a column from a RecordBatch: a Float64 column is borrowed
+/// as is, and any other numeric column (Float16/Float32, signed or unsigned integers, Decimal128
+/// or Decimal256) is cast to Float64, nulls staying null.
 /// It's used for accessing numeric temperature data from the Parquet file.
 ///
 /// # Arguments
 ///
 /// * `batch` - Reference to the Arrow RecordBatch containing the data
 /// * `name` - Name of the column to extract
+/// * `file_path` - Source file, reported in errors
 ///
 /// # Returns
 ///
-/// Returns a `Result<&Float64Array>` containing a reference to the typed array.
+/// Returns a `Result<Cow<Float64Array>>` borrowing or owning the Float64 values.
 ///
 /// # Errors
 ///
-/// Returns `PipelineError::Data` if:
-/// - Column with the specified name doesn't exist
-/// - Column exists but is not of Float64 type
-/// ```
-fn get_column_f64<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a Float64Array> {
-    batch
-        .column_by_name(name)
-        .ok_or_else(|| PipelineError::Data(format!("Column not found: {}", name)))?
-        .as_any()
-        .downcast_ref::<Float64Array>()
-        .ok_or_else(|| PipelineError::Data(format!("Column {} is not Float64", name)))
+/// Returns `PipelineError::MissingColumn` if the column with the specified name doesn't exist,
+/// or `PipelineError::ColumnType` if it exists but is not numeric.
+fn get_column_f64<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+    file_path: &Path,
+) -> Result<Cow<'a, Float64Array>> {
+    let column = get_column(batch, name, file_path)?;
+    if let Some(values) = column.as_any().downcast_ref::<Float64Array>() {
+        return Ok(Cow::Borrowed(values));
+    }
+    let type_error = || {
+        column_type_error(
+            file_path,
+            name,
+            "Float64 (or another numeric type)",
+            column.data_type(),
+        )
+    };
+    if !column.data_type().is_numeric() {
+        return Err(type_error());
+    }
+    let values = arrow_cast::cast(column, &DataType::Float64).map_err(|_| type_error())?;
+    Ok(Cow::Owned(values.as_primitive::<Float64Type>().clone()))
 }
 
 /// Extracts a String column from an Arrow RecordBatch by name.
@@ -206,6 +2315,7 @@ fn get_column_f64<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a Float64A
 ///
 /// * `batch` - Reference to the Arrow RecordBatch containing the data
 /// * `name` - Name of the column to extract
+/// * `file_path` - Source file, reported in errors
 ///
 /// # Returns
 ///
@@ -213,115 +2323,169 @@ fn get_column_f64<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a Float64A
 ///
 /// # Errors
 ///
-/// Returns `PipelineError::Data` if:
-/// - Column with the specified name doesn't exist
-/// - Column exists but is not of String/Utf8 type
-/// ```
-fn get_column_str<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray> {
-    batch
-        .column_by_name(name)
-        .ok_or_else(|| PipelineError::Data(format!("Column not found: {}", name)))?
+/// Returns `PipelineError::MissingColumn` if the column with the specified name doesn't exist,
+/// or `PipelineError::ColumnType` if it exists but is not of String/Utf8 type.
+fn get_column_str<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+    file_path: &Path,
+) -> Result<&'a StringArray> {
+    let column = get_column(batch, name, file_path)?;
+    column
         .as_any()
         .downcast_ref::<StringArray>()
-        .ok_or_else(|| PipelineError::Data(format!("Column {} is not Utf8/String", name)))
+        .ok_or_else(|| column_type_error(file_path, name, "Utf8/String", column.data_type()))
 }
 
-/// Cleans and validates temperature data by applying quality control checks.
+/// Extracts the date column from an Arrow RecordBatch by name: `DATE_FORMAT` strings, or
+/// native `Date32`, `Date64`, or `Timestamp` values.
 ///
-/// This function performs data quality validation on temperature readings to filter out
-/// invalid, unrealistic, or corrupted values. It checks for reasonable temperature bounds
-/// and ensures the value is finite (not NaN or infinite).
+/// # Errors
+///
+/// Returns `PipelineError::MissingColumn` if the column doesn't exist, or
+/// `PipelineError::ColumnType` if it is neither a string nor a date column.
+fn get_date_column<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+    file_path: &Path,
+) -> Result<DateColumn<'a>> {
+    let column = get_column(batch, name, file_path)?;
+    DateColumn::new(column).ok_or_else(|| {
+        column_type_error(
+            file_path,
+            name,
+            "Utf8/String, Date32, Date64, or Timestamp",
+            column.data_type(),
+        )
+    })
+}
+
+/// Looks up a column by name, reporting the source file when it is missing.
+fn get_column<'a>(batch: &'a RecordBatch, name: &str, file_path: &Path) -> Result<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| PipelineError::MissingColumn {
+            file: file_path.to_path_buf(),
+            column: name.to_string(),
+        })
+}
+
+/// Builds a `PipelineError::ColumnType` for a column with an unexpected Arrow type.
+fn column_type_error(
+    file_path: &Path,
+    name: &str,
+    expected: &str,
+    found: &DataType,
+) -> PipelineError {
+    PipelineError::ColumnType {
+        file: file_path.to_path_buf(),
+        column: name.to_string(),
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+/// Reads and parses the date, country, and temperature of a single row.
 ///
 /// # Arguments
 ///
-/// * `temp` - Raw temperature value in Celsius to validate
-/// * `_config` - Transform configuration
+/// * `date_col` - Dates, as strings formatted as `%Y-%m-%d` or native date values
+/// * `country_col` - Country alpha-2 codes
+/// * `temp_col` - Temperatures in Celsius
+/// * `i` - Row index within the batch
 ///
 /// # Returns
 ///
-/// Returns `Some(f64)` if the temperature passes all validation checks, `None` if it should be filtered out.
-///
-/// # Validation Rules
-///
-/// - Temperature must be finite (not NaN or infinity)
-/// - Temperature must be within reasonable bounds (-100°C to 70°C)
-/// - These bounds cover extreme Earth temperatures from Antarctica to Death Valley
-/// ```
-fn clean_temp(temp: f64, _config: &TransformConfig) -> Option<f64> {
-    // Check for reasonable temperature bounds (e.g., -100°C to 70°C)
-    if !(-100.0..=70.0).contains(&temp) {
-        return None;
+/// Returns the parsed `(date, country, temp)` tuple, or a human-readable reason when the row
+/// contains nulls or an unparsable date.
+fn parse_row<'a>(
+    date_col: &DateColumn,
+    country_col: &'a StringArray,
+    temp_col: &Float64Array,
+    i: usize,
+) -> std::result::Result<(NaiveDate, &'a str, f64), String> {
+    if date_col.is_null(i) {
+        return Err("date is null".to_string());
     }
-
-    // Check for NaN or infinite values
-    if !temp.is_finite() {
-        return None;
+    if country_col.is_null(i) {
+        return Err("country is null".to_string());
+    }
+    if temp_col.is_null(i) {
+        return Err("temperature is null".to_string());
     }
 
-    Some(temp)
+    let date = date_col.date(i)?;
+    Ok((date, country_col.value(i), temp_col.value(i)))
 }
 
-/// Converts temperature from Celsius to the specified unit.
-///
-/// This function performs temperature unit conversion from Celsius (the source unit in the dataset)
-/// to the target unit specified in the configuration. All calculations use standard conversion formulas.
-///
-/// # Arguments
+/// Range of plausible readings in Celsius: `config.valid_min` and `config.valid_max`, given in
+/// `config.unit`, converted to Celsius, with [`VALID_CELSIUS`] for a bound that is not set.
 ///
-/// * `temp_celsius` - Temperature value in Celsius degrees
-/// * `unit` - Target temperature unit for conversion
+/// The readings are cleaned before they are converted, so the bounds are converted instead;
+/// a unit decreasing with Celsius (a negative custom scale) swaps them.
 ///
-/// # Returns
-///
-/// Returns the converted temperature value as `f64` in the target unit.
-///
-/// # Conversion Formulas
+/// # Errors
 ///
-/// - **Celsius**: No conversion (identity)
-/// - **Fahrenheit**: °F = (°C × 9/5) + 32
-/// - **Kelvin**: K = °C + 273.15
-/// ```
-fn convert_temp(temp_celsius: f64, unit: &TemperatureUnit) -> f64 {
-    match unit {
-        TemperatureUnit::Celsius => temp_celsius,
-        TemperatureUnit::Fahrenheit => temp_celsius * 9.0 / 5.0 + 32.0,
-        TemperatureUnit::Kelvin => temp_celsius + 273.15,
+/// Returns `PipelineError::Data` if the range is empty or not finite.
+fn valid_bounds(config: &TransformConfig) -> Result<(f64, f64)> {
+    let scale = scale_for(config.unit, &config.custom_scale);
+    let decreasing = scale.convert_celsius(1.0) < scale.convert_celsius(0.0);
+    let (min, max) = if decreasing {
+        (config.valid_max, config.valid_min)
+    } else {
+        (config.valid_min, config.valid_max)
+    };
+    let bounds = (
+        min.map_or(VALID_CELSIUS.0, |min| scale.to_celsius(min)),
+        max.map_or(VALID_CELSIUS.1, |max| scale.to_celsius(max)),
+    );
+    if !(bounds.0.is_finite() && bounds.1.is_finite() && bounds.0 < bounds.1) {
+        return Err(PipelineError::Data(format!(
+            "The valid temperature range is empty: {}..{} {} ({:.2}..{:.2} °C)",
+            config
+                .valid_min
+                .map_or("default".to_string(), |min| min.to_string()),
+            config
+                .valid_max
+                .map_or("default".to_string(), |max| max.to_string()),
+            config.unit,
+            bounds.0,
+            bounds.1
+        )));
     }
+    Ok(bounds)
 }
 
-/// Removes statistical outliers from temperature data using standard deviation method.
+/// Cleans and validates temperature data by applying quality control checks.
 ///
-/// This function identifies and filters out outliers based on how many standard deviations
-/// they are from the mean. Values beyond the threshold are considered outliers and removed.
+/// This function performs data quality validation on temperature readings to filter out
+/// invalid, unrealistic, or corrupted values. It checks for reasonable temperature bounds
+/// and ensures the value is finite (not NaN or infinite).
 ///
 /// # Arguments
 ///
-/// * `data` - Slice of temperature values to filter
-/// * `threshold` - Number of standard deviations beyond which values are considered outliers
+/// * `temp` - Raw temperature value in Celsius to validate
+/// * `bounds` - Plausible range in Celsius, see [`valid_bounds`]
 ///
 /// # Returns
 ///
-/// Returns a `Vec<f64>` containing only the values that fall within the threshold.
-/// If input has fewer than 2 values, returns all values unchanged.
+/// Returns `Some(f64)` if the temperature passes all validation checks, `None` if it should be filtered out.
+///
+/// # Validation Rules
 ///
-/// Common threshold values:
-/// - 1.0: Removes ~32% of data (aggressive)
-/// - 2.0: Removes ~5% of data (moderate)
-/// - 3.0: Removes ~0.3% of data (conservative)
-fn remove_outliers(data: &[f64], threshold: f64) -> Vec<f64> {
-    if data.len() < 2 {
-        return data.to_vec();
+/// - Temperature must be finite (not NaN or infinity)
+/// - Temperature must be within `bounds` (-100°C to 70°C by default)
+fn clean_temp(temp: f64, bounds: (f64, f64)) -> Option<f64> {
+    if !(bounds.0..=bounds.1).contains(&temp) {
+        return None;
     }
 
-    let mean_val = data.iter().sum::<f64>() / data.len() as f64;
-    let variance =
-        data.iter().map(|x| (x - mean_val).powi(2)).sum::<f64>() / (data.len() - 1) as f64;
-    let std_dev = variance.sqrt();
+    // Check for NaN or infinite values
+    if !temp.is_finite() {
+        return None;
+    }
 
-    data.iter()
-        .filter(|&&x| (x - mean_val).abs() <= threshold * std_dev)
-        .copied()
-        .collect()
+    Some(temp)
 }
 
 /// Calculates comprehensive statistical analysis of temperature data for a specific location and time.
@@ -330,25 +2494,31 @@ fn remove_outliers(data: &[f64], threshold: f64) -> Vec<f64> {
 ///
 /// # Arguments
 ///
-/// * `country` - Country identifier (alpha-2 code or aggregated name)
-/// * `year` - Year of the measurements
-/// * `month` - Month of the measurements (1-12)
+/// * `key` - Group identifiers (country alpha-2 code or aggregated name, year, month, ENSO phase)
 /// * `temps` - Slice of temperature values in the target unit
+/// * `unit` - Unit the temperature values are expressed in
 ///
 /// # Returns
 ///
 /// Returns a `Record` struct containing:
 /// - **Basic statistics**: count, mean, min, max, standard deviation
-/// - **Percentiles**: 25th, 50th (median), 75th, 90th, 95th
-/// - **Metadata**: country, year, month identifiers
+/// - **Percentiles**: 25th, 50th (median), 75th, 90th, 95th, and those of `config.percentiles`
+/// - **Metadata**: country, year, month, and ENSO phase identifiers
 ///
 /// # Statistical Methods
 ///
 /// - **Mean**: Arithmetic average of all values
-/// - **Standard Deviation**: Sample standard deviation (N-1 denominator)
-/// - **Percentiles**: Linear interpolation method for precise quantile calculation
+/// - **Standard Deviation**: Sample (N-1 denominator) or, with `StdDevMode::Population`,
+///   population standard deviation
+/// - **Percentiles**: `config.quantile_method` between neighbouring readings (linear
+///   interpolation by default), the median always averages the two middle readings
 /// - **Min/Max**: Extreme values in the dataset
-fn analyze_temps(country: String, year: i32, month: u32, temps: &[f64]) -> Record {
+fn analyze_temps(
+    key: GroupKey,
+    temps: &[f64],
+    unit: TemperatureUnit,
+    config: &TransformConfig,
+) -> Record {
     let count = temps.len() as u32;
     let avg_temp = if temps.is_empty() {
         0.0
@@ -359,23 +2529,36 @@ fn analyze_temps(country: String, year: i32, month: u32, temps: &[f64]) -> Recor
     let max_temp = temps.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
 
     let std_dev = if temps.len() > 1 {
-        let variance =
-            temps.iter().map(|x| (x - avg_temp).powi(2)).sum::<f64>() / (temps.len() - 1) as f64;
+        let variance = temps.iter().map(|x| (x - avg_temp).powi(2)).sum::<f64>()
+            / config.std_dev.divisor(temps.len());
         variance.sqrt()
     } else {
         0.0
     };
 
-    let median_temp = calculate_median(temps);
-    let percentile_25 = calculate_percentile(temps, 25.0);
-    let percentile_75 = calculate_percentile(temps, 75.0);
-    let percentile_90 = calculate_percentile(temps, 90.0);
-    let percentile_95 = calculate_percentile(temps, 95.0);
+    let sorted = sort_readings(temps, config);
+    let median_temp = sorted_median(&sorted);
+    let method = config.quantile_method;
+    let percentile_25 = sorted_percentile(&sorted, 25.0, method);
+    let percentile_75 = sorted_percentile(&sorted, 75.0, method);
+    let percentile_90 = sorted_percentile(&sorted, 90.0, method);
+    let percentile_95 = sorted_percentile(&sorted, 95.0, method);
+    let percentiles = config
+        .percentiles
+        .iter()
+        .map(|&percent| {
+            (
+                Record::percentile_name(percent),
+                sorted_percentile(&sorted, percent, method),
+            )
+        })
+        .collect();
 
     Record {
-        country,
-        year,
-        month,
+        country: key.country,
+        year: key.year,
+        month: key.month,
+        day: (key.day != 0).then_some(key.day),
         avg_temp,
         min_temp,
         max_temp,
@@ -386,7 +2569,83 @@ fn analyze_temps(country: String, year: i32, month: u32, temps: &[f64]) -> Recor
         percentile_75,
         percentile_90,
         percentile_95,
+        percentiles,
+        unit,
+        enso_phase: key.enso_phase,
+        season: key.season,
+        distinct_days: None,
+        outliers_removed: 0,
+        ci_low: None,
+        ci_high: None,
+        range_temp: None,
+        mode_temp: None,
+        trimmed_mean: None,
+        geometric_mean: None,
+        harmonic_mean: None,
+        zscore_vs_period: None,
+        anomaly_c: None,
+        plugin_metric: None,
+        source_file: None,
+        run_id: None,
+        processed_at: None,
+        celsius: None,
+        aridity_index: None,
+    }
+}
+
+/// Confidence interval of a mean: `mean ± t · s / √count`, where `s` is the sample standard
+/// deviation (`std_dev` converted from its `mode`).
+///
+/// `t` is the two-sided [`CONFIDENCE_LEVEL`] quantile of Student's t distribution with
+/// `count - 1` degrees of freedom rather than the normal 1.96, which understates the interval
+/// for samples as small as a month of daily readings (t = 2.04 for 31 readings).
+///
+/// # Returns
+///
+/// Returns `None` for fewer than 2 readings, where the interval is undefined.
+pub(crate) fn mean_confidence_interval(
+    mean: f64,
+    std_dev: f64,
+    count: u32,
+    mode: StdDevMode,
+) -> Option<(f64, f64)> {
+    if count < 2 {
+        return None;
+    }
+    let n = count as f64;
+    let t = student_t_upper_quantile((1.0 - CONFIDENCE_LEVEL) / 2.0, n - 1.0);
+    let margin = t * mode.to_sample(std_dev, count as usize) / n.sqrt();
+    Some((mean - margin, mean + margin))
+}
+
+/// Most frequent value of binned data.
+///
+/// Values are assigned to bins `[k * width, (k + 1) * width)`; the center of the most populated
+/// bin is returned, preferring the lowest bin on ties so the result does not depend on input
+/// order.
+fn binned_mode(data: &[f64], width: f64) -> f64 {
+    let mut bins: BTreeMap<i64, usize> = BTreeMap::new();
+    for value in data {
+        *bins.entry((value / width).floor() as i64).or_default() += 1;
+    }
+    let mut mode = (0, 0);
+    for (bin, count) in bins {
+        if count > mode.1 {
+            mode = (bin, count);
+        }
     }
+    (mode.0 as f64 + 0.5) * width
+}
+
+/// Mean after dropping `floor(n * fraction)` of the lowest and of the highest values.
+///
+/// `fraction` must be in `[0, 0.5)`, so at least one value is always kept.
+fn trimmed_mean(data: &[f64], fraction: f64) -> f64 {
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let trim = (sorted.len() as f64 * fraction).floor() as usize;
+    let kept = &sorted[trim..sorted.len() - trim];
+    kept.iter().sum::<f64>() / kept.len() as f64
 }
 
 /// Calculates the median (50th percentile) from temperature data.
@@ -402,12 +2661,34 @@ fn analyze_temps(country: String, year: i32, month: u32, temps: &[f64]) -> Recor
 ///
 /// Returns the median value as `f64`. Returns 0.0 for empty datasets.
 fn calculate_median(data: &[f64]) -> f64 {
-    if data.is_empty() {
-        return 0.0;
-    }
-
     let mut sorted_data = data.to_vec();
     sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted_median(&sorted_data)
+}
+
+/// Sorts a group's readings ascending for the median and percentiles.
+///
+/// With the `gpu` feature, groups of at least `config.gpu_min_group` readings are sorted on the
+/// GPU, falling back to the CPU when no GPU is available. Both give identical results.
+fn sort_readings(temps: &[f64], config: &TransformConfig) -> Vec<f64> {
+    #[cfg(feature = "gpu")]
+    if config.gpu_min_group.is_some_and(|min| temps.len() >= min)
+        && let Some(sorted) = crate::gpu::sort(temps)
+    {
+        return sorted;
+    }
+    #[cfg(not(feature = "gpu"))]
+    let _ = config;
+    let mut sorted_data = temps.to_vec();
+    sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted_data
+}
+
+/// Median of readings already sorted ascending (0.0 when empty), see [`calculate_median`].
+pub(crate) fn sorted_median(sorted_data: &[f64]) -> f64 {
+    if sorted_data.is_empty() {
+        return 0.0;
+    }
 
     let len = sorted_data.len();
     if len.is_multiple_of(2) {
@@ -417,34 +2698,831 @@ fn calculate_median(data: &[f64]) -> f64 {
     }
 }
 
-/// Calculates a specific percentile from temperature data using linear interpolation.
+/// Calculates a specific percentile from temperature data.
 ///
 /// This function computes the value below which a given percentage of observations fall.
 ///
 /// # Arguments
 ///
-/// * `data` - Slice of temperature values to analyze
+/// * `sorted_data` - Temperature values sorted ascending (see [`sort_readings`])
 /// * `percentile` - Desired percentile as a percentage (0.0 to 100.0)
+/// * `method` - How a percentile between two readings is computed, as NumPy's methods
 ///
 /// # Returns
 ///
 /// Returns the calculated percentile value as `f64`. Returns 0.0 for empty datasets.
-fn calculate_percentile(data: &[f64], percentile: f64) -> f64 {
-    if data.is_empty() {
+pub(crate) fn sorted_percentile(
+    sorted_data: &[f64],
+    percentile: f64,
+    method: QuantileMethod,
+) -> f64 {
+    if sorted_data.is_empty() {
         return 0.0;
     }
 
-    let mut sorted_data = data.to_vec();
-    sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
     let index = (percentile / 100.0) * (sorted_data.len() - 1) as f64;
     let lower = index.floor() as usize;
     let upper = index.ceil() as usize;
 
     if lower == upper {
-        sorted_data[lower]
-    } else {
-        let weight = index - lower as f64;
-        sorted_data[lower] * (1.0 - weight) + sorted_data[upper] * weight
+        return sorted_data[lower];
+    }
+    match method {
+        QuantileMethod::Linear => {
+            let weight = index - lower as f64;
+            sorted_data[lower] * (1.0 - weight) + sorted_data[upper] * weight
+        }
+        // NumPy rounds half to even, so 0.5 picks the lower and 1.5 the upper reading
+        QuantileMethod::Nearest => sorted_data[index.round_ties_even() as usize],
+        QuantileMethod::Lower => sorted_data[lower],
+        QuantileMethod::Higher => sorted_data[upper],
+        QuantileMethod::Midpoint => (sorted_data[lower] + sorted_data[upper]) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enso::EnsoTable;
+    use crate::schema::Value;
+    use crate::season::Hemisphere;
+    use crate::structs::{Granularity, OutputConfig, SchemaVersion};
+    use crate::units::LinearScale;
+
+    /// Processes CSV readings (`date,country_alpha2,temp_mean_c_approx` rows) of 2000 to 2020.
+    fn process_csv(
+        name: &str,
+        rows: &[String],
+        config: &TransformConfig,
+    ) -> (Vec<Record>, ProcessingStats) {
+        process_csv_excluding(name, rows, &[], config)
+    }
+
+    /// Like `process_csv`, dropping the `exclude` countries
+    fn process_csv_excluding(
+        name: &str,
+        rows: &[String],
+        exclude: &[String],
+        config: &TransformConfig,
+    ) -> (Vec<Record>, ProcessingStats) {
+        let path = std::env::temp_dir().join(format!(
+            "transformer-transform-{}-{}.csv",
+            std::process::id(),
+            name
+        ));
+        let mut contents = "date,country_alpha2,temp_mean_c_approx\n".to_string();
+        for row in rows {
+            contents.push_str(row);
+            contents.push('\n');
+        }
+        std::fs::write(&path, contents).unwrap();
+        let result = process_inputs_with_stats(
+            &[InputSource::new(&path)],
+            &[],
+            exclude,
+            2000,
+            2020,
+            None,
+            config,
+        );
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap()
+    }
+
+    /// One January of readings: nine close to 10 °C and a stray 50 °C, which lies more than
+    /// two standard deviations from the group mean.
+    fn january_with_outlier() -> Vec<String> {
+        [10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 50.0]
+            .iter()
+            .enumerate()
+            .map(|(day, temp)| format!("2020-01-{:02},DE,{}", day + 1, temp))
+            .collect()
+    }
+
+    fn outlier_config(action: OutlierAction) -> TransformConfig {
+        TransformConfig {
+            threshold: Some(2.0),
+            outlier_action: action,
+            ..TransformConfig::default()
+        }
+    }
+
+    #[test]
+    fn remove_drops_the_outlier_from_the_group() {
+        let (records, stats) = process_csv(
+            "remove",
+            &january_with_outlier(),
+            &outlier_config(OutlierAction::Remove),
+        );
+
+        let record = &records[0];
+        assert_eq!(record.count, 9);
+        assert_eq!(record.max_temp, 11.0);
+        assert!((record.avg_temp - 94.0 / 9.0).abs() < 1e-9);
+        assert_eq!(record.outliers_removed, 1);
+        assert_eq!((stats.outliers_removed, stats.outliers_winsorized), (1, 0));
+    }
+
+    #[test]
+    fn winsorize_clips_the_outlier_and_keeps_the_reading_count() {
+        let (records, stats) = process_csv(
+            "winsorize",
+            &january_with_outlier(),
+            &outlier_config(OutlierAction::Winsorize),
+        );
+
+        let readings = [10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 50.0];
+        let bounds = outliers::std_dev_bounds(&readings, 2.0, StdDevMode::Sample).unwrap();
+        let upper = bounds.center + bounds.radius;
+        let record = &records[0];
+        assert_eq!(record.count, 10);
+        assert!((record.max_temp - upper).abs() < 1e-9);
+        assert!((record.avg_temp - (94.0 + upper) / 10.0).abs() < 1e-9);
+        assert_eq!(record.outliers_removed, 1);
+        assert_eq!((stats.outliers_removed, stats.outliers_winsorized), (0, 1));
+    }
+
+    #[test]
+    fn groups_without_outliers_are_identical_under_both_actions() {
+        let rows: Vec<String> = (1..=10)
+            .map(|day| format!("2020-01-{:02},DE,{}", day, 10 + day % 2))
+            .collect();
+        let (removed, _) = process_csv(
+            "clean-remove",
+            &rows,
+            &outlier_config(OutlierAction::Remove),
+        );
+        let (winsorized, stats) = process_csv(
+            "clean-winsorize",
+            &rows,
+            &outlier_config(OutlierAction::Winsorize),
+        );
+
+        assert_eq!(removed[0].count, 10);
+        assert_eq!(removed[0].avg_temp, winsorized[0].avg_temp);
+        assert_eq!(removed[0].std_dev, winsorized[0].std_dev);
+        assert_eq!(stats.outliers_winsorized, 0);
+    }
+
+    #[test]
+    fn constant_groups_keep_every_reading_under_every_method() {
+        // Five Januaries of identical readings, so the rolling and climatological windows are
+        // constant as well
+        let rows: Vec<String> = (2016..=2020)
+            .flat_map(|year| (1..=6).map(move |day| format!("{}-01-{:02},DE,0.1", year, day)))
+            .collect();
+        let methods = [
+            (OutlierMethod::StdDev, 2.0),
+            (OutlierMethod::Grubbs, 0.05),
+            (OutlierMethod::Rolling, 2.0),
+            (OutlierMethod::Climatological, 2.0),
+            (OutlierMethod::Iqr, 1.5),
+            (OutlierMethod::Mad, 3.0),
+            (OutlierMethod::ZScoreModified, 3.5),
+        ];
+        for (method, threshold) in methods {
+            for action in [OutlierAction::Remove, OutlierAction::Winsorize] {
+                let config = TransformConfig {
+                    threshold: Some(threshold),
+                    outlier_method: method,
+                    outlier_action: action,
+                    ..TransformConfig::default()
+                };
+                let name = format!("constant-{:?}-{:?}", method, action);
+                let (records, stats) = process_csv(&name, &rows, &config);
+
+                assert_eq!(records.len(), 5, "{}", name);
+                for record in &records {
+                    assert_eq!(record.count, 6, "{}", name);
+                    assert!((record.avg_temp - 0.1).abs() < 1e-12, "{}", name);
+                    assert_eq!(record.outliers_removed, 0, "{}", name);
+                }
+                assert_eq!(
+                    stats.outliers_removed + stats.outliers_winsorized,
+                    0,
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn constant_group_in_a_varying_window_is_judged_against_the_window() {
+        // Januaries around 5 °C, one of which is constant at 30 °C
+        let mut rows: Vec<String> = (2011..=2020)
+            .filter(|&year| year != 2015)
+            .flat_map(|year| {
+                (1..=4).map(move |day| format!("{}-01-{:02},DE,{}", year, day, 4 + day % 3))
+            })
+            .collect();
+        rows.extend((1..=4).map(|day| format!("2015-01-{:02},DE,30", day)));
+        let config = TransformConfig {
+            threshold: Some(1.5),
+            outlier_method: OutlierMethod::Climatological,
+            ..TransformConfig::default()
+        };
+
+        let (records, stats) = process_csv("constant-window", &rows, &config);
+
+        assert!(records.iter().all(|record| record.year != 2015));
+        assert_eq!(stats.outliers_removed, 4);
+    }
+
+    /// Readings in January 2000 (La Niña), January 2013 (neutral), and January 2016 (El Niño)
+    fn readings_in_three_phases() -> Vec<String> {
+        ["2000-01-15,DE,1", "2013-01-15,DE,2", "2016-01-15,DE,3"]
+            .iter()
+            .map(|row| row.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn enso_phase_is_collapsed_unless_grouped_by() {
+        let rows = readings_in_three_phases();
+        let tagged = TransformConfig {
+            enso: Some(EnsoTable::bundled()),
+            group_by: vec![GroupDimension::Country],
+            ..TransformConfig::default()
+        };
+        let (records, _) = process_csv("enso-collapsed", &rows, &tagged);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].count, 3);
+        assert_eq!(records[0].enso_phase, None);
+
+        let by_phase = TransformConfig {
+            group_by: vec![GroupDimension::Country, GroupDimension::EnsoPhase],
+            ..tagged
+        };
+        let (records, _) = process_csv("enso-grouped", &rows, &by_phase);
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|record| record.enso_phase.is_some()));
+    }
+
+    #[test]
+    fn granularity_replaces_group_by() {
+        let rows: Vec<String> = ["2010-01-10,DE,1", "2010-01-20,DE,3", "2010-02-10,DE,5"]
+            .iter()
+            .map(|row| row.to_string())
+            .collect();
+        let yearly = TransformConfig {
+            granularity: Some(Granularity::Yearly),
+            ..TransformConfig::default()
+        };
+        let (records, _) = process_csv("granularity-yearly", &rows, &yearly);
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].month, records[0].count), (0, 3));
+
+        let daily = TransformConfig {
+            granularity: Some(Granularity::Daily),
+            ..TransformConfig::default()
+        };
+        let (records, _) = process_csv("granularity-daily", &rows, &daily);
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|record| record.count == 1));
+    }
+
+    #[test]
+    fn trends_of_daily_records_match_monthly_records() {
+        let mut rows = Vec::new();
+        for year in 2000..2010 {
+            for (month, level) in [(1, 0.0), (7, 20.0)] {
+                let noise = f64::from((year * 7 + month) % 5) * 0.3;
+                let value = level + f64::from(year - 2000) * 0.05 + noise;
+                rows.push(format!("{year}-{month:02}-05,DE,{value}"));
+                rows.push(format!("{year}-{month:02}-05,DE,{}", value + 1.0));
+                rows.push(format!("{year}-{month:02}-20,DE,{}", value - 0.5));
+            }
+        }
+        let monthly = TransformConfig {
+            threshold: None,
+            ..TransformConfig::default()
+        };
+        let daily = TransformConfig {
+            granularity: Some(Granularity::Daily),
+            ..monthly.clone()
+        };
+        let (monthly, _) = process_csv("trends-monthly", &rows, &monthly);
+        let (daily, _) = process_csv("trends-daily", &rows, &daily);
+        assert_eq!(daily.len(), 2 * monthly.len());
+
+        let scale = LinearScale::default();
+        let expected = &crate::trends::country_trends(&monthly, &scale)[0];
+        let actual = &crate::trends::country_trends(&daily, &scale)[0];
+        assert_eq!(actual.months, 20);
+        assert_eq!(actual.months, expected.months);
+        let close = |a: Option<f64>, b: Option<f64>| (a.unwrap() - b.unwrap()).abs() < 1e-9;
+        assert!(close(actual.slope_per_decade, expected.slope_per_decade));
+        assert!(close(actual.intercept, expected.intercept));
+        assert!(close(actual.r_squared, expected.r_squared));
+        assert!(close(actual.p_value, expected.p_value));
+    }
+
+    /// Header of schema version 1, which never changes
+    const V1_HEADER: &str = "Country,Year,Month,Avg_Temp,Min_Temp,Max_Temp,Std_Dev,\
+                             Median_Temp,Count,Percentile_25,Percentile_75,Percentile_90,\
+                             Percentile_95";
+
+    /// Writes `records` to a scratch CSV file with `config` and returns its header line.
+    fn csv_header(name: &str, records: &[Record], config: &OutputConfig) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "transformer-header-{}-{}.csv",
+            std::process::id(),
+            name
+        ));
+        crate::load::write_csv(records, &path, config).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        contents.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn outlier_counts_leave_the_v1_header_unchanged() {
+        let (records, _) = process_csv(
+            "v1-header",
+            &january_with_outlier(),
+            &outlier_config(OutlierAction::Remove),
+        );
+        let v1 = OutputConfig {
+            include_outliers_removed: true,
+            ..OutputConfig::default()
+        };
+        assert_eq!(csv_header("v1", &records, &v1), V1_HEADER);
+
+        let v2 = OutputConfig {
+            schema_version: SchemaVersion::V2,
+            ..v1
+        };
+        assert!(csv_header("v2", &records, &v2).ends_with(",Outliers_Removed"));
+    }
+
+    #[test]
+    fn v1_parquet_with_outlier_counts_is_rejected() {
+        use arrow_array::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let (records, _) = process_csv(
+            "v1-parquet",
+            &january_with_outlier(),
+            &outlier_config(OutlierAction::Remove),
+        );
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!(
+                "transformer-verify-{}-{}.parquet",
+                std::process::id(),
+                name
+            ))
+        };
+        let (v2_path, v1_path) = (path("v2"), path("v1"));
+        let v2 = OutputConfig {
+            schema_version: SchemaVersion::V2,
+            include_outliers_removed: true,
+            ..OutputConfig::default()
+        };
+        crate::load::write_parquet(&records, &v2_path, &v2).unwrap();
+        assert!(crate::verify::check_parquet(&v2_path, Some(SchemaVersion::V2)).is_ok());
+
+        // The V1 columns followed by outliers_removed, without the V2 unit and period dates
+        let batches: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&v2_path).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .map(|batch| {
+                    batch
+                        .unwrap()
+                        .project(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 16])
+                })
+                .collect::<std::result::Result<_, _>>()
+                .unwrap();
+        let mut writer = ArrowWriter::try_new(
+            std::fs::File::create(&v1_path).unwrap(),
+            batches[0].schema(),
+            None,
+        )
+        .unwrap();
+        for batch in &batches {
+            writer.write(batch).unwrap();
+        }
+        writer.close().unwrap();
+
+        assert_eq!(batches[0].num_columns(), 14);
+        assert!(crate::verify::check_parquet(&v1_path, Some(SchemaVersion::V1)).is_err());
+        assert!(crate::verify::check_parquet(&v1_path, None).is_err());
+        std::fs::remove_file(&v2_path).unwrap();
+        std::fs::remove_file(&v1_path).unwrap();
+    }
+
+    /// Readings of December 2010, January, February, and July 2011
+    fn readings_across_a_winter() -> Vec<String> {
+        [
+            "2010-12-15,DE,1",
+            "2011-01-15,DE,2",
+            "2011-02-15,DE,3",
+            "2011-07-15,DE,20",
+        ]
+        .iter()
+        .map(|row| row.to_string())
+        .collect()
+    }
+
+    /// Sorted `period_start` and `period_end` values of `records` in schema version 2.
+    fn v2_periods(records: &[Record], hemisphere: Hemisphere) -> Vec<(String, String)> {
+        let config = OutputConfig {
+            schema_version: SchemaVersion::V2,
+            hemisphere,
+            ..OutputConfig::default()
+        };
+        let columns = crate::schema::output_columns(&config);
+        let text = |name: &str, record: &Record| match columns
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap()
+            .value(record)
+        {
+            Value::Utf8(text) => text,
+            _ => String::new(),
+        };
+        let mut periods: Vec<(String, String)> = records
+            .iter()
+            .map(|record| (text("period_start", record), text("period_end", record)))
+            .collect();
+        periods.sort();
+        periods
+    }
+
+    fn periods(bounds: &[(&str, &str)]) -> Vec<(String, String)> {
+        bounds
+            .iter()
+            .map(|&(start, end)| (start.to_string(), end.to_string()))
+            .collect()
+    }
+
+    fn granularity_config(granularity: Granularity, hemisphere: Hemisphere) -> TransformConfig {
+        TransformConfig {
+            granularity: Some(granularity),
+            hemisphere,
+            ..TransformConfig::default()
+        }
+    }
+
+    #[test]
+    fn v2_periods_of_daily_and_monthly_records() {
+        let rows = readings_across_a_winter();
+        let config = granularity_config(Granularity::Daily, Hemisphere::North);
+        let (records, _) = process_csv("periods-daily", &rows, &config);
+        assert_eq!(
+            v2_periods(&records, Hemisphere::North),
+            periods(&[
+                ("2010-12-15", "2010-12-15"),
+                ("2011-01-15", "2011-01-15"),
+                ("2011-02-15", "2011-02-15"),
+                ("2011-07-15", "2011-07-15"),
+            ])
+        );
+
+        let config = granularity_config(Granularity::Monthly, Hemisphere::North);
+        let (records, _) = process_csv("periods-monthly", &rows, &config);
+        assert_eq!(
+            v2_periods(&records, Hemisphere::North),
+            periods(&[
+                ("2010-12-01", "2010-12-31"),
+                ("2011-01-01", "2011-01-31"),
+                ("2011-02-01", "2011-02-28"),
+                ("2011-07-01", "2011-07-31"),
+            ])
+        );
+    }
+
+    #[test]
+    fn v2_periods_of_seasonal_records_span_the_season() {
+        let rows = readings_across_a_winter();
+        for hemisphere in [Hemisphere::North, Hemisphere::South] {
+            let config = granularity_config(Granularity::Seasonal, hemisphere);
+            let (records, _) = process_csv("periods-seasonal", &rows, &config);
+            let seasons: Vec<(i32, Option<Season>, u32)> = records
+                .iter()
+                .map(|record| (record.year, record.season, record.count))
+                .collect();
+            let (december, july) = match hemisphere {
+                Hemisphere::South => (Season::Summer, Season::Winter),
+                _ => (Season::Winter, Season::Summer),
+            };
+            assert!(seasons.contains(&(2011, Some(december), 3)));
+            assert!(seasons.contains(&(2011, Some(july), 1)));
+            assert_eq!(
+                v2_periods(&records, hemisphere),
+                periods(&[("2010-12-01", "2011-02-28"), ("2011-06-01", "2011-08-31")])
+            );
+        }
+    }
+
+    #[test]
+    fn v2_periods_of_yearly_records_span_the_year() {
+        let config = granularity_config(Granularity::Yearly, Hemisphere::North);
+        let (records, _) = process_csv("periods-yearly", &readings_across_a_winter(), &config);
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.year, r.count))
+                .collect::<Vec<_>>(),
+            [(2010, 1), (2011, 3)]
+        );
+        assert_eq!(
+            v2_periods(&records, Hemisphere::North),
+            periods(&[("2010-01-01", "2010-12-31"), ("2011-01-01", "2011-12-31")])
+        );
+    }
+
+    #[test]
+    fn seasons_cut_by_the_year_range_are_dropped() {
+        // Winter 2000 lacks December 1999, and winter 2021 holds only December 2020
+        let rows: Vec<String> = [
+            "2000-01-15,DE,1",
+            "2000-02-15,DE,2",
+            "2000-03-15,DE,8",
+            "2000-12-15,DE,3",
+            "2001-01-15,DE,4",
+            "2001-02-15,DE,5",
+            "2020-12-15,DE,6",
+        ]
+        .iter()
+        .map(|row| row.to_string())
+        .collect();
+        let config = granularity_config(Granularity::Seasonal, Hemisphere::North);
+        let (records, stats) = process_csv("partial-seasons", &rows, &config);
+
+        let seasons: Vec<(i32, Option<Season>, u32)> = records
+            .iter()
+            .map(|record| (record.year, record.season, record.count))
+            .collect();
+        assert_eq!(
+            seasons,
+            [
+                (2000, Some(Season::Spring), 1),
+                (2001, Some(Season::Winter), 3)
+            ]
+        );
+        assert_eq!(stats.partial_seasons, 3);
+        assert_eq!(stats.filtered_rows, 7);
+    }
+
+    #[test]
+    fn seasons_across_years_keep_every_month() {
+        let rows: Vec<String> = ["2000-01-15,DE,1", "2020-12-15,DE,6"]
+            .iter()
+            .map(|row| row.to_string())
+            .collect();
+        let config = TransformConfig {
+            group_by: vec![GroupDimension::Country, GroupDimension::Season],
+            ..TransformConfig::default()
+        };
+        let (records, stats) = process_csv("seasons-across-years", &rows, &config);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].count, 2);
+        assert_eq!(stats.partial_seasons, 0);
+    }
+
+    /// One reading on the 1st and 15th of every month of 2010, valued by its month
+    fn readings_of_2010() -> Vec<String> {
+        (1..=12)
+            .flat_map(|month| [1, 15].map(|day| format!("2010-{month:02}-{day:02},DE,{month}")))
+            .collect()
+    }
+
+    #[test]
+    fn daily_granularity_keeps_one_record_per_day() {
+        let config = granularity_config(Granularity::Daily, Hemisphere::North);
+        let (records, _) = process_csv("bucket-daily", &readings_of_2010(), &config);
+        assert_eq!(records.len(), 24);
+        assert!(records.iter().all(|r| r.count == 1 && r.day.is_some()));
+        assert_eq!(
+            (records[1].month, records[1].day, records[1].avg_temp),
+            (1, Some(15), 1.0)
+        );
+    }
+
+    #[test]
+    fn seasonal_granularity_buckets_months_into_seasons() {
+        let config = granularity_config(Granularity::Seasonal, Hemisphere::North);
+        let (records, _) = process_csv("bucket-seasonal", &readings_of_2010(), &config);
+        let seasons: Vec<(i32, Option<Season>, u32, f64)> = records
+            .iter()
+            .map(|r| (r.year, r.season, r.count, r.avg_temp))
+            .collect();
+        assert_eq!(
+            seasons,
+            [
+                // January and February of 2010 are the winter of 2010, December its next winter
+                (2010, Some(Season::Winter), 4, 1.5),
+                (2010, Some(Season::Spring), 6, 4.0),
+                (2010, Some(Season::Summer), 6, 7.0),
+                (2010, Some(Season::Autumn), 6, 10.0),
+                (2011, Some(Season::Winter), 2, 12.0),
+            ]
+        );
+        assert!(records.iter().all(|r| r.month == 0));
+    }
+
+    #[test]
+    fn yearly_granularity_collapses_the_months() {
+        let config = granularity_config(Granularity::Yearly, Hemisphere::North);
+        let (records, _) = process_csv("bucket-yearly", &readings_of_2010(), &config);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!((record.year, record.month, record.count), (2010, 0, 24));
+        assert_eq!(
+            (record.min_temp, record.max_temp, record.avg_temp),
+            (1.0, 12.0, 6.5)
+        );
+        assert_eq!(record.season, None);
+    }
+
+    #[test]
+    fn collapsed_years_and_months_are_written_as_null() {
+        let config = TransformConfig {
+            enso: Some(EnsoTable::bundled()),
+            group_by: vec![GroupDimension::Country, GroupDimension::EnsoPhase],
+            ..TransformConfig::default()
+        };
+        let (records, _) = process_csv("collapsed-null", &readings_in_three_phases(), &config);
+        let output = OutputConfig {
+            collapsed_year: true,
+            collapsed_month: true,
+            include_enso_phase: true,
+            ..OutputConfig::default()
+        };
+        let path = |extension: &str| {
+            std::env::temp_dir().join(format!(
+                "transformer-collapsed-{}.{}",
+                std::process::id(),
+                extension
+            ))
+        };
+
+        crate::load::write_csv(&records, &path("csv"), &output).unwrap();
+        let csv = std::fs::read_to_string(path("csv")).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().skip(1).all(|line| line.starts_with("DE,,,")));
+
+        let columns = crate::schema::output_columns(&output);
+        assert!(columns[1].nullable && columns[2].nullable);
+        assert!(records.iter().all(|r| columns[1].value(r) == Value::Null));
+        let schema = crate::schema::json_schema(&output);
+        let year = &schema["$defs"]["record"]["properties"]["year"]["type"];
+        assert_eq!(year, &serde_json::json!(["integer", "null"]));
+        let arrow = crate::schema::arrow_schema_json(&output);
+        assert_eq!(arrow["fields"][2]["nullable"], serde_json::json!(true));
+
+        crate::load::write_parquet(&records, &path("parquet"), &output).unwrap();
+        let report = crate::verify::check_parquet(&path("parquet"), None).unwrap();
+        assert_eq!(report.rows, 3);
+
+        let nested = OutputConfig {
+            json_layout: crate::structs::JsonLayout::Nested,
+            ..output
+        };
+        assert!(crate::load::write_json(&records, &path("json"), &nested).is_err());
+        for extension in ["csv", "parquet"] {
+            std::fs::remove_file(path(extension)).unwrap();
+        }
+        let _ = std::fs::remove_file(path("json"));
+    }
+
+    #[test]
+    fn grouped_years_and_months_stay_non_nullable() {
+        let columns = crate::schema::output_columns(&OutputConfig::default());
+        assert!(!columns[1].nullable && !columns[2].nullable);
+        let (records, _) = process_csv(
+            "grouped-non-null",
+            &readings_in_three_phases(),
+            &TransformConfig::default(),
+        );
+        assert_eq!(columns[1].value(&records[0]), Value::Int32(2000));
+        assert_eq!(columns[2].value(&records[0]), Value::UInt32(1));
+    }
+
+    #[test]
+    fn period_zscores_follow_the_std_dev_mode() {
+        let rows: Vec<String> = (1..=4)
+            .map(|month| format!("2010-{month:02}-15,DE,{month}"))
+            .collect();
+        for (mode, divisor) in [(StdDevMode::Sample, 3.0), (StdDevMode::Population, 4.0)] {
+            let config = TransformConfig {
+                std_dev: mode,
+                ..TransformConfig::default()
+            };
+            let (mut records, _) = process_csv("zscores", &rows, &config);
+            crate::baseline::attach_period_zscores(&mut records, mode);
+
+            // Monthly means 1 to 4: mean 2.5, squared deviations summing to 5
+            let std_dev = (5.0_f64 / divisor).sqrt();
+            for record in &records {
+                let expected = (record.avg_temp - 2.5) / std_dev;
+                assert!((record.zscore_vs_period.unwrap() - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    /// Readings of 10 °C in January to April 2010 and a second March reading of 12 °C
+    #[cfg(any(feature = "plugins", feature = "scripting"))]
+    fn readings_for_row_hooks() -> Vec<String> {
+        [
+            "2010-01-15,DE,10",
+            "2010-02-15,DE,10",
+            "2010-03-15,DE,10",
+            "2010-03-20,DE,12",
+            "2010-04-15,DE,10",
+        ]
+        .iter()
+        .map(|row| row.to_string())
+        .collect()
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn plugin_results_are_validated_again() {
+        // January is pushed above 70 °C, February dropped with NaN, April made infinite
+        let module = r#"(module
+              (func (export "transform_row")
+                (param i32 i32 i32 i32 f64) (result f64)
+                (if (result f64) (i32.eq (local.get 2) (i32.const 1))
+                  (then (f64.add (local.get 4) (f64.const 100)))
+                  (else
+                    (if (result f64) (i32.eq (local.get 2) (i32.const 2))
+                      (then (f64.const nan))
+                      (else
+                        (if (result f64) (i32.eq (local.get 2) (i32.const 4))
+                          (then (f64.const inf))
+                          (else (local.get 4)))))))))"#;
+        let path =
+            std::env::temp_dir().join(format!("transformer-plugin-{}.wat", std::process::id()));
+        std::fs::write(&path, module).unwrap();
+        let plugin = crate::plugin::Plugin::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let config = TransformConfig {
+            plugin: Some(std::sync::Arc::new(plugin)),
+            ..TransformConfig::default()
+        };
+        let (records, stats) = process_csv("plugin-bounds", &readings_for_row_hooks(), &config);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].month, records[0].count), (3, 2));
+        assert_eq!((stats.hook_dropped, stats.hook_rejected), (1, 2));
+        assert_eq!(stats.invalid_temps, 0);
+        assert!(
+            stats
+                .warnings
+                .iter()
+                .any(|warning| warning.to_string().contains("2 readings rejected"))
+        );
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn row_script_results_are_validated_again() {
+        // January is pushed above 70 °C, February dropped, April made infinite
+        let source = r#"
+            if month == 1 { temp += 100.0; }
+            if month == 2 { return false; }
+            if month == 4 { temp = parse_float("inf"); }
+        "#;
+        let path =
+            std::env::temp_dir().join(format!("transformer-script-{}.rhai", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let script = crate::script::Script::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let config = TransformConfig {
+            row_script: Some(std::sync::Arc::new(script)),
+            ..TransformConfig::default()
+        };
+        let (records, stats) = process_csv("script-bounds", &readings_for_row_hooks(), &config);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].month, records[0].count), (3, 2));
+        assert_eq!((stats.hook_dropped, stats.hook_rejected), (1, 2));
+        assert_eq!(stats.invalid_temps, 0);
+    }
+
+    #[test]
+    fn aggregate_label_names_excluded_countries() {
+        let rows: Vec<String> = ["2010-01-15,DE,10", "2010-01-15,FR,12", "2010-01-15,RU,-5"]
+            .iter()
+            .map(|row| row.to_string())
+            .collect();
+        let config = TransformConfig {
+            aggregate: true,
+            ..TransformConfig::default()
+        };
+        let exclude = ["RU".to_string()];
+        let (records, _) = process_csv_excluding("aggregate-exclude", &rows, &exclude, &config);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].country, "ALL-EXCEPT-RU");
+        assert_eq!((records[0].count, records[0].avg_temp), (2, 11.0));
+
+        let (records, _) = process_csv("aggregate-all", &rows, &config);
+        assert_eq!(records[0].country, "ALL");
     }
 }
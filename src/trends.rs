@@ -0,0 +1,188 @@
+use crate::distribution::student_t_upper_tail;
+use crate::error::Result;
+use crate::structs::Record;
+use crate::units::{LinearScale, scale_for};
+use csv::Writer;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+/// Least-squares linear trend of one country's monthly means, written by `--trends`
+#[derive(Debug, Clone)]
+pub struct CountryTrend {
+    pub country: String,
+    /// Distinct months the trend is fitted to
+    pub months: usize,
+    /// First and last `YYYY-MM` fitted
+    pub first_period: String,
+    pub last_period: String,
+    /// Trend in degrees Celsius per decade
+    pub slope_per_decade: Option<f64>,
+    /// Fitted annual mean temperature in degrees Celsius at the first period
+    pub intercept: Option<f64>,
+    /// Share of the deseasonalized variance explained by the trend
+    pub r_squared: Option<f64>,
+    /// Two-sided p-value of a zero slope (Student's t)
+    pub p_value: Option<f64>,
+}
+
+/// Fits a linear trend to the monthly means of every country in `results`, sorted by country
+/// code. Records not grouped by month are left out.
+///
+/// The means are converted to Celsius, and records sharing a country and month (daily
+/// records, or one per ENSO phase or unit) are first combined into a single mean weighted by
+/// their reading counts, so every month is one point of the fit. The seasonal cycle is removed
+/// by fitting one level per calendar month with a common slope, so series starting or ending
+/// mid-year are not biased by the season they cover. Countries with fewer months than
+/// parameters plus one, or covering a single month, get no fit.
+pub fn country_trends(results: &[Record], custom: &LinearScale) -> Vec<CountryTrend> {
+    let mut by_country: BTreeMap<&str, MonthSums> = BTreeMap::new();
+    for record in results.iter().filter(|r| r.month != 0) {
+        let weight = f64::from(record.count.max(1));
+        let celsius = scale_for(record.unit, custom).to_celsius(record.avg_temp);
+        let month = by_country
+            .entry(&record.country)
+            .or_default()
+            .entry((record.year, record.month))
+            .or_default();
+        month.0 += celsius * weight;
+        month.1 += weight;
+    }
+    by_country
+        .into_iter()
+        .map(|(country, months)| {
+            let points: Vec<MonthlyMean> = months
+                .into_iter()
+                .map(|((year, month), (sum, weight))| MonthlyMean {
+                    year,
+                    month,
+                    celsius: sum / weight,
+                })
+                .collect();
+            fit(country, &points)
+        })
+        .collect()
+}
+
+/// Sums of count-weighted Celsius means and of counts per `(year, month)`
+type MonthSums = BTreeMap<(i32, u32), (f64, f64)>;
+
+/// Reading-weighted mean of one country and month, in degrees Celsius
+struct MonthlyMean {
+    year: i32,
+    month: u32,
+    celsius: f64,
+}
+
+fn fit(country: &str, points: &[MonthlyMean]) -> CountryTrend {
+    let period = |point: &MonthlyMean| format!("{}-{:02}", point.year, point.month);
+    let first = &points[0];
+    // Time in decades since the first period
+    let decades = |point: &MonthlyMean| {
+        f64::from((point.year - first.year) * 12 + point.month as i32 - first.month as i32) / 120.0
+    };
+
+    let mut seasons: BTreeMap<u32, Vec<(f64, f64)>> = BTreeMap::new();
+    for point in points {
+        seasons
+            .entry(point.month)
+            .or_default()
+            .push((decades(point), point.celsius));
+    }
+    let means: Vec<(f64, f64)> = seasons
+        .values()
+        .map(|points| {
+            let n = points.len() as f64;
+            (
+                points.iter().map(|(x, _)| x).sum::<f64>() / n,
+                points.iter().map(|(_, y)| y).sum::<f64>() / n,
+            )
+        })
+        .collect();
+    let centered = || {
+        seasons
+            .values()
+            .zip(&means)
+            .flat_map(|(points, (mean_x, mean_y))| {
+                points.iter().map(move |(x, y)| (x - mean_x, y - mean_y))
+            })
+    };
+    let sxx: f64 = centered().map(|(x, _)| x * x).sum();
+    let sxy: f64 = centered().map(|(x, y)| x * y).sum();
+    let syy: f64 = centered().map(|(_, y)| y * y).sum();
+
+    let df = points.len() as f64 - means.len() as f64 - 1.0;
+    let fitted = (sxx > 0.0 && df >= 1.0).then(|| {
+        let slope = sxy / sxx;
+        let intercept = means.iter().map(|(x, y)| y - slope * x).sum::<f64>() / means.len() as f64;
+        let residual = (syy - slope * sxy).max(0.0);
+        let r_squared = if syy > 0.0 { 1.0 - residual / syy } else { 1.0 };
+        let p_value = if residual > 0.0 {
+            let t = slope / (residual / df / sxx).sqrt();
+            (2.0 * student_t_upper_tail(t.abs(), df)).min(1.0)
+        } else {
+            0.0
+        };
+        (slope, intercept, r_squared, p_value)
+    });
+
+    CountryTrend {
+        country: country.to_string(),
+        months: points.len(),
+        first_period: period(first),
+        last_period: period(&points[points.len() - 1]),
+        slope_per_decade: fitted.map(|f| f.0),
+        intercept: fitted.map(|f| f.1),
+        r_squared: fitted.map(|f| f.2),
+        p_value: fitted.map(|f| f.3),
+    }
+}
+
+/// Writes the per-country linear trends of `results` to a CSV file.
+///
+/// # Returns
+///
+/// Returns the number of countries with a fitted trend.
+///
+/// # Errors
+/// Returns error if file cannot be created or written to.
+pub fn write_trends_csv(
+    results: &[Record],
+    custom: &LinearScale,
+    output_path: &Path,
+) -> Result<usize> {
+    let trends = country_trends(results, custom);
+    let mut writer = Writer::from_writer(File::create(output_path)?);
+
+    writer.write_record([
+        "Country",
+        "Months",
+        "First_Period",
+        "Last_Period",
+        "Slope_C_Per_Decade",
+        "Intercept_C",
+        "R_Squared",
+        "P_Value",
+    ])?;
+    let cell = |value: Option<f64>, precision: usize| {
+        value.map_or(String::new(), |v| format!("{:.*}", precision, v))
+    };
+    for trend in &trends {
+        writer.write_record(&[
+            trend.country.clone(),
+            trend.months.to_string(),
+            trend.first_period.clone(),
+            trend.last_period.clone(),
+            cell(trend.slope_per_decade, 4),
+            cell(trend.intercept, 2),
+            cell(trend.r_squared, 4),
+            cell(trend.p_value, 6),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(trends
+        .iter()
+        .filter(|t| t.slope_per_decade.is_some())
+        .count())
+}
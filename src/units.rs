@@ -0,0 +1,88 @@
+use crate::structs::TemperatureUnit;
+
+/// Output temperature scale that Celsius readings are converted into.
+///
+/// Each unit is its own implementation, so adding a unit means adding a type and mapping a
+/// `TemperatureUnit` variant to it in [`scale_for`].
+pub trait TemperatureScale: Send + Sync {
+    /// Converts a reading in degrees Celsius into this scale.
+    fn convert_celsius(&self, celsius: f64) -> f64;
+
+    /// Converts a value in this scale back into degrees Celsius.
+    ///
+    /// Every scale is a linear transform of Celsius, so the inverse follows from the images of
+    /// 0 °C and 1 °C.
+    fn to_celsius(&self, value: f64) -> f64 {
+        let offset = self.convert_celsius(0.0);
+        (value - offset) / (self.convert_celsius(1.0) - offset)
+    }
+}
+
+/// Degrees Celsius (identity)
+pub struct Celsius;
+
+/// Degrees Fahrenheit: °F = (°C × 9/5) + 32
+pub struct Fahrenheit;
+
+/// Kelvin: K = °C + 273.15
+pub struct Kelvin;
+
+/// Degrees Rankine: °R = (°C + 273.15) × 9/5
+pub struct Rankine;
+
+/// User-defined linear scale: `value = °C × scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearScale {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Default for LinearScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl TemperatureScale for Celsius {
+    fn convert_celsius(&self, celsius: f64) -> f64 {
+        celsius
+    }
+}
+
+impl TemperatureScale for Fahrenheit {
+    fn convert_celsius(&self, celsius: f64) -> f64 {
+        celsius * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl TemperatureScale for Kelvin {
+    fn convert_celsius(&self, celsius: f64) -> f64 {
+        celsius + 273.15
+    }
+}
+
+impl TemperatureScale for Rankine {
+    fn convert_celsius(&self, celsius: f64) -> f64 {
+        (celsius + 273.15) * 9.0 / 5.0
+    }
+}
+
+impl TemperatureScale for LinearScale {
+    fn convert_celsius(&self, celsius: f64) -> f64 {
+        celsius * self.scale + self.offset
+    }
+}
+
+/// Returns the scale of `unit`, using `custom` for `TemperatureUnit::Custom`.
+pub fn scale_for(unit: TemperatureUnit, custom: &LinearScale) -> &dyn TemperatureScale {
+    match unit {
+        TemperatureUnit::Celsius => &Celsius,
+        TemperatureUnit::Fahrenheit => &Fahrenheit,
+        TemperatureUnit::Kelvin => &Kelvin,
+        TemperatureUnit::Rankine => &Rankine,
+        TemperatureUnit::Custom => custom,
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::{PipelineError, Result};
+use crate::status;
+use log::debug;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// GitHub API endpoint of the latest published release
+pub const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/datkareem/Transformer/releases/latest";
+
+/// GitHub rejects API requests without a user agent
+const USER_AGENT: &str = concat!("Transformer/", env!("CARGO_PKG_VERSION"));
+
+/// Release as returned by the GitHub releases API (only the fields used here)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// Downloadable file attached to a release
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+impl Release {
+    /// Whether the release is newer than `version` (e.g. `CARGO_PKG_VERSION`), comparing the
+    /// numeric components of the tag; a leading `v` is ignored.
+    pub fn is_newer_than(&self, version: &str) -> bool {
+        version_parts(&self.tag_name) > version_parts(version)
+    }
+
+    /// Binary built for the running platform: the first asset whose name contains both the
+    /// CPU architecture (`x86_64`, `aarch64`) and the OS (`linux`, `windows`, or `macos` /
+    /// `darwin`), skipping archives and checksum files.
+    pub fn platform_asset(&self) -> Option<&ReleaseAsset> {
+        let os_names: &[&str] = match std::env::consts::OS {
+            "macos" => &["macos", "darwin", "apple"],
+            os => &[os],
+        };
+        self.assets.iter().find(|asset| {
+            let name = asset.name.to_lowercase();
+            let skipped = [".sha256", ".asc", ".sig", ".zip", ".tar", ".gz", ".tgz"]
+                .iter()
+                .any(|ext| name.ends_with(ext));
+            !skipped
+                && name.contains(std::env::consts::ARCH)
+                && os_names.iter().any(|os| name.contains(os))
+        })
+    }
+}
+
+/// Numeric components of a version or tag (`v1.10.2` -> `[1, 10, 2]`); pre-release suffixes
+/// are ignored.
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Fetches the latest release from GitHub.
+///
+/// # Errors
+/// Returns `PipelineError::Http` if the API cannot be reached or answers with an error, and
+/// `PipelineError::Json` if the response is not a release.
+pub fn latest_release() -> Result<Release> {
+    let mut response = ureq::get(LATEST_RELEASE_URL)
+        .config()
+        .http_status_as_error(false)
+        .build()
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", USER_AGENT)
+        .call()
+        .map_err(|e| http_error(LATEST_RELEASE_URL, e.to_string()))?;
+    let status = response.status();
+    let body = response
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| http_error(LATEST_RELEASE_URL, e.to_string()))?;
+    if !status.is_success() {
+        return Err(http_error(
+            LATEST_RELEASE_URL,
+            format!("{}: {}", status, body.trim()),
+        ));
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Replaces the running executable with `asset`.
+///
+/// The binary is downloaded next to the executable as `<exe>.update` and then renamed over
+/// it, so an interrupted download leaves the installed binary untouched. On Windows, where a
+/// running executable cannot be overwritten, the old binary is first moved aside to
+/// `<exe>.old`, which the next update removes.
+///
+/// # Returns
+///
+/// Returns the path of the replaced executable.
+///
+/// # Errors
+/// Returns `PipelineError::Http` if the download fails or is truncated, and
+/// `PipelineError::Io` if the executable's directory is not writable.
+pub fn install(asset: &ReleaseAsset) -> Result<PathBuf> {
+    let exe = fs::canonicalize(std::env::current_exe()?)?;
+    let update = sibling(&exe, "update");
+    let old = sibling(&exe, "old");
+    if old.exists() {
+        let _ = fs::remove_file(&old);
+    }
+
+    status!("Downloading {}", asset.browser_download_url);
+    let downloaded = download(asset, &update);
+    let bytes = match downloaded {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_file(&update);
+            return Err(e);
+        }
+    };
+    debug!("Downloaded {} bytes to {}", bytes, update.display());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&update, fs::Permissions::from_mode(0o755))?;
+    }
+    if cfg!(windows) {
+        fs::rename(&exe, &old)?;
+    }
+    if let Err(e) = fs::rename(&update, &exe) {
+        if cfg!(windows) {
+            let _ = fs::rename(&old, &exe);
+        }
+        let _ = fs::remove_file(&update);
+        return Err(e.into());
+    }
+    Ok(exe)
+}
+
+/// Downloads an asset to `path`, returning the number of bytes written.
+fn download(asset: &ReleaseAsset, path: &Path) -> Result<u64> {
+    let url = &asset.browser_download_url;
+    let response = ureq::get(url)
+        .header("User-Agent", USER_AGENT)
+        .call()
+        .map_err(|e| http_error(url, e.to_string()))?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    let bytes = io::copy(&mut response.into_body().into_reader(), &mut writer)?;
+    writer.flush()?;
+    if asset.size > 0 && bytes != asset.size {
+        return Err(http_error(
+            url,
+            format!("downloaded {} of {} bytes", bytes, asset.size),
+        ));
+    }
+    Ok(bytes)
+}
+
+/// `<exe>.<suffix>` in the executable's directory
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn http_error(url: &str, reason: String) -> PipelineError {
+    PipelineError::Http {
+        url: url.to_string(),
+        reason,
+    }
+}
@@ -0,0 +1,406 @@
+use crate::error::{PipelineError, Result};
+use crate::load::{arrow_type, csv_delimiter, format_csv_value};
+use crate::schema::{Column, ColumnType, Value, json_columns, json_rows, output_columns};
+use crate::structs::{OutputConfig, OutputFormat, ParquetReport, Record, SchemaVersion};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Int32Type, UInt32Type};
+use arrow_array::{Array, Float64Array, Int32Array, RecordBatch, StringArray, UInt32Array};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Maximum number of rows compared value-by-value per file
+const SAMPLE_ROWS: usize = 100;
+
+/// Relative tolerance for floats parsed back from JSON
+const JSON_TOLERANCE: f64 = 1e-12;
+
+/// Returns evenly spread row indices to compare (always includes the first and last row).
+fn sample_indices(len: usize) -> Vec<usize> {
+    if len <= SAMPLE_ROWS {
+        return (0..len).collect();
+    }
+    let step = (len - 1) as f64 / (SAMPLE_ROWS - 1) as f64;
+    (0..SAMPLE_ROWS)
+        .map(|i| (i as f64 * step).round() as usize)
+        .collect()
+}
+
+fn mismatch(path: &Path, reason: String) -> PipelineError {
+    PipelineError::Verification {
+        file: path.to_path_buf(),
+        reason,
+    }
+}
+
+fn check_count(path: &Path, found: usize, expected: usize) -> Result<()> {
+    if found != expected {
+        return Err(mismatch(
+            path,
+            format!("expected {} records, found {}", expected, found),
+        ));
+    }
+    Ok(())
+}
+
+/// Re-reads a file written by `write_output` in `format` and checks it against the in-memory
+/// results with [`verify_csv`], [`verify_json`], or [`verify_parquet`].
+///
+/// # Errors
+/// Returns the errors of the format's check.
+pub fn verify_output(
+    format: OutputFormat,
+    results: &[Record],
+    path: &Path,
+    config: &OutputConfig,
+) -> Result<()> {
+    match format {
+        OutputFormat::Csv => verify_csv(results, path, config),
+        OutputFormat::Json => verify_json(results, path, config),
+        OutputFormat::Parquet => verify_parquet(results, path, config),
+    }
+}
+
+/// Re-reads a CSV file written by `write_csv` and checks it against the in-memory results.
+///
+/// The header must match the configured columns, the row count must match, and sampled rows
+/// must match the writer's formatted values exactly.
+///
+/// # Errors
+/// Returns `PipelineError::Verification` on the first mismatch, or I/O/CSV errors.
+pub fn verify_csv(results: &[Record], path: &Path, config: &OutputConfig) -> Result<()> {
+    let columns = output_columns(config);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(csv_delimiter(config))
+        .from_path(path)?;
+
+    let header: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+    let expected_header: Vec<String> = columns.iter().map(Column::csv_header).collect();
+    if header != expected_header {
+        return Err(mismatch(path, format!("unexpected header {:?}", header)));
+    }
+
+    let rows: Vec<csv::StringRecord> = reader.records().collect::<csv::Result<_>>()?;
+    check_count(path, rows.len(), results.len())?;
+    for i in sample_indices(rows.len()) {
+        for (j, column) in columns.iter().enumerate() {
+            let expected = format_csv_value(column.value(&results[i]), config);
+            let found = rows[i].get(j).unwrap_or_default();
+            if found != expected {
+                return Err(mismatch(
+                    path,
+                    format!(
+                        "row {} column {}: expected '{}', found '{}'",
+                        i, column.name, expected, found
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Re-reads a JSON file written by `write_json` and checks it against the in-memory results.
+///
+/// # Errors
+/// Returns `PipelineError::Verification` on the first mismatch, or I/O/JSON errors.
+pub fn verify_json(results: &[Record], path: &Path, config: &OutputConfig) -> Result<()> {
+    let columns = json_columns(config);
+    let document = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let rows = json_rows(document, config.json_case);
+    check_count(path, rows.len(), results.len())?;
+
+    for i in sample_indices(rows.len()) {
+        for column in &columns {
+            let found = rows[i]
+                .get(&column.name)
+                .unwrap_or(&serde_json::Value::Null);
+            let matches = match column.value(&results[i]) {
+                Value::Utf8(v) => found.as_str() == Some(v.as_str()),
+                Value::Int32(v) => found.as_i64() == Some(v as i64),
+                Value::UInt32(v) => found.as_u64() == Some(v as u64),
+                Value::Float64(v) if v.is_finite() => found
+                    .as_f64()
+                    .is_some_and(|f| (f - v).abs() <= JSON_TOLERANCE * v.abs().max(1.0)),
+                Value::Float64(_) => found.is_null(),
+                Value::Null => match config.json_null() {
+                    Some(text) => found.as_str() == Some(text),
+                    None => found.is_null(),
+                },
+            };
+            if !matches {
+                return Err(mismatch(
+                    path,
+                    format!(
+                        "row {} column {}: unexpected value {}",
+                        i, column.name, found
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Re-reads a Parquet file written by `write_parquet` and checks it against the in-memory
+/// results. Sampled values must match exactly.
+///
+/// # Errors
+/// Returns `PipelineError::Verification` on the first mismatch, or I/O/Parquet errors.
+pub fn verify_parquet(results: &[Record], path: &Path, config: &OutputConfig) -> Result<()> {
+    let columns = output_columns(config);
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let declared = builder.metadata().file_metadata().num_rows() as usize;
+    check_count(path, declared, results.len())?;
+
+    let field_names: Vec<String> = builder
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    let expected_names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+    if field_names != expected_names {
+        return Err(mismatch(
+            path,
+            format!("unexpected columns {:?}", field_names),
+        ));
+    }
+
+    let batches: Vec<RecordBatch> = builder.build()?.collect::<std::result::Result<_, _>>()?;
+    let decoded: usize = batches.iter().map(RecordBatch::num_rows).sum();
+    check_count(path, decoded, results.len())?;
+
+    for i in sample_indices(decoded) {
+        let (batch, row) = locate_row(&batches, i);
+        for (j, column) in columns.iter().enumerate() {
+            let expected = column.value(&results[i]);
+            let found = read_value(batch.column(j).as_ref(), column.data_type, row);
+            let matches = match (&expected, &found) {
+                (Value::Float64(a), Value::Float64(b)) => a == b || (a.is_nan() && b.is_nan()),
+                _ => expected == found,
+            };
+            if !matches {
+                return Err(mismatch(
+                    path,
+                    format!(
+                        "row {} column {}: expected {:?}, found {:?}",
+                        i, column.name, expected, found
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The fixed percentiles, whose columns are written unless `--percentiles` replaces them
+const FIXED_PERCENTILES: [f64; 4] = [25.0, 75.0, 90.0, 95.0];
+
+/// Checks a Parquet output on its own, without the results it was written from, e.g. before
+/// a downstream loader picks it up.
+///
+/// The file must decode completely, hold exactly the columns (names, order, types, and nulls
+/// only in nullable columns) the writer emits for its schema version and opt-in columns, and
+/// list its rows in non-decreasing (country, year, month) order. The opt-in columns are
+/// recognized by name, and the schema version by the `unit` column of version 2.
+///
+/// # Arguments
+///
+/// * `path` - Parquet file written by the pipeline
+/// * `expected_version` - Schema version the file must have, or `None` to accept either
+///
+/// # Errors
+/// Returns `PipelineError::Verification` on the first problem found, or I/O/Parquet/Arrow
+/// errors if the file cannot be read.
+pub fn check_parquet(
+    path: &Path,
+    expected_version: Option<SchemaVersion>,
+) -> Result<ParquetReport> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let row_groups = builder.metadata().num_row_groups();
+    let declared = builder.metadata().file_metadata().num_rows() as usize;
+    let fields = builder.schema().fields().clone();
+    let names: Vec<String> = fields.iter().map(|f| f.name().clone()).collect();
+
+    let nullable = |name: &str| {
+        fields
+            .iter()
+            .any(|field| field.name() == name && field.is_nullable())
+    };
+    let config = detected_config(&names, nullable("year"), nullable("month"));
+    if let Some(expected) = expected_version
+        && expected != config.schema_version
+    {
+        return Err(mismatch(
+            path,
+            format!(
+                "expected output schema version {}, found the columns of version {}",
+                expected, config.schema_version
+            ),
+        ));
+    }
+    let columns = output_columns(&config);
+    let expected_names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    if names != expected_names {
+        return Err(mismatch(
+            path,
+            format!(
+                "columns {:?} are not those of output schema version {}, expected {:?}",
+                names, config.schema_version, expected_names
+            ),
+        ));
+    }
+    for (field, column) in fields.iter().zip(&columns) {
+        let expected = arrow_type(column.data_type);
+        if field.data_type() != &expected {
+            return Err(mismatch(
+                path,
+                format!(
+                    "column {} is {}, expected {}",
+                    column.name,
+                    field.data_type(),
+                    expected
+                ),
+            ));
+        }
+    }
+
+    let index = |name: &str| names.iter().position(|n| n == name).unwrap_or_default();
+    let (country, year, month) = (index("country"), index("year"), index("month"));
+    let mut previous: Option<(String, i32, u32)> = None;
+    let (mut rows, mut countries) = (0, 0);
+    let mut years: Option<(i32, i32)> = None;
+    for batch in builder.build()? {
+        let batch = batch?;
+        for (j, column) in columns.iter().enumerate() {
+            if !column.nullable && batch.column(j).null_count() > 0 {
+                return Err(mismatch(
+                    path,
+                    format!("non-nullable column {} holds nulls", column.name),
+                ));
+            }
+        }
+        let country = batch.column(country).as_string::<i32>();
+        let year = batch.column(year).as_primitive::<Int32Type>();
+        let month = batch.column(month).as_primitive::<UInt32Type>();
+        for i in 0..batch.num_rows() {
+            let key = (country.value(i), year.value(i), month.value(i));
+            if let Some(last) = &previous
+                && key < (last.0.as_str(), last.1, last.2)
+            {
+                return Err(mismatch(
+                    path,
+                    format!(
+                        "row {} ({} {}-{}) is out of (country, year, month) order after {} \
+                         {}-{}",
+                        rows + i,
+                        key.0,
+                        key.1,
+                        key.2,
+                        last.0,
+                        last.1,
+                        last.2
+                    ),
+                ));
+            }
+            if previous.as_ref().is_none_or(|last| last.0 != key.0) {
+                countries += 1;
+            }
+            if !year.is_null(i) {
+                years = Some(years.map_or((key.1, key.1), |(first, last)| {
+                    (first.min(key.1), last.max(key.1))
+                }));
+            }
+            previous = Some((key.0.to_string(), key.1, key.2));
+        }
+        rows += batch.num_rows();
+    }
+    check_count(path, rows, declared)?;
+
+    Ok(ParquetReport {
+        schema_version: config.schema_version,
+        columns: columns.len(),
+        rows,
+        row_groups,
+        countries,
+        years,
+    })
+}
+
+/// Output configuration of a file with the columns `names`: the schema version and opt-in
+/// columns are inferred from the columns each of them adds, and collapsed years and months
+/// from a nullable `year` or `month` column.
+fn detected_config(names: &[String], collapsed_year: bool, collapsed_month: bool) -> OutputConfig {
+    let has = |name: &str| names.iter().any(|n| n == name);
+    let mut percentiles: Vec<f64> = names
+        .iter()
+        .filter_map(|name| name.strip_prefix("percentile_"))
+        .filter(|percent| !percent.ends_with("_celsius"))
+        .filter_map(|percent| percent.replace('_', ".").parse().ok())
+        .collect();
+    if percentiles == FIXED_PERCENTILES {
+        percentiles.clear();
+    }
+    OutputConfig {
+        schema_version: if has("unit") {
+            SchemaVersion::V2
+        } else {
+            SchemaVersion::V1
+        },
+        collapsed_year,
+        collapsed_month,
+        include_country_names: has("country_name"),
+        include_day: has("day"),
+        include_enso_phase: has("enso_phase"),
+        include_season: has("season"),
+        include_distinct_days: has("distinct_days"),
+        include_outliers_removed: has("outliers_removed"),
+        include_confidence_interval: has("ci_low"),
+        include_mode_range: has("range_temp"),
+        include_trimmed_mean: has("trimmed_mean"),
+        include_positive_means: has("geometric_mean"),
+        include_zscore: has("zscore_vs_period"),
+        include_anomaly: has("anomaly_c"),
+        include_plugin_metric: has("plugin_metric"),
+        include_aridity_index: has("aridity_index"),
+        include_provenance: has("source_file"),
+        include_celsius: has("avg_temp_celsius"),
+        percentiles,
+        ..OutputConfig::default()
+    }
+}
+
+/// Finds the batch and in-batch row of a file-level row index.
+fn locate_row(batches: &[RecordBatch], mut index: usize) -> (&RecordBatch, usize) {
+    for batch in batches {
+        if index < batch.num_rows() {
+            return (batch, index);
+        }
+        index -= batch.num_rows();
+    }
+    unreachable!("row index within decoded row count")
+}
+
+/// Reads one cell of a decoded Arrow array as a `Value`.
+fn read_value(array: &dyn Array, data_type: ColumnType, row: usize) -> Value {
+    if array.is_null(row) {
+        return Value::Null;
+    }
+    let any = array.as_any();
+    match data_type {
+        ColumnType::Utf8 => any
+            .downcast_ref::<StringArray>()
+            .map_or(Value::Null, |a| Value::Utf8(a.value(row).to_string())),
+        ColumnType::Int32 => any
+            .downcast_ref::<Int32Array>()
+            .map_or(Value::Null, |a| Value::Int32(a.value(row))),
+        ColumnType::UInt32 => any
+            .downcast_ref::<UInt32Array>()
+            .map_or(Value::Null, |a| Value::UInt32(a.value(row))),
+        ColumnType::Float64 => any
+            .downcast_ref::<Float64Array>()
+            .map_or(Value::Null, |a| Value::Float64(a.value(row))),
+    }
+}